@@ -15,7 +15,7 @@ import { ThemeManager } from './components/ThemeManager';
 import { PasswordGenerator } from './components/PasswordGenerator';
 import { GeneratorWindow } from './components/GeneratorWindow';
 import { UpdateModal } from './components/UpdateModal';
-import { checkForUpdates, GitHubRelease } from './services/updateService';
+import { checkForUpdates, ReleaseInfo } from './services/updateService';
 
 type AppMode = 'launcher' | 'vault' | 'about' | 'auth' | 'create' | 'large-type' | 'markdown-preview' | 'generator' | 'settings';
 
@@ -26,7 +26,7 @@ const AppContent: React.FC = () => {
     return pathParam || undefined;
   });
 
-  const [updateModal, setUpdateModal] = useState<{ isOpen: boolean; release?: GitHubRelease; currentVersion?: string }>({
+  const [updateModal, setUpdateModal] = useState<{ isOpen: boolean; release?: ReleaseInfo; currentVersion?: string }>({
     isOpen: false
   });
 
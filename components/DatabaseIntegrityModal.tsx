@@ -1,9 +1,26 @@
 import React, { useState, useEffect } from 'react';
 import { createPortal } from 'react-dom';
-import { X, Shield, CheckCircle, AlertTriangle, RefreshCw, FileText, Clock } from 'lucide-react';
+import { open as openDialog, save } from '@tauri-apps/plugin-dialog';
+import { X, Shield, CheckCircle, AlertTriangle, RefreshCw, FileText, Clock, GitCompare, Wrench, Info } from 'lucide-react';
 import { useVault } from '../context/VaultContext';
 import { useToast } from './ui/Toaster';
-import { verifyDatabaseFile, restoreFromBackup, listBackups } from '../services/databaseIntegrityService';
+import { verifyDatabaseFile, isReportValid, lintVault, restoreFromBackup, listBackups, getVaultIdentity, repairVault, BackupInfo, FindingSeverity, LintReport, RepairReport, VerifyReport } from '../services/databaseIntegrityService';
+import { VaultDiff, diffVaults, toVaultCredentials } from '../services/syncService';
+import { errorMessage, hasErrorCode } from '../services/appError';
+import { PasswordPromptModal } from './PasswordPromptModal';
+import { VaultDiffView } from './VaultDiffView';
+
+const formatBackupDate = (backup: BackupInfo) => new Date(backup.createdAt).toLocaleString();
+
+const formatSize = (bytes: number) => bytes < 1024 * 1024
+    ? `${(bytes / 1024).toFixed(1)} KB`
+    : `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+
+const SEVERITY_STYLES: Record<FindingSeverity, { color: string; icon: typeof Info }> = {
+    error: { color: 'rgb(239, 68, 68)', icon: AlertTriangle },
+    warning: { color: 'rgb(217, 119, 6)', icon: AlertTriangle },
+    info: { color: 'rgb(59, 130, 246)', icon: Info }
+};
 
 interface DatabaseIntegrityModalProps {
     isOpen: boolean;
@@ -11,11 +28,21 @@ interface DatabaseIntegrityModalProps {
 }
 
 export const DatabaseIntegrityModal: React.FC<DatabaseIntegrityModalProps> = ({ isOpen, onClose }) => {
-    const { vaults, activeVaultId } = useVault();
+    const { vaults, activeVaultId, fixCompatibility, reloadVault } = useVault();
     const { addToast } = useToast();
     const [isVerifying, setIsVerifying] = useState(false);
-    const [verificationResult, setVerificationResult] = useState<any>(null);
-    const [backups, setBackups] = useState<string[]>([]);
+    const [verificationResult, setVerificationResult] = useState<VerifyReport | null>(null);
+    const [isLinting, setIsLinting] = useState(false);
+    const [lintReport, setLintReport] = useState<LintReport | null>(null);
+    const [backups, setBackups] = useState<BackupInfo[]>([]);
+    // Changes restoring a backup would make, shown under that backup
+    const [review, setReview] = useState<{ backupId: string; diff: VaultDiff } | null>(null);
+    const [comparingId, setComparingId] = useState<string | null>(null);
+    const [passwordFor, setPasswordFor] = useState<BackupInfo | null>(null);
+    const [isRepairing, setIsRepairing] = useState(false);
+    const [repair, setRepair] = useState<{ target: string; report: RepairReport } | null>(null);
+    // Repair waiting for the damaged file's password
+    const [repairPending, setRepairPending] = useState<{ source: string; target: string } | null>(null);
 
     const activeVault = vaults.find(v => v.id === activeVaultId);
 
@@ -28,7 +55,7 @@ export const DatabaseIntegrityModal: React.FC<DatabaseIntegrityModalProps> = ({
     const loadBackups = async () => {
         if (!activeVault?.path) return;
         try {
-            const backupList = await listBackups(activeVault.path);
+            const backupList = await listBackups(activeVault.path, getVaultIdentity(activeVault.db));
             setBackups(backupList);
         } catch (e) {
             console.error('Failed to load backups:', e);
@@ -36,8 +63,8 @@ export const DatabaseIntegrityModal: React.FC<DatabaseIntegrityModalProps> = ({
     };
 
     const handleVerify = async () => {
-        if (!activeVault?.path || !activeVault.db.credentials) {
-            addToast({ title: 'Cannot verify: No file path or credentials', type: 'error' });
+        if (!activeVault?.path) {
+            addToast({ title: 'Cannot verify: No file path', type: 'error' });
             return;
         }
 
@@ -45,43 +72,155 @@ export const DatabaseIntegrityModal: React.FC<DatabaseIntegrityModalProps> = ({
         setVerificationResult(null);
 
         try {
-            const result = await verifyDatabaseFile(activeVault.path, activeVault.db.credentials);
-            setVerificationResult(result);
+            const report = await verifyDatabaseFile(
+                activeVault.path,
+                toVaultCredentials(activeVault.password, activeVault.keyFileData)
+            );
+            setVerificationResult(report);
 
-            if (result.valid) {
+            if (isReportValid(report)) {
                 addToast({
                     title: 'Database verified',
-                    description: 'File integrity check passed',
+                    description: report.findings.length === 0
+                        ? 'File integrity check passed'
+                        : `Passed with ${report.findings.length} note(s)`,
                     type: 'success'
                 });
             } else {
                 addToast({
                     title: 'Verification failed',
-                    description: result.error,
+                    description: report.findings.find(f => f.severity === 'error')?.message,
                     type: 'error'
                 });
             }
-        } catch (e: any) {
-            addToast({ title: 'Verification error', description: e.message, type: 'error' });
+        } catch (e) {
+            const description = hasErrorCode(e, 'WrongCredentials')
+                ? 'The file on disk does not open with this vault\'s current credentials'
+                : errorMessage(e);
+            addToast({ title: 'Verification error', description, type: 'error' });
         } finally {
             setIsVerifying(false);
         }
     };
 
-    const handleRestoreBackup = async (backupPath: string) => {
+    // Checks the file on disk; fixing saves pending edits first (see fixCompatibility)
+    const handleLint = async (fix: boolean) => {
         if (!activeVault?.path) return;
 
-        const confirmed = confirm(`Restore from backup?\n\nThis will replace your current database with:\n${backupPath}\n\nCurrent database will be backed up first.`);
+        setIsLinting(true);
+        try {
+            const report = fix
+                ? await fixCompatibility(activeVault.id)
+                : await lintVault(
+                    activeVault.path,
+                    activeVault.db,
+                    toVaultCredentials(activeVault.password, activeVault.keyFileData),
+                    false
+                );
+            setLintReport(report);
+            if (fix) {
+                addToast({ title: 'Compatibility issues fixed', description: `${report.fixed} value(s) corrected; a backup was taken first`, type: 'success' });
+            } else if (report.issues.length === 0) {
+                addToast({ title: 'No compatibility issues', description: 'KeePassXC and KeePass 2 can read every value', type: 'success' });
+            }
+        } catch (e) {
+            addToast({ title: fix ? 'Fix failed' : 'Compatibility check failed', description: errorMessage(e), type: 'error' });
+        } finally {
+            setIsLinting(false);
+        }
+    };
+
+    // `password` unlocks a backup saved before the master key changed
+    const handleCompareBackup = async (backup: BackupInfo, password?: string) => {
+        if (!activeVault?.path) return;
+        if (review?.backupId === backup.id && password === undefined) {
+            setReview(null);
+            return;
+        }
+
+        setComparingId(backup.id);
+        try {
+            const diff = await diffVaults(
+                activeVault.path,
+                backup.path,
+                toVaultCredentials(activeVault.password, activeVault.keyFileData),
+                password !== undefined ? { password } : undefined
+            );
+            setReview({ backupId: backup.id, diff });
+        } catch (e) {
+            if (hasErrorCode(e, 'WrongCredentials')) {
+                if (password !== undefined) {
+                    addToast({ title: 'Incorrect password', type: 'error' });
+                }
+                setPasswordFor(backup);
+                return;
+            }
+            addToast({ title: 'Compare failed', description: errorMessage(e), type: 'error' });
+        } finally {
+            setComparingId(null);
+        }
+    };
+
+    // Pick the damaged file (this vault's by default) and where to save what can be recovered
+    const handleRepair = async () => {
+        const filters = [{ name: 'KeePass Database', extensions: ['kdbx'] }];
+        const source = await openDialog({ multiple: false, directory: false, defaultPath: activeVault?.path, filters });
+        if (typeof source !== 'string') return;
+        const target = await save({
+            defaultPath: `${source.replace(/\.kdbx$/i, '')} (repaired).kdbx`,
+            filters
+        });
+        if (!target) return;
+        await runRepair(source, target);
+    };
+
+    // `password` is asked for when the file does not open with this vault's credentials
+    const runRepair = async (source: string, target: string, password?: string) => {
+        if (!activeVault) return;
+        setIsRepairing(true);
+        setRepair(null);
+        try {
+            const report = await repairVault(
+                source,
+                password !== undefined ? { password } : toVaultCredentials(activeVault.password, activeVault.keyFileData),
+                target
+            );
+            setRepair({ target, report });
+            addToast({
+                title: report.lost.length === 0 ? 'No damage found' : 'Vault repaired',
+                description: `Saved as ${target}`,
+                type: report.lost.length === 0 ? 'success' : 'info'
+            });
+        } catch (e) {
+            if (hasErrorCode(e, 'WrongCredentials')) {
+                if (password !== undefined) {
+                    addToast({ title: 'Incorrect password', type: 'error' });
+                }
+                setRepairPending({ source, target });
+                return;
+            }
+            addToast({ title: 'Repair failed', description: errorMessage(e), type: 'error' });
+        } finally {
+            setIsRepairing(false);
+        }
+    };
+
+    const handleRestoreBackup = async (backup: BackupInfo) => {
+        if (!activeVault?.path) return;
+
+        const confirmed = confirm(`Restore from backup?\n\nThis will replace your current database with the version from:\n${formatBackupDate(backup)}\n\nCurrent database will be backed up first.`);
 
         if (!confirmed) return;
 
         try {
-            const result = await restoreFromBackup(activeVault.path, backupPath);
+            const result = await restoreFromBackup(activeVault.path, getVaultIdentity(activeVault.db), backup.id);
 
             if (result.success) {
+                // The backend recorded the restored file as loaded: only a reload shows it
+                await reloadVault(activeVault.id);
                 addToast({
                     title: 'Backup restored',
-                    description: 'Please reload the vault',
+                    description: 'The vault was reloaded from the backup',
                     type: 'success'
                 });
                 onClose();
@@ -100,247 +239,434 @@ export const DatabaseIntegrityModal: React.FC<DatabaseIntegrityModalProps> = ({
     if (!isOpen) return null;
 
     return createPortal(
-        <div
-            className="fixed inset-0 z-50 flex items-center justify-center"
-            style={{ backgroundColor: 'rgba(0, 0, 0, 0.5)' }}
-            onClick={onClose}
-        >
+        <>
             <div
-                className="relative w-full max-w-2xl mx-4 rounded-lg shadow-xl"
-                style={{
-                    backgroundColor: 'var(--color-bg-primary)',
-                    border: '1px solid var(--color-border-light)',
-                    maxHeight: '80vh',
-                    display: 'flex',
-                    flexDirection: 'column'
-                }}
-                onClick={(e) => e.stopPropagation()}
+                className="fixed inset-0 z-50 flex items-center justify-center"
+                style={{ backgroundColor: 'rgba(0, 0, 0, 0.5)' }}
+                onClick={onClose}
             >
-                {/* Header */}
                 <div
-                    className="flex items-center justify-between p-4"
-                    style={{ borderBottom: '1px solid var(--color-border-light)' }}
+                    className="relative w-full max-w-2xl mx-4 rounded-lg shadow-xl"
+                    style={{
+                        backgroundColor: 'var(--color-bg-primary)',
+                        border: '1px solid var(--color-border-light)',
+                        maxHeight: '80vh',
+                        display: 'flex',
+                        flexDirection: 'column'
+                    }}
+                    onClick={(e) => e.stopPropagation()}
                 >
-                    <div className="flex items-center gap-2">
-                        <Shield size={20} style={{ color: 'var(--color-primary)' }} />
-                        <h2 className="text-lg font-semibold" style={{ color: 'var(--color-text-primary)' }}>
-                            Database Integrity
-                        </h2>
-                    </div>
-                    <button
-                        onClick={onClose}
-                        className="p-1 rounded hover:bg-opacity-10"
-                        style={{ color: 'var(--color-text-secondary)' }}
+                    {/* Header */}
+                    <div
+                        className="flex items-center justify-between p-4"
+                        style={{ borderBottom: '1px solid var(--color-border-light)' }}
                     >
-                        <X size={20} />
-                    </button>
-                </div>
-
-                {/* Content */}
-                <div className="flex-1 overflow-y-auto p-6 space-y-6">
-                    {/* Current Database Info */}
-                    <div className="space-y-2">
-                        <h3 className="font-medium" style={{ color: 'var(--color-text-primary)' }}>
-                            Current Database
-                        </h3>
-                        <div
-                            className="p-4 rounded-lg"
-                            style={{ backgroundColor: 'var(--color-bg-secondary)' }}
-                        >
-                            <div className="space-y-2 text-sm">
-                                <div className="flex justify-between">
-                                    <span style={{ color: 'var(--color-text-secondary)' }}>Name:</span>
-                                    <span style={{ color: 'var(--color-text-primary)' }}>{activeVault?.name}</span>
-                                </div>
-                                <div className="flex justify-between">
-                                    <span style={{ color: 'var(--color-text-secondary)' }}>Path:</span>
-                                    <span
-                                        className="text-xs truncate max-w-md"
-                                        style={{ color: 'var(--color-text-primary)' }}
-                                        title={activeVault?.path}
-                                    >
-                                        {activeVault?.path || 'No path'}
-                                    </span>
-                                </div>
-                            </div>
+                        <div className="flex items-center gap-2">
+                            <Shield size={20} style={{ color: 'var(--color-primary)' }} />
+                            <h2 className="text-lg font-semibold" style={{ color: 'var(--color-text-primary)' }}>
+                                Database Integrity
+                            </h2>
                         </div>
+                        <button
+                            onClick={onClose}
+                            className="p-1 rounded hover:bg-opacity-10"
+                            style={{ color: 'var(--color-text-secondary)' }}
+                        >
+                            <X size={20} />
+                        </button>
                     </div>
 
-                    {/* Verification Section */}
-                    <div className="space-y-3">
-                        <div className="flex items-center justify-between">
+                    {/* Content */}
+                    <div className="flex-1 overflow-y-auto p-6 space-y-6">
+                        {/* Current Database Info */}
+                        <div className="space-y-2">
                             <h3 className="font-medium" style={{ color: 'var(--color-text-primary)' }}>
-                                Integrity Check
+                                Current Database
                             </h3>
-                            <button
-                                onClick={handleVerify}
-                                disabled={isVerifying || !activeVault?.path}
-                                className="flex items-center gap-2 px-4 py-2 rounded-lg font-medium transition-colors disabled:opacity-50"
-                                style={{
-                                    backgroundColor: 'var(--color-primary)',
-                                    color: 'white'
-                                }}
-                            >
-                                {isVerifying ? (
-                                    <>
-                                        <RefreshCw size={16} className="animate-spin" />
-                                        Verifying...
-                                    </>
-                                ) : (
-                                    <>
-                                        <Shield size={16} />
-                                        Verify Now
-                                    </>
-                                )}
-                            </button>
-                        </div>
-
-                        {verificationResult && (
                             <div
                                 className="p-4 rounded-lg"
-                                style={{
-                                    backgroundColor: verificationResult.valid
-                                        ? 'rgba(34, 197, 94, 0.1)'
-                                        : 'rgba(239, 68, 68, 0.1)',
-                                    border: `1px solid ${verificationResult.valid ? 'rgb(34, 197, 94)' : 'rgb(239, 68, 68)'}`
-                                }}
+                                style={{ backgroundColor: 'var(--color-bg-secondary)' }}
                             >
-                                <div className="flex items-start gap-3">
-                                    {verificationResult.valid ? (
-                                        <CheckCircle size={20} style={{ color: 'rgb(34, 197, 94)' }} />
+                                <div className="space-y-2 text-sm">
+                                    <div className="flex justify-between">
+                                        <span style={{ color: 'var(--color-text-secondary)' }}>Name:</span>
+                                        <span style={{ color: 'var(--color-text-primary)' }}>{activeVault?.name}</span>
+                                    </div>
+                                    <div className="flex justify-between">
+                                        <span style={{ color: 'var(--color-text-secondary)' }}>Path:</span>
+                                        <span
+                                            className="text-xs truncate max-w-md"
+                                            style={{ color: 'var(--color-text-primary)' }}
+                                            title={activeVault?.path}
+                                        >
+                                            {activeVault?.path || 'No path'}
+                                        </span>
+                                    </div>
+                                </div>
+                            </div>
+                        </div>
+
+                        {/* Verification Section */}
+                        <div className="space-y-3">
+                            <div className="flex items-center justify-between">
+                                <h3 className="font-medium" style={{ color: 'var(--color-text-primary)' }}>
+                                    Integrity Check
+                                </h3>
+                                <button
+                                    onClick={handleVerify}
+                                    disabled={isVerifying || !activeVault?.path}
+                                    className="flex items-center gap-2 px-4 py-2 rounded-lg font-medium transition-colors disabled:opacity-50"
+                                    style={{
+                                        backgroundColor: 'var(--color-primary)',
+                                        color: 'white'
+                                    }}
+                                >
+                                    {isVerifying ? (
+                                        <>
+                                            <RefreshCw size={16} className="animate-spin" />
+                                            Verifying...
+                                        </>
                                     ) : (
-                                        <AlertTriangle size={20} style={{ color: 'rgb(239, 68, 68)' }} />
+                                        <>
+                                            <Shield size={16} />
+                                            Verify Now
+                                        </>
                                     )}
-                                    <div className="flex-1">
-                                        <div className="font-medium mb-1" style={{
-                                            color: verificationResult.valid ? 'rgb(34, 197, 94)' : 'rgb(239, 68, 68)'
-                                        }}>
-                                            {verificationResult.valid ? 'Database is valid' : 'Database verification failed'}
-                                        </div>
-                                        {verificationResult.error && (
-                                            <div className="text-sm" style={{ color: 'var(--color-text-secondary)' }}>
-                                                {verificationResult.error}
-                                            </div>
-                                        )}
-                                        {verificationResult.details && (
-                                            <div className="mt-2 text-sm space-y-1" style={{ color: 'var(--color-text-secondary)' }}>
-                                                <div>Groups: {verificationResult.details.totalGroups}</div>
-                                                <div>Entries: {verificationResult.details.totalEntries}</div>
-                                                <div>Database: {verificationResult.details.databaseName}</div>
+                                </button>
+                            </div>
+
+                            {verificationResult && (() => {
+                                const valid = isReportValid(verificationResult);
+                                return (
+                                    <div
+                                        className="p-4 rounded-lg"
+                                        style={{
+                                            backgroundColor: valid
+                                                ? 'rgba(34, 197, 94, 0.1)'
+                                                : 'rgba(239, 68, 68, 0.1)',
+                                            border: `1px solid ${valid ? 'rgb(34, 197, 94)' : 'rgb(239, 68, 68)'}`
+                                        }}
+                                    >
+                                        <div className="flex items-start gap-3">
+                                            {valid ? (
+                                                <CheckCircle size={20} style={{ color: 'rgb(34, 197, 94)' }} />
+                                            ) : (
+                                                <AlertTriangle size={20} style={{ color: 'rgb(239, 68, 68)' }} />
+                                            )}
+                                            <div className="flex-1 min-w-0">
+                                                <div className="font-medium mb-1" style={{
+                                                    color: valid ? 'rgb(34, 197, 94)' : 'rgb(239, 68, 68)'
+                                                }}>
+                                                    {valid ? 'Database is valid' : 'Database verification failed'}
+                                                </div>
+                                                <div className="text-sm" style={{ color: 'var(--color-text-secondary)' }}>
+                                                    {verificationResult.format || 'Unknown format'}
+                                                    {` · ${verificationResult.blocks} verified block(s)`}
+                                                    {verificationResult.entries > 0 && ` · ${verificationResult.groups} groups · ${verificationResult.entries} entries · ${verificationResult.attachments} attachments`}
+                                                </div>
+                                                {verificationResult.findings.length > 0 && (
+                                                    <ul className="mt-2 space-y-1 text-xs max-h-48 overflow-y-auto">
+                                                        {verificationResult.findings.map((finding, index) => {
+                                                            const { color, icon: Icon } = SEVERITY_STYLES[finding.severity];
+                                                            return (
+                                                                <li key={index} className="flex items-start gap-2" title={finding.check}>
+                                                                    <Icon size={12} className="shrink-0 mt-0.5" style={{ color }} />
+                                                                    <span style={{ color: 'var(--color-text-primary)' }}>{finding.message}</span>
+                                                                </li>
+                                                            );
+                                                        })}
+                                                    </ul>
+                                                )}
+                                                {!valid && (
+                                                    <div className="mt-2 text-xs" style={{ color: 'var(--color-text-secondary)' }}>
+                                                        Restore a backup below, or use Repair to recover what can still be read.
+                                                    </div>
+                                                )}
                                             </div>
-                                        )}
+                                        </div>
                                     </div>
+                                );
+                            })()}
+                        </div>
+
+                        {/* Compatibility Section */}
+                        <div className="space-y-3">
+                            <div className="flex items-center justify-between">
+                                <div>
+                                    <h3 className="font-medium" style={{ color: 'var(--color-text-primary)' }}>
+                                        Compatibility
+                                    </h3>
+                                    <p className="text-xs mt-1" style={{ color: 'var(--color-text-tertiary)' }}>
+                                        Find values KeePassXC or KeePass 2 reject or read differently, such as empty booleans or unknown icons.
+                                    </p>
                                 </div>
+                                <button
+                                    onClick={() => handleLint(false)}
+                                    disabled={isLinting || !activeVault?.path}
+                                    className="flex items-center gap-2 px-3 py-1.5 rounded text-sm shrink-0 ml-4 disabled:opacity-50"
+                                    style={{
+                                        color: 'var(--color-primary)',
+                                        backgroundColor: 'var(--color-bg-secondary)'
+                                    }}
+                                >
+                                    {isLinting ? <RefreshCw size={14} className="animate-spin" /> : <Shield size={14} />}
+                                    Check
+                                </button>
                             </div>
-                        )}
-                    </div>
 
-                    {/* Backup Section */}
-                    <div className="space-y-3">
-                        <div className="flex items-center justify-between">
-                            <h3 className="font-medium" style={{ color: 'var(--color-text-primary)' }}>
-                                Backups
-                            </h3>
-                            <button
-                                onClick={loadBackups}
-                                className="flex items-center gap-2 px-3 py-1.5 rounded text-sm"
-                                style={{
-                                    color: 'var(--color-primary)',
-                                    backgroundColor: 'var(--color-bg-secondary)'
-                                }}
-                            >
-                                <RefreshCw size={14} />
-                                Refresh
-                            </button>
+                            {lintReport && (
+                                <div
+                                    className="p-4 rounded-lg space-y-2 text-sm"
+                                    style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                                >
+                                    {lintReport.issues.length === 0 ? (
+                                        <div className="flex items-center gap-2" style={{ color: 'rgb(34, 197, 94)' }}>
+                                            <CheckCircle size={14} />
+                                            No compatibility issues found
+                                        </div>
+                                    ) : (
+                                        <>
+                                            <ul className="space-y-2 text-xs max-h-48 overflow-y-auto">
+                                                {lintReport.issues.map((issue, index) => {
+                                                    const { color, icon: Icon } = SEVERITY_STYLES[issue.severity];
+                                                    return (
+                                                        <li key={index} className="flex items-start gap-2" title={issue.check}>
+                                                            <Icon size={12} className="shrink-0 mt-0.5" style={{ color }} />
+                                                            <div>
+                                                                <div style={{ color: 'var(--color-text-primary)' }}>{issue.message}</div>
+                                                                <div style={{ color: 'var(--color-text-secondary)' }}>
+                                                                    {issue.fixed ? 'Fixed: ' : 'Fix: '}{issue.fix}
+                                                                </div>
+                                                            </div>
+                                                        </li>
+                                                    );
+                                                })}
+                                            </ul>
+                                            {lintReport.fixed === 0 && !activeVault?.readOnly && (
+                                                <button
+                                                    onClick={() => handleLint(true)}
+                                                    disabled={isLinting}
+                                                    className="flex items-center gap-2 px-3 py-1.5 rounded text-sm disabled:opacity-50"
+                                                    style={{ backgroundColor: 'var(--color-primary)', color: 'white' }}
+                                                >
+                                                    <Wrench size={14} />
+                                                    Fix {lintReport.issues.length} Issue(s)
+                                                </button>
+                                            )}
+                                        </>
+                                    )}
+                                </div>
+                            )}
                         </div>
 
-                        {backups.length === 0 ? (
-                            <div
-                                className="p-8 text-center rounded-lg"
-                                style={{ backgroundColor: 'var(--color-bg-secondary)' }}
-                            >
-                                <FileText size={32} className="mx-auto mb-2" style={{ color: 'var(--color-text-tertiary)' }} />
-                                <div style={{ color: 'var(--color-text-secondary)' }}>
-                                    No backups found
+                        {/* Backup Section */}
+                        <div className="space-y-3">
+                            <div className="flex items-center justify-between">
+                                <h3 className="font-medium" style={{ color: 'var(--color-text-primary)' }}>
+                                    Backups
+                                </h3>
+                                <button
+                                    onClick={loadBackups}
+                                    className="flex items-center gap-2 px-3 py-1.5 rounded text-sm"
+                                    style={{
+                                        color: 'var(--color-primary)',
+                                        backgroundColor: 'var(--color-bg-secondary)'
+                                    }}
+                                >
+                                    <RefreshCw size={14} />
+                                    Refresh
+                                </button>
+                            </div>
+
+                            {backups.length === 0 ? (
+                                <div
+                                    className="p-8 text-center rounded-lg"
+                                    style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                                >
+                                    <FileText size={32} className="mx-auto mb-2" style={{ color: 'var(--color-text-tertiary)' }} />
+                                    <div style={{ color: 'var(--color-text-secondary)' }}>
+                                        No backups found
+                                    </div>
+                                    <div className="text-sm mt-1" style={{ color: 'var(--color-text-tertiary)' }}>
+                                        Backups are created automatically when you save
+                                    </div>
                                 </div>
-                                <div className="text-sm mt-1" style={{ color: 'var(--color-text-tertiary)' }}>
-                                    Backups are created automatically when you save
+                            ) : (
+                                <div className="space-y-2">
+                                    {backups.map((backup) => (
+                                        <React.Fragment key={backup.id}>
+                                            <div
+                                                className="flex items-center justify-between p-3 rounded-lg"
+                                                style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                                            >
+                                                <div className="flex items-center gap-3 flex-1 min-w-0">
+                                                    <Clock size={16} style={{ color: 'var(--color-text-tertiary)' }} />
+                                                    <div className="flex-1 min-w-0">
+                                                        <div
+                                                            className="text-sm truncate"
+                                                            style={{ color: 'var(--color-text-primary)' }}
+                                                            title={backup.path}
+                                                        >
+                                                            {formatBackupDate(backup)}
+                                                        </div>
+                                                        <div className="text-xs" style={{ color: 'var(--color-text-tertiary)' }}>
+                                                            {formatSize(backup.size)}
+                                                            {backup.entryCount !== undefined && ` · ${backup.entryCount} entries`}
+                                                        </div>
+                                                    </div>
+                                                </div>
+                                                <div className="flex items-center gap-2 ml-3">
+                                                    <button
+                                                        onClick={() => handleCompareBackup(backup)}
+                                                        disabled={comparingId !== null}
+                                                        className="flex items-center gap-1 px-3 py-1.5 rounded text-sm disabled:opacity-50"
+                                                        style={{
+                                                            color: 'var(--color-text-primary)',
+                                                            backgroundColor: 'var(--color-bg-primary)'
+                                                        }}
+                                                        title="Show what restoring this backup would change"
+                                                    >
+                                                        {comparingId === backup.id ? <RefreshCw size={14} className="animate-spin" /> : <GitCompare size={14} />}
+                                                        Changes
+                                                    </button>
+                                                    <button
+                                                        onClick={() => handleRestoreBackup(backup)}
+                                                        className="px-3 py-1.5 rounded text-sm font-medium"
+                                                        style={{
+                                                            backgroundColor: 'var(--color-primary)',
+                                                            color: 'white'
+                                                        }}
+                                                    >
+                                                        Restore
+                                                    </button>
+                                                </div>
+                                            </div>
+                                            {review?.backupId === backup.id && (
+                                                <VaultDiffView diff={review.diff} caption="Current file → this backup" />
+                                            )}
+                                        </React.Fragment>
+                                    ))}
+                                </div>
+                            )}
+                        </div>
+
+                        {/* Repair Section */}
+                        <div className="space-y-3">
+                            <div className="flex items-center justify-between">
+                                <div>
+                                    <h3 className="font-medium" style={{ color: 'var(--color-text-primary)' }}>
+                                        Repair
+                                    </h3>
+                                    <p className="text-xs mt-1" style={{ color: 'var(--color-text-tertiary)' }}>
+                                        Recover what can still be read from a damaged vault file into a new file. The damaged file is not changed.
+                                    </p>
                                 </div>
+                                <button
+                                    onClick={handleRepair}
+                                    disabled={isRepairing || !activeVault}
+                                    className="flex items-center gap-2 px-3 py-1.5 rounded text-sm shrink-0 ml-4 disabled:opacity-50"
+                                    style={{
+                                        color: 'var(--color-primary)',
+                                        backgroundColor: 'var(--color-bg-secondary)'
+                                    }}
+                                >
+                                    {isRepairing ? <RefreshCw size={14} className="animate-spin" /> : <Wrench size={14} />}
+                                    Repair a File...
+                                </button>
                             </div>
-                        ) : (
-                            <div className="space-y-2">
-                                {backups.map((backup, index) => (
-                                    <div
-                                        key={index}
-                                        className="flex items-center justify-between p-3 rounded-lg"
-                                        style={{ backgroundColor: 'var(--color-bg-secondary)' }}
-                                    >
-                                        <div className="flex items-center gap-3 flex-1 min-w-0">
-                                            <Clock size={16} style={{ color: 'var(--color-text-tertiary)' }} />
-                                            <div className="flex-1 min-w-0">
-                                                <div
-                                                    className="text-sm truncate"
-                                                    style={{ color: 'var(--color-text-primary)' }}
-                                                    title={backup}
-                                                >
-                                                    {backup.split('/').pop()}
-                                                </div>
+
+                            {repair && (
+                                <div
+                                    className="p-4 rounded-lg space-y-2 text-sm"
+                                    style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                                >
+                                    <div className="truncate" style={{ color: 'var(--color-text-primary)' }} title={repair.target}>
+                                        Saved as {repair.target}
+                                    </div>
+                                    <div style={{ color: 'var(--color-text-secondary)' }}>
+                                        Recovered {repair.report.entries} entries in {repair.report.groups} groups
+                                        {` · ${repair.report.historyItems} history versions · ${repair.report.attachments} attachments`}
+                                    </div>
+                                    {repair.report.unverifiedBytes > 0 && (
+                                        <div className="flex items-start gap-2" style={{ color: 'rgb(217, 119, 6)' }}>
+                                            <AlertTriangle size={14} className="shrink-0 mt-0.5" />
+                                            Part of the data could not be verified. Check the entries recovered last before relying on them.
+                                        </div>
+                                    )}
+                                    {repair.report.lost.length > 0 ? (
+                                        <>
+                                            <div className="font-medium" style={{ color: 'rgb(239, 68, 68)' }}>
+                                                Could not be recovered:
                                             </div>
+                                            <ul className="space-y-1 text-xs max-h-40 overflow-y-auto" style={{ color: 'var(--color-text-secondary)' }}>
+                                                {repair.report.lost.map((line, index) => <li key={index}>• {line}</li>)}
+                                            </ul>
+                                        </>
+                                    ) : (
+                                        <div className="flex items-center gap-2" style={{ color: 'rgb(34, 197, 94)' }}>
+                                            <CheckCircle size={14} />
+                                            Nothing was lost
                                         </div>
-                                        <button
-                                            onClick={() => handleRestoreBackup(backup)}
-                                            className="px-3 py-1.5 rounded text-sm font-medium"
-                                            style={{
-                                                backgroundColor: 'var(--color-primary)',
-                                                color: 'white'
-                                            }}
-                                        >
-                                            Restore
-                                        </button>
-                                    </div>
-                                ))}
+                                    )}
+                                </div>
+                            )}
+                        </div>
+
+                        {/* Info Section */}
+                        <div
+                            className="p-4 rounded-lg text-sm"
+                            style={{
+                                backgroundColor: 'rgba(59, 130, 246, 0.1)',
+                                border: '1px solid rgba(59, 130, 246, 0.3)'
+                            }}
+                        >
+                            <div className="font-medium mb-2" style={{ color: 'rgb(59, 130, 246)' }}>
+                                Protection Features
                             </div>
-                        )}
+                            <ul className="space-y-1" style={{ color: 'var(--color-text-secondary)' }}>
+                                <li>• Automatic backup before each save</li>
+                                <li>• Read-after-write verification</li>
+                                <li>• Automatic rollback on corruption</li>
+                                <li>• Old backups pruned by count, age and size (Settings → Security)</li>
+                            </ul>
+                        </div>
                     </div>
 
-                    {/* Info Section */}
+                    {/* Footer */}
                     <div
-                        className="p-4 rounded-lg text-sm"
-                        style={{
-                            backgroundColor: 'rgba(59, 130, 246, 0.1)',
-                            border: '1px solid rgba(59, 130, 246, 0.3)'
-                        }}
+                        className="flex justify-end gap-3 p-4"
+                        style={{ borderTop: '1px solid var(--color-border-light)' }}
                     >
-                        <div className="font-medium mb-2" style={{ color: 'rgb(59, 130, 246)' }}>
-                            Protection Features
-                        </div>
-                        <ul className="space-y-1" style={{ color: 'var(--color-text-secondary)' }}>
-                            <li>• Automatic backup before each save</li>
-                            <li>• Read-after-write verification</li>
-                            <li>• Automatic rollback on corruption</li>
-                            <li>• Up to 5 recent backups retained</li>
-                        </ul>
+                        <button
+                            onClick={onClose}
+                            className="px-4 py-2 rounded-lg font-medium"
+                            style={{
+                                backgroundColor: 'var(--color-bg-secondary)',
+                                color: 'var(--color-text-primary)'
+                            }}
+                        >
+                            Close
+                        </button>
                     </div>
                 </div>
-
-                {/* Footer */}
-                <div
-                    className="flex justify-end gap-3 p-4"
-                    style={{ borderTop: '1px solid var(--color-border-light)' }}
-                >
-                    <button
-                        onClick={onClose}
-                        className="px-4 py-2 rounded-lg font-medium"
-                        style={{
-                            backgroundColor: 'var(--color-bg-secondary)',
-                            color: 'var(--color-text-primary)'
-                        }}
-                    >
-                        Close
-                    </button>
-                </div>
             </div>
-        </div>,
+
+            <PasswordPromptModal
+                isOpen={passwordFor !== null}
+                title="Unlock Backup"
+                description="This backup does not open with the vault's current credentials. Enter the password it was saved with."
+                onClose={() => setPasswordFor(null)}
+                onConfirm={(password) => { if (passwordFor) handleCompareBackup(passwordFor, password); }}
+            />
+
+            <PasswordPromptModal
+                isOpen={repairPending !== null}
+                title="Unlock Damaged File"
+                description="This file does not open with the vault's current credentials. Enter its password."
+                onClose={() => setRepairPending(null)}
+                onConfirm={(password) => { if (repairPending) runRepair(repairPending.source, repairPending.target, password); }}
+            />
+        </>,
         document.body
     );
 };
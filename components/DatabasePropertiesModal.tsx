@@ -1,9 +1,15 @@
 import React, { useState, useEffect } from 'react';
-import { X, Database, Folder, File, Calendar, HardDrive, Shield, Globe, Key, Lock, Save, RefreshCw, Info } from 'lucide-react';
+import { X, Database, Folder, File, Calendar, HardDrive, Shield, Globe, Key, Lock, Save, RefreshCw, Info, Copy, Plus, Trash2, AlertTriangle, History } from 'lucide-react';
 import { Vault } from '../types';
 import { format } from 'date-fns';
 import { useVault } from '../context/VaultContext';
-import * as kdbxweb from 'kdbxweb';
+import { listen } from '@tauri-apps/api/event';
+import { ask, open as openDialog } from '@tauri-apps/plugin-dialog';
+import { BackupMirror, MirrorConfig, getBackupMirrors, getVaultIdentity, setBackupMirrors } from '../services/databaseIntegrityService';
+import { errorMessage } from '../services/appError';
+import { CryptoSettings, KdfBenchmark, benchmarkKdf, isTwofishVault } from '../services/encryptionService';
+import { HistoryStatus, deleteVaultHistory, getVaultHistory, setVaultHistory } from '../services/historyService';
+import { VaultHistoryModal } from './VaultHistoryModal';
 
 // --- Helper functions for encryption parameter display ---
 
@@ -54,6 +60,7 @@ const getCipherName = (cipherUuid: any): string => {
     const cipherMap: Record<string, string> = {
         'McHy5r9xQ1C+WAUhavxa/w==': 'AES-256',
         '1gOKK4tvTLWlJDOaMdu1mg==': 'ChaCha20',
+        'rWjyn1dvS7mjatR6+WU0bA==': 'Twofish',
     };
     return cipherMap[uuid] || 'Unknown';
 };
@@ -111,10 +118,10 @@ interface DatabasePropertiesModalProps {
     onChangeCredentials?: () => void;
 }
 
-type Tab = 'general' | 'security' | 'browser';
+type Tab = 'general' | 'security' | 'backup' | 'browser';
 
 export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = ({ isOpen, onClose, vault, stats, onChangeCredentials }) => {
-    const { saveVault, refreshVault } = useVault();
+    const { saveVault, refreshVault, changeEncryption } = useVault();
     const [activeTab, setActiveTab] = useState<Tab>('general');
     const [dbName, setDbName] = useState('');
     const [dbDesc, setDbDesc] = useState('');
@@ -129,7 +136,18 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
     const [secCompression, setSecCompression] = useState<number>(1); // 0=None, 1=GZip
     const [secEncryption, setSecEncryption] = useState<string>('ChaCha20');
     const [secKdf, setSecKdf] = useState<string>('Argon2d');
+    const [secRounds, setSecRounds] = useState<number>(60000); // AES-KDF
     const [securityChanged, setSecurityChanged] = useState(false);
+    const [benchmark, setBenchmark] = useState<KdfBenchmark | null>(null);
+
+    // Backup mirror state (stored by the backend, applied immediately)
+    const [mirrors, setMirrors] = useState<BackupMirror[]>([]);
+    const [mirrorError, setMirrorError] = useState<string | null>(null);
+
+    // Git-backed history (opt-in, stored by the backend)
+    const [history, setHistory] = useState<HistoryStatus | null>(null);
+    const [historyError, setHistoryError] = useState<string | null>(null);
+    const [historyOpen, setHistoryOpen] = useState(false);
 
     useEffect(() => {
         if (isOpen && vault) {
@@ -145,14 +163,18 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
             // Compression
             setSecCompression(header.compression ?? 1);
 
-            // Encryption cipher
-            const cipherName = getCipherName(header.dataCipherUuid);
+            // Encryption cipher; a Twofish vault is loaded from a ChaCha20 copy (see loadVault)
+            const cipherName = isTwofishVault(vault.db) ? 'Twofish' : getCipherName(header.dataCipherUuid);
             setSecEncryption(cipherName !== 'Unknown' ? cipherName : 'ChaCha20');
 
             // KDF type
             const kdfUuid = getKdfParam(kdfParams, '$UUID');
             const kdfNameValue = getKdfName(kdfUuid);
-            setSecKdf(kdfNameValue.startsWith('Argon2') ? kdfNameValue : 'Argon2d');
+            setSecKdf(kdfNameValue !== 'Unknown' ? kdfNameValue : 'Argon2d');
+
+            // KDF parameters (AES-KDF)
+            const rounds = getKdfParam(kdfParams, 'R') ?? header.keyEncryptionRounds;
+            if (rounds !== undefined) setSecRounds(rounds);
 
             // KDF parameters (Argon2)
             const iterations = getKdfParam(kdfParams, 'I');
@@ -168,6 +190,52 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
         }
     }, [isOpen, vault]);
 
+    useEffect(() => {
+        if (!isOpen || !vault?.path) return;
+        const path = vault.path;
+        const vaultId = getVaultIdentity(vault.db);
+
+        const loadMirrors = () => getBackupMirrors(path, vaultId).then(setMirrors);
+        loadMirrors();
+        setMirrorError(null);
+        getVaultHistory(path, vault.db).then(setHistory).catch(() => setHistory(null));
+        setHistoryError(null);
+
+        // Mirrors are copied in the background after each save
+        const unlisten = listen('backup-mirrors-updated', () => loadMirrors());
+        return () => {
+            unlisten.then(fn => fn());
+        };
+    }, [isOpen, vault]);
+
+    const cryptoSettings = (): CryptoSettings => {
+        const isArgon2 = secKdf.startsWith('Argon2');
+        return {
+            cipher: secEncryption as CryptoSettings['cipher'],
+            kdf: secKdf as CryptoSettings['kdf'],
+            iterations: isArgon2 ? secIterations : secRounds,
+            memory: isArgon2 ? secMemory * 1024 * 1024 : undefined,
+            parallelism: isArgon2 ? secParallelism : undefined,
+            compression: secCompression === 1
+        };
+    };
+
+    // Calibration hint: how long unlocking takes with the chosen KDF on this device
+    useEffect(() => {
+        if (!isOpen || activeTab !== 'security') return;
+        let cancelled = false;
+        setBenchmark(null);
+        const timer = setTimeout(() => {
+            benchmarkKdf(cryptoSettings())
+                .then(result => { if (!cancelled) setBenchmark(result); })
+                .catch(() => { if (!cancelled) setBenchmark(null); });
+        }, 500);
+        return () => {
+            cancelled = true;
+            clearTimeout(timer);
+        };
+    }, [isOpen, activeTab, secKdf, secIterations, secMemory, secParallelism, secRounds]);
+
     if (!isOpen) return null;
 
     const handleSave = async () => {
@@ -189,63 +257,79 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
             (vault.db.meta as any).descriptionChanged = new Date();
             vault.db.meta.defaultUserChanged = new Date();
 
-            // Update security settings if changed
-            if (securityChanged) {
-                const header = vault.db.header;
-
-                // Update compression
-                header.compression = secCompression;
-
-                // Update encryption cipher
-                if (secEncryption === 'AES-256') {
-                    header.dataCipherUuid = new kdbxweb.KdbxUuid(kdbxweb.Consts.CipherId.Aes);
-                } else if (secEncryption === 'ChaCha20') {
-                    header.dataCipherUuid = new kdbxweb.KdbxUuid(kdbxweb.Consts.CipherId.ChaCha20);
-                }
-
-                // Update KDF type (use db.setKdf as KeeWeb does)
-                const currentKdfUuid = getKdfParam(header.kdfParameters, '$UUID');
-                const currentKdfName = getKdfName(currentKdfUuid);
-                if (secKdf !== currentKdfName) {
-                    // Change KDF type
-                    switch (secKdf) {
-                        case 'Argon2d':
-                            vault.db.setKdf(kdbxweb.Consts.KdfId.Argon2d);
-                            break;
-                        case 'Argon2id':
-                            vault.db.setKdf(kdbxweb.Consts.KdfId.Argon2id);
-                            break;
-                        case 'AES-KDF':
-                            vault.db.setKdf(kdbxweb.Consts.KdfId.Aes);
-                            break;
-                    }
-                }
+            await saveVault(vault.id);
 
-                // Update KDF parameters using kdbxweb.Int64.from() as KeeWeb does
-                // https://github.com/keeweb/keeweb/blob/master/app/scripts/models/file-model.js
-                const kdfParams = vault.db.header.kdfParameters;
-                if (kdfParams && secKdf.startsWith('Argon2')) {
-                    const ValueType = kdbxweb.VarDictionary.ValueType;
-                    // Memory (M) in bytes
-                    kdfParams.set('M', ValueType.UInt64, kdbxweb.Int64.from(secMemory * 1024 * 1024));
-                    // Iterations (I)
-                    kdfParams.set('I', ValueType.UInt64, kdbxweb.Int64.from(secIterations));
-                    // Parallelism (P)
-                    kdfParams.set('P', ValueType.UInt32, secParallelism);
-                }
+            // Re-encrypt in the backend, which verifies the result and backs up the old file
+            if (securityChanged) {
+                if (!vault.path) throw new Error('Changing encryption requires a database saved to a file');
+                await changeEncryption(vault.id, cryptoSettings());
             }
 
-            await saveVault(vault.id);
             refreshVault(vault.id);
             onClose();
         } catch (e: any) {
             console.error("Failed to save database properties", e);
-            setSaveError(e?.message || 'Failed to save database properties');
+            setSaveError(errorMessage(e) || 'Failed to save database properties');
         } finally {
             setIsSaving(false);
         }
     };
 
+    const updateMirrors = async (configs: MirrorConfig[]) => {
+        if (!vault.path) return;
+        setMirrorError(null);
+        try {
+            setMirrors(await setBackupMirrors(vault.path, getVaultIdentity(vault.db), configs));
+        } catch (e) {
+            console.error('Failed to update backup mirrors', e);
+            setMirrorError(errorMessage(e));
+        }
+    };
+
+    const mirrorConfigs = (): MirrorConfig[] =>
+        mirrors.map(({ dir, maxCount, maxAgeDays }) => ({ dir, maxCount, maxAgeDays }));
+
+    const handleAddMirror = async () => {
+        const dir = await openDialog({ directory: true, title: 'Choose a mirror folder' });
+        if (typeof dir === 'string') {
+            await updateMirrors([...mirrorConfigs(), { dir }]);
+        }
+    };
+
+    const handleToggleHistory = async (enabled: boolean) => {
+        if (!vault.path) return;
+        setHistoryError(null);
+        try {
+            setHistory(await setVaultHistory(vault.path, vault.db, enabled));
+        } catch (e) {
+            console.error('Failed to update vault history', e);
+            setHistoryError(errorMessage(e));
+        }
+    };
+
+    const handleDeleteHistory = async () => {
+        if (!vault.path) return;
+        const confirmed = await ask(
+            `Delete all ${history?.snapshotCount ?? 0} history snapshots of this database?\n\nBackups are not affected.`,
+            { title: 'Delete History', kind: 'warning', okLabel: 'Delete', cancelLabel: 'Cancel' }
+        );
+        if (!confirmed) return;
+        setHistoryError(null);
+        try {
+            await deleteVaultHistory(vault.path, vault.db);
+            setHistory(await getVaultHistory(vault.path, vault.db));
+        } catch (e) {
+            console.error('Failed to delete vault history', e);
+            setHistoryError(errorMessage(e));
+        }
+    };
+
+    const lastMirrored = mirrors
+        .map(m => m.lastSuccess)
+        .filter((t): t is string => !!t)
+        .sort()
+        .pop();
+
     const handleRevealInFinder = async () => {
         if (vault.path) {
             try {
@@ -286,6 +370,7 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                     {[
                         { id: 'general', label: 'General' },
                         { id: 'security', label: 'Security' },
+                        { id: 'backup', label: 'Backup' },
                         { id: 'browser', label: 'Browser' }
                     ].map((tab) => (
                         <button
@@ -295,7 +380,7 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                                 : 'border-transparent hover:text-gray-700'
                                 }`}
                             style={activeTab !== tab.id ? { color: 'var(--color-text-secondary)' } : {}}
-                            onClick={() => setActiveTab(tab.id as 'general' | 'security' | 'browser')}
+                            onClick={() => setActiveTab(tab.id as Tab)}
                         >
                             {tab.label}
                         </button>
@@ -376,6 +461,14 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                                         <span className="flex items-center gap-1.5" style={{ color: 'var(--color-text-secondary)' }}><File size={11} /> Entries</span>
                                         <span className="font-mono text-[10px]" style={{ color: 'var(--color-text-primary)' }}>{stats.totalEntries}</span>
                                     </div>
+                                    {mirrors.length > 0 && (
+                                        <div className="flex justify-between items-center text-xs">
+                                            <span className="flex items-center gap-1.5" style={{ color: 'var(--color-text-secondary)' }}><Copy size={11} /> Last Mirrored</span>
+                                            <span className="font-mono text-[10px]" style={{ color: 'var(--color-text-primary)' }}>
+                                                {lastMirrored ? format(new Date(lastMirrored), 'PP pp') : 'Never'}
+                                            </span>
+                                        </div>
+                                    )}
                                 </div>
                             </div>
                         </div>
@@ -384,14 +477,9 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                     {activeTab === 'security' && (() => {
                         // Extract encryption parameters from database header
                         const header = vault.db.header;
-                        const kdfParams = header.kdfParameters;
-
-                        const kdfUuid = getKdfParam(kdfParams, '$UUID');
-                        const kdfName = getKdfName(kdfUuid);
-                        const isArgon2 = kdfName.startsWith('Argon2');
 
-                        // AES-KDF parameter
-                        const transformRounds = getKdfParam(kdfParams, 'R') || header.keyEncryptionRounds;
+                        // Parameter fields follow the selected KDF
+                        const isArgon2 = secKdf.startsWith('Argon2');
 
                         // Format version
                         const formatVersion = `KDBX ${header.versionMajor}.${header.versionMinor}`;
@@ -443,6 +531,7 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                                                 >
                                                     <option value="AES-256">AES-256</option>
                                                     <option value="ChaCha20">ChaCha20</option>
+                                                    <option value="Twofish">Twofish</option>
                                                 </select>
                                             </div>
                                             <div>
@@ -549,16 +638,41 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                                     </div>
                                 )}
 
-                                {/* AES-KDF Parameters (read-only) */}
-                                {kdfName === 'AES-KDF' && transformRounds !== undefined && (
+                                {/* KDF Parameters - AES-KDF */}
+                                {secKdf === 'AES-KDF' && (
                                     <div className="border rounded-lg p-3" style={{ backgroundColor: 'var(--color-bg-secondary)', borderColor: 'var(--color-border-light)' }}>
-                                        <div className="flex items-center justify-between">
-                                            <div className="flex items-center gap-2">
-                                                <Shield size={12} className="text-gray-500" />
-                                                <span className="text-xs font-medium" style={{ color: 'var(--color-text-primary)' }}>Key Derivation</span>
-                                            </div>
-                                            <span className="text-xs font-mono" style={{ color: 'var(--color-text-secondary)' }}>{formatNumber(transformRounds)} rounds</span>
-                                        </div>
+                                        <label className="flex items-center gap-2 text-[10px] font-medium mb-1" style={{ color: 'var(--color-text-secondary)' }}>
+                                            <Shield size={12} className="text-gray-500" />
+                                            Transform Rounds
+                                        </label>
+                                        <input
+                                            type="number"
+                                            min={1}
+                                            value={secRounds}
+                                            onChange={(e) => { setSecRounds(Number(e.target.value) || 1); setSecurityChanged(true); }}
+                                            onBlur={(e) => setSecRounds(Math.max(1, Number(e.target.value) || 1))}
+                                            className="w-full px-2 py-1 text-xs border rounded focus:ring-1 focus:ring-indigo-500 focus:border-indigo-500"
+                                            style={{ backgroundColor: 'var(--color-bg-tertiary)', color: 'var(--color-text-primary)', borderColor: 'var(--color-border-medium)' }}
+                                        />
+                                    </div>
+                                )}
+
+                                {/* Calibration hint from the backend KDF benchmark */}
+                                {benchmark && (
+                                    <div className="flex items-center justify-between px-1">
+                                        <p className="text-[10px]" style={{ color: 'var(--color-text-tertiary)' }}>
+                                            Unlocking takes about {(benchmark.estimatedMillis / 1000).toFixed(1)} s on this device
+                                        </p>
+                                        <button
+                                            onClick={() => {
+                                                if (isArgon2) setSecIterations(benchmark.oneSecondIterations);
+                                                else setSecRounds(benchmark.oneSecondIterations);
+                                                setSecurityChanged(true);
+                                            }}
+                                            className="text-[10px] font-medium text-indigo-600 hover:text-indigo-800"
+                                        >
+                                            Use 1 s ({formatNumber(benchmark.oneSecondIterations)} {isArgon2 ? 'iterations' : 'rounds'})
+                                        </button>
                                     </div>
                                 )}
 
@@ -571,13 +685,115 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                                 {securityChanged && (
                                     <div className="flex items-center gap-2 px-2 py-1.5 rounded border border-amber-300 bg-amber-50">
                                         <div className="w-1.5 h-1.5 rounded-full bg-amber-500" />
-                                        <p className="text-[10px] text-amber-700">Settings modified — save to apply</p>
+                                        <p className="text-[10px] text-amber-700">Settings modified — saving re-encrypts the database and keeps a backup of the current file</p>
                                     </div>
                                 )}
                             </div>
                         );
                     })()}
 
+                    {activeTab === 'backup' && (
+                        <div className="space-y-3">
+                            <div className="border rounded-lg" style={{ backgroundColor: 'var(--color-bg-secondary)', borderColor: 'var(--color-border-light)' }}>
+                                <div className="px-3 py-2 border-b flex items-center justify-between" style={{ borderColor: 'var(--color-border-light)' }}>
+                                    <span className="text-xs font-medium" style={{ color: 'var(--color-text-primary)' }}>Mirror Folders</span>
+                                    <button
+                                        onClick={handleAddMirror}
+                                        disabled={!vault.path}
+                                        className="flex items-center gap-1 text-[10px] font-medium text-indigo-600 hover:text-indigo-800 disabled:opacity-50"
+                                    >
+                                        <Plus size={11} /> Add
+                                    </button>
+                                </div>
+                                <div className="p-3 space-y-2">
+                                    {mirrors.length === 0 && (
+                                        <p className="text-[10px]" style={{ color: 'var(--color-text-secondary)' }}>
+                                            {vault.path
+                                                ? 'Add a folder on a USB drive or NAS to receive a copy after every verified save.'
+                                                : 'Mirrors are available for databases saved to a file.'}
+                                        </p>
+                                    )}
+                                    {mirrors.map((mirror, index) => (
+                                        <div key={mirror.dir} className="space-y-1">
+                                            <div className="flex justify-between items-center text-xs gap-2">
+                                                <span className="font-mono truncate text-[10px]" title={mirror.dir} style={{ color: 'var(--color-text-primary)' }}>{mirror.dir}</span>
+                                                <div className="flex items-center gap-1.5 flex-shrink-0">
+                                                    <select
+                                                        value={mirror.maxCount ?? 5}
+                                                        onChange={(e) => updateMirrors(mirrorConfigs().map((m, i) => i === index ? { ...m, maxCount: parseInt(e.target.value) } : m))}
+                                                        className="px-1 py-0.5 text-[10px] border rounded"
+                                                        style={{ backgroundColor: 'var(--color-bg-primary)', color: 'var(--color-text-primary)', borderColor: 'var(--color-border-medium)' }}
+                                                    >
+                                                        {[1, 3, 5, 10, 20].map(n => <option key={n} value={n}>Keep {n}</option>)}
+                                                        <option value={0}>Keep all</option>
+                                                    </select>
+                                                    <button
+                                                        onClick={() => updateMirrors(mirrorConfigs().filter((_, i) => i !== index))}
+                                                        className="text-gray-400 hover:text-red-600"
+                                                        title="Remove mirror (existing copies are kept)"
+                                                    >
+                                                        <Trash2 size={11} />
+                                                    </button>
+                                                </div>
+                                            </div>
+                                            <div className="text-[10px] flex items-center gap-1" style={{ color: mirror.lastError ? 'rgb(220, 38, 38)' : 'var(--color-text-secondary)' }}>
+                                                {mirror.lastError && <AlertTriangle size={10} />}
+                                                {mirror.lastError
+                                                    ? mirror.lastError
+                                                    : mirror.lastSuccess
+                                                        ? `Last copy ${format(new Date(mirror.lastSuccess), 'PP pp')}`
+                                                        : 'No copy yet — written on next save'}
+                                            </div>
+                                        </div>
+                                    ))}
+                                    {mirrorError && <p className="text-[10px] text-red-600">{mirrorError}</p>}
+                                </div>
+                            </div>
+                            <div className="border rounded-lg" style={{ backgroundColor: 'var(--color-bg-secondary)', borderColor: 'var(--color-border-light)' }}>
+                                <div className="px-3 py-2 border-b flex items-center justify-between" style={{ borderColor: 'var(--color-border-light)' }}>
+                                    <span className="text-xs font-medium" style={{ color: 'var(--color-text-primary)' }}>History</span>
+                                    <button
+                                        onClick={() => setHistoryOpen(true)}
+                                        disabled={!history?.snapshotCount}
+                                        className="flex items-center gap-1 text-[10px] font-medium text-indigo-600 hover:text-indigo-800 disabled:opacity-50"
+                                    >
+                                        <History size={11} /> Browse History...
+                                    </button>
+                                </div>
+                                <div className="p-3 space-y-2">
+                                    <label className="flex items-center gap-2 text-xs" style={{ color: 'var(--color-text-primary)' }}>
+                                        <input
+                                            type="checkbox"
+                                            checked={history?.enabled ?? false}
+                                            disabled={!vault.path || vault.readOnly}
+                                            onChange={(e) => handleToggleHistory(e.target.checked)}
+                                        />
+                                        Keep every save in a local history
+                                        {!history?.enabled && !!history?.snapshotCount && (
+                                            <button
+                                                onClick={(e) => { e.preventDefault(); handleDeleteHistory(); }}
+                                                className="ml-auto text-gray-400 hover:text-red-600"
+                                                title="Delete all snapshots"
+                                            >
+                                                <Trash2 size={11} />
+                                            </button>
+                                        )}
+                                    </label>
+                                    <p className="text-[10px]" style={{ color: 'var(--color-text-secondary)' }}>
+                                        {history?.snapshotCount
+                                            ? `${history.snapshotCount} snapshot${history.snapshotCount === 1 ? '' : 's'}, stored encrypted in a private git repository.`
+                                            : 'Snapshots are stored encrypted in a private git repository and are never pruned.'}
+                                    </p>
+                                    {historyError && <p className="text-[10px] text-red-600">{historyError}</p>}
+                                </div>
+                            </div>
+                            <p className="text-[10px] flex items-start gap-1" style={{ color: 'var(--color-text-tertiary)' }}>
+                                <Info size={10} className="mt-0.5 flex-shrink-0" />
+                                A mirror that is not mounted is skipped and retried on the next save; saving never fails because of a mirror.
+                            </p>
+                        </div>
+                    )}
+
                     {activeTab === 'browser' && (
                         <div className="flex flex-col items-center justify-center h-40 text-center space-y-3">
                             <div className="p-3 rounded-full" style={{ backgroundColor: 'var(--color-bg-tertiary)' }}>
@@ -620,6 +836,13 @@ export const DatabasePropertiesModal: React.FC<DatabasePropertiesModalProps> = (
                         Save
                     </button>
                 </div>
+
+                {/* Inside the dialog so clicks in the portal do not reach the backdrop */}
+                <VaultHistoryModal
+                    isOpen={historyOpen}
+                    onClose={() => setHistoryOpen(false)}
+                    vault={vault}
+                />
             </div>
         </div>
     );
@@ -1,8 +1,10 @@
 import React, { useState, useEffect } from 'react';
-import { Settings, Shield, Globe, Terminal, Check, Moon, Sun, Monitor, ChevronRight, Clock, Lock, FileText as FileTextIcon, Hash, Eye, Fingerprint, Save, XCircle, Power, Info } from 'lucide-react';
+import { Settings, Shield, Globe, Terminal, Check, Moon, Sun, Monitor, ChevronRight, Clock, Lock, FileText as FileTextIcon, Hash, Eye, Fingerprint, Save, XCircle, Power, Info, Bug, Download } from 'lucide-react';
 import { getUISettings, saveUISettings, UISettings } from '../services/uiSettingsService';
 import { Image } from '@tauri-apps/api/image';
 import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
+import { invoke } from '@tauri-apps/api/core';
+import { save } from '@tauri-apps/plugin-dialog';
 
 type Tab = 'general' | 'security' | 'browser' | 'ssh';
 
@@ -94,7 +96,7 @@ export const SettingsWindow: React.FC = () => {
         setSettings(s);
     };
 
-    const updateSetting = async (section: 'general' | 'security', key: string, value: string | number | boolean) => {
+    const updateSetting = async (section: 'general' | 'security' | 'updates' | 'backup', key: string, value: string | number | boolean) => {
         if (!settings) return;
 
         const newSettings = {
@@ -109,6 +111,46 @@ export const SettingsWindow: React.FC = () => {
         await saveUISettings(newSettings);
     };
 
+    const setVerboseLogging = async (enabled: boolean) => {
+        await updateSetting('security', 'verboseLogging', enabled);
+        try {
+            await invoke('set_verbose_logging', { enabled });
+        } catch (e) {
+            console.error('Failed to update logging mode:', e);
+        }
+    };
+
+    const exportDiagnostics = async () => {
+        try {
+            const stamp = new Date().toISOString().slice(0, 10);
+            const destination = await save({
+                defaultPath: `keedavault-diagnostics-${stamp}.txt`,
+                filters: [{ name: 'Text', extensions: ['txt'] }]
+            });
+            if (!destination) return;
+
+            await invoke('export_diagnostics', { destination });
+            document.dispatchEvent(new CustomEvent('show-toast', {
+                detail: {
+                    id: crypto.randomUUID(),
+                    type: 'success',
+                    title: 'Diagnostics Exported',
+                    description: 'Attach the file to your bug report'
+                }
+            }));
+        } catch (e) {
+            console.error('Failed to export diagnostics:', e);
+            document.dispatchEvent(new CustomEvent('show-toast', {
+                detail: {
+                    id: crypto.randomUUID(),
+                    type: 'error',
+                    title: 'Export Failed',
+                    description: String(e)
+                }
+            }));
+        }
+    };
+
     const changeAppIcon = async (iconId: string) => {
         const icon = AVAILABLE_ICONS.find(i => i.id === iconId);
         if (!icon) return;
@@ -219,6 +261,16 @@ export const SettingsWindow: React.FC = () => {
                                             onChange={(v) => updateSetting('general', 'recentFileCount', parseInt(v))}
                                             options={[1, 2, 3, 4, 5, 10].map(n => ({ label: `${n} files`, value: String(n) }))}
                                         />
+                                        <Select
+                                            label="Update Channel"
+                                            icon={Download}
+                                            value={settings.updates?.channel ?? 'stable'}
+                                            onChange={(v) => updateSetting('updates', 'channel', v)}
+                                            options={[
+                                                { label: 'Stable', value: 'stable' },
+                                                { label: 'Beta', value: 'beta' },
+                                            ]}
+                                        />
                                         <Toggle
                                             label="Markdown Notes"
                                             icon={FileTextIcon}
@@ -403,6 +455,84 @@ export const SettingsWindow: React.FC = () => {
                                         </div>
                                     </div>
                                 </div>
+
+                                <div>
+                                    <h3 className="text-xs font-semibold uppercase tracking-wider mb-4 px-1" style={{ color: 'var(--color-text-secondary)' }}>Backups</h3>
+                                    <div className="rounded-xl border shadow-sm divide-y px-4" style={{ backgroundColor: 'var(--color-bg-secondary)', borderColor: 'var(--color-border-light)' }}>
+                                        <Toggle
+                                            label="Backup Before Saving"
+                                            icon={Save}
+                                            description="Keep a copy of the previous version on every save"
+                                            checked={settings.security.autoBackup}
+                                            onChange={(v) => updateSetting('security', 'autoBackup', v)}
+                                        />
+                                        <Select
+                                            label="Keep Backups"
+                                            icon={Hash}
+                                            value={String(settings.backup?.maxCount ?? 10)}
+                                            onChange={(v) => updateSetting('backup', 'maxCount', parseInt(v))}
+                                            options={[
+                                                ...[3, 5, 10, 20, 50].map(n => ({ label: `${n} versions`, value: String(n) })),
+                                                { label: 'Unlimited', value: '0' },
+                                            ]}
+                                        />
+                                        <Select
+                                            label="Delete Older Than"
+                                            icon={Clock}
+                                            value={String(settings.backup?.maxAgeDays ?? 0)}
+                                            onChange={(v) => updateSetting('backup', 'maxAgeDays', parseInt(v))}
+                                            options={[
+                                                { label: 'Never', value: '0' },
+                                                { label: '7 days', value: '7' },
+                                                { label: '30 days', value: '30' },
+                                                { label: '90 days', value: '90' },
+                                                { label: '1 year', value: '365' },
+                                            ]}
+                                        />
+                                        <Select
+                                            label="Total Size Limit"
+                                            icon={FileTextIcon}
+                                            value={String(settings.backup?.maxTotalMb ?? 0)}
+                                            onChange={(v) => updateSetting('backup', 'maxTotalMb', parseInt(v))}
+                                            options={[
+                                                { label: 'No limit', value: '0' },
+                                                { label: '10 MB', value: '10' },
+                                                { label: '50 MB', value: '50' },
+                                                { label: '100 MB', value: '100' },
+                                                { label: '500 MB', value: '500' },
+                                            ]}
+                                        />
+                                    </div>
+                                </div>
+
+                                <div>
+                                    <h3 className="text-xs font-semibold uppercase tracking-wider mb-4 px-1" style={{ color: 'var(--color-text-secondary)' }}>Diagnostics</h3>
+                                    <div className="rounded-xl border shadow-sm divide-y px-4" style={{ backgroundColor: 'var(--color-bg-secondary)', borderColor: 'var(--color-border-light)' }}>
+                                        <Toggle
+                                            label="Verbose Logging"
+                                            icon={Bug}
+                                            description="Include file paths and debug details in logs"
+                                            checked={settings.security.verboseLogging ?? false}
+                                            onChange={setVerboseLogging}
+                                        />
+                                        <div className="flex items-center justify-between py-3 group">
+                                            <div className="flex items-start flex-1 pr-4">
+                                                <div className="mr-3 mt-0.5 text-gray-400 group-hover:text-indigo-500 transition-colors"><Download size={18} strokeWidth={1.5} /></div>
+                                                <div>
+                                                    <label className="text-sm font-medium block" style={{ color: 'var(--color-text-primary)' }}>Export Diagnostics</label>
+                                                    <p className="text-xs mt-0.5" style={{ color: 'var(--color-text-secondary)' }}>Save logs and system info for a bug report</p>
+                                                </div>
+                                            </div>
+                                            <button
+                                                onClick={exportDiagnostics}
+                                                className="px-3 py-1.5 rounded-lg text-xs font-semibold transition-colors"
+                                                style={{ backgroundColor: 'var(--color-bg-tertiary)', color: 'var(--color-text-primary)' }}
+                                            >
+                                                Export...
+                                            </button>
+                                        </div>
+                                    </div>
+                                </div>
                             </div>
                         )}
 
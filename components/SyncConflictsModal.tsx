@@ -0,0 +1,262 @@
+import React, { useState, useEffect } from 'react';
+import { createPortal } from 'react-dom';
+import { ask } from '@tauri-apps/plugin-dialog';
+import { X, GitMerge, Archive, RefreshCw, FileText, CloudOff } from 'lucide-react';
+import { useVault } from '../context/VaultContext';
+import { useToast } from './ui/Toaster';
+import { PasswordPromptModal } from './PasswordPromptModal';
+import { ConflictCopy, ConflictKind, archiveSyncConflict, describeMergeSummary, findSyncConflicts } from '../services/syncService';
+import { errorMessage, hasErrorCode } from '../services/appError';
+
+const KIND_LABELS: Record<ConflictKind, string> = {
+    dropbox: 'Dropbox',
+    nextcloud: 'Nextcloud',
+    syncthing: 'Syncthing',
+    iCloud: 'iCloud'
+};
+
+const formatDate = (value?: string | null) => value ? new Date(value).toLocaleString() : 'Unknown';
+
+const formatSize = (bytes: number) => bytes < 1024 * 1024
+    ? `${(bytes / 1024).toFixed(1)} KB`
+    : `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+
+interface SyncConflictsModalProps {
+    isOpen: boolean;
+    onClose: () => void;
+}
+
+export const SyncConflictsModal: React.FC<SyncConflictsModalProps> = ({ isOpen, onClose }) => {
+    const { vaults, activeVaultId, mergeFromFile } = useVault();
+    const { addToast } = useToast();
+    const [conflicts, setConflicts] = useState<ConflictCopy[]>([]);
+    const [busyPath, setBusyPath] = useState<string | null>(null);
+    const [passwordFor, setPasswordFor] = useState<ConflictCopy | null>(null);
+
+    const activeVault = vaults.find(v => v.id === activeVaultId);
+
+    useEffect(() => {
+        if (isOpen && activeVault?.path) {
+            loadConflicts();
+        }
+    }, [isOpen, activeVault?.path]);
+
+    const loadConflicts = async () => {
+        if (!activeVault?.path) return;
+        try {
+            const scan = await findSyncConflicts(activeVault.path);
+            setConflicts(scan.conflicts);
+        } catch (e) {
+            console.error('Failed to scan for sync conflicts:', e);
+            addToast({ title: 'Failed to scan for conflicts', description: errorMessage(e), type: 'error' });
+        }
+    };
+
+    const archive = async (conflict: ConflictCopy) => {
+        if (!activeVault?.path) return;
+        await archiveSyncConflict(activeVault.path, activeVault.db, conflict.path);
+        setConflicts(prev => prev.filter(c => c.path !== conflict.path));
+    };
+
+    // Merge the copy into the vault, then move it out of the synced folder
+    const handleMerge = async (conflict: ConflictCopy, password?: string) => {
+        if (!activeVaultId) return;
+        setBusyPath(conflict.path);
+        try {
+            const summary = await mergeFromFile(activeVaultId, conflict.path, password);
+            await archive(conflict);
+            addToast({ title: 'Conflict merged and archived', description: describeMergeSummary(summary), type: 'success' });
+        } catch (e) {
+            if (hasErrorCode(e, 'WrongCredentials')) {
+                if (password !== undefined) {
+                    addToast({ title: 'Incorrect password', type: 'error' });
+                }
+                setPasswordFor(conflict);
+                return;
+            }
+            addToast({ title: 'Merge failed', description: errorMessage(e), type: 'error' });
+        } finally {
+            setBusyPath(null);
+        }
+    };
+
+    const handleArchive = async (conflict: ConflictCopy) => {
+        const confirmed = await ask(
+            `Archive "${conflict.fileName}" without merging it?\n\nChanges that only exist in this copy will not be in the vault. The copy is kept in the vault's backup folder.`,
+            { title: 'Archive Conflict Copy', kind: 'warning', okLabel: 'Archive', cancelLabel: 'Cancel' }
+        );
+        if (!confirmed) return;
+
+        setBusyPath(conflict.path);
+        try {
+            await archive(conflict);
+            addToast({ title: 'Conflict copy archived', type: 'success' });
+        } catch (e) {
+            addToast({ title: 'Archive failed', description: errorMessage(e), type: 'error' });
+        } finally {
+            setBusyPath(null);
+        }
+    };
+
+    if (!isOpen) return null;
+
+    return createPortal(
+        <>
+            <div
+                className="fixed inset-0 z-50 flex items-center justify-center"
+                style={{ backgroundColor: 'rgba(0, 0, 0, 0.5)' }}
+                onClick={onClose}
+            >
+                <div
+                    className="relative w-full max-w-2xl mx-4 rounded-lg shadow-xl"
+                    style={{
+                        backgroundColor: 'var(--color-bg-primary)',
+                        border: '1px solid var(--color-border-light)',
+                        maxHeight: '80vh',
+                        display: 'flex',
+                        flexDirection: 'column'
+                    }}
+                    onClick={(e) => e.stopPropagation()}
+                >
+                    {/* Header */}
+                    <div
+                        className="flex items-center justify-between p-4"
+                        style={{ borderBottom: '1px solid var(--color-border-light)' }}
+                    >
+                        <div className="flex items-center gap-2">
+                            <GitMerge size={20} style={{ color: 'var(--color-primary)' }} />
+                            <h2 className="text-lg font-semibold" style={{ color: 'var(--color-text-primary)' }}>
+                                Sync Conflicts
+                            </h2>
+                        </div>
+                        <button
+                            onClick={onClose}
+                            className="p-1 rounded hover:bg-opacity-10"
+                            style={{ color: 'var(--color-text-secondary)' }}
+                        >
+                            <X size={20} />
+                        </button>
+                    </div>
+
+                    {/* Content */}
+                    <div className="flex-1 overflow-y-auto p-6 space-y-3">
+                        <div className="flex items-center justify-between">
+                            <p className="text-sm" style={{ color: 'var(--color-text-secondary)' }}>
+                                Copies your sync service created next to {activeVault?.filename}. Merge each one into the vault, then it is moved to the backup folder.
+                            </p>
+                            <button
+                                onClick={loadConflicts}
+                                className="flex items-center gap-2 px-3 py-1.5 rounded text-sm shrink-0 ml-4"
+                                style={{
+                                    color: 'var(--color-primary)',
+                                    backgroundColor: 'var(--color-bg-secondary)'
+                                }}
+                            >
+                                <RefreshCw size={14} />
+                                Refresh
+                            </button>
+                        </div>
+
+                        {conflicts.length === 0 ? (
+                            <div
+                                className="p-8 text-center rounded-lg"
+                                style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                            >
+                                <FileText size={32} className="mx-auto mb-2" style={{ color: 'var(--color-text-tertiary)' }} />
+                                <div style={{ color: 'var(--color-text-secondary)' }}>
+                                    No conflict copies found
+                                </div>
+                            </div>
+                        ) : (
+                            <div className="space-y-2">
+                                {conflicts.map((conflict) => (
+                                    <div
+                                        key={conflict.path}
+                                        className="flex items-center justify-between p-3 rounded-lg"
+                                        style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                                    >
+                                        <div className="flex-1 min-w-0">
+                                            <div
+                                                className="text-sm truncate"
+                                                style={{ color: 'var(--color-text-primary)' }}
+                                                title={conflict.path}
+                                            >
+                                                {conflict.fileName}
+                                            </div>
+                                            <div className="text-xs" style={{ color: 'var(--color-text-tertiary)' }}>
+                                                {KIND_LABELS[conflict.kind]}
+                                                {conflict.device && ` · ${conflict.device}`}
+                                                {conflict.conflictAt && ` · conflict ${formatDate(conflict.conflictAt)}`}
+                                                {` · modified ${formatDate(conflict.modifiedAt)}`}
+                                                {!conflict.placeholder && ` · ${formatSize(conflict.size)}`}
+                                            </div>
+                                            {conflict.placeholder && (
+                                                <div className="flex items-center gap-1 text-xs mt-1" style={{ color: 'var(--color-text-secondary)' }}>
+                                                    <CloudOff size={12} />
+                                                    Not downloaded from iCloud yet. Open the folder in Finder to download it.
+                                                </div>
+                                            )}
+                                        </div>
+                                        <div className="flex items-center gap-2 ml-3">
+                                            <button
+                                                onClick={() => handleMerge(conflict)}
+                                                disabled={conflict.placeholder || busyPath !== null}
+                                                className="flex items-center gap-1 px-3 py-1.5 rounded text-sm font-medium disabled:opacity-50"
+                                                style={{
+                                                    backgroundColor: 'var(--color-primary)',
+                                                    color: 'white'
+                                                }}
+                                            >
+                                                {busyPath === conflict.path ? <RefreshCw size={14} className="animate-spin" /> : <GitMerge size={14} />}
+                                                Merge
+                                            </button>
+                                            <button
+                                                onClick={() => handleArchive(conflict)}
+                                                disabled={conflict.placeholder || busyPath !== null}
+                                                className="flex items-center gap-1 px-3 py-1.5 rounded text-sm disabled:opacity-50"
+                                                style={{
+                                                    color: 'var(--color-text-primary)',
+                                                    backgroundColor: 'var(--color-bg-primary)'
+                                                }}
+                                                title="Archive without merging"
+                                            >
+                                                <Archive size={14} />
+                                                Archive
+                                            </button>
+                                        </div>
+                                    </div>
+                                ))}
+                            </div>
+                        )}
+                    </div>
+
+                    {/* Footer */}
+                    <div
+                        className="flex justify-end gap-3 p-4"
+                        style={{ borderTop: '1px solid var(--color-border-light)' }}
+                    >
+                        <button
+                            onClick={onClose}
+                            className="px-4 py-2 rounded-lg font-medium"
+                            style={{
+                                backgroundColor: 'var(--color-bg-secondary)',
+                                color: 'var(--color-text-primary)'
+                            }}
+                        >
+                            Close
+                        </button>
+                    </div>
+                </div>
+            </div>
+
+            <PasswordPromptModal
+                isOpen={passwordFor !== null}
+                title="Unlock Conflict Copy"
+                description="This copy does not open with the vault's current credentials. Enter its password to merge it."
+                onClose={() => setPasswordFor(null)}
+                onConfirm={(password) => { if (passwordFor) handleMerge(passwordFor, password); }}
+            />
+        </>,
+        document.body
+    );
+};
@@ -1,12 +1,12 @@
 import React from 'react';
 import { Download, ExternalLink, X } from 'lucide-react';
-import { GitHubRelease } from '../services/updateService';
+import { ReleaseInfo } from '../services/updateService';
 import { open } from '@tauri-apps/plugin-shell';
 
 interface UpdateModalProps {
     isOpen: boolean;
     onClose: () => void;
-    release: GitHubRelease;
+    release: ReleaseInfo;
     currentVersion: string;
 }
 
@@ -14,7 +14,7 @@ export const UpdateModal: React.FC<UpdateModalProps> = ({ isOpen, onClose, relea
     if (!isOpen) return null;
 
     const handleDownload = async () => {
-        await open(release.html_url);
+        await open(release.url);
     };
 
     return (
@@ -55,7 +55,7 @@ export const UpdateModal: React.FC<UpdateModalProps> = ({ isOpen, onClose, relea
                         <div className="text-2xl" style={{ color: 'var(--color-text-tertiary)' }}>→</div>
                         <div>
                             <p className="text-xs font-medium" style={{ color: 'var(--color-text-tertiary)' }}>Latest Version</p>
-                            <p className="text-sm font-semibold mt-0.5" style={{ color: 'var(--color-accent)' }}>{release.tag_name}</p>
+                            <p className="text-sm font-semibold mt-0.5" style={{ color: 'var(--color-accent)' }}>v{release.version}</p>
                         </div>
                     </div>
 
@@ -70,13 +70,13 @@ export const UpdateModal: React.FC<UpdateModalProps> = ({ isOpen, onClose, relea
                                 whiteSpace: 'pre-wrap'
                             }}
                         >
-                            {release.body || 'No release notes available.'}
+                            {release.notes || 'No release notes available.'}
                         </div>
                     </div>
 
                     {/* Release Date */}
                     <p className="text-xs" style={{ color: 'var(--color-text-tertiary)' }}>
-                        Released {new Date(release.published_at).toLocaleDateString('en-US', {
+                        Released {new Date(release.publishedAt).toLocaleDateString('en-US', {
                             year: 'numeric',
                             month: 'long',
                             day: 'numeric'
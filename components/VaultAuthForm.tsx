@@ -1,11 +1,14 @@
 import React, { useState, useRef, useEffect } from 'react';
 import { useVault } from '../context/VaultContext';
-import { Lock, Key, FileKey, AlertCircle, HardDrive, X, FolderOpen, PlusCircle, Fingerprint } from 'lucide-react';
+import { Lock, Key, FileKey, AlertCircle, HardDrive, X, FolderOpen, PlusCircle, Fingerprint, Globe, User } from 'lucide-react';
 import { FileSystemFileHandle } from '../types';
 import { SavedVaultInfo } from '../services/storageService';
 import { fileSystem } from '../services/fileSystemAdapter';
 import { modernBiometricService as biometricService } from '../services/modernBiometricService';
 import { getUISettings } from '../services/uiSettingsService';
+import { open as openDialog } from '@tauri-apps/plugin-dialog';
+import { isRemoteLocation, setRemoteCredentials } from '../services/remoteVaultService';
+import { hasErrorCode } from '../services/appError';
 
 interface VaultAuthFormProps {
     onSuccess?: () => void;
@@ -26,6 +29,16 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
     const [biometricAvailable, setBiometricAvailable] = useState(false);
     const [hasSavedPassword, setHasSavedPassword] = useState(false);
     const [touchIdEnabled, setTouchIdEnabled] = useState(false);
+    const [readOnly, setReadOnly] = useState(false);
+    // Remote (WebDAV, S3, SFTP) vaults: URL entry and the server login
+    const [urlInput, setUrlInput] = useState<string | null>(null);
+    const [serverLoginOpen, setServerLoginOpen] = useState(false);
+    const [serverUser, setServerUser] = useState('');
+    const [serverPassword, setServerPassword] = useState('');
+    const [serverKeyFile, setServerKeyFile] = useState('');
+    const isRemote = !!path && isRemoteLocation(path);
+    const isS3 = isRemote && /^s3:/i.test(path!);
+    const isSftp = isRemote && /^sftp:/i.test(path!);
 
     const { addVault, isUnlocking, unlockError, clearError } = useVault();
 
@@ -95,11 +108,44 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
         setPath(null);
         setKeyFile(null);
         setPassword('');
+        setUrlInput(null);
+        setServerLoginOpen(false);
+        setServerUser('');
+        setServerPassword('');
+        setServerKeyFile('');
         clearError();
         setFormError(null);
         if (fileInputRef.current) fileInputRef.current.value = '';
     };
 
+    const handleUrlSubmit = () => {
+        const url = urlInput?.trim() ?? '';
+        if (!isRemoteLocation(url)) {
+            setFormError("Enter a WebDAV URL (https://, webdavs://), an s3:// or an sftp:// URL");
+            return;
+        }
+        setFormError(null);
+        setPath(url);
+        setFile(null);
+        setFileHandle(null);
+        setUrlInput(null);
+    };
+
+    // A login entered in the form replaces the one in the secret store; SFTP without a
+    // key file logs in with ssh-agent
+    const saveServerLogin = async () => {
+        if (path && isSftp && serverKeyFile) {
+            await setRemoteCredentials(path, '', serverPassword, serverKeyFile);
+        } else if (path && isRemote && !isSftp && serverUser) {
+            await setRemoteCredentials(path, serverUser, serverPassword);
+        }
+    };
+
+    const handleBrowseSshKey = async () => {
+        const selected = await openDialog({ multiple: false, directory: false, title: 'Choose SSH Private Key' });
+        if (typeof selected === 'string') setServerKeyFile(selected);
+    };
+
     const handleUnlock = async (e: React.FormEvent) => {
         e.preventDefault();
         clearError();
@@ -111,7 +157,14 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
         }
 
         try {
-            await addVault(path || fileHandle || file!, password, keyFile || undefined);
+            await saveServerLogin();
+        } catch (err) {
+            setFormError("Failed to store the server login: " + String(err));
+            return;
+        }
+
+        try {
+            await addVault(path || fileHandle || file!, password, keyFile || undefined, { readOnly });
 
             // Save password for Touch ID if enabled
             console.log('=== Touch ID Password Save Debug ===');
@@ -138,6 +191,7 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
             onSuccess?.();
         } catch (err) {
             // Error is handled in the VaultContext
+            if (hasErrorCode(err, 'AuthFailed')) setServerLoginOpen(true);
         }
     };
 
@@ -171,7 +225,7 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
             }
 
             console.log('Touch ID: Unlocking vault with saved password...');
-            await addVault(path, savedPassword, keyFile || undefined);
+            await addVault(path, savedPassword, keyFile || undefined, { readOnly });
             resetForm();
             onSuccess?.();
             console.log('Touch ID: Unlock successful!');
@@ -226,10 +280,10 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
                     {(file || path) ? (
                         <div>
                             <div className="w-16 h-16 rounded-[1.25rem] flex items-center justify-center mb-4 shadow-lg bg-gradient-to-br from-blue-500 to-blue-600 text-white mx-auto">
-                                <HardDrive size={32} strokeWidth={1.5} />
+                                {isRemote ? <Globe size={32} strokeWidth={1.5} /> : <HardDrive size={32} strokeWidth={1.5} />}
                             </div>
                             <h2 className="text-xl font-semibold tracking-tight truncate max-w-[280px] mx-auto" style={{ color: 'var(--color-text-primary)' }}>
-                                {file?.name || (path ? path.split(/[/\\]/).pop() : 'Database')}
+                                {file?.name || (path ? path.split(/[?#]/)[0].split(/[/\\]/).pop() : 'Database')}
                             </h2>
                             {path && (
                                 <p className="text-[11px] font-medium mt-1 truncate max-w-[280px] px-4 mx-auto" style={{ color: 'var(--color-text-placeholder)' }}>
@@ -307,6 +361,47 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
                                 Browse
                             </div>
                         </div>
+                        {!initialVaultInfo && (urlInput === null ? (
+                            <button
+                                type="button"
+                                onClick={() => setUrlInput('')}
+                                className="flex items-center gap-1.5 ml-1 pt-1 text-[12px] hover:text-blue-500 transition-colors"
+                                style={{ color: 'var(--color-text-secondary)' }}
+                            >
+                                <Globe size={12} />
+                                Open from a server (WebDAV, S3, SFTP)...
+                            </button>
+                        ) : (
+                            <div className="flex gap-2 pt-1">
+                                <input
+                                    type="url"
+                                    value={urlInput}
+                                    onChange={(e) => setUrlInput(e.target.value)}
+                                    onKeyDown={(e) => {
+                                        if (e.key === 'Enter') {
+                                            e.preventDefault();
+                                            handleUrlSubmit();
+                                        }
+                                    }}
+                                    className="flex-1 px-3 py-2 border rounded-xl text-sm focus:outline-none focus:ring-4 focus:ring-blue-500/10 focus:border-blue-500"
+                                    style={{
+                                        backgroundColor: 'var(--color-bg-primary)',
+                                        borderColor: 'var(--color-border-medium)',
+                                        color: 'var(--color-text-primary)'
+                                    }}
+                                    placeholder="https://cloud.example.com/remote.php/dav/files/me/Vault.kdbx"
+                                    autoFocus
+                                />
+                                <button
+                                    type="button"
+                                    onClick={handleUrlSubmit}
+                                    className="px-3 py-2 rounded-xl text-sm font-medium"
+                                    style={{ backgroundColor: 'var(--color-accent)', color: '#ffffff' }}
+                                >
+                                    Connect
+                                </button>
+                            </div>
+                        ))}
                     </div>
                 )}
 
@@ -322,7 +417,7 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
                                 </div>
                                 <input
                                     type="password"
-                                    required
+                                    required={!keyFile}
                                     value={password}
                                     onChange={(e) => {
                                         setPassword(e.target.value);
@@ -340,7 +435,7 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
                                         borderColor: (unlockError || formError) ? undefined : 'var(--color-border-medium)',
                                         color: 'var(--color-text-primary)'
                                     }}
-                                    placeholder="Enter password..."
+                                    placeholder={keyFile ? "Password (if any)..." : "Enter password..."}
                                     autoFocus
                                 />
                             </div>
@@ -386,6 +481,74 @@ export const VaultAuthForm: React.FC<VaultAuthFormProps & { initialVaultInfo?: S
                                 </div>
                             </div>
                         </div>
+
+                        {/* Server login for remote vaults, kept in the secret store */}
+                        {isRemote && (serverLoginOpen ? (
+                            <div className="space-y-2 pt-1">
+                                <label className="block text-[11px] font-semibold uppercase tracking-wider ml-1" style={{ color: 'var(--color-text-secondary)' }}>
+                                    Server Login
+                                </label>
+                                {(isSftp ? [
+                                    { icon: <FileKey size={16} />, type: 'text', value: serverKeyFile, set: setServerKeyFile, placeholder: 'Private key file (empty: ssh-agent)', browse: true },
+                                    { icon: <Key size={16} />, type: 'password', value: serverPassword, set: setServerPassword, placeholder: 'Key passphrase', browse: false }
+                                ] : [
+                                    { icon: <User size={16} />, type: 'text', value: serverUser, set: setServerUser, placeholder: isS3 ? 'Access key ID' : 'Username', browse: false },
+                                    { icon: <Key size={16} />, type: 'password', value: serverPassword, set: setServerPassword, placeholder: isS3 ? 'Secret access key' : 'Password or app password', browse: false }
+                                ]).map(field => (
+                                    <div key={field.placeholder} className="relative">
+                                        <div className="absolute left-4 top-3" style={{ color: 'var(--color-text-placeholder)' }}>
+                                            {field.icon}
+                                        </div>
+                                        <input
+                                            type={field.type}
+                                            value={field.value}
+                                            onChange={(e) => field.set(e.target.value)}
+                                            className={`w-full pl-11 ${field.browse ? 'pr-20' : 'pr-4'} py-2.5 border rounded-xl text-sm focus:outline-none focus:ring-4 focus:ring-blue-500/10 focus:border-blue-500`}
+                                            style={{
+                                                backgroundColor: 'var(--color-bg-primary)',
+                                                borderColor: 'var(--color-border-medium)',
+                                                color: 'var(--color-text-primary)'
+                                            }}
+                                            placeholder={field.placeholder}
+                                            autoComplete="off"
+                                        />
+                                        {field.browse && (
+                                            <button
+                                                type="button"
+                                                onClick={handleBrowseSshKey}
+                                                className="absolute right-2 top-1.5 px-2 py-1 rounded-lg text-[12px] hover:text-blue-500 transition-colors"
+                                                style={{ color: 'var(--color-text-secondary)' }}
+                                            >
+                                                Browse
+                                            </button>
+                                        )}
+                                    </div>
+                                ))}
+                            </div>
+                        ) : (
+                            <button
+                                type="button"
+                                onClick={() => setServerLoginOpen(true)}
+                                className="flex items-center gap-1.5 ml-1 text-[12px] hover:text-blue-500 transition-colors"
+                                style={{ color: 'var(--color-text-secondary)' }}
+                            >
+                                <User size={12} />
+                                Server login...
+                            </button>
+                        ))}
+
+                        {/* Read-only mode: no lock is taken and nothing is saved */}
+                        {path && (
+                            <label className="flex items-center gap-2 pt-1 text-sm cursor-pointer select-none" style={{ color: 'var(--color-text-secondary)' }}>
+                                <input
+                                    type="checkbox"
+                                    checked={readOnly}
+                                    onChange={(e) => setReadOnly(e.target.checked)}
+                                    className="rounded"
+                                />
+                                Open read-only
+                            </label>
+                        )}
                     </div>
                 )}
 
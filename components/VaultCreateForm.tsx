@@ -47,8 +47,8 @@ export const VaultCreateForm: React.FC<VaultCreateFormProps> = ({ onSuccess, cla
             setFormError("Please enter a database name.");
             return;
         }
-        if (!password) {
-            setFormError("Password is required.");
+        if (!password && !keyFile) {
+            setFormError("Enter a password or choose a key file.");
             return;
         }
 
@@ -123,7 +123,7 @@ export const VaultCreateForm: React.FC<VaultCreateFormProps> = ({ onSuccess, cla
                             </div>
                             <input
                                 type="password"
-                                required
+                                required={!keyFile}
                                 value={password}
                                 onChange={(e) => setPassword(e.target.value)}
                                 className="w-full pl-11 pr-4 py-2.5 border rounded-xl text-sm placeholder:text-gray-400 focus:border-blue-500 focus:ring-4 focus:ring-blue-500/10 focus:outline-none transition-all shadow-sm"
@@ -132,7 +132,7 @@ export const VaultCreateForm: React.FC<VaultCreateFormProps> = ({ onSuccess, cla
                                     borderColor: 'var(--color-border-medium)',
                                     color: 'var(--color-text-primary)'
                                 }}
-                                placeholder="Create strong password"
+                                placeholder={keyFile ? "Optional with a key file" : "Create strong password"}
                             />
                         </div>
                     </div>
@@ -145,7 +145,7 @@ export const VaultCreateForm: React.FC<VaultCreateFormProps> = ({ onSuccess, cla
                             </div>
                             <input
                                 type="password"
-                                required
+                                required={!keyFile}
                                 value={confirmPassword}
                                 onChange={(e) => setConfirmPassword(e.target.value)}
                                 className="w-full pl-11 pr-4 py-2.5 border rounded-xl text-sm placeholder:text-gray-400 focus:border-blue-500 focus:ring-4 focus:ring-blue-500/10 focus:outline-none transition-all shadow-sm"
@@ -204,15 +204,15 @@ export const VaultCreateForm: React.FC<VaultCreateFormProps> = ({ onSuccess, cla
                 <div className="pt-4">
                     <button
                         type="submit"
-                        disabled={isUnlocking || !newName || !password || password !== confirmPassword}
+                        disabled={isUnlocking || !newName || (!password && !keyFile) || password !== confirmPassword}
                         className={`w-full py-3 text-[13px] font-semibold rounded-xl transition-all shadow-sm flex items-center justify-center
                         ${isUnlocking
                                 ? 'cursor-wait'
                                 : 'hover:bg-[#0062cc] active:scale-[0.98] shadow-blue-500/20 disabled:shadow-none disabled:cursor-not-allowed'
                             }`}
                         style={{
-                            backgroundColor: (isUnlocking || !newName || !password || password !== confirmPassword) ? 'var(--color-bg-active)' : 'var(--color-accent)',
-                            color: (isUnlocking || !newName || !password || password !== confirmPassword) ? 'var(--color-text-placeholder)' : '#ffffff'
+                            backgroundColor: (isUnlocking || !newName || (!password && !keyFile) || password !== confirmPassword) ? 'var(--color-bg-active)' : 'var(--color-accent)',
+                            color: (isUnlocking || !newName || (!password && !keyFile) || password !== confirmPassword) ? 'var(--color-text-placeholder)' : '#ffffff'
                         }}
                     >
                         {isUnlocking && <div className="w-4 h-4 border-2 border-white/30 border-t-white rounded-full animate-spin mr-2" />}
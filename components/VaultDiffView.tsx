@@ -0,0 +1,103 @@
+import React, { useState } from 'react';
+import { ArrowRight, ChevronDown, ChevronRight, Folder, Key, History, Paperclip } from 'lucide-react';
+import { ChangeKind, EntryDiff, FieldDiff, GroupDiff, VaultDiff, describeVaultDiff } from '../services/syncService';
+
+const KIND_STYLES: Record<ChangeKind, { label: string; color: string }> = {
+    added: { label: 'Added', color: 'rgb(22, 163, 74)' },
+    changed: { label: 'Changed', color: 'rgb(217, 119, 6)' },
+    removed: { label: 'Removed', color: 'rgb(220, 38, 38)' }
+};
+
+const formatValue = (field: FieldDiff, value?: string | null) => {
+    if (field.masked) return '••••••';
+    if (value === undefined || value === null) return '—';
+    return value === '' ? '(empty)' : value;
+};
+
+const FieldRow: React.FC<{ field: FieldDiff }> = ({ field }) => (
+    <div className="grid grid-cols-[8rem_1fr] gap-2 text-xs">
+        <span className="truncate" style={{ color: 'var(--color-text-secondary)' }} title={field.name}>
+            {field.kind === 'attachment' && <Paperclip size={10} className="inline mr-1" />}
+            {field.name}
+        </span>
+        <span className="min-w-0 break-words" style={{ color: 'var(--color-text-primary)' }}>
+            {field.change === 'added' ? (
+                <span style={{ color: KIND_STYLES.added.color }}>{formatValue(field, field.after)}</span>
+            ) : field.change === 'removed' ? (
+                <span className="line-through" style={{ color: KIND_STYLES.removed.color }}>{formatValue(field, field.before)}</span>
+            ) : (
+                <>
+                    <span className="line-through" style={{ color: 'var(--color-text-tertiary)' }}>{formatValue(field, field.before)}</span>
+                    <ArrowRight size={10} className="inline mx-1" />
+                    {formatValue(field, field.after)}
+                </>
+            )}
+        </span>
+    </div>
+);
+
+const ChangeRow: React.FC<{ change: EntryDiff | GroupDiff; isGroup: boolean }> = ({ change, isGroup }) => {
+    const [expanded, setExpanded] = useState(false);
+    const history = 'history' in change ? change.history : null;
+    const title = isGroup ? (change as GroupDiff).name : (change as EntryDiff).title;
+    const hasDetails = change.fields.length > 0 || !!history;
+
+    return (
+        <div className="p-2 rounded" style={{ backgroundColor: 'var(--color-bg-primary)' }}>
+            <button
+                onClick={() => setExpanded(!expanded)}
+                disabled={!hasDetails}
+                className="w-full flex items-center gap-2 text-xs text-left"
+            >
+                {hasDetails
+                    ? (expanded ? <ChevronDown size={12} /> : <ChevronRight size={12} />)
+                    : <span className="w-3" />}
+                <span className="font-medium w-16 shrink-0" style={{ color: KIND_STYLES[change.kind].color }}>
+                    {KIND_STYLES[change.kind].label}
+                </span>
+                {isGroup
+                    ? <Folder size={12} style={{ color: 'var(--color-text-tertiary)' }} />
+                    : <Key size={12} style={{ color: 'var(--color-text-tertiary)' }} />}
+                <span className="truncate" style={{ color: 'var(--color-text-primary)' }}>{title || '(untitled)'}</span>
+                <span className="truncate ml-auto" style={{ color: 'var(--color-text-tertiary)' }}>
+                    {change.movedFrom
+                        ? <>{change.movedFrom} <ArrowRight size={10} className="inline" /> {change.path}</>
+                        : change.path}
+                </span>
+            </button>
+            {expanded && (
+                <div className="mt-2 ml-5 space-y-1">
+                    {change.fields.map(field => <FieldRow key={`${field.kind}:${field.name}`} field={field} />)}
+                    {history && (
+                        <div className="flex items-center gap-1 text-xs" style={{ color: 'var(--color-text-secondary)' }}>
+                            <History size={10} />
+                            History: {history.countBefore} → {history.countAfter} versions
+                            {history.added > 0 && `, ${history.added} new`}
+                            {history.removed > 0 && `, ${history.removed} dropped`}
+                        </div>
+                    )}
+                </div>
+            )}
+        </div>
+    );
+};
+
+interface VaultDiffViewProps {
+    diff: VaultDiff;
+    /** What the two sides are, e.g. "Current file → backup" */
+    caption?: string;
+}
+
+/**
+ * Field-by-field changes between two vaults; protected values are shown masked
+ */
+export const VaultDiffView: React.FC<VaultDiffViewProps> = ({ diff, caption }) => (
+    <div className="p-3 rounded-lg space-y-2" style={{ backgroundColor: 'var(--color-bg-secondary)' }}>
+        <div className="flex items-center justify-between text-sm">
+            <span className="font-medium" style={{ color: 'var(--color-text-primary)' }}>{describeVaultDiff(diff)}</span>
+            {caption && <span className="text-xs" style={{ color: 'var(--color-text-tertiary)' }}>{caption}</span>}
+        </div>
+        {diff.groups.map(group => <ChangeRow key={group.uuid} change={group} isGroup />)}
+        {diff.entries.map(entry => <ChangeRow key={entry.uuid} change={entry} isGroup={false} />)}
+    </div>
+);
@@ -0,0 +1,267 @@
+import React, { useState, useEffect } from 'react';
+import { createPortal } from 'react-dom';
+import { save } from '@tauri-apps/plugin-dialog';
+import { X, History, RefreshCw, GitCompare, FileDown, FileText } from 'lucide-react';
+import { Vault } from '../types';
+import { useToast } from './ui/Toaster';
+import { PasswordPromptModal } from './PasswordPromptModal';
+import { VaultDiffView } from './VaultDiffView';
+import { Snapshot, diffVaultSnapshots, listVaultSnapshots, restoreVaultSnapshot } from '../services/historyService';
+import { VaultDiff, toVaultCredentials } from '../services/syncService';
+import { errorMessage, hasErrorCode } from '../services/appError';
+
+const formatSize = (bytes: number) => bytes < 1024 * 1024
+    ? `${(bytes / 1024).toFixed(1)} KB`
+    : `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+
+interface VaultHistoryModalProps {
+    isOpen: boolean;
+    onClose: () => void;
+    vault: Vault;
+}
+
+export const VaultHistoryModal: React.FC<VaultHistoryModalProps> = ({ isOpen, onClose, vault }) => {
+    const { addToast } = useToast();
+    const [snapshots, setSnapshots] = useState<Snapshot[]>([]);
+    // Up to two snapshot ids to compare
+    const [selected, setSelected] = useState<string[]>([]);
+    const [diff, setDiff] = useState<VaultDiff | null>(null);
+    const [busy, setBusy] = useState(false);
+    const [passwordPrompt, setPasswordPrompt] = useState(false);
+
+    useEffect(() => {
+        if (isOpen && vault.path) {
+            setSelected([]);
+            setDiff(null);
+            loadSnapshots();
+        }
+    }, [isOpen, vault.path]);
+
+    const loadSnapshots = async () => {
+        if (!vault.path) return;
+        try {
+            setSnapshots(await listVaultSnapshots(vault.path, vault.db));
+        } catch (e) {
+            console.error('Failed to list history snapshots:', e);
+            addToast({ title: 'Failed to load history', description: errorMessage(e), type: 'error' });
+        }
+    };
+
+    const toggleSelected = (id: string) => {
+        setDiff(null);
+        setSelected(prev => prev.includes(id)
+            ? prev.filter(s => s !== id)
+            : [...prev, id].slice(-2));
+    };
+
+    // `password` unlocks the older snapshot, for snapshots saved before a password change
+    const handleCompare = async (password?: string) => {
+        if (!vault.path || selected.length !== 2) return;
+        // Snapshots are listed newest first; compare older → newer
+        const [to, from] = [...selected].sort((a, b) =>
+            snapshots.findIndex(s => s.id === a) - snapshots.findIndex(s => s.id === b));
+        const current = toVaultCredentials(vault.password, vault.keyFileData);
+        const older = password !== undefined ? { password } : current;
+        setBusy(true);
+        try {
+            let result: VaultDiff;
+            try {
+                result = await diffVaultSnapshots(vault.path, vault.db, from, to, older, current);
+            } catch (e) {
+                // Both snapshots may predate the change
+                if (password === undefined || !hasErrorCode(e, 'WrongCredentials')) throw e;
+                result = await diffVaultSnapshots(vault.path, vault.db, from, to, older, older);
+            }
+            setDiff(result);
+        } catch (e) {
+            if (hasErrorCode(e, 'WrongCredentials')) {
+                if (password !== undefined) {
+                    addToast({ title: 'Incorrect password', type: 'error' });
+                }
+                setPasswordPrompt(true);
+                return;
+            }
+            addToast({ title: 'Compare failed', description: errorMessage(e), type: 'error' });
+        } finally {
+            setBusy(false);
+        }
+    };
+
+    const handleRestore = async (snapshot: Snapshot) => {
+        if (!vault.path) return;
+        const stamp = new Date(snapshot.createdAt).toISOString().slice(0, 19).replace(/[:T]/g, '-');
+        const baseName = vault.filename.replace(/\.kdbx$/i, '');
+        const targetPath = await save({
+            defaultPath: `${baseName} (${stamp}).kdbx`,
+            filters: [{ name: 'KeePass Database', extensions: ['kdbx'] }]
+        });
+        if (!targetPath) return;
+
+        setBusy(true);
+        try {
+            await restoreVaultSnapshot(vault.path, vault.db, snapshot.id, targetPath);
+            addToast({ title: 'Snapshot restored', description: `Saved as ${targetPath}`, type: 'success' });
+        } catch (e) {
+            addToast({ title: 'Restore failed', description: errorMessage(e), type: 'error' });
+        } finally {
+            setBusy(false);
+        }
+    };
+
+    if (!isOpen) return null;
+
+    return createPortal(
+        <>
+            <div
+                className="fixed inset-0 z-50 flex items-center justify-center"
+                style={{ backgroundColor: 'rgba(0, 0, 0, 0.5)' }}
+                onClick={onClose}
+            >
+                <div
+                    className="relative w-full max-w-2xl mx-4 rounded-lg shadow-xl"
+                    style={{
+                        backgroundColor: 'var(--color-bg-primary)',
+                        border: '1px solid var(--color-border-light)',
+                        maxHeight: '80vh',
+                        display: 'flex',
+                        flexDirection: 'column'
+                    }}
+                    onClick={(e) => e.stopPropagation()}
+                >
+                    {/* Header */}
+                    <div
+                        className="flex items-center justify-between p-4"
+                        style={{ borderBottom: '1px solid var(--color-border-light)' }}
+                    >
+                        <div className="flex items-center gap-2">
+                            <History size={20} style={{ color: 'var(--color-primary)' }} />
+                            <h2 className="text-lg font-semibold" style={{ color: 'var(--color-text-primary)' }}>
+                                Vault History
+                            </h2>
+                        </div>
+                        <button
+                            onClick={onClose}
+                            className="p-1 rounded hover:bg-opacity-10"
+                            style={{ color: 'var(--color-text-secondary)' }}
+                        >
+                            <X size={20} />
+                        </button>
+                    </div>
+
+                    {/* Content */}
+                    <div className="flex-1 overflow-y-auto p-6 space-y-3">
+                        <div className="flex items-center justify-between">
+                            <p className="text-sm" style={{ color: 'var(--color-text-secondary)' }}>
+                                Every save of {vault.filename} is kept as an encrypted snapshot. Select two snapshots to compare them, or restore one as a new file.
+                            </p>
+                            <div className="flex items-center gap-2 shrink-0 ml-4">
+                                <button
+                                    onClick={() => handleCompare()}
+                                    disabled={selected.length !== 2 || busy}
+                                    className="flex items-center gap-2 px-3 py-1.5 rounded text-sm font-medium disabled:opacity-50"
+                                    style={{ backgroundColor: 'var(--color-primary)', color: 'white' }}
+                                >
+                                    {busy ? <RefreshCw size={14} className="animate-spin" /> : <GitCompare size={14} />}
+                                    Compare
+                                </button>
+                                <button
+                                    onClick={loadSnapshots}
+                                    className="flex items-center gap-2 px-3 py-1.5 rounded text-sm"
+                                    style={{
+                                        color: 'var(--color-primary)',
+                                        backgroundColor: 'var(--color-bg-secondary)'
+                                    }}
+                                >
+                                    <RefreshCw size={14} />
+                                    Refresh
+                                </button>
+                            </div>
+                        </div>
+
+                        {diff && <VaultDiffView diff={diff} caption="Older → newer snapshot" />}
+
+                        {snapshots.length === 0 ? (
+                            <div
+                                className="p-8 text-center rounded-lg"
+                                style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                            >
+                                <FileText size={32} className="mx-auto mb-2" style={{ color: 'var(--color-text-tertiary)' }} />
+                                <div style={{ color: 'var(--color-text-secondary)' }}>
+                                    No snapshots yet
+                                </div>
+                            </div>
+                        ) : (
+                            <div className="space-y-2">
+                                {snapshots.map((snapshot, index) => (
+                                    <label
+                                        key={snapshot.id}
+                                        className="flex items-center justify-between p-3 rounded-lg cursor-pointer"
+                                        style={{ backgroundColor: 'var(--color-bg-secondary)' }}
+                                    >
+                                        <div className="flex items-center gap-3 flex-1 min-w-0">
+                                            <input
+                                                type="checkbox"
+                                                checked={selected.includes(snapshot.id)}
+                                                onChange={() => toggleSelected(snapshot.id)}
+                                            />
+                                            <div className="min-w-0">
+                                                <div className="text-sm" style={{ color: 'var(--color-text-primary)' }}>
+                                                    {new Date(snapshot.createdAt).toLocaleString()}
+                                                    {index === 0 && ' (latest)'}
+                                                </div>
+                                                <div className="text-xs font-mono" style={{ color: 'var(--color-text-tertiary)' }}>
+                                                    {snapshot.id.slice(0, 10)}
+                                                    {` · ${formatSize(snapshot.size)}`}
+                                                    {snapshot.entryCount != null && ` · ${snapshot.entryCount} entries`}
+                                                </div>
+                                            </div>
+                                        </div>
+                                        <button
+                                            onClick={(e) => { e.preventDefault(); handleRestore(snapshot); }}
+                                            disabled={busy}
+                                            className="flex items-center gap-1 px-3 py-1.5 rounded text-sm disabled:opacity-50 ml-3"
+                                            style={{
+                                                color: 'var(--color-text-primary)',
+                                                backgroundColor: 'var(--color-bg-primary)'
+                                            }}
+                                            title="Save this snapshot as a new file"
+                                        >
+                                            <FileDown size={14} />
+                                            Restore As...
+                                        </button>
+                                    </label>
+                                ))}
+                            </div>
+                        )}
+                    </div>
+
+                    {/* Footer */}
+                    <div
+                        className="flex justify-end gap-3 p-4"
+                        style={{ borderTop: '1px solid var(--color-border-light)' }}
+                    >
+                        <button
+                            onClick={onClose}
+                            className="px-4 py-2 rounded-lg font-medium"
+                            style={{
+                                backgroundColor: 'var(--color-bg-secondary)',
+                                color: 'var(--color-text-primary)'
+                            }}
+                        >
+                            Close
+                        </button>
+                    </div>
+                </div>
+            </div>
+
+            <PasswordPromptModal
+                isOpen={passwordPrompt}
+                title="Unlock Older Snapshot"
+                description="This snapshot does not open with the vault's current credentials. Enter the password it was saved with."
+                onClose={() => setPasswordPrompt(false)}
+                onConfirm={(password) => handleCompare(password)}
+            />
+        </>,
+        document.body
+    );
+};
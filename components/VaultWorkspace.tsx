@@ -1,6 +1,6 @@
 import React, { useState, useEffect, useRef } from 'react';
 import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
-import { save, open } from '@tauri-apps/plugin-dialog';
+import { save, open, ask } from '@tauri-apps/plugin-dialog';
 import { writeFile, readFile } from '@tauri-apps/plugin-fs';
 import { invoke } from '@tauri-apps/api/core';
 import { useVault } from '../context/VaultContext';
@@ -19,14 +19,18 @@ import { PasswordGenerator } from './PasswordGenerator';
 import { CreateEntryModal } from './CreateEntryModal';
 import { ChangeCredentialsModal } from './ChangeCredentialsModal';
 import { DatabasePropertiesModal } from './DatabasePropertiesModal';
+import { SyncConflictsModal } from './SyncConflictsModal';
 import { updateWindowMenu } from '../services/windowMenuService';
+import { ConflictFound, DiskChange, describeMergeSummary, findSyncConflicts } from '../services/syncService';
+import { errorMessage, hasErrorCode } from '../services/appError';
 import { VaultGroup, EntryFormData } from '../types';
 
 export const VaultWorkspace: React.FC = () => {
     const [selectedEntryIds, setSelectedEntryIds] = useState<Set<string>>(new Set());
-    const { vaults, activeVaultId, activeGroupId, activeEntries, onAddGroup, onUpdateGroup, onMoveEntry, onMoveEntries, saveVault, onAddEntry, lockVault } = useVault();
+    const { vaults, activeVaultId, activeGroupId, activeEntries, onAddGroup, onUpdateGroup, onMoveEntry, onMoveEntries, saveVault, onAddEntry, lockVault, mergeFromFile, mergeDiskChanges, reloadVault } = useVault();
     const activeVault = vaults.find(v => v.id === activeVaultId);
     const vaultName = activeVault ? activeVault.name : 'KeedaVault';
+    const readOnly = !!activeVault?.readOnly;
 
 
     // UI Settings - Ensure both sidebars are visible by default
@@ -46,13 +50,25 @@ export const VaultWorkspace: React.FC = () => {
     // Update window title when vault changes
     useEffect(() => {
         const win = getCurrentWebviewWindow();
-        const title = vaultName ? `${vaultName} - KeedaVault` : 'KeedaVault';
+        const name = readOnly ? `${vaultName} (Read-Only)` : vaultName;
+        const title = vaultName ? `${name} - KeedaVault` : 'KeedaVault';
         win.setTitle(title);
-    }, [vaultName]);
+    }, [vaultName, readOnly]);
 
-    // Enable database menu items when vault is unlocked and update window menu
+    // Enable database menu items when vault is unlocked; the menu is shared by all
+    // windows, so re-apply this window's read-only state whenever it gains focus
+    useEffect(() => {
+        invoke('set_database_menu_state', { unlocked: true, readOnly });
+        const unlisten = getCurrentWebviewWindow().onFocusChanged(({ payload: focused }) => {
+            if (focused) invoke('set_database_menu_state', { unlocked: true, readOnly });
+        });
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [readOnly]);
+
+    // Update the Window menu when the vault opens; disable database items when it closes
     useEffect(() => {
-        invoke('set_database_menu_state', { unlocked: true });
         updateWindowMenu(); // Update Window menu to show this vault
         return () => {
             invoke('set_database_menu_state', { unlocked: false });
@@ -83,6 +99,7 @@ export const VaultWorkspace: React.FC = () => {
 
     const [importModalOpen, setImportModalOpen] = useState(false);
     const [passwordPrompt, setPasswordPrompt] = useState<{ isOpen: boolean, fileBuffer: ArrayBuffer | null }>({ isOpen: false, fileBuffer: null });
+    const [mergeSourcePath, setMergeSourcePath] = useState<string | null>(null);
     const [exportModal, setExportModal] = useState<{
         isOpen: boolean;
         type: 'database' | 'selected';
@@ -95,6 +112,7 @@ export const VaultWorkspace: React.FC = () => {
     const [createEntryModalOpen, setCreateEntryModalOpen] = useState(false);
     const [changeCredentialsModalOpen, setChangeCredentialsModalOpen] = useState(false);
     const [showDbProperties, setShowDbProperties] = useState(false);
+    const [syncConflictsOpen, setSyncConflictsOpen] = useState(false);
     const [clipboardTimer, setClipboardTimer] = useState<number | null>(null);
 
     // Clipboard Timer Logic
@@ -134,6 +152,7 @@ export const VaultWorkspace: React.FC = () => {
 
         listeners.push(getCurrentWebviewWindow().listen('import-database', () => {
             if (!activeVaultId) { addToast({ title: 'No active vault', type: 'error' }); return; }
+            if (readOnly) { addToast({ title: 'Vault is read-only', type: 'info' }); return; }
             setImportModalOpen(true);
         }));
 
@@ -154,6 +173,7 @@ export const VaultWorkspace: React.FC = () => {
 
         listeners.push(getCurrentWebviewWindow().listen('create-entry', () => {
             if (!activeVaultId) { addToast({ title: 'No active vault', type: 'error' }); return; }
+            if (readOnly) { addToast({ title: 'Vault is read-only', type: 'info' }); return; }
             setCreateEntryModalOpen(true);
         }));
 
@@ -162,6 +182,7 @@ export const VaultWorkspace: React.FC = () => {
         }));
 
         listeners.push(getCurrentWebviewWindow().listen('change-credentials', () => {
+            if (readOnly) { addToast({ title: 'Vault is read-only', type: 'info' }); return; }
             setChangeCredentialsModalOpen(true);
         }));
 
@@ -171,10 +192,118 @@ export const VaultWorkspace: React.FC = () => {
             }
         }));
 
+        listeners.push(getCurrentWebviewWindow().listen('merge-database', async () => {
+            if (!activeVaultId) { addToast({ title: 'No active vault', type: 'error' }); return; }
+            if (readOnly) { addToast({ title: 'Vault is read-only', type: 'info' }); return; }
+            const selected = await open({
+                multiple: false,
+                filters: [{ name: 'KeePass Database', extensions: ['kdbx'] }]
+            });
+            if (typeof selected === 'string') {
+                runMergeRef.current(selected);
+            }
+        }));
+
+        listeners.push(getCurrentWebviewWindow().listen<DiskChange>('vault-changed-on-disk', (event) => {
+            onDiskChangeRef.current(event.payload);
+        }));
+
+        listeners.push(getCurrentWebviewWindow().listen('sync-conflicts', () => {
+            if (!activeVaultId) { addToast({ title: 'No active vault', type: 'error' }); return; }
+            if (readOnly) { addToast({ title: 'Vault is read-only', type: 'info' }); return; }
+            setSyncConflictsOpen(true);
+        }));
+
+        listeners.push(getCurrentWebviewWindow().listen<ConflictFound>('sync-conflict-found', (event) => {
+            offerSyncConflictsRef.current(event.payload.path, 1);
+        }));
+
         return () => {
             listeners.forEach(p => p.then(f => f()));
         };
-    }, [activeVaultId, selectedEntryIds, lockVault, addToast]);
+    }, [activeVaultId, readOnly, selectedEntryIds, lockVault, addToast]);
+
+    // Merge another database file into the active vault; asks for a password when the
+    // source does not open with the vault's own credentials
+    const runMerge = async (sourcePath: string, sourcePassword?: string) => {
+        if (!activeVaultId) return;
+        try {
+            const summary = await mergeFromFile(activeVaultId, sourcePath, sourcePassword);
+            addToast({ title: 'Databases merged', description: describeMergeSummary(summary), type: 'success' });
+        } catch (e) {
+            if (hasErrorCode(e, 'WrongCredentials')) {
+                if (sourcePassword !== undefined) {
+                    addToast({ title: 'Incorrect password', type: 'error' });
+                }
+                setMergeSourcePath(sourcePath);
+                return;
+            }
+            addToast({ title: 'Merge failed', description: errorMessage(e), type: 'error' });
+        }
+    };
+    // The menu listener outlives renders; always call the version that sees the current vault
+    const runMergeRef = useRef(runMerge);
+    runMergeRef.current = runMerge;
+
+    // Another device or program changed the vault file; offer to merge it in right away.
+    // Declining is safe: the next save asks again instead of overwriting.
+    const onDiskChange = async (change: DiskChange) => {
+        const vault = vaults.find(v => v.path === change.path);
+        if (!vault) return;
+        if (!change.exists) {
+            addToast({ title: 'Vault file moved or deleted', description: 'Saving will ask before writing it again', type: 'info' });
+            return;
+        }
+        if (vault.readOnly) {
+            // Nothing to merge from a read-only window; just show the new version
+            try {
+                await reloadVault(vault.id);
+                addToast({ title: 'Vault reloaded', description: 'It was changed on disk', type: 'info' });
+            } catch (e) {
+                addToast({ title: 'Reload failed', description: errorMessage(e), type: 'error' });
+            }
+            return;
+        }
+        const mergeNow = await ask(
+            `"${vault.filename}" was changed on disk by another device or program.\n\nMerge those changes into this window now?`,
+            { title: 'Vault Changed on Disk', kind: 'info', okLabel: 'Merge Now', cancelLabel: 'Later' }
+        );
+        if (!mergeNow) return;
+        try {
+            const summary = await mergeDiskChanges(vault.id);
+            addToast({ title: 'Changes merged', description: describeMergeSummary(summary), type: 'success' });
+        } catch (e) {
+            addToast({ title: 'Merge failed', description: errorMessage(e), type: 'error' });
+        }
+    };
+    const onDiskChangeRef = useRef(onDiskChange);
+    onDiskChangeRef.current = onDiskChange;
+
+    // Sync clients leave conflict copies next to the vault; offer to review them
+    const offerSyncConflicts = async (path: string, count: number) => {
+        const vault = vaults.find(v => v.path === path);
+        if (!vault || vault.readOnly || syncConflictsOpen) return;
+        const review = await ask(
+            count === 1
+                ? `Your sync service created a conflicting copy of "${vault.filename}".\n\nReview it now? You can merge it into the vault and archive it.`
+                : `Your sync service created ${count} conflicting copies of "${vault.filename}".\n\nReview them now? You can merge each into the vault and archive it.`,
+            { title: 'Sync Conflict', kind: 'info', okLabel: 'Review', cancelLabel: 'Later' }
+        );
+        if (review) setSyncConflictsOpen(true);
+    };
+    const offerSyncConflictsRef = useRef(offerSyncConflicts);
+    offerSyncConflictsRef.current = offerSyncConflicts;
+
+    // Check for conflict copies left from earlier sessions when a vault is opened
+    useEffect(() => {
+        const path = activeVault?.path;
+        if (!path) return;
+        findSyncConflicts(path)
+            .then(scan => {
+                if (scan.conflicts.length > 0) offerSyncConflictsRef.current(path, scan.conflicts.length);
+            })
+            .catch(e => console.warn('Failed to scan for sync conflicts:', e));
+    }, [activeVault?.path]);
 
     const importEntries = async (entries: EntryFormData[]) => {
         if (!activeVault) return;
@@ -257,8 +386,8 @@ export const VaultWorkspace: React.FC = () => {
                 const text = new TextDecoder().decode(content);
 
                 // Parse CSV
-                const { parseCsvToEntries } = await import('../services/importService');
-                const entries = parseCsvToEntries(text);
+                const { parseImportFile } = await import('../services/importService');
+                const entries = await parseImportFile('csv', text);
 
                 if (entries.length === 0) {
                     addToast({ title: 'No entries found in CSV', type: 'error' });
@@ -280,8 +409,8 @@ export const VaultWorkspace: React.FC = () => {
                 const text = new TextDecoder().decode(content);
 
                 // Parse JSON
-                const { parseBitwardenJsonToEntries } = await import('../services/importService');
-                const entries = parseBitwardenJsonToEntries(text);
+                const { parseImportFile } = await import('../services/importService');
+                const entries = await parseImportFile('bitwarden', text);
 
                 if (entries.length === 0) {
                     addToast({ title: 'No entries found in Bitwarden JSON', type: 'error' });
@@ -303,8 +432,8 @@ export const VaultWorkspace: React.FC = () => {
                 const text = new TextDecoder().decode(content);
 
                 // Parse CSV
-                const { parseLastPassCsvToEntries } = await import('../services/importService');
-                const entries = parseLastPassCsvToEntries(text);
+                const { parseImportFile } = await import('../services/importService');
+                const entries = await parseImportFile('lastpass', text);
 
                 if (entries.length === 0) {
                     addToast({ title: 'No entries found in LastPass CSV', type: 'error' });
@@ -326,8 +455,8 @@ export const VaultWorkspace: React.FC = () => {
                 const text = new TextDecoder().decode(content);
 
                 // Parse CSV
-                const { parseAppleCsvToEntries } = await import('../services/importService');
-                const entries = parseAppleCsvToEntries(text);
+                const { parseImportFile } = await import('../services/importService');
+                const entries = await parseImportFile('apple', text);
 
                 if (entries.length === 0) {
                     addToast({ title: 'No entries found in Apple CSV', type: 'error' });
@@ -349,8 +478,8 @@ export const VaultWorkspace: React.FC = () => {
                 const text = new TextDecoder().decode(content);
 
                 // Parse CSV
-                const { parseChromeCsvToEntries } = await import('../services/importService');
-                const entries = parseChromeCsvToEntries(text);
+                const { parseImportFile } = await import('../services/importService');
+                const entries = await parseImportFile('chrome', text);
 
                 if (entries.length === 0) {
                     addToast({ title: 'No entries found in Chrome CSV', type: 'error' });
@@ -372,8 +501,8 @@ export const VaultWorkspace: React.FC = () => {
                 const text = new TextDecoder().decode(content);
 
                 // Parse CSV
-                const { parseFirefoxCsvToEntries } = await import('../services/importService');
-                const entries = parseFirefoxCsvToEntries(text);
+                const { parseImportFile } = await import('../services/importService');
+                const entries = await parseImportFile('firefox', text);
 
                 if (entries.length === 0) {
                     addToast({ title: 'No entries found in Firefox CSV', type: 'error' });
@@ -731,6 +860,13 @@ export const VaultWorkspace: React.FC = () => {
                 title="Unlock Import Database"
                 description="Enter the master password for the KDBX file you want to import."
             />
+            <PasswordPromptModal
+                isOpen={mergeSourcePath !== null}
+                onClose={() => setMergeSourcePath(null)}
+                onConfirm={(password) => { if (mergeSourcePath) runMerge(mergeSourcePath, password); }}
+                title="Unlock Database to Merge"
+                description="Enter the master password for the database you want to merge into this vault."
+            />
             {/* Global Password Generator Modal */}
             {passwordGeneratorOpen && (
                 <div className="fixed inset-0 z-[100] flex items-center justify-center bg-black/20 backdrop-blur-sm" onClick={() => setPasswordGeneratorOpen(false)}>
@@ -753,6 +889,10 @@ export const VaultWorkspace: React.FC = () => {
                 isOpen={changeCredentialsModalOpen}
                 onClose={() => setChangeCredentialsModalOpen(false)}
             />
+            <SyncConflictsModal
+                isOpen={syncConflictsOpen}
+                onClose={() => setSyncConflictsOpen(false)}
+            />
             {activeVault && (
                 <DatabasePropertiesModal
                     isOpen={showDbProperties}
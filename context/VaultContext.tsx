@@ -1,8 +1,9 @@
-import React, { createContext, useContext, useState, useCallback, useMemo } from 'react';
+import React, { createContext, useContext, useState, useCallback, useEffect, useMemo, useRef } from 'react';
 import { readFile, writeFile } from '@tauri-apps/plugin-fs';
 import { save, ask, message } from '@tauri-apps/plugin-dialog';
 import { emit } from '@tauri-apps/api/event';
 import { invoke } from '@tauri-apps/api/core';
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
 import * as kdbxweb from 'kdbxweb';
 import { Vault, VaultGroup, VaultEntry, FileSystemFileHandle, EntryFormData } from '../types';
 // ... rest of imports unchanged
@@ -27,13 +28,25 @@ import { auditPassword } from '../utils/passwordAudit';
 import { useToast } from '../components/ui/Toaster';
 import { saveRecentVault, getRecentVaults } from '../services/storageService';
 import { fileSystem, FileHandle } from '../services/fileSystemAdapter';
+import { MergeSummary, describeMergeSummary, mergeVaultData, mergeVaultFile, sha256Hex, toVaultCredentials, unwatchVault, watchVault } from '../services/syncService';
+import { LintReport } from '../services/databaseIntegrityService';
+import { CryptoSettings } from '../services/encryptionService';
+import { errorMessage, hasErrorCode } from '../services/appError';
+import { LockTakenOver, acquireVaultLock, askLockedVaultChoice, markVaultReadOnly, releaseVaultLock } from '../services/vaultLockService';
+import { RemoteVault, isRemoteLocation, openRemoteVault, pushRemoteVault } from '../services/remoteVaultService';
+import { checkUnlockAllowed, describeFailedAttempts, recordFailedUnlock, recordSuccessfulUnlock } from '../services/unlockAttemptsService';
+
+export interface OpenVaultOptions {
+    /** Open without taking the vault lock; nothing is saved */
+    readOnly?: boolean;
+}
 
 interface VaultContextType {
     vaults: Vault[];
     activeVaultId: string | null;
     activeGroupId: string | null;
     searchQuery: string;
-    addVault: (fileOrPath: File | FileSystemFileHandle | string, password: string, keyFile?: File) => Promise<void>;
+    addVault: (fileOrPath: File | FileSystemFileHandle | string, password: string, keyFile?: File, options?: OpenVaultOptions) => Promise<void>;
     createVault: (name: string, password: string, keyFile?: File) => Promise<void>;
     saveVault: (id: string, isAutoSave?: boolean) => Promise<void>;
     removeVault: (id: string) => void;
@@ -64,6 +77,11 @@ interface VaultContextType {
     onEmptyRecycleBin: () => Promise<void>;
     lockVault: (id: string) => void;
     changeCredentials: (vaultId: string, oldPassword: string, newPassword: string | null, keyFileAction: 'keep' | 'remove' | 'change', newKeyFile?: File | Uint8Array) => Promise<void>;
+    reloadVault: (vaultId: string) => Promise<void>;
+    mergeFromFile: (vaultId: string, sourcePath: string, sourcePassword?: string) => Promise<MergeSummary>;
+    fixCompatibility: (vaultId: string) => Promise<LintReport>;
+    changeEncryption: (vaultId: string, settings: CryptoSettings) => Promise<void>;
+    mergeDiskChanges: (vaultId: string) => Promise<MergeSummary>;
 }
 
 // ...
@@ -188,10 +206,48 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
         }));
     }, []);
 
+    // Another window took the lock over; the backend already refuses this window's saves
+    useEffect(() => {
+        const unlisten = getCurrentWebviewWindow().listen<LockTakenOver>('vault-lock-taken-over', (event) => {
+            setVaults(prev => prev.map(v => v.path === event.payload.path ? { ...v, readOnly: true } : v));
+            addToast({ title: "Vault is now read-only", description: "Another window took over editing it", type: "info" });
+        });
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [addToast]);
+
+    // Remote vaults whose last upload failed; autosaves report it only once
+    const uploadFailed = useRef<Set<string>>(new Set());
+
+    // Upload a remote vault's saved cache; the backend merges when the server copy changed,
+    // and the window then reloads the merged cache
+    const pushRemote = async (vault: Vault, isAutoSave: boolean) => {
+        if (!vault.remoteUrl) return;
+        try {
+            const report = await pushRemoteVault(vault.remoteUrl, vault.db, toVaultCredentials(vault.password, vault.keyFileData));
+            uploadFailed.current.delete(vault.id);
+            if (report.merge) {
+                await reloadVaultFile(vault);
+                addToast({ title: "Merged with changes on the server", description: describeMergeSummary(report.merge), type: "info" });
+            }
+        } catch (e) {
+            console.warn('Failed to upload remote vault:', e);
+            if (!isAutoSave || !uploadFailed.current.has(vault.id)) {
+                addToast({ title: "Saved locally, upload failed", description: errorMessage(e), type: "error" });
+            }
+            uploadFailed.current.add(vault.id);
+        }
+    };
+
     // Persist changes to disk with integrity protection
     const saveVault = async (id: string, isAutoSave = false) => {
         const vault = vaults.find(v => v.id === id);
         if (!vault) return;
+        if (vault.readOnly) {
+            if (!isAutoSave) addToast({ title: "Vault is read-only", description: "Changes are not saved", type: "info" });
+            return;
+        }
 
         try {
             // Only block UI for manual saves
@@ -214,11 +270,16 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
 
                 const result = await safeSaveDatabase(vault.path, vault.db, {
                     createBackup: autoBackupEnabled,
-                    maxBackups: 2,
                     verifyAfterWrite: true,
                     silent: isAutoSave
                 });
 
+                if (result.conflict) {
+                    await resolveSaveConflict(vault.id, autoBackupEnabled);
+                    await pushRemote(vault, isAutoSave);
+                    return;
+                }
+
                 if (!result.success) {
                     throw new Error(result.error || 'Failed to save database');
                 }
@@ -246,6 +307,8 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                     autoBackupEnabled
                 });
 
+                await pushRemote(vault, isAutoSave);
+
             } else if (vault.fileHandle) {
                 const writable = await vault.fileHandle.createWritable();
                 await writable.write(data);
@@ -276,8 +339,15 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
         }
     };
 
+    // Read-only vaults refuse edits up front instead of changing memory that is never saved
+    const refuseReadOnly = (vault: Vault) => {
+        if (!vault.readOnly) return false;
+        addToast({ title: "Vault is read-only", description: "Reopen it for editing to make changes", type: "info" });
+        return true;
+    };
+
     const onAddGroup = async (name: string, parentGroupId?: string, icon?: number, allowAdd?: boolean) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         const targetGroup = parentGroupId || activeGroupId || activeVault.groups[0].uuid;
         try {
             addGroupToDb(activeVault.db, targetGroup, name, icon, allowAdd);
@@ -291,7 +361,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onDeleteGroup = async (groupId: string) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
 
         // Helper to find group
         const findGroupById = (groups: VaultGroup[], id: string): VaultGroup | undefined => {
@@ -365,7 +435,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onRestoreEntry = async (entryId: string) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         try {
             const { groupName, groupIcon } = restoreEntryFromRecycleBin(activeVault.db, entryId);
             refreshVault(activeVault.id);
@@ -378,7 +448,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onAddEntry = async (data: EntryFormData) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         const targetGroup = data.groupUuid || activeGroupId;
         if (!targetGroup) return;
 
@@ -394,7 +464,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onEditEntry = async (data: EntryFormData) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         try {
             updateEntryInDb(activeVault.db, data);
             refreshVault(activeVault.id);
@@ -407,7 +477,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onDeleteEntry = async (entryId: string) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
 
         // Check if entry is in recycle bin OR if we are currently viewing the recycle bin
         // This handles cases where isEntryInRecycleBin might fail or be slow, but we know we are in the bin
@@ -438,7 +508,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onMoveEntry = async (entryId: string, targetGroupId: string) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         try {
             moveEntryInDb(activeVault.db, entryId, targetGroupId);
             refreshVault(activeVault.id);
@@ -451,7 +521,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onMoveEntries = async (entryIds: string[], targetGroupId: string) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         try {
             let movedCount = 0;
             for (const entryId of entryIds) {
@@ -473,22 +543,33 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
             addToast({ title: e.message || "Failed to move entries", type: "error" });
         }
     };
-    const addVault = async (fileOrPath: File | FileSystemFileHandle | string, password: string, keyFile?: File) => {
+    const addVault = async (fileOrPath: File | FileSystemFileHandle | string, password: string, keyFile?: File, options: OpenVaultOptions = {}) => {
         initializeArgon2();
         setIsUnlocking(true);
         setUnlockError(null);
+        // Failed attempts are counted per location (server URL, path or file name)
+        let attemptLocation: string | undefined;
         try {
             let file: File | undefined;
             let handle: FileSystemFileHandle | undefined;
             let path: string | undefined;
             let arrayBuffer: ArrayBuffer;
             let filename: string;
+            let fileSha256: string | undefined;
+            let remote: RemoteVault | undefined;
 
             if (typeof fileOrPath === 'string') {
-                path = fileOrPath;
+                // A server URL opens through its local cache file
+                if (isRemoteLocation(fileOrPath)) {
+                    remote = await openRemoteVault(fileOrPath);
+                    path = remote.cachePath;
+                } else {
+                    path = fileOrPath;
+                }
                 // Read file using Tauri fs
                 const data = await readFile(path);
                 arrayBuffer = data.buffer as ArrayBuffer;
+                fileSha256 = await sha256Hex(data);
                 // Extract filename from path (simple split)
                 const parts = path.split(/[/\\]/);
                 filename = parts[parts.length - 1];
@@ -503,19 +584,73 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                 filename = file.name;
             }
 
+            const unlockLocation = remote?.url ?? path ?? filename;
+            attemptLocation = unlockLocation;
+            await checkUnlockAllowed(unlockLocation);
+
             const keyFileBuffer = keyFile ? await keyFile.arrayBuffer() : undefined;
+            const keyFileData = keyFileBuffer ? new Uint8Array(keyFileBuffer) : undefined;
+
+            const { checkCredentials, passwordComponent } = await import('../services/credentialsService');
+            let masterPassword = passwordComponent(password, !!keyFileBuffer);
+            await checkCredentials(toVaultCredentials(masterPassword, keyFileData), false);
+            let credentials = new kdbxweb.Credentials(masterPassword ?? null, keyFileBuffer);
 
-            const credentials = new kdbxweb.Credentials(kdbxweb.ProtectedValue.fromString(password), keyFileBuffer);
+            // Twofish vaults are converted in memory only (see loadVault)
+            const { loadVault } = await import('../services/encryptionService');
+            const load = async (buffer: ArrayBuffer) => {
+                try {
+                    return await loadVault(unlockLocation, buffer, credentials, toVaultCredentials(masterPassword, keyFileData));
+                } catch (keyError: any) {
+                    // Earlier versions created key file vaults with an empty password
+                    if (masterPassword || !/InvalidKey/.test(keyError?.message ?? '')) {
+                        throw keyError;
+                    }
+                    const emptyPassword = kdbxweb.ProtectedValue.fromString('');
+                    const fallback = new kdbxweb.Credentials(emptyPassword, keyFileBuffer);
+                    const db = await kdbxweb.Kdbx.load(buffer, fallback);
+                    masterPassword = emptyPassword;
+                    credentials = fallback;
+                    return db;
+                }
+            };
 
-            const db = await kdbxweb.Kdbx.load(arrayBuffer as ArrayBuffer, credentials);
+            const db = await load(arrayBuffer as ArrayBuffer);
             // Explicitly set credentials to ensure they are available for verification later
             db.credentials = credentials;
 
+            const failedAttempts = await recordSuccessfulUnlock(attemptLocation)
+                .then(describeFailedAttempts)
+                .catch(e => console.warn('Failed to reset unlock attempts:', e));
+            if (failedAttempts) {
+                addToast({ title: failedAttempts, type: "info" });
+            }
+
             // Apply compatibility fixes to ensure KeePassXC compatibility
             // This fixes AutoType fields that may have null/undefined values
             const { applyCompatibilityFixes } = await import('../services/kdbxService');
             applyCompatibilityFixes(db);
 
+            // Another window, instance or machine may have it open already
+            let readOnly = !!options.readOnly;
+            if (path && !readOnly) {
+                const lock = await acquireVaultLock(path);
+                if (!lock.acquired && lock.holder) {
+                    const choice = await askLockedVaultChoice(filename, lock.holder);
+                    if (choice === 'cancel') {
+                        throw new Error("The vault is open elsewhere.");
+                    }
+                    if (choice === 'takeOver') {
+                        await acquireVaultLock(path, true);
+                    }
+                    readOnly = choice === 'readOnly';
+                }
+            }
+            if (path && readOnly) {
+                // The backend refuses saves for this window from now on
+                await markVaultReadOnly(path);
+            }
+
             const parsedStructure = parseKdbxStructure(db);
 
             const newVault: Vault = {
@@ -527,8 +662,10 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                 fileHandle: handle,
                 path: path,
                 hasKeyFile: !!keyFile,
-                keyFileData: keyFileBuffer ? new Uint8Array(keyFileBuffer) : undefined,
-                password: kdbxweb.ProtectedValue.fromString(password)
+                keyFileData,
+                password: masterPassword,
+                readOnly,
+                remoteUrl: remote?.url
             };
 
             setVaults(prev => [...prev, newVault]);
@@ -537,19 +674,34 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                 setActiveGroupId(newVault.groups[0].uuid);
             }
 
+            if (path) {
+                watchVault(path, fileSha256).catch(e => console.warn('Failed to watch vault file:', e));
+            }
+
+            if (remote?.offline) {
+                addToast({ title: "Server unreachable", description: "Opened the cached copy; changes upload on the next save", type: "info" });
+            } else if (remote?.pending && !readOnly) {
+                // Saves from an earlier session never reached the server
+                pushRemote(newVault, false);
+            }
+
+            // Remote vaults are reopened (and remembered for Touch ID) by their URL
+            const location = remote?.url ?? path;
+
             // Save to recent vaults
             saveRecentVault({
-                path: path,
+                path: location,
                 filename: filename,
                 lastOpened: Date.now()
             });
 
             // Save password for Touch ID if enabled and vault has a path
             console.log('VaultContext: Checking Touch ID save conditions...');
-            console.log('VaultContext: Path:', path);
-            console.log('VaultContext: Path type:', typeof path);
+            console.log('VaultContext: Path:', location);
+            console.log('VaultContext: Path type:', typeof location);
 
-            if (path) {
+            // Touch ID keeps the master password; a key-file-only vault has none
+            if (location && password) {
                 try {
                     const { biometricService } = await import('../services/biometricService');
                     const { getUISettings } = await import('../services/uiSettingsService');
@@ -563,9 +715,9 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                         console.log('VaultContext: Biometric available:', available);
 
                         if (available) {
-                            console.log('Touch ID: Auto-saving password for', path);
+                            console.log('Touch ID: Auto-saving password for', location);
                             try {
-                                await biometricService.storePassword(path, password);
+                                await biometricService.storePassword(location, password);
                                 console.log('Touch ID: Password saved successfully');
                                 addToast({ title: "Touch ID enabled for this vault", type: "success" });
                             } catch (saveErr) {
@@ -589,19 +741,27 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
             }
 
             // Emit event to enable menu items
-            emit('vault-unlocked').catch(console.error);
+            emit('vault-unlocked', { readOnly }).catch(console.error);
 
             // Directly update macOS menu state
-            invoke('set_database_menu_state', { unlocked: true }).catch(console.error);
+            invoke('set_database_menu_state', { unlocked: true, readOnly }).catch(console.error);
 
-            addToast({ title: "Vault unlocked successfully", type: "success" });
+            addToast({ title: readOnly ? "Vault opened read-only" : "Vault unlocked successfully", type: "success" });
         } catch (error: any) {
             console.error("Failed to unlock vault:", error);
             let errorMessage = error.message || "Invalid credentials or corrupted file.";
 
             // Map technical kdbxweb errors to user-friendly messages
-            if (errorMessage.includes("InvalidKey") || errorMessage.includes("HMAC mismatch")) {
+            if (errorMessage.includes("InvalidKey") || errorMessage.includes("HMAC mismatch") || hasErrorCode(error, 'WrongCredentials')) {
                 errorMessage = "Incorrect password or key file.";
+                // The backend counts the failures it sees itself (Twofish vaults)
+                if (attemptLocation && !hasErrorCode(error, 'WrongCredentials')) {
+                    const delay = await recordFailedUnlock(attemptLocation)
+                        .catch(e => console.warn('Failed to record unlock attempt:', e));
+                    if (delay && delay.retryAfterMs > 0) {
+                        errorMessage += ` Try again in ${Math.ceil(delay.retryAfterMs / 1000)} seconds.`;
+                    }
+                }
             }
 
             setUnlockError(errorMessage);
@@ -616,9 +776,12 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
         setUnlockError(null);
         try {
             const keyFileBuffer = keyFile ? await keyFile.arrayBuffer() : undefined;
-            const db = createDatabase(name, password, keyFileBuffer);
+            const { checkCredentials, passwordComponent } = await import('../services/credentialsService');
+            const masterPassword = passwordComponent(password, !!keyFileBuffer);
+            await checkCredentials(toVaultCredentials(masterPassword, keyFileBuffer ? new Uint8Array(keyFileBuffer) : undefined), true);
+            const db = createDatabase(name, masterPassword, keyFileBuffer);
             // Explicitly set credentials on the new DB instance
-            db.credentials = new kdbxweb.Credentials(kdbxweb.ProtectedValue.fromString(password), keyFileBuffer);
+            db.credentials = new kdbxweb.Credentials(masterPassword ?? null, keyFileBuffer);
 
             let handle: FileSystemFileHandle | undefined;
             let path: string | undefined;
@@ -695,7 +858,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                 path: path,
                 hasKeyFile: !!keyFile,
                 keyFileData: keyFileBuffer ? new Uint8Array(keyFileBuffer) : undefined,
-                password: kdbxweb.ProtectedValue.fromString(password)
+                password: masterPassword
             };
 
             setVaults(prev => [...prev, newVault]);
@@ -704,6 +867,11 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                 setActiveGroupId(newVault.groups[0].uuid);
             }
 
+            if (path) {
+                watchVault(path).catch(e => console.warn('Failed to watch vault file:', e));
+                // Saves are refused until the window holds the lock
+                await acquireVaultLock(path).catch(e => console.warn('Failed to lock vault file:', e));
+            }
         } catch (error: any) {
             setUnlockError(error.message || "Failed to create database");
             throw error;
@@ -713,6 +881,16 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const removeVault = (id: string) => {
+        const vault = vaults.find(v => v.id === id);
+        const path = vault?.path;
+        if (path) {
+            unwatchVault(path).catch(e => console.warn('Failed to unwatch vault file:', e));
+            // A read-only session stays until the window closes or reopens the vault with
+            // its lock
+            if (!vault.readOnly) {
+                releaseVaultLock(path).catch(e => console.warn('Failed to release vault lock:', e));
+            }
+        }
         // Check for unsaved if we implemented dirty checking, but for now just remove
         setVaults(prev => prev.filter(v => v.id !== id));
         if (activeVaultId === id) {
@@ -747,7 +925,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onUpdateGroup = async (groupId: string, name: string, icon?: number, parentGroupId?: string, allowAdd?: boolean) => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         try {
             updateGroupInDb(activeVault.db, groupId, name, icon, parentGroupId, allowAdd);
             refreshVault(activeVault.id);
@@ -760,7 +938,7 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
     };
 
     const onEmptyRecycleBin = async () => {
-        if (!activeVault) return;
+        if (!activeVault || refuseReadOnly(activeVault)) return;
         const currentGroup = getActiveGroup();
         if (!currentGroup || !currentGroup.isRecycleBin) {
             addToast({ title: "Not in Recycle Bin", type: "error" });
@@ -788,30 +966,14 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
         }
     };
 
+    // Re-key the file on disk, then load it with the new credentials
     const changeCredentials = async (vaultId: string, oldPassword: string, newPassword: string | null, keyFileAction: 'keep' | 'remove' | 'change', newKeyFile?: File | Uint8Array) => {
         const vault = vaults.find(v => v.id === vaultId);
         if (!vault) throw new Error("Vault not found");
+        if (!vault.path) throw new Error("Changing credentials requires a vault saved on disk");
+        if (vault.readOnly) throw new Error("The vault is open read-only");
 
-        // 1. Verify Old Credentials
-        // We must verify that the provided oldPassword matches the current database password
-        // to prevent unauthorized changes or typos locking the user out.
-        const currentPassword = vault.password;
-        const currentPasswordText = currentPassword ? currentPassword.getText() : '';
-
-        console.log('Verifying credentials:', {
-            hasCurrentPassword: !!currentPassword,
-            currentPasswordLength: currentPasswordText.length,
-            oldPasswordLength: oldPassword.length,
-            match: oldPassword === currentPasswordText
-        });
-
-        if (oldPassword !== currentPasswordText) {
-            throw new Error("Current password is incorrect");
-        }
-
-        // 2. Set New Credentials
         let keyFileData: Uint8Array | undefined;
-
         if (keyFileAction === 'change') {
             if (newKeyFile instanceof File) {
                 keyFileData = new Uint8Array(await newKeyFile.arrayBuffer());
@@ -819,32 +981,200 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
                 keyFileData = newKeyFile;
             }
         } else if (keyFileAction === 'keep') {
-            // Use stored key file data
             keyFileData = vault.keyFileData;
-        } else {
-            // 'remove' - keyFileData remains undefined
-        }
-
-        // Determine the password to use for the new credentials
-        let passwordValue: kdbxweb.ProtectedValue;
-        if (newPassword !== null) {
-            // User wants to change password
-            passwordValue = kdbxweb.ProtectedValue.fromString(newPassword);
-        } else {
-            // User wants to keep existing password
-            // We reuse the ProtectedValue from the current credentials
-            passwordValue = currentPassword || kdbxweb.ProtectedValue.fromString('');
-        }
-
-        const creds = new kdbxweb.Credentials(passwordValue, keyFileData);
-        vault.db.credentials = creds;
-        vault.hasKeyFile = !!keyFileData;
-        vault.keyFileData = keyFileData;
-        vault.password = passwordValue; // Update stored password
-
-        // 3. Save Vault
-        await saveVault(vaultId);
-        addToast({ title: "Credentials updated", type: "success" });
+        }
+
+        // No new password keeps the current one; the backend checks the typed current
+        // password against the file, so a typo fails instead of becoming the new key.
+        // An empty password next to a key file leaves the key file alone
+        const { changeVaultCredentials, passwordComponent } = await import('../services/credentialsService');
+        const current = vault.password ? kdbxweb.ProtectedValue.fromString(oldPassword) : undefined;
+        const password = passwordComponent(newPassword ?? oldPassword, !!keyFileData);
+
+        const { applyCompatibilityFixes } = await import('../services/kdbxService');
+        const { safeSaveDatabase } = await import('../services/databaseIntegrityService');
+        applyCompatibilityFixes(vault.db);
+        const saved = await safeSaveDatabase(vault.path, vault.db, { createBackup: false, verifyAfterWrite: true, silent: true });
+        if (!saved.success) {
+            throw new Error(saved.error || 'Failed to save database');
+        }
+
+        const change = await changeVaultCredentials(
+            vault.path,
+            vault.db,
+            vault.remoteUrl ?? vault.path,
+            toVaultCredentials(current, vault.keyFileData),
+            toVaultCredentials(password, keyFileData)
+        );
+
+        vault.db.credentials = new kdbxweb.Credentials(password ?? null, keyFileData);
+        const updated: Vault = { ...vault, password, keyFileData, hasKeyFile: !!keyFileData };
+        await reloadVaultFile(updated);
+        await pushRemote(updated, false);
+
+        addToast({
+            title: "Credentials updated",
+            description: change.quickUnlock === 'removed' ? "Touch ID is set up again the next time you unlock with your password" : undefined,
+            type: "success"
+        });
+    };
+
+    // Replace the in-memory database with the file on disk (after a backend rewrite)
+    const reloadVault = async (vaultId: string) => {
+        const vault = vaults.find(v => v.id === vaultId);
+        if (vault) await reloadVaultFile(vault);
+    };
+
+    const reloadVaultFile = async (vault: Vault) => {
+        if (!vault.path) return;
+        const vaultId = vault.id;
+
+        const data = await readFile(vault.path);
+        const { loadVault } = await import('../services/encryptionService');
+        const credentials = toVaultCredentials(vault.password, vault.keyFileData);
+        const db = await loadVault(vault.remoteUrl ?? vault.path, data.buffer as ArrayBuffer, vault.db.credentials, credentials);
+        db.credentials = vault.db.credentials;
+        const { applyCompatibilityFixes } = await import('../services/kdbxService');
+        applyCompatibilityFixes(db);
+
+        setVaults(prev => prev.map(v => v.id === vaultId ? {
+            ...v,
+            db,
+            groups: parseKdbxStructure(db),
+            password: vault.password,
+            keyFileData: vault.keyFileData,
+            hasKeyFile: vault.hasKeyFile
+        } : v));
+        // The window now holds this version of the file
+        await watchVault(vault.path, await sha256Hex(data));
+    };
+
+    // Merge this window's state into the vault file after another device or program
+    // changed it, then load the result
+    const mergeDiskChanges = async (vaultId: string) => {
+        const vault = vaults.find(v => v.id === vaultId);
+        if (!vault?.path) throw new Error("Vault not found");
+
+        const { applyCompatibilityFixes } = await import('../services/kdbxService');
+        applyCompatibilityFixes(vault.db);
+        const credentials = toVaultCredentials(vault.password, vault.keyFileData);
+        const summary = await mergeVaultData(vault.path, vault.db, credentials);
+        await reloadVault(vaultId);
+        return summary;
+    };
+
+    // A save was refused because the file changed on disk: merge, overwrite or keep the
+    // changes in memory. Only one prompt per vault at a time (auto-save runs often).
+    const resolvingConflicts = useRef(new Set<string>());
+    const resolveSaveConflict = async (vaultId: string, createBackup: boolean) => {
+        const vault = vaults.find(v => v.id === vaultId);
+        if (!vault?.path || resolvingConflicts.current.has(vaultId)) return;
+        resolvingConflicts.current.add(vaultId);
+        try {
+            const merge = await ask(
+                `"${vault.filename}" was changed on disk by another device or program since it was opened.\n\nMerge your changes with it? Entries are matched and the newer version of each is kept; older versions stay in the entry history.`,
+                { title: 'Vault Changed on Disk', kind: 'warning', okLabel: 'Merge', cancelLabel: 'Other Options…' }
+            );
+            if (merge) {
+                try {
+                    const summary = await mergeDiskChanges(vaultId);
+                    addToast({ title: 'Changes merged', description: describeMergeSummary(summary), type: 'success' });
+                    return;
+                } catch (e) {
+                    // A file that was deleted or moved away can only be written again
+                    if (!hasErrorCode(e, 'NotFound')) {
+                        addToast({ title: 'Merge failed', description: errorMessage(e), type: 'error' });
+                        return;
+                    }
+                }
+            }
+
+            const overwrite = await ask(
+                `Overwrite "${vault.filename}" with the version in this window? The changes made on disk are lost${createBackup ? ', except in the backup taken before saving' : ''}.\n\nIf you cancel, your changes stay unsaved in this window.`,
+                { title: 'Overwrite Vault File', kind: 'warning', okLabel: 'Overwrite', cancelLabel: 'Cancel' }
+            );
+            if (!overwrite) {
+                addToast({ title: 'Changes not saved', description: 'The vault file changed on disk', type: 'info' });
+                return;
+            }
+
+            const { safeSaveDatabase } = await import('../services/databaseIntegrityService');
+            const result = await safeSaveDatabase(vault.path, vault.db, { createBackup, verifyAfterWrite: true, overwrite: true });
+            if (result.success) {
+                addToast({ title: 'Vault file overwritten', type: 'success' });
+            } else {
+                addToast({ title: 'Failed to save vault', description: result.error, type: 'error' });
+            }
+        } finally {
+            resolvingConflicts.current.delete(vaultId);
+        }
+    };
+
+    // Merge another copy of the vault into this one (KeePassXC "Synchronize")
+    const mergeFromFile = async (vaultId: string, sourcePath: string, sourcePassword?: string) => {
+        const vault = vaults.find(v => v.id === vaultId);
+        if (!vault) throw new Error("Vault not found");
+        if (!vault.path) throw new Error("Merging requires a vault saved on disk");
+        if (vault.readOnly) throw new Error("The vault is open read-only");
+
+        // The backend merges into the file, so pending edits must reach it first
+        const { applyCompatibilityFixes } = await import('../services/kdbxService');
+        const { safeSaveDatabase } = await import('../services/databaseIntegrityService');
+        applyCompatibilityFixes(vault.db);
+        const saved = await safeSaveDatabase(vault.path, vault.db, { createBackup: false, verifyAfterWrite: true, silent: true });
+        if (!saved.success) {
+            throw new Error(saved.error || 'Failed to save database');
+        }
+
+        const credentials = toVaultCredentials(vault.password, vault.keyFileData);
+        const sourceCredentials = sourcePassword !== undefined ? { password: sourcePassword } : undefined;
+        const summary = await mergeVaultFile(vault.path, vault.db, credentials, sourcePath, sourceCredentials);
+        if (summary.changes.length > 0) {
+            await reloadVault(vaultId);
+        }
+        return summary;
+    };
+
+    // Fix values other KeePass clients reject, in the file on disk, then load the result
+    const fixCompatibility = async (vaultId: string) => {
+        const vault = vaults.find(v => v.id === vaultId);
+        if (!vault?.path) throw new Error("Fixing requires a vault saved on disk");
+        if (vault.readOnly) throw new Error("The vault is open read-only");
+
+        const { applyCompatibilityFixes } = await import('../services/kdbxService');
+        const { safeSaveDatabase, lintVault } = await import('../services/databaseIntegrityService');
+        applyCompatibilityFixes(vault.db);
+        const saved = await safeSaveDatabase(vault.path, vault.db, { createBackup: false, verifyAfterWrite: true, silent: true });
+        if (!saved.success) {
+            throw new Error(saved.error || 'Failed to save database');
+        }
+
+        const credentials = toVaultCredentials(vault.password, vault.keyFileData);
+        const report = await lintVault(vault.path, vault.db, credentials, true);
+        if (report.fixed > 0) {
+            await reloadVault(vaultId);
+        }
+        return report;
+    };
+
+    // Re-encrypt the file on disk with new cipher / KDF settings, then load the result
+    const changeEncryption = async (vaultId: string, settings: CryptoSettings) => {
+        const vault = vaults.find(v => v.id === vaultId);
+        if (!vault?.path) throw new Error("Changing encryption requires a vault saved on disk");
+        if (vault.readOnly) throw new Error("The vault is open read-only");
+
+        const { applyCompatibilityFixes } = await import('../services/kdbxService');
+        const { safeSaveDatabase } = await import('../services/databaseIntegrityService');
+        const { setVaultCrypto } = await import('../services/encryptionService');
+        applyCompatibilityFixes(vault.db);
+        const saved = await safeSaveDatabase(vault.path, vault.db, { createBackup: false, verifyAfterWrite: true, silent: true });
+        if (!saved.success) {
+            throw new Error(saved.error || 'Failed to save database');
+        }
+
+        const credentials = toVaultCredentials(vault.password, vault.keyFileData);
+        await setVaultCrypto(vault.path, vault.db, credentials, settings);
+        await reloadVault(vaultId);
     };
 
     return (
@@ -881,6 +1211,11 @@ export const VaultProvider: React.FC<{ children: React.ReactNode }> = ({ childre
             isEntryInRecycleBin,
             onEmptyRecycleBin,
             changeCredentials,
+            reloadVault,
+            mergeFromFile,
+            fixCompatibility,
+            changeEncryption,
+            mergeDiskChanges,
             lockVault: (id: string) => {
                 const vault = vaults.find(v => v.id === id);
                 if (vault) {
@@ -0,0 +1,58 @@
+#!/usr/bin/env sh
+
+# 本地 sshd 替身，用于端到端测试 SFTP 远程数据库（src-tauri/src/remote/sftp.rs）
+#
+# 以当前用户身份在 127.0.0.1 上运行一个独立的 OpenSSH sshd（不需要 root，不影响系统 sshd），
+# 只接受公钥登录。授权的公钥来自 ~/.ssh/*.pub 和 ssh-agent 中的密钥；设置 SFTP_AUTHORIZED_KEYS
+# 时只授权该文件中的公钥（src-tauri/src/remote/sftp.rs 的测试用它启动替身）。
+#
+# 用法：
+#   scripts/sftp-standin.sh [目录] [端口]
+#   SFTP_ONLY=1 scripts/sftp-standin.sh ./sftp 2222   # 只允许 SFTP，不能执行命令
+#
+# 然后在 KeedaVault 中打开 sftp://$USER@127.0.0.1:2222/<目录的绝对路径>/data/Vault.kdbx
+# SFTP_ONLY=1 时服务器不允许执行 mv，可以测试上传时的换名回退路径。
+
+set -eu
+
+dir=$(mkdir -p "${1:-./sftp}" && cd "${1:-./sftp}" && pwd)
+port=${2:-2222}
+sshd=$(command -v sshd || echo /usr/sbin/sshd)
+
+mkdir -p "$dir/etc" "$dir/data"
+[ -f "$dir/etc/host_key" ] || ssh-keygen -q -t ed25519 -N '' -f "$dir/etc/host_key"
+
+keys="$dir/etc/authorized_keys"
+: > "$keys"
+if [ -n "${SFTP_AUTHORIZED_KEYS:-}" ]; then
+    cat "$SFTP_AUTHORIZED_KEYS" >> "$keys"
+else
+    for pub in "$HOME"/.ssh/*.pub; do
+        [ -f "$pub" ] && cat "$pub" >> "$keys"
+    done
+    ssh-add -L >> "$keys" 2>/dev/null || true
+fi
+if [ ! -s "$keys" ]; then
+    echo "No public keys found in ~/.ssh or ssh-agent" >&2
+    exit 1
+fi
+
+cat > "$dir/etc/sshd_config" <<EOF
+Port $port
+ListenAddress 127.0.0.1
+HostKey $dir/etc/host_key
+PidFile $dir/etc/sshd.pid
+AuthorizedKeysFile $keys
+PubkeyAuthentication yes
+PasswordAuthentication no
+KbdInteractiveAuthentication no
+UsePAM no
+StrictModes no
+Subsystem sftp internal-sftp
+EOF
+if [ "${SFTP_ONLY:-}" = "1" ]; then
+    echo "ForceCommand internal-sftp" >> "$dir/etc/sshd_config"
+fi
+
+echo "SFTP stand-in serving $dir/data at sftp://$(id -un)@127.0.0.1:$port$dir/data/"
+exec "$sshd" -D -e -f "$dir/etc/sshd_config"
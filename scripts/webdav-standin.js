@@ -0,0 +1,123 @@
+#!/usr/bin/env node
+
+/**
+ * 本地 WebDAV 替身服务器，用于端到端测试远程数据库（src-tauri/src/remote/）
+ *
+ * 只实现数据库同步用到的部分：GET / HEAD / PUT / DELETE，强 ETag，
+ * If-Match / If-None-Match / If-Unmodified-Since 条件请求，可选 Basic 认证。
+ *
+ * 用法：
+ *   node scripts/webdav-standin.js [目录] [端口]
+ *   WEBDAV_USER=alice WEBDAV_PASSWORD=secret node scripts/webdav-standin.js ./dav 8080
+ *
+ * 然后在 KeedaVault 中打开 http://127.0.0.1:8080/Vault.kdbx（webdav:// 表示 HTTPS，替身只提供明文 HTTP）
+ * 在另一个终端修改 ./dav/Vault.kdbx（或用 curl -T 上传）即可模拟其他设备的保存。
+ */
+
+import http from 'http';
+import fs from 'fs';
+import path from 'path';
+import crypto from 'crypto';
+
+const root = path.resolve(process.argv[2] || './dav');
+const port = Number(process.argv[3] || 8080);
+const user = process.env.WEBDAV_USER;
+const password = process.env.WEBDAV_PASSWORD;
+
+fs.mkdirSync(root, { recursive: true });
+
+function etagOf(data) {
+    return `"${crypto.createHash('sha256').update(data).digest('hex').slice(0, 32)}"`;
+}
+
+function resolve(urlPath) {
+    const file = path.join(root, decodeURIComponent(urlPath.split('?')[0]));
+    return file.startsWith(root + path.sep) ? file : null;
+}
+
+function authorized(req) {
+    if (!user) return true;
+    const expected = 'Basic ' + Buffer.from(`${user}:${password ?? ''}`).toString('base64');
+    return req.headers.authorization === expected;
+}
+
+function readCurrent(file) {
+    try {
+        const data = fs.readFileSync(file);
+        return { data, etag: etagOf(data), modified: fs.statSync(file).mtime };
+    } catch {
+        return null;
+    }
+}
+
+// If-Match / If-None-Match / If-Unmodified-Since, as RFC 9110 evaluates them for writes
+function preconditionFailed(req, current) {
+    const ifMatch = req.headers['if-match'];
+    if (ifMatch !== undefined) {
+        return !current || (ifMatch !== '*' && !ifMatch.split(',').map(s => s.trim()).includes(current.etag));
+    }
+    const ifNoneMatch = req.headers['if-none-match'];
+    if (ifNoneMatch !== undefined) {
+        return !!current && (ifNoneMatch === '*' || ifNoneMatch.split(',').map(s => s.trim()).includes(current.etag));
+    }
+    const since = req.headers['if-unmodified-since'];
+    if (since !== undefined && current) {
+        return Math.floor(current.modified.getTime() / 1000) > Math.floor(new Date(since).getTime() / 1000);
+    }
+    return false;
+}
+
+const server = http.createServer((req, res) => {
+    const file = resolve(req.url || '/');
+    const send = (status, headers = {}, body) => {
+        res.writeHead(status, headers);
+        res.end(body);
+        console.log(`${req.method} ${req.url} -> ${status}`);
+    };
+
+    if (!authorized(req)) return send(401, { 'WWW-Authenticate': 'Basic realm="webdav-standin"' });
+    if (!file) return send(400);
+
+    const current = readCurrent(file);
+    const versionHeaders = current
+        ? { ETag: current.etag, 'Last-Modified': current.modified.toUTCString() }
+        : {};
+
+    switch (req.method) {
+        case 'GET':
+        case 'HEAD': {
+            if (!current) return send(404);
+            if (req.headers['if-none-match'] === current.etag) return send(304, versionHeaders);
+            return send(200, { ...versionHeaders, 'Content-Length': current.data.length },
+                req.method === 'GET' ? current.data : undefined);
+        }
+        case 'PUT': {
+            const chunks = [];
+            req.on('data', chunk => chunks.push(chunk));
+            req.on('end', () => {
+                // Re-read: another request may have written while the body arrived
+                const latest = readCurrent(file);
+                if (preconditionFailed(req, latest)) return send(412, latest ? { ETag: latest.etag } : {});
+                fs.mkdirSync(path.dirname(file), { recursive: true });
+                const temp = `${file}.${process.pid}.tmp`;
+                fs.writeFileSync(temp, Buffer.concat(chunks));
+                fs.renameSync(temp, file);
+                const written = readCurrent(file);
+                send(latest ? 204 : 201, { ETag: written.etag, 'Last-Modified': written.modified.toUTCString() });
+            });
+            return;
+        }
+        case 'DELETE': {
+            if (!current) return send(404);
+            if (preconditionFailed(req, current)) return send(412, versionHeaders);
+            fs.unlinkSync(file);
+            return send(204);
+        }
+        default:
+            return send(405, { Allow: 'GET, HEAD, PUT, DELETE' });
+    }
+});
+
+server.listen(port, '127.0.0.1', () => {
+    console.log(`WebDAV stand-in serving ${root} at http://127.0.0.1:${port}/`);
+});
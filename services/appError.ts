@@ -0,0 +1,50 @@
+// Typed errors returned by Tauri commands (mirrors src-tauri/src/error.rs)
+
+export type AppErrorCode =
+    | 'NotFound'
+    | 'UserCancelled'
+    | 'AuthFailed'
+    | 'BackendUnavailable'
+    | 'Unsupported'
+    | 'Io'
+    | 'Corrupt'
+    | 'WrongCredentials'
+    | 'InvalidInput'
+    | 'Conflict'
+    | 'ReadOnly'
+    | 'TooManyAttempts'
+    | 'UnknownHostKey'
+    | 'Internal';
+
+export interface AppError {
+    code: AppErrorCode;
+    message: string;
+    osStatus?: number;
+    retryable: boolean;
+}
+
+/**
+ * Check whether a value rejected by `invoke` is a structured backend error
+ */
+export function isAppError(error: unknown): error is AppError {
+    return typeof error === 'object'
+        && error !== null
+        && typeof (error as AppError).code === 'string'
+        && typeof (error as AppError).message === 'string';
+}
+
+/**
+ * Check a rejected `invoke` value against one or more error codes
+ */
+export function hasErrorCode(error: unknown, ...codes: AppErrorCode[]): boolean {
+    return isAppError(error) && codes.includes(error.code);
+}
+
+/**
+ * Human readable message for any rejected `invoke` value
+ */
+export function errorMessage(error: unknown): string {
+    if (isAppError(error)) return error.message;
+    if (error instanceof Error) return error.message;
+    return String(error);
+}
@@ -1,5 +1,6 @@
 // Biometric Authentication Service
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage, hasErrorCode } from './appError';
 
 /**
  * Encode vault path to Base64 for consistent keychain storage
@@ -39,7 +40,11 @@ export const biometricService = {
         try {
             return await invoke<boolean>('authenticate_biometric', { reason });
         } catch (error) {
-            console.error('Biometric authentication failed:', error);
+            if (hasErrorCode(error, 'UserCancelled')) {
+                console.log('[BiometricService] Biometric authentication cancelled');
+            } else {
+                console.error('Biometric authentication failed:', error);
+            }
             return false;
         }
     },
@@ -70,7 +75,11 @@ export const biometricService = {
             return await invoke<string>('secure_get_password_native', { vaultPath: encodedPath });
         } catch (error) {
             // It's normal to fail if password not found
-            console.log('[BiometricService] Password not found or error:', error);
+            if (hasErrorCode(error, 'NotFound', 'UserCancelled')) {
+                console.log('[BiometricService] No password returned:', errorMessage(error));
+            } else {
+                console.warn('[BiometricService] Failed to read stored password:', error);
+            }
             return null;
         }
     },
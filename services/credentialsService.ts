@@ -0,0 +1,51 @@
+import { invoke } from '@tauri-apps/api/core';
+import * as kdbxweb from 'kdbxweb';
+import { getVaultIdentity } from './databaseIntegrityService';
+import { VaultCredentials } from './syncService';
+
+/**
+ * What happened to the vault's Touch ID entries (see src-tauri/src/quick_unlock.rs)
+ */
+export type QuickUnlockChange = 'none' | 'updated' | 'removed';
+
+export interface CredentialChange {
+    quickUnlock: QuickUnlockChange;
+}
+
+/**
+ * Password component for what was typed: with a key file, an empty password means a
+ * vault keyed by the key file alone (KeePassXC's key-file-only vaults)
+ */
+export function passwordComponent(password: string, hasKeyFile: boolean): kdbxweb.ProtectedValue | undefined {
+    return password || !hasKeyFile ? kdbxweb.ProtectedValue.fromString(password) : undefined;
+}
+
+/**
+ * Ask the backend whether `credentials` can unlock a vault, or with `isNew` become a
+ * vault's master key; rejects with a message the form can show
+ */
+export async function checkCredentials(credentials: VaultCredentials, isNew: boolean): Promise<void> {
+    await invoke('check_credentials', { credentials, new: isNew });
+}
+
+/**
+ * Re-key the vault file at `path` from `current` to `next` credentials
+ * The backend checks `current` against the file, backs up the previous version and
+ * updates the Touch ID entries stored under `location`; the vault must be saved before
+ * and reloaded with `next` after
+ */
+export async function changeVaultCredentials(
+    path: string,
+    db: kdbxweb.Kdbx,
+    location: string,
+    current: VaultCredentials,
+    next: VaultCredentials
+): Promise<CredentialChange> {
+    return await invoke<CredentialChange>('change_credentials', {
+        path,
+        vaultId: getVaultIdentity(db),
+        location,
+        current,
+        new: next
+    });
+}
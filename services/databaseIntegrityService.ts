@@ -1,7 +1,10 @@
-import { readFile, writeFile, exists, remove, mkdir } from '@tauri-apps/plugin-fs';
-import { appDataDir } from '@tauri-apps/api/path';
+import { readFile } from '@tauri-apps/plugin-fs';
+import { invoke } from '@tauri-apps/api/core';
 import * as kdbxweb from 'kdbxweb';
-import { initializeArgon2, isInCloudStorage } from './kdbxService';
+import { initializeArgon2 } from './kdbxService';
+import { errorMessage, hasErrorCode } from './appError';
+import { isTwofishVault } from './encryptionService';
+import { VaultCredentials } from './syncService';
 
 /**
  * 数据完整性保护服务
@@ -11,17 +14,34 @@ import { initializeArgon2, isInCloudStorage } from './kdbxService';
  * 2. 写入后验证
  * 3. 验证失败自动回滚
  * 4. 完整性检查
+ * 5. 修复损坏的数据库（见 src-tauri/src/repair.rs）
  */
 
 export interface SaveOptions {
     /** 是否创建备份 */
     createBackup?: boolean;
-    /** 备份保留数量 */
-    maxBackups?: number;
     /** 是否验证写入 */
     verifyAfterWrite?: boolean;
     /** 静默模式（不显示 toast） */
     silent?: boolean;
+    /** 即使文件在磁盘上已被修改也覆盖 */
+    overwrite?: boolean;
+}
+
+export interface BackupInfo {
+    id: string;
+    path: string;
+    createdAt: string;
+    size: number;
+    sha256: string;
+    /** 备份版本的条目数（保存时数据库已解锁才有） */
+    entryCount?: number;
+}
+
+interface SaveReport {
+    sha256: string;
+    size: number;
+    backup?: BackupInfo | null;
 }
 
 export interface SaveResult {
@@ -29,209 +49,45 @@ export interface SaveResult {
     verified: boolean;
     backupPath?: string;
     error?: string;
+    /** 文件自加载以来已在磁盘上被修改，未写入 */
+    conflict?: boolean;
 }
 
 /**
- * Generate a simple hash from file path for backup identification
- * Browser-compatible version (no Buffer dependency)
+ * Identity of a vault used to key its backups (root group UUID)
+ * Combined with the canonical path in the backend, so backups never mix between vaults
  */
-function hashPath(filePath: string): string {
-    // Normalize path
-    const normalized = filePath.replace(/\\/g, '/');
-
-    // Simple hash using character codes
-    let hash = 0;
-    for (let i = 0; i < normalized.length; i++) {
-        const char = normalized.charCodeAt(i);
-        hash = ((hash << 5) - hash) + char;
-        hash = hash & hash; // Convert to 32bit integer
-    }
-
-    // Convert to hex string and take first 16 characters
-    return Math.abs(hash).toString(36).substring(0, 16).padEnd(16, '0');
+export function getVaultIdentity(db: kdbxweb.Kdbx): string | undefined {
+    return db.getDefaultGroup()?.uuid?.id;
 }
 
-/**
- * Get app data directory for backups
- */
-async function getAppDataBackupDir(): Promise<string> {
-    const appData = await appDataDir();
-    // Ensure proper path separator
-    const backupDir = appData.endsWith('/') || appData.endsWith('\\')
-        ? `${appData}backups`
-        : `${appData}/backups`;
-
-    console.log(`[Backup] App data directory: ${appData}`);
-    console.log(`[Backup] Backup directory: ${backupDir}`);
-
-    // Ensure directory exists
-    try {
-        const { exists: dirExists } = await import('@tauri-apps/plugin-fs');
-        const exists = await dirExists(backupDir);
-
-        if (!exists) {
-            console.log(`[Backup] Creating backup directory: ${backupDir}`);
-            await mkdir(backupDir, { recursive: true });
-            console.log(`[Backup] Backup directory created successfully`);
-        } else {
-            console.log(`[Backup] Backup directory already exists`);
-        }
-    } catch (e) {
-        console.error(`[Backup] Error creating backup directory:`, e);
-        // Try to create anyway
-        try {
-            await mkdir(backupDir, { recursive: true });
-        } catch (mkdirError) {
-            console.error(`[Backup] Failed to create backup directory:`, mkdirError);
-            throw new Error(`Cannot create backup directory: ${backupDir}`);
-        }
-    }
-
-    return backupDir;
+function countEntries(db: kdbxweb.Kdbx): number {
+    let total = 0;
+    const countRecursive = (group: kdbxweb.KdbxGroup) => {
+        total += group.entries.length;
+        group.groups.forEach(g => countRecursive(g));
+    };
+    const root = db.getDefaultGroup();
+    if (root) countRecursive(root);
+    return total;
 }
 
 /**
- * Generate backup file path
- * For cloud storage: use app data directory
- * For local storage: use same directory as database
+ * 验证序列化后的数据（写入前，在内存中）
  */
-async function getBackupPath(originalPath: string, timestamp?: number): Promise<string> {
-    const ts = timestamp || Date.now();
-    const date = new Date(ts);
-    const dateStr = date.toISOString().replace(/[:.]/g, '-').slice(0, -5); // 2025-12-12T10-30-45
-
-    // Check if database is in cloud storage
-    if (isInCloudStorage(originalPath)) {
-        // Use app data directory for cloud-stored databases
-        const backupDir = await getAppDataBackupDir();
-        const dbHash = hashPath(originalPath);
-        return `${backupDir}/${dbHash}.backup.${dateStr}.kdbx`;
-    } else {
-        // Use same directory for local databases
-        const lastSlash = Math.max(originalPath.lastIndexOf('/'), originalPath.lastIndexOf('\\'));
-        const dir = originalPath.substring(0, lastSlash);
-        const filename = originalPath.substring(lastSlash + 1);
-        const nameWithoutExt = filename.replace('.kdbx', '');
-
-        return `${dir}/${nameWithoutExt}.backup.${dateStr}.kdbx`;
-    }
-}
-
-/**
- * 获取临时文件路径
- */
-function getTempPath(originalPath: string): string {
-    return `${originalPath}.tmp`;
-}
-
-/**
- * Clean up old backup files
- */
-async function cleanupOldBackups(originalPath: string, maxBackups: number = 2): Promise<void> {
+async function verifyKdbxData(
+    data: ArrayBuffer,
+    credentials: kdbxweb.Credentials
+): Promise<{ valid: boolean; error?: string }> {
     try {
-        const { readDir } = await import('@tauri-apps/plugin-fs');
-
-        let dir: string;
-        let backupPattern: string;
-
-        // Check if database is in cloud storage
-        if (isInCloudStorage(originalPath)) {
-            // Use app data directory
-            dir = await getAppDataBackupDir();
-            const dbHash = hashPath(originalPath);
-            backupPattern = `${dbHash}.backup.`;
-
-            console.log(`[Backup Cleanup] Cloud storage detected`);
-            console.log(`[Backup Cleanup] Using app data directory: ${dir}`);
-        } else {
-            // Use same directory as database
-            const lastSlash = Math.max(originalPath.lastIndexOf('/'), originalPath.lastIndexOf('\\'));
-            dir = originalPath.substring(0, lastSlash);
-            const filename = originalPath.substring(lastSlash + 1);
-            const nameWithoutExt = filename.replace('.kdbx', '');
-            backupPattern = `${nameWithoutExt}.backup.`;
-
-            console.log(`[Backup Cleanup] Local storage detected`);
-            console.log(`[Backup Cleanup] Using same directory: ${dir}`);
-        }
-
-        console.log(`[Backup Cleanup] Starting cleanup`);
-        console.log(`[Backup Cleanup] Max backups: ${maxBackups}`);
-
-        // Read directory
-        const entries = await readDir(dir);
-        console.log(`[Backup Cleanup] Found ${entries.length} total files in directory`);
-
-        // Filter backup files
-        console.log(`[Backup Cleanup] Looking for pattern: ${backupPattern}*.kdbx`);
-        const backupFiles: Array<{ path: string; name: string; timestamp: number }> = [];
-
-        for (const entry of entries) {
-            if (entry.name && entry.name.startsWith(backupPattern) && entry.name.endsWith('.kdbx')) {
-                console.log(`[Backup Cleanup] Found backup file: ${entry.name}`);
-
-                // Extract timestamp
-                const timestampStr = entry.name
-                    .replace(backupPattern, '')
-                    .replace('.kdbx', '');
-
-                console.log(`[Backup Cleanup] Extracted timestamp string: ${timestampStr}`);
-
-                try {
-                    // 解析时间戳 (格式: 2025-12-12T18-23-00)
-                    // 转换为标准 ISO 格式: 2025-12-12T18:23:00
-                    const isoStr = timestampStr
-                        .replace(/^(\d{4})-(\d{2})-(\d{2})T(\d{2})-(\d{2})-(\d{2})$/, '$1-$2-$3T$4:$5:$6');
-
-                    console.log(`[Backup Cleanup] Converted to ISO: ${isoStr}`);
-
-                    const timestamp = new Date(isoStr).getTime();
-
-                    if (isNaN(timestamp)) {
-                        console.warn(`[Backup Cleanup] Invalid timestamp for ${entry.name}: ${timestampStr} -> ${isoStr}`);
-                        continue;
-                    }
-
-                    console.log(`[Backup Cleanup] Parsed timestamp: ${timestamp} (${new Date(timestamp).toLocaleString()})`);
-
-                    backupFiles.push({
-                        path: `${dir}/${entry.name}`,
-                        name: entry.name,
-                        timestamp: timestamp
-                    });
-                } catch (e) {
-                    console.warn(`[Backup Cleanup] Failed to parse timestamp for ${entry.name}:`, e);
-                }
-            }
-        }
-
-        // 按时间戳排序（最新的在前）
-        backupFiles.sort((a, b) => b.timestamp - a.timestamp);
-
-        console.log(`[Backup Cleanup] Found ${backupFiles.length} backup files total`);
-        backupFiles.forEach((f, i) => {
-            console.log(`[Backup Cleanup]   ${i + 1}. ${f.name} (${new Date(f.timestamp).toLocaleString()})`);
-        });
-
-        // 删除超过限制的旧备份
-        if (backupFiles.length > maxBackups) {
-            const filesToDelete = backupFiles.slice(maxBackups);
-            console.log(`[Backup Cleanup] Will delete ${filesToDelete.length} old backups (keeping ${maxBackups})`);
-
-            for (const file of filesToDelete) {
-                try {
-                    console.log(`[Backup Cleanup] Deleting: ${file.name}`);
-                    await remove(file.path);
-                    console.log(`[Backup Cleanup] ✅ Deleted: ${file.name}`);
-                } catch (e) {
-                    console.error(`[Backup Cleanup] ❌ Failed to delete ${file.name}:`, e);
-                }
-            }
-        } else {
-            console.log(`[Backup Cleanup] No cleanup needed (${backupFiles.length} <= ${maxBackups})`);
+        initializeArgon2();
+        const db = await kdbxweb.Kdbx.load(data.slice(0), credentials);
+        if (!db.getDefaultGroup()) {
+            return { valid: false, error: 'No root group found' };
         }
-    } catch (e) {
-        console.warn('Failed to cleanup old backups:', e);
+        return { valid: true };
+    } catch (e: any) {
+        return { valid: false, error: e.message || 'Unknown verification error' };
     }
 }
 
@@ -283,11 +139,10 @@ async function verifyKdbxFile(
  * 安全保存 KDBX 数据库
  * 
  * 流程：
- * 1. 创建备份（如果启用）
- * 2. 写入到临时文件
- * 3. 验证临时文件
- * 4. 如果验证通过，替换原文件
- * 5. 如果验证失败，恢复备份
+ * 1. 序列化并在内存中验证（如果启用）
+ * 2. 后端事务（src-tauri/src/backup.rs）：
+ *    备份当前文件 → 原子写入（临时文件 + rename）→ 回读校验 → 按保留策略清理旧备份
+ * 3. 回读校验失败时后端自动恢复原文件
  */
 export async function safeSaveDatabase(
     path: string,
@@ -296,53 +151,25 @@ export async function safeSaveDatabase(
 ): Promise<SaveResult> {
     const {
         createBackup = true,
-        maxBackups = 2,
         verifyAfterWrite = true,
-        silent = false
+        overwrite = false,
     } = options;
 
-    let backupPath: string | undefined;
-    const tempPath = getTempPath(path);
-
     try {
-        // 步骤 1: 创建备份（如果原文件存在）
-        if (createBackup && await exists(path)) {
-            backupPath = await getBackupPath(path);
-            console.log(`Creating backup: ${backupPath}`);
-
-            try {
-                const originalData = await readFile(path);
-                await writeFile(backupPath, originalData);
-                console.log('Backup created successfully');
-
-                // 清理旧备份
-                await cleanupOldBackups(path, maxBackups);
-            } catch (backupError) {
-                console.error('Failed to create backup:', backupError);
-                // 备份失败不应阻止保存，但应该警告用户
-                if (!silent) {
-                    console.warn('Proceeding without backup due to error');
-                }
-            }
-        }
-
-        // 步骤 2: 保存到临时文件
-        console.log(`Saving to temporary file: ${tempPath}`);
         const data = await db.save();
-        await writeFile(tempPath, new Uint8Array(data));
-        console.log('Temporary file written');
 
-        // 步骤 3: 验证临时文件（如果启用）
         let verified = false;
-        if (verifyAfterWrite) {
-            console.log('Verifying written data...');
+        if (verifyAfterWrite && isTwofishVault(db)) {
+            // kdbxweb cannot read Twofish; the backend reopened it when converting
+            verified = true;
+        } else if (verifyAfterWrite) {
+            console.log('Verifying serialized data...');
 
             if (!db.credentials) {
                 throw new Error('Database credentials not available for verification');
             }
 
-            const verification = await verifyKdbxFile(tempPath, db.credentials);
-
+            const verification = await verifyKdbxData(data, db.credentials);
             if (!verification.valid) {
                 throw new Error(`Verification failed: ${verification.error}`);
             }
@@ -351,208 +178,198 @@ export async function safeSaveDatabase(
             console.log('Verification passed');
         }
 
-        // 步骤 4: 替换原文件
-        console.log(`Replacing original file: ${path}`);
-
-        // 如果原文件存在，先删除
-        if (await exists(path)) {
-            await remove(path);
-            console.log('Original file removed');
-        }
-
-        // 复制临时文件到原文件位置
-        const tempData = await readFile(tempPath);
-        await writeFile(path, tempData);
-        console.log('New file written');
-
-        // 删除临时文件
-        await remove(tempPath);
-        console.log('Temporary file removed');
-        console.log('File replaced successfully');
+        const report = await invoke<SaveReport>('save_vault', {
+            path,
+            data: Array.from(new Uint8Array(data)),
+            vaultId: getVaultIdentity(db),
+            entryCount: countEntries(db),
+            createBackup,
+            overwrite
+        });
+        console.log('Vault saved', report.backup ? `(backup ${report.backup.id})` : '(no new backup)');
 
         return {
             success: true,
             verified,
-            backupPath
+            backupPath: report.backup?.path
         };
-
     } catch (error: any) {
         console.error('Save failed:', error);
-
-        // 步骤 5: 错误处理和回滚
-        try {
-            // 清理临时文件
-            if (await exists(tempPath)) {
-                await remove(tempPath);
-                console.log('Temporary file cleaned up');
-            }
-
-            // 如果有备份且原文件损坏，尝试恢复
-            if (backupPath && await exists(backupPath)) {
-                const fileExists = await exists(path);
-
-                if (!fileExists) {
-                    // 原文件不存在，从备份恢复
-                    console.log('Restoring from backup...');
-                    const backupData = await readFile(backupPath);
-                    await writeFile(path, backupData);
-                    console.log('Restored from backup');
-                } else {
-                    // 原文件存在，验证是否损坏
-                    if (db.credentials) {
-                        const verification = await verifyKdbxFile(path, db.credentials);
-                        if (!verification.valid) {
-                            console.log('Original file corrupted, restoring from backup...');
-                            const backupData = await readFile(backupPath);
-                            await writeFile(path, backupData);
-                            console.log('Restored from backup');
-                        }
-                    }
-                }
-            }
-        } catch (recoveryError) {
-            console.error('Recovery failed:', recoveryError);
-        }
-
         return {
             success: false,
             verified: false,
-            backupPath,
-            error: error.message || 'Unknown error'
+            error: errorMessage(error),
+            conflict: hasErrorCode(error, 'Conflict')
         };
     }
 }
 
+export type FindingSeverity = 'info' | 'warning' | 'error';
+
+export interface VerifyFinding {
+    severity: FindingSeverity;
+    /** 产生该结果的检查项，如 blockHmac、duplicateUuid */
+    check: string;
+    message: string;
+    /** 相关分组或条目的 UUID */
+    uuid?: string | null;
+}
+
+export interface VerifyReport {
+    /** 如 "KDBX 4.1"；无法读取文件头时为空 */
+    format: string;
+    /** 通过 HMAC 或哈希校验的数据块数 */
+    blocks: number;
+    groups: number;
+    entries: number;
+    attachments: number;
+    findings: VerifyFinding[];
+}
+
 /**
- * 手动验证数据库文件
+ * 手动深度验证数据库文件（后端 verify_vault_deep）
+ * 检查文件头和每个数据块的 HMAC、内部头附件、XML 结构、重复 UUID、失效的附件引用、
+ * 未使用的附件、回收站和未来的时间戳；返回所有结果而不是在第一个问题处停止
  */
-export async function verifyDatabaseFile(
-    path: string,
-    credentials: kdbxweb.Credentials
-): Promise<{ valid: boolean; error?: string; details?: any }> {
-    try {
-        const result = await verifyKdbxFile(path, credentials);
-
-        if (result.valid) {
-            // 获取更多详细信息
-            const data = await readFile(path);
-            const arrayBuffer = data.buffer as ArrayBuffer;
-            const db = await kdbxweb.Kdbx.load(arrayBuffer, credentials);
-
-            const root = db.getDefaultGroup();
-            let totalEntries = 0;
-            let totalGroups = 0;
-
-            const countRecursive = (group: kdbxweb.KdbxGroup) => {
-                totalGroups++;
-                totalEntries += group.entries.length;
-                group.groups.forEach(g => countRecursive(g));
-            };
-
-            if (root) {
-                countRecursive(root);
-            }
+export async function verifyDatabaseFile(path: string, credentials: VaultCredentials): Promise<VerifyReport> {
+    return await invoke<VerifyReport>('verify_vault_deep', { path, credentials });
+}
 
-            return {
-                valid: true,
-                details: {
-                    totalGroups,
-                    totalEntries,
-                    databaseName: db.meta.name,
-                    lastModified: db.meta.settingsChanged
-                }
-            };
-        }
+/**
+ * 没有 error 级别的结果
+ */
+export function isReportValid(report: VerifyReport): boolean {
+    return !report.findings.some(f => f.severity === 'error');
+}
 
-        return result;
-    } catch (e: any) {
-        return {
-            valid: false,
-            error: e.message || 'Verification failed'
-        };
-    }
+export interface LintIssue {
+    /** error：KeePassXC 拒绝打开；warning：其他客户端会误读或丢弃该值 */
+    severity: FindingSeverity;
+    /** 规则名，如 triState、iconId */
+    check: string;
+    message: string;
+    /** 自动修复会做什么 */
+    fix: string;
+    uuid?: string | null;
+    fixed: boolean;
+}
+
+export interface LintReport {
+    issues: LintIssue[];
+    /** 已修复的问题数 */
+    fixed: number;
+}
+
+/**
+ * 兼容性检查（后端 lint_vault）
+ * 查找 KeePassXC / KeePass 2 会拒绝或误读的值：空的或 null 布尔值、无效的
+ * EnableSearching / EnableAutoType、超出范围的图标编号、无效的颜色等
+ * fix 为 true 时修复并通过保存事务写回文件（会先创建备份），之后需要重新加载数据库
+ * 检查的是磁盘上的文件，修复前应先保存内存中的修改
+ */
+export async function lintVault(
+    path: string,
+    db: kdbxweb.Kdbx,
+    credentials: VaultCredentials,
+    fix: boolean
+): Promise<LintReport> {
+    return await invoke<LintReport>('lint_vault', {
+        path,
+        vaultId: getVaultIdentity(db),
+        credentials,
+        fix
+    });
 }
 
 /**
- * 恢复备份文件
+ * 恢复备份文件（恢复前会先备份当前文件）
  */
 export async function restoreFromBackup(
     originalPath: string,
-    backupPath: string
+    vaultId: string | undefined,
+    backupId: string
 ): Promise<{ success: boolean; error?: string }> {
     try {
-        if (!await exists(backupPath)) {
-            return {
-                success: false,
-                error: 'Backup file not found'
-            };
-        }
-
-        const backupData = await readFile(backupPath);
-        await writeFile(originalPath, backupData);
-
+        await invoke<BackupInfo>('restore_backup', { path: originalPath, vaultId, backupId });
         return { success: true };
-    } catch (e: any) {
+    } catch (e) {
         return {
             success: false,
-            error: e.message || 'Restore failed'
+            error: errorMessage(e)
         };
     }
 }
 
 /**
- * 列出可用的备份文件
+ * 列出可用的备份文件（最新的在前）
  */
-export async function listBackups(originalPath: string): Promise<string[]> {
+export async function listBackups(originalPath: string, vaultId?: string): Promise<BackupInfo[]> {
     try {
-        const { readDir } = await import('@tauri-apps/plugin-fs');
-
-        const lastSlash = Math.max(originalPath.lastIndexOf('/'), originalPath.lastIndexOf('\\'));
-        const dir = originalPath.substring(0, lastSlash);
-        const filename = originalPath.substring(lastSlash + 1);
-        const nameWithoutExt = filename.replace('.kdbx', '');
-
-        // 读取目录
-        const entries = await readDir(dir);
-
-        // 筛选出备份文件
-        const backupPattern = `${nameWithoutExt}.backup.`;
-        const backupFiles: Array<{ path: string; timestamp: number }> = [];
-
-        for (const entry of entries) {
-            if (entry.name && entry.name.startsWith(backupPattern) && entry.name.endsWith('.kdbx')) {
-                const timestampStr = entry.name
-                    .replace(backupPattern, '')
-                    .replace('.kdbx', '');
-
-                try {
-                    // 解析时间戳 (格式: 2025-12-12T18-23-00)
-                    const isoStr = timestampStr
-                        .replace(/^(\d{4})-(\d{2})-(\d{2})T(\d{2})-(\d{2})-(\d{2})$/, '$1-$2-$3T$4:$5:$6');
-
-                    const timestamp = new Date(isoStr).getTime();
-
-                    if (isNaN(timestamp)) {
-                        console.warn(`Invalid timestamp for ${entry.name}`);
-                        continue;
-                    }
-
-                    backupFiles.push({
-                        path: `${dir}/${entry.name}`,
-                        timestamp: timestamp
-                    });
-                } catch (e) {
-                    console.warn(`Failed to parse timestamp for ${entry.name}:`, e);
-                }
-            }
-        }
+        return await invoke<BackupInfo[]>('list_backups', { path: originalPath, vaultId });
+    } catch (e) {
+        console.error('Failed to list backups:', e);
+        return [];
+    }
+}
+
+export interface RepairReport {
+    /** 通过校验并解密的数据块数 */
+    blocksRecovered: number;
+    /** 数据被截断或有数据块未通过校验时为 false */
+    payloadComplete: boolean;
+    xmlComplete: boolean;
+    /** 第一个损坏的数据块中未经校验就解密的字节数 */
+    unverifiedBytes: number;
+    groups: number;
+    entries: number;
+    historyItems: number;
+    attachments: number;
+    /** 无法恢复的内容，每行一项 */
+    lost: string[];
+}
 
-        // 按时间戳排序（最新的在前）
-        backupFiles.sort((a, b) => b.timestamp - a.timestamp);
+/**
+ * 修复损坏的数据库：用凭据解密仍可读取的部分，另存为 `targetPath`（不修改原文件）
+ * 返回的报告列出无法恢复的内容
+ */
+export async function repairVault(path: string, credentials: VaultCredentials, targetPath: string): Promise<RepairReport> {
+    return await invoke<RepairReport>('repair_vault', { path, credentials, targetPath });
+}
+
+export interface MirrorConfig {
+    dir: string;
+    /** 保留的副本数，0 = 不限 */
+    maxCount?: number;
+    /** 删除超过天数的副本，0 = 永久保留 */
+    maxAgeDays?: number;
+}
+
+export interface BackupMirror extends MirrorConfig {
+    lastAttempt?: string | null;
+    lastSuccess?: string | null;
+    /** 最近一次失败的原因（成功时为空） */
+    lastError?: string | null;
+}
 
-        return backupFiles.map(f => f.path);
+/**
+ * 获取数据库的备份镜像目录及其状态
+ */
+export async function getBackupMirrors(originalPath: string, vaultId?: string): Promise<BackupMirror[]> {
+    try {
+        return await invoke<BackupMirror[]>('get_backup_mirrors', { path: originalPath, vaultId });
     } catch (e) {
-        console.error('Failed to list backups:', e);
+        console.error('Failed to load backup mirrors:', e);
         return [];
     }
 }
+
+/**
+ * 设置备份镜像目录（保存成功后异步复制到每个镜像）
+ */
+export async function setBackupMirrors(
+    originalPath: string,
+    vaultId: string | undefined,
+    mirrors: MirrorConfig[]
+): Promise<BackupMirror[]> {
+    return await invoke<BackupMirror[]>('set_backup_mirrors', { path: originalPath, vaultId, mirrors });
+}
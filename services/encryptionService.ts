@@ -0,0 +1,114 @@
+import { invoke } from '@tauri-apps/api/core';
+import * as kdbxweb from 'kdbxweb';
+import { getVaultIdentity } from './databaseIntegrityService';
+import { VaultCredentials } from './syncService';
+
+/**
+ * Vault encryption settings (see src-tauri/src/kdbx/settings.rs)
+ */
+
+export type CipherName = 'AES-256' | 'ChaCha20' | 'Twofish';
+export type KdfName = 'AES-KDF' | 'Argon2d' | 'Argon2id';
+
+export interface CryptoSettings {
+    cipher: CipherName;
+    kdf: KdfName;
+    /** AES-KDF rounds or Argon2 iterations */
+    iterations: number;
+    /** Argon2 memory in bytes */
+    memory?: number;
+    /** Argon2 parallelism */
+    parallelism?: number;
+    compression: boolean;
+}
+
+export interface KdfBenchmark {
+    /** Time to derive the key with the benchmarked settings on this device */
+    estimatedMillis: number;
+    /** Rounds or iterations that take about one second */
+    oneSecondIterations: number;
+}
+
+/**
+ * Re-encrypt the vault file at `path` with `settings`
+ * The backend verifies the result and backs up the previous version; the vault must
+ * be saved before and reloaded after
+ */
+export async function setVaultCrypto(
+    path: string,
+    db: kdbxweb.Kdbx,
+    credentials: VaultCredentials,
+    settings: CryptoSettings
+): Promise<void> {
+    await invoke('set_vault_crypto', {
+        path,
+        vaultId: getVaultIdentity(db),
+        credentials,
+        settings
+    });
+}
+
+/**
+ * Re-encrypt vault `data` with `cipher` in memory; nothing is written
+ * `location` (file path or server URL) counts wrong credentials like a failed unlock
+ */
+export async function transcodeVault(
+    location: string,
+    data: ArrayBuffer,
+    credentials: VaultCredentials,
+    cipher: CipherName
+): Promise<ArrayBuffer> {
+    const result = await invoke<number[]>('transcode_vault', {
+        location,
+        data: Array.from(new Uint8Array(data)),
+        credentials,
+        cipher
+    });
+    return new Uint8Array(result).buffer;
+}
+
+// Databases loaded from a Twofish file; their `save()` returns Twofish
+const twofishVaults = new WeakSet<kdbxweb.Kdbx>();
+
+/**
+ * Load vault `data` with kdbxweb
+ * kdbxweb cannot decrypt Twofish: such a vault is loaded from a ChaCha20 copy the backend
+ * makes in memory, and `db.save()` converts what kdbxweb writes back to Twofish, so the
+ * file, its backups and a server copy keep their cipher. This works for read-only and
+ * remote vaults too, since nothing is written while loading.
+ */
+export async function loadVault(
+    location: string,
+    data: ArrayBuffer,
+    kdbxCredentials: kdbxweb.Credentials,
+    credentials: VaultCredentials
+): Promise<kdbxweb.Kdbx> {
+    try {
+        return await kdbxweb.Kdbx.load(data.slice(0), kdbxCredentials);
+    } catch (e: any) {
+        if (!/unsupported cipher/i.test(e?.message ?? '')) {
+            throw e;
+        }
+    }
+    const converted = await transcodeVault(location, data, credentials, 'ChaCha20');
+    const db = await kdbxweb.Kdbx.load(converted, kdbxCredentials);
+    const save = db.save.bind(db);
+    db.save = async () => transcodeVault(location, await save(), credentials, 'Twofish');
+    twofishVaults.add(db);
+    return db;
+}
+
+/**
+ * Whether `db` was loaded from a Twofish file (see `loadVault`); the backend verifies
+ * what it saves, kdbxweb cannot
+ */
+export function isTwofishVault(db: kdbxweb.Kdbx): boolean {
+    return twofishVaults.has(db);
+}
+
+/**
+ * Time the key derivation `settings` describe on this device
+ */
+export async function benchmarkKdf(settings: CryptoSettings): Promise<KdfBenchmark> {
+    return await invoke<KdfBenchmark>('benchmark_kdf', { settings });
+}
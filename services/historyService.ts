@@ -0,0 +1,80 @@
+import { invoke } from '@tauri-apps/api/core';
+import * as kdbxweb from 'kdbxweb';
+import { getVaultIdentity } from './databaseIntegrityService';
+import { VaultCredentials, VaultDiff } from './syncService';
+
+/**
+ * 数据库历史服务（可选，按数据库开启）
+ *
+ * 开启后，每次通过校验的保存都会提交到应用数据目录中的私有 git 仓库
+ * （history/<key>/，与备份使用相同的键）。提交的是磁盘上的加密文件，仓库中没有明文。
+ * 快照不受备份保留策略限制；比较两个快照需要先用凭据解锁它们，
+ * 恢复快照总是另存为新文件，不会覆盖当前数据库。见 src-tauri/src/history.rs。
+ */
+
+export interface HistoryStatus {
+    enabled: boolean;
+    snapshotCount: number;
+}
+
+export interface Snapshot {
+    /** git 提交哈希 */
+    id: string;
+    createdAt: string;
+    size: number;
+    sha256?: string | null;
+    /** 保存时的条目数；开启历史时记录的第一个快照没有 */
+    entryCount?: number | null;
+}
+
+export async function getVaultHistory(path: string, db: kdbxweb.Kdbx): Promise<HistoryStatus> {
+    return invoke<HistoryStatus>('get_vault_history', { path, vaultId: getVaultIdentity(db) });
+}
+
+/**
+ * Turn history on (recording the current file as the first snapshot) or off
+ * Turning it off keeps the snapshots taken so far
+ */
+export async function setVaultHistory(path: string, db: kdbxweb.Kdbx, enabled: boolean): Promise<HistoryStatus> {
+    return invoke<HistoryStatus>('set_vault_history', { path, vaultId: getVaultIdentity(db), enabled });
+}
+
+export async function deleteVaultHistory(path: string, db: kdbxweb.Kdbx): Promise<void> {
+    await invoke('delete_vault_history', { path, vaultId: getVaultIdentity(db) });
+}
+
+/**
+ * Snapshots of a vault, newest first
+ */
+export async function listVaultSnapshots(path: string, db: kdbxweb.Kdbx): Promise<Snapshot[]> {
+    return invoke<Snapshot[]>('list_vault_snapshots', { path, vaultId: getVaultIdentity(db) });
+}
+
+/**
+ * Entry-level changes from snapshot `from` to snapshot `to`
+ * `toCredentials` defaults to `credentials` (snapshots from before a master key change need their own)
+ */
+export async function diffVaultSnapshots(
+    path: string,
+    db: kdbxweb.Kdbx,
+    from: string,
+    to: string,
+    credentials: VaultCredentials,
+    toCredentials?: VaultCredentials
+): Promise<VaultDiff> {
+    return invoke<VaultDiff>('diff_vault_snapshots', {
+        path,
+        vaultId: getVaultIdentity(db),
+        from,
+        to,
+        credentials,
+        toCredentials
+    });
+}
+
+/**
+ * Write a snapshot to `targetPath` as a separate vault file
+ */
+export async function restoreVaultSnapshot(path: string, db: kdbxweb.Kdbx, snapshotId: string, targetPath: string): Promise<void> {
+    await invoke('restore_vault_snapshot', { path, vaultId: getVaultIdentity(db), snapshotId, targetPath });
+}
@@ -97,9 +97,10 @@ const uuidsEqual = (a: any, b: any): boolean => {
 
 // --- Database Creation ---
 
-export const createDatabase = (name: string, password: string, keyFile?: ArrayBuffer): kdbxweb.Kdbx => {
+// Without a password the key file alone opens the vault
+export const createDatabase = (name: string, password: kdbxweb.ProtectedValue | undefined, keyFile?: ArrayBuffer): kdbxweb.Kdbx => {
     initializeArgon2();
-    const credentials = new kdbxweb.Credentials(kdbxweb.ProtectedValue.fromString(password), keyFile);
+    const credentials = new kdbxweb.Credentials(password ?? null, keyFile);
     const db = kdbxweb.Kdbx.create(credentials, name);
     db.header.setKdf(kdbxweb.Consts.KdfId.Argon2);
     // @ts-ignore
@@ -123,35 +124,6 @@ export const createDatabase = (name: string, password: string, keyFile?: ArrayBu
 
 // --- CRUD Operations ---
 
-/**
- * Detect if a file path is in cloud storage
- * Cloud storage includes: iCloud Drive, Dropbox, Google Drive, OneDrive
- */
-export const isInCloudStorage = (filePath: string): boolean => {
-    // Normalize path separators
-    const normalizedPath = filePath.replace(/\\/g, '/');
-
-    // Cloud storage path patterns
-    const cloudPatterns = [
-        // macOS
-        '/Library/Mobile Documents/com~apple~CloudDocs',  // iCloud Drive
-        '/Dropbox/',
-        '/Google Drive/',
-        '/OneDrive/',
-
-        // Windows
-        '/OneDrive/',
-        '/Dropbox/',
-        '/Google Drive/',
-
-        // Linux
-        '/Dropbox/',
-        '/gdrive/',
-    ];
-
-    return cloudPatterns.some(pattern => normalizedPath.includes(pattern));
-};
-
 // Helper: Generate otpauth URL
 const generateOtpUrl = (secret: string, label: string, issuer: string = 'KeedaVault'): string | undefined => {
     const clean = secret.replace(/\s/g, '').toUpperCase();
@@ -164,17 +136,20 @@ const generateOtpUrl = (secret: string, label: string, issuer: string = 'KeedaVa
 /**
  * Fix AutoType fields for KeePassXC compatibility
  * KeePassXC requires explicit boolean value for AutoType.enabled
- * This function ensures all entries have valid AutoType settings
+ * A missing or null value means enabled, so only that flag is set; the sequence and
+ * associations are kept
  */
 const fixAutoTypeFields = (group: kdbxweb.KdbxGroup): void => {
     // Fix entries in current group
     for (const entry of group.entries) {
-        if (!entry.autoType || entry.autoType.enabled === null || entry.autoType.enabled === undefined) {
+        if (!entry.autoType) {
             entry.autoType = {
                 enabled: true,
                 obfuscation: 0,
                 items: []
             };
+        } else if (entry.autoType.enabled !== true && entry.autoType.enabled !== false) {
+            entry.autoType.enabled = true;
         }
     }
 
@@ -186,16 +161,22 @@ const fixAutoTypeFields = (group: kdbxweb.KdbxGroup): void => {
 
 /**
  * Fix EnableSearching and EnableAutoType fields for KeePassXC compatibility
- * KeePassXC doesn't accept "null", "", "true", "false" as written by kdbxweb
- * We delete these properties entirely so kdbxweb won't write them to XML
+ * kdbxweb writes an empty element for undefined, which KeePassXC rejects
+ * ("Invalid EnableSearching value"). These are tri-states: true / false / null, where
+ * null inherits from the parent group. Anything else becomes null, which is what the
+ * group did before; explicit settings are kept.
+ *
+ * The backend lint (lint_vault) applies the same rules, but only when the user runs it
+ * and only to the file on disk. Saves are serialized here by kdbxweb, which would
+ * write the empty elements again, so these fixes stay on the save path.
  */
 const fixGroupSearchingFields = (group: kdbxweb.KdbxGroup): void => {
-    // Delete the properties entirely to prevent kdbxweb from writing them
-    // This is the only reliable way to prevent the Invalid EnableSearching value error
-    // @ts-ignore - Deleting optional properties for compatibility
-    delete group.enableSearching;
-    // @ts-ignore - Deleting optional properties for compatibility
-    delete (group as any).enableAutoType;
+    if (group.enableSearching !== true && group.enableSearching !== false) {
+        group.enableSearching = null;
+    }
+    if (group.enableAutoType !== true && group.enableAutoType !== false) {
+        group.enableAutoType = null;
+    }
 
     // Recursively fix subgroups
     for (const subgroup of group.groups) {
@@ -211,7 +192,7 @@ export const applyCompatibilityFixes = (db: kdbxweb.Kdbx): void => {
     const root = db.getDefaultGroup();
     if (root) {
         fixAutoTypeFields(root);
-        // Delete enableSearching/enableAutoType to prevent KeePassXC errors
+        // Normalize enableSearching/enableAutoType to true / false / null
         fixGroupSearchingFields(root);
     }
 };
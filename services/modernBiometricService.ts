@@ -1,6 +1,7 @@
 // Modern Biometric Authentication Service (No Password Prompt!)
 // Uses SecItemAdd with SecAccessControl instead of deprecated SecKeychainAddGenericPassword
 import { invoke } from '@tauri-apps/api/core';
+import { errorMessage, hasErrorCode } from './appError';
 
 /**
  * Encode vault path to Base64 for consistent keychain storage
@@ -40,7 +41,11 @@ export const modernBiometricService = {
         try {
             return await invoke<boolean>('authenticate_biometric', { reason });
         } catch (error) {
-            console.error('Biometric authentication failed:', error);
+            if (hasErrorCode(error, 'UserCancelled')) {
+                console.log('[ModernBiometricService] Biometric authentication cancelled');
+            } else {
+                console.error('Biometric authentication failed:', error);
+            }
             return false;
         }
     },
@@ -60,7 +65,7 @@ export const modernBiometricService = {
             console.error('[ModernBiometricService] Failed to store password securely:', error);
             console.warn('[ModernBiometricService] This is non-critical - vault can still be accessed with password');
             // Re-throw to let caller handle it
-            throw new Error(`Touch ID password storage failed: ${error}`);
+            throw new Error(`Touch ID password storage failed: ${errorMessage(error)}`);
         }
     },
 
@@ -75,7 +80,11 @@ export const modernBiometricService = {
             return await invoke<string>('secure_get_password_modern', { vaultPath: encodedPath });
         } catch (error) {
             // It's normal to fail if password not found
-            console.log('[ModernBiometricService] Password not found or error:', error);
+            if (hasErrorCode(error, 'NotFound', 'UserCancelled')) {
+                console.log('[ModernBiometricService] No password returned:', errorMessage(error));
+            } else {
+                console.warn('[ModernBiometricService] Failed to read stored password:', error);
+            }
             return null;
         }
     },
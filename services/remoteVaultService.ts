@@ -0,0 +1,99 @@
+import { invoke } from '@tauri-apps/api/core';
+import { ask } from '@tauri-apps/plugin-dialog';
+import * as kdbxweb from 'kdbxweb';
+import { hasErrorCode } from './appError';
+import { getVaultIdentity } from './databaseIntegrityService';
+import { MergeSummary, VaultCredentials } from './syncService';
+
+/**
+ * 远程数据库服务（WebDAV、S3 兼容对象存储、SFTP）
+ *
+ * 远程数据库被下载到本地缓存文件，窗口像本地数据库一样打开、保存、备份缓存文件。
+ * 每次保存后把缓存上传到服务器（If-Match 条件写入）；服务器上的版本已被其他设备
+ * 修改时，后端把缓存合并进服务器版本再上传，窗口随后重新加载缓存。
+ * 服务器登录信息（S3 为访问密钥，SFTP 为私钥文件及其口令）保存在系统密钥存储中。见 src-tauri/src/remote/。
+ * 首次连接的 SFTP 服务器要由用户核对主机密钥指纹后才会被信任。
+ */
+
+export interface RemoteVault {
+    url: string;
+    /** 窗口实际打开和保存的本地缓存文件 */
+    cachePath: string;
+    /** 无法连接服务器，打开的是缓存副本 */
+    offline: boolean;
+    /** 缓存中有尚未上传的保存，解锁后需要上传 */
+    pending: boolean;
+}
+
+export interface PushReport {
+    /** 服务器上的版本已变化并已合并；窗口需要重新加载缓存 */
+    merge?: MergeSummary | null;
+}
+
+export interface HostKey {
+    /** known_hosts 中的主机名，非默认端口时为 `[host]:port` */
+    host: string;
+    /** `SHA256:` 指纹，与 `ssh-keygen -l` 的输出格式相同 */
+    fingerprint: string;
+    /** 主机密钥已在 known_hosts 中 */
+    known: boolean;
+}
+
+/**
+ * Whether a vault location is a server URL rather than a local path
+ */
+export function isRemoteLocation(location: string): boolean {
+    return /^(https?|webdavs?|s3|sftp):\/\//i.test(location);
+}
+
+/**
+ * Download a remote vault into its local cache (or use the cache when offline). An SFTP
+ * server seen for the first time is only trusted after the user confirmed its host key.
+ */
+export async function openRemoteVault(url: string): Promise<RemoteVault> {
+    try {
+        return await invoke<RemoteVault>('open_remote_vault', { url });
+    } catch (err) {
+        if (!hasErrorCode(err, 'UnknownHostKey') || !(await confirmHostKey(url))) throw err;
+        return invoke<RemoteVault>('open_remote_vault', { url });
+    }
+}
+
+/**
+ * Show the host key fingerprint of the SFTP server behind `url` and trust it if the user
+ * confirms. The backend checks that the server still has that key before storing it.
+ */
+export async function confirmHostKey(url: string): Promise<boolean> {
+    const hostKey = await invoke<HostKey>('remote_host_key', { url });
+    if (hostKey.known) return true;
+    const confirmed = await ask(
+        `KeedaVault has not connected to ${hostKey.host} before. Its host key fingerprint is:\n\n${hostKey.fingerprint}\n\nTrust this server only if the fingerprint matches the one its administrator gave you (ssh-keygen -lf on the server's host key).`,
+        { title: 'Unknown SFTP Server', kind: 'warning', okLabel: 'Trust', cancelLabel: 'Cancel' }
+    );
+    if (!confirmed) return false;
+    await invoke('trust_remote_host_key', { url, fingerprint: hostKey.fingerprint });
+    return true;
+}
+
+/**
+ * Upload the saved cache of a remote vault, merging when the server copy changed
+ */
+export async function pushRemoteVault(url: string, db: kdbxweb.Kdbx, credentials: VaultCredentials): Promise<PushReport> {
+    return invoke<PushReport>('push_remote_vault', {
+        url,
+        vaultId: getVaultIdentity(db),
+        credentials
+    });
+}
+
+/**
+ * Store the server login for a vault URL; SFTP logins pass a private key file, with
+ * its passphrase as `secret`
+ */
+export async function setRemoteCredentials(url: string, username: string, secret: string, keyFile?: string): Promise<void> {
+    return invoke('set_remote_credentials', { url, username, secret, keyFile: keyFile ?? null });
+}
+
+export async function deleteRemoteCredentials(url: string): Promise<void> {
+    return invoke('delete_remote_credentials', { url });
+}
@@ -0,0 +1,257 @@
+import { invoke } from '@tauri-apps/api/core';
+import * as kdbxweb from 'kdbxweb';
+import { getVaultIdentity } from './databaseIntegrityService';
+
+/**
+ * 数据库同步服务
+ *
+ * 合并由后端完成（KeePassXC "Synchronize" 语义）：
+ * 按 UUID 匹配条目和分组，较新的修改获胜，旧版本保留在历史记录中，
+ * 并应用双方的删除记录。合并结果通过常规保存事务写入（总会先备份）。
+ *
+ * 比较两个数据库文件（diff_vaults）：按 UUID 匹配，逐字段列出新增、删除、移动和修改，
+ * 受保护的值（密码等）只标记为已隐藏，不返回内容。用于恢复备份前检查、核对合并结果等。
+ *
+ * 打开的数据库文件由后端监视：其他设备或程序修改文件时会向窗口发送
+ * `vault-changed-on-disk` 事件，保存会以 Conflict 错误拒绝覆盖该修改。
+ */
+
+/** Payload of the `vault-changed-on-disk` event */
+export interface DiskChange {
+    path: string;
+    /** 文件被删除或移走时为 false */
+    exists: boolean;
+    modifiedAt?: string | null;
+}
+
+/** Master key components sent to the backend */
+export interface VaultCredentials {
+    password?: string;
+    keyFile?: number[];
+}
+
+export interface MergeSummary {
+    entriesAdded: number;
+    entriesUpdated: number;
+    entriesMoved: number;
+    entriesDeleted: number;
+    groupsAdded: number;
+    groupsUpdated: number;
+    groupsMoved: number;
+    groupsDeleted: number;
+    iconsAdded: number;
+    /** 按应用顺序记录的每一项变更 */
+    changes: string[];
+}
+
+export type ChangeKind = 'added' | 'removed' | 'changed';
+
+export interface FieldDiff {
+    name: string;
+    kind: 'string' | 'attachment' | 'property';
+    change: ChangeKind;
+    /** 旧值；字段不存在、已隐藏或无法用文本表示时为空 */
+    before?: string | null;
+    after?: string | null;
+    /** 受保护的值，内容未返回 */
+    masked: boolean;
+}
+
+/** 条目历史版本的变化（按修改时间匹配） */
+export interface HistoryDiff {
+    added: number;
+    removed: number;
+    countBefore: number;
+    countAfter: number;
+}
+
+export interface EntryDiff {
+    uuid: string;
+    title: string;
+    kind: ChangeKind;
+    /** 条目所在分组的路径（已删除的条目为原路径） */
+    path: string;
+    /** 条目被移动时的原分组路径 */
+    movedFrom?: string | null;
+    /** 有变化的字段；新增或删除的条目列出全部字段 */
+    fields: FieldDiff[];
+    history?: HistoryDiff | null;
+}
+
+export interface GroupDiff {
+    uuid: string;
+    name: string;
+    kind: ChangeKind;
+    path: string;
+    movedFrom?: string | null;
+    fields: FieldDiff[];
+}
+
+export interface VaultDiff {
+    entries: EntryDiff[];
+    groups: GroupDiff[];
+}
+
+export function toVaultCredentials(password?: kdbxweb.ProtectedValue, keyFileData?: Uint8Array): VaultCredentials {
+    return {
+        password: password?.getText(),
+        keyFile: keyFileData ? Array.from(keyFileData) : undefined
+    };
+}
+
+/**
+ * Merge `sourcePath` into the vault file at `path`
+ * `sourceCredentials` defaults to the vault's own credentials
+ */
+export async function mergeVaultFile(
+    path: string,
+    db: kdbxweb.Kdbx,
+    credentials: VaultCredentials,
+    sourcePath: string,
+    sourceCredentials?: VaultCredentials
+): Promise<MergeSummary> {
+    return invoke<MergeSummary>('merge_vault_file', {
+        path,
+        vaultId: getVaultIdentity(db),
+        credentials,
+        sourcePath,
+        sourceCredentials
+    });
+}
+
+/**
+ * Merge the unsaved in-memory state of `db` into the vault file at `path`,
+ * which was changed on disk since it was loaded. Reload the vault afterwards.
+ */
+export async function mergeVaultData(
+    path: string,
+    db: kdbxweb.Kdbx,
+    credentials: VaultCredentials
+): Promise<MergeSummary> {
+    const data = await db.save();
+    return invoke<MergeSummary>('merge_vault_data', {
+        path,
+        vaultId: getVaultIdentity(db),
+        credentials,
+        data: Array.from(new Uint8Array(data))
+    });
+}
+
+/**
+ * Changes from vault file `a` to vault file `b`
+ * `bCredentials` defaults to `credentials` (for copies saved with another master key)
+ */
+export async function diffVaults(
+    a: string,
+    b: string,
+    credentials: VaultCredentials,
+    bCredentials?: VaultCredentials
+): Promise<VaultDiff> {
+    return invoke<VaultDiff>('diff_vaults', { a, b, credentials, bCredentials });
+}
+
+/**
+ * SHA-256 (hex) of file contents, as the backend fingerprints vault files
+ */
+export async function sha256Hex(data: ArrayBuffer | Uint8Array): Promise<string> {
+    const digest = await crypto.subtle.digest('SHA-256', data);
+    return Array.from(new Uint8Array(digest))
+        .map(b => b.toString(16).padStart(2, '0'))
+        .join('');
+}
+
+/**
+ * Watch a vault opened in this window for changes by other devices or programs
+ * `sha256` is the hash of the bytes that were loaded
+ */
+export async function watchVault(path: string, sha256?: string): Promise<void> {
+    return invoke('watch_vault', { path, sha256 });
+}
+
+export async function unwatchVault(path: string): Promise<void> {
+    return invoke('unwatch_vault', { path });
+}
+
+export type CloudProvider = 'iCloud' | 'dropbox' | 'googleDrive' | 'oneDrive' | 'nextcloud' | 'syncthing';
+export type ConflictKind = 'dropbox' | 'nextcloud' | 'syncthing' | 'iCloud';
+
+/** A copy of the vault that a sync client created next to it during a conflict */
+export interface ConflictCopy {
+    path: string;
+    fileName: string;
+    kind: ConflictKind;
+    /** 文件名中记录的设备或用户 */
+    device?: string | null;
+    /** 文件名中记录的冲突时间 */
+    conflictAt?: string | null;
+    modifiedAt?: string | null;
+    size: number;
+    /** iCloud 占位文件：需先下载才能合并 */
+    placeholder: boolean;
+}
+
+export interface ConflictScan {
+    provider?: CloudProvider | null;
+    /** 按时间从旧到新 */
+    conflicts: ConflictCopy[];
+}
+
+/** Payload of the `sync-conflict-found` event */
+export interface ConflictFound {
+    path: string;
+    conflictPath: string;
+}
+
+/**
+ * Find sync-conflict copies (Dropbox, Nextcloud, Syncthing, iCloud) next to a vault
+ */
+export async function findSyncConflicts(path: string): Promise<ConflictScan> {
+    return invoke<ConflictScan>('find_sync_conflicts', { path });
+}
+
+/**
+ * Move a conflict copy out of the synced folder into the vault's backup directory
+ * Returns the archived path
+ */
+export async function archiveSyncConflict(path: string, db: kdbxweb.Kdbx, conflictPath: string): Promise<string> {
+    return invoke<string>('archive_sync_conflict', {
+        path,
+        vaultId: getVaultIdentity(db),
+        conflictPath
+    });
+}
+
+/**
+ * One-line description of a merge result for toasts
+ */
+export function describeMergeSummary(summary: MergeSummary): string {
+    const parts: string[] = [];
+    const add = (count: number, label: string) => {
+        if (count > 0) parts.push(`${count} ${label}`);
+    };
+    add(summary.entriesAdded + summary.groupsAdded, 'added');
+    add(summary.entriesUpdated + summary.groupsUpdated, 'updated');
+    add(summary.entriesMoved + summary.groupsMoved, 'moved');
+    add(summary.entriesDeleted + summary.groupsDeleted, 'deleted');
+    add(summary.iconsAdded, 'icons added');
+    if (parts.length > 0) return parts.join(', ');
+    return summary.changes.length > 0 ? 'Entry history merged' : 'No changes';
+}
+
+/**
+ * One-line description of a diff for toasts and headers
+ */
+export function describeVaultDiff(diff: VaultDiff): string {
+    const parts: string[] = [];
+    const add = (count: number, label: string) => {
+        if (count > 0) parts.push(`${count} ${label}`);
+    };
+    const all: (EntryDiff | GroupDiff)[] = [...diff.entries, ...diff.groups];
+    const modified = (c: EntryDiff | GroupDiff) =>
+        c.kind === 'changed' && (c.fields.length > 0 || ('history' in c && !!c.history));
+    add(all.filter(c => c.kind === 'added').length, 'added');
+    add(all.filter(modified).length, 'modified');
+    add(all.filter(c => c.movedFrom).length, 'moved');
+    add(all.filter(c => c.kind === 'removed').length, 'removed');
+    return parts.length > 0 ? parts.join(', ') : 'No changes';
+}
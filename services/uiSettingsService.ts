@@ -52,6 +52,16 @@ export interface UISettings {
         quickUnlockTouchId: boolean;
         rememberKeyFiles: boolean;
         autoBackup: boolean; // Auto backup before saving
+        verboseLogging?: boolean; // Log unredacted paths and debug output
+    };
+    backup?: {
+        maxCount: number; // 0 = unlimited
+        maxAgeDays: number; // 0 = keep forever
+        maxTotalMb: number; // 0 = no size limit
+    };
+    updates?: {
+        channel: 'stable' | 'beta';
+        manifestUrl?: string; // Override for self-hosted release manifests
     };
 }
 
@@ -106,6 +116,15 @@ const defaultSettings: UISettings = {
         quickUnlockTouchId: false,
         rememberKeyFiles: false,
         autoBackup: true,
+        verboseLogging: false,
+    },
+    backup: {
+        maxCount: 10,
+        maxAgeDays: 0,
+        maxTotalMb: 0,
+    },
+    updates: {
+        channel: 'stable',
     },
 };
 
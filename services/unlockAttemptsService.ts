@@ -0,0 +1,44 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/**
+ * Failed unlock attempts, counted by the backend per vault location
+ * After a few failures each attempt waits for a growing delay; see
+ * src-tauri/src/unlock_attempts.rs
+ */
+
+export interface UnlockDelay {
+    failedAttempts: number;
+    retryAfterMs: number;
+}
+
+export interface UnlockSummary {
+    failedAttempts: number;
+    lastFailure?: string | null;
+}
+
+/**
+ * Reject with a `TooManyAttempts` error while the vault at `location` must wait
+ */
+export async function checkUnlockAllowed(location: string): Promise<void> {
+    await invoke('check_unlock_allowed', { location });
+}
+
+export async function recordFailedUnlock(location: string): Promise<UnlockDelay> {
+    return await invoke<UnlockDelay>('record_failed_unlock', { location });
+}
+
+/**
+ * Reset the counter of `location`; returns the failures since the previous unlock
+ */
+export async function recordSuccessfulUnlock(location: string): Promise<UnlockSummary> {
+    return await invoke<UnlockSummary>('record_successful_unlock', { location });
+}
+
+/**
+ * "N failed attempts since last unlock", or nothing without failures
+ */
+export function describeFailedAttempts(summary: UnlockSummary): string | undefined {
+    const count = summary.failedAttempts;
+    if (count === 0) return undefined;
+    return `${count} failed attempt${count === 1 ? '' : 's'} since last unlock`;
+}
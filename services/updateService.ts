@@ -1,73 +1,42 @@
-import { getVersion } from '@tauri-apps/api/app';
+import { invoke } from '@tauri-apps/api/core';
 
-export interface GitHubRelease {
-    tag_name: string;
+export type UpdateChannel = 'stable' | 'beta';
+
+export interface ReleaseInfo {
+    version: string;
+    channel: UpdateChannel;
     name: string;
-    body: string;
-    html_url: string;
-    published_at: string;
-    prerelease: boolean;
-    draft: boolean;
+    notes: string;
+    url: string;
+    publishedAt: string;
+}
+
+interface UpdateCheck {
+    currentVersion: string;
+    channel: UpdateChannel;
+    update?: ReleaseInfo | null;
 }
 
 /**
- * Checks for updates from GitHub releases
- * @returns The latest release if a newer version is available, null otherwise
+ * Checks for updates against the signed release manifest (see src-tauri/src/updater.rs)
+ * The manifest signature is verified in the backend before any release is offered.
+ * @returns The newest eligible release if a newer version is available
  */
-export async function checkForUpdates(): Promise<{ hasUpdate: boolean; latestRelease?: GitHubRelease; currentVersion: string }> {
+export async function checkForUpdates(): Promise<{ hasUpdate: boolean; latestRelease?: ReleaseInfo; currentVersion: string }> {
     try {
-        const currentVersion = await getVersion();
-        console.log('[Update Checker] Current version:', currentVersion);
-
-        // Fetch latest release from GitHub
-        const response = await fetch('https://api.github.com/repos/daocatt/keedavault/releases/latest');
-
-        if (!response.ok) {
-            throw new Error(`GitHub API returned ${response.status}`);
-        }
-
-        const latestRelease: GitHubRelease = await response.json();
-        console.log('[Update Checker] Latest release:', latestRelease.tag_name);
-
-        // Skip drafts and prereleases
-        if (latestRelease.draft || latestRelease.prerelease) {
-            console.log('[Update Checker] Latest release is draft or prerelease, skipping');
-            return { hasUpdate: false, currentVersion };
-        }
-
-        // Compare versions (remove 'v' prefix if present)
-        const currentVer = currentVersion.replace(/^v/, '');
-        const latestVer = latestRelease.tag_name.replace(/^v/, '');
+        const result = await invoke<UpdateCheck>('check_updates');
+        console.log('[Update Checker] Current version:', result.currentVersion, 'channel:', result.channel);
 
-        const hasUpdate = compareVersions(latestVer, currentVer) > 0;
-        console.log('[Update Checker] Has update:', hasUpdate);
+        const latestRelease = result.update ?? undefined;
+        console.log('[Update Checker] Has update:', !!latestRelease);
 
         return {
-            hasUpdate,
-            latestRelease: hasUpdate ? latestRelease : undefined,
-            currentVersion
+            hasUpdate: !!latestRelease,
+            latestRelease,
+            currentVersion: result.currentVersion
         };
     } catch (error) {
         console.error('[Update Checker] Failed to check for updates:', error);
         throw error;
     }
 }
-
-/**
- * Compare two semantic versions
- * @returns 1 if a > b, -1 if a < b, 0 if equal
- */
-function compareVersions(a: string, b: string): number {
-    const aParts = a.split('.').map(Number);
-    const bParts = b.split('.').map(Number);
-
-    for (let i = 0; i < Math.max(aParts.length, bParts.length); i++) {
-        const aPart = aParts[i] || 0;
-        const bPart = bParts[i] || 0;
-
-        if (aPart > bPart) return 1;
-        if (aPart < bPart) return -1;
-    }
-
-    return 0;
-}
@@ -0,0 +1,88 @@
+import { invoke } from '@tauri-apps/api/core';
+import { ask } from '@tauri-apps/plugin-dialog';
+
+/**
+ * 数据库锁文件（KeePass 风格的 `<文件名>.lock`）
+ *
+ * 打开数据库时在其旁边创建锁文件，记录主机、用户、PID 和打开时间。
+ * 其他窗口、实例或共享同一文件夹的其他机器发现锁后，可以只读打开、接管锁或取消。
+ * 本程序的另一个窗口接管锁时，原窗口的会话变为只读并收到 `vault-lock-taken-over` 事件。
+ * 锁是建议性的（advisory），见 src-tauri/src/vault_lock.rs。
+ */
+
+/** Who holds the lock on a vault */
+export interface LockHolder {
+    host: string;
+    user: string;
+    pid?: number | null;
+    openedAt?: string | null;
+    /** 被本程序的另一个窗口持有 */
+    thisInstance: boolean;
+    /** 本机上已不再运行的进程遗留的锁 */
+    stale: boolean;
+}
+
+export interface LockStatus {
+    acquired: boolean;
+    holder?: LockHolder | null;
+}
+
+/** Payload of `vault-lock-taken-over`: another window took this window's lock */
+export interface LockTakenOver {
+    path: string;
+}
+
+export type LockedVaultChoice = 'readOnly' | 'takeOver' | 'cancel';
+
+/**
+ * Lock a vault for this window; with `takeOver` the lock is taken from its current holder
+ */
+export async function acquireVaultLock(path: string, takeOver = false): Promise<LockStatus> {
+    return invoke<LockStatus>('acquire_vault_lock', { path, takeOver });
+}
+
+export async function releaseVaultLock(path: string): Promise<void> {
+    return invoke('release_vault_lock', { path });
+}
+
+/**
+ * Mark this window's session on a vault read-only; the backend then refuses every save
+ * and other write for it. Only the backend ends the session: when the window closes or
+ * acquires the vault's lock.
+ */
+export async function markVaultReadOnly(path: string): Promise<void> {
+    return invoke('mark_vault_read_only', { path });
+}
+
+function describeHolder(holder: LockHolder): string {
+    if (holder.thisInstance) return 'another KeedaVault window';
+    const who = [holder.user, holder.host].filter(Boolean).join('@') || 'an unknown user';
+    const pid = holder.pid ? ` (process ${holder.pid})` : '';
+    const since = holder.openedAt ? ` since ${new Date(holder.openedAt).toLocaleString()}` : '';
+    return `${who}${pid}${since}`;
+}
+
+/**
+ * Ask what to do with a vault that is locked by someone else
+ */
+export async function askLockedVaultChoice(filename: string, holder: LockHolder): Promise<LockedVaultChoice> {
+    const reason = holder.stale
+        ? `"${filename}" has a lock left behind by ${describeHolder(holder)}, which is no longer running.`
+        : `"${filename}" is already open in ${describeHolder(holder)}. Editing it in two places can lose changes.`;
+
+    const readOnly = await ask(`${reason}\n\nOpen it read-only?`, {
+        title: 'Vault In Use',
+        kind: 'warning',
+        okLabel: 'Open Read-Only',
+        cancelLabel: 'Other Options…'
+    });
+    if (readOnly) return 'readOnly';
+
+    const takeOver = await ask(
+        holder.stale
+            ? `Take over the lock on "${filename}" and open it for editing?`
+            : `Take over the lock on "${filename}" and open it for editing? Changes saved by ${describeHolder(holder)} are detected before saving here.`,
+        { title: 'Take Over Lock', kind: 'warning', okLabel: 'Take Over', cancelLabel: 'Cancel' }
+    );
+    return takeOver ? 'takeOver' : 'cancel';
+}
@@ -0,0 +1,7 @@
+// Gzip-compressed KDBX 4 payload, including decompression bombs
+#![no_main]
+
+use keedavault_lib::kdbx::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| fuzz::compressed(data));
@@ -0,0 +1,7 @@
+// Outer header and KDF parameter dictionary from raw bytes
+#![no_main]
+
+use keedavault_lib::kdbx::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| fuzz::header(data));
@@ -0,0 +1,210 @@
+// Structured header fuzzer: builds headers field by field so inputs get past the
+// signature and version checks and reach the TLV and variant dictionary parsers with
+// plausible values, occasionally with a wrong length
+#![no_main]
+
+use arbitrary::Arbitrary;
+use keedavault_lib::kdbx::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+const SIGNATURE: [u8; 8] = [0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5];
+
+const CIPHER_AES256: [u8; 16] = [
+    0x31, 0xc1, 0xf2, 0xe6, 0xbf, 0x71, 0x43, 0x50, 0xbe, 0x58, 0x05, 0x21, 0x6a, 0xfc, 0x5a, 0xff,
+];
+const CIPHER_CHACHA20: [u8; 16] = [
+    0xd6, 0x03, 0x8a, 0x2b, 0x8b, 0x6f, 0x4c, 0xb5, 0xa5, 0x24, 0x33, 0x9a, 0x31, 0xdb, 0xb5, 0x9a,
+];
+const CIPHER_TWOFISH: [u8; 16] = [
+    0xad, 0x68, 0xf2, 0x9f, 0x57, 0x6f, 0x4b, 0xb9, 0xa3, 0x6a, 0xd4, 0x7a, 0xf9, 0x65, 0x34, 0x6c,
+];
+const KDF_AES: [u8; 16] = [
+    0xc9, 0xd9, 0xf3, 0x9a, 0x62, 0x8a, 0x44, 0x60, 0xbf, 0x74, 0x0d, 0x08, 0xc1, 0x8a, 0x4f, 0xea,
+];
+const KDF_ARGON2D: [u8; 16] = [
+    0xef, 0x63, 0x6d, 0xdf, 0x8c, 0x29, 0x44, 0x4b, 0x91, 0xf7, 0xa9, 0xa4, 0x03, 0xe3, 0x0a, 0x0c,
+];
+const KDF_ARGON2ID: [u8; 16] = [
+    0x9e, 0x29, 0x8b, 0x19, 0x56, 0xdb, 0x47, 0x73, 0xb2, 0x3d, 0xfc, 0x3e, 0xc6, 0xf0, 0xa1, 0xe6,
+];
+
+#[derive(Arbitrary, Debug)]
+enum Version {
+    V3_1,
+    V4_0,
+    V4_1,
+    Other(u32),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Uuid {
+    Known(u8),
+    Other([u8; 16]),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Name {
+    Uuid,
+    Salt,
+    Rounds,
+    Memory,
+    Iterations,
+    Parallelism,
+    Version,
+    Secret,
+    AssociatedData,
+    Other(String),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Value {
+    U32(u32),
+    U64(u64),
+    Bool(bool),
+    I32(i32),
+    I64(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+    Raw { type_id: u8, data: Vec<u8> },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Item {
+    name: Name,
+    value: Value,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Dictionary {
+    version: Option<u16>,
+    kdf: Option<Uuid>,
+    items: Vec<Item>,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Field {
+    Cipher(Uuid),
+    Compression(u32),
+    MasterSeed(Vec<u8>),
+    TransformSeed(Vec<u8>),
+    TransformRounds(u64),
+    EncryptionIv(Vec<u8>),
+    ProtectedStreamKey(Vec<u8>),
+    StreamStartBytes(Vec<u8>),
+    InnerStreamId(u32),
+    KdfParameters(Dictionary),
+    PublicCustomData(Dictionary),
+    Unknown { id: u8, data: Vec<u8> },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    version: Version,
+    fields: Vec<Field>,
+    /// Field whose length prefix is off by the given amount
+    bad_length: Option<(u8, i8)>,
+    /// Omit the end-of-header field
+    truncated: bool,
+    trailing: Vec<u8>,
+}
+
+fn uuid(uuid: &Uuid, known: &[[u8; 16]]) -> [u8; 16] {
+    match uuid {
+        Uuid::Known(index) => known[*index as usize % known.len()],
+        Uuid::Other(bytes) => *bytes,
+    }
+}
+
+fn dictionary(dictionary: &Dictionary) -> Vec<u8> {
+    let mut out = dictionary.version.unwrap_or(0x0100).to_le_bytes().to_vec();
+    let mut item = |type_id: u8, name: &str, data: &[u8]| {
+        out.push(type_id);
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    };
+    if let Some(kdf) = &dictionary.kdf {
+        item(0x42, "$UUID", &uuid(kdf, &[KDF_AES, KDF_ARGON2D, KDF_ARGON2ID]));
+    }
+    for Item { name, value } in &dictionary.items {
+        let name = match name {
+            Name::Uuid => "$UUID",
+            Name::Salt => "S",
+            Name::Rounds => "R",
+            Name::Memory => "M",
+            Name::Iterations => "I",
+            Name::Parallelism => "P",
+            Name::Version => "V",
+            Name::Secret => "K",
+            Name::AssociatedData => "A",
+            Name::Other(name) => name.as_str(),
+        };
+        match value {
+            Value::U32(v) => item(0x04, name, &v.to_le_bytes()),
+            Value::U64(v) => item(0x05, name, &v.to_le_bytes()),
+            Value::Bool(v) => item(0x08, name, &[*v as u8]),
+            Value::I32(v) => item(0x0c, name, &v.to_le_bytes()),
+            Value::I64(v) => item(0x0d, name, &v.to_le_bytes()),
+            Value::Str(v) => item(0x18, name, v.as_bytes()),
+            Value::Bytes(v) => item(0x42, name, v),
+            Value::Raw { type_id, data } => item(*type_id, name, data),
+        }
+    }
+    out.push(0);
+    out
+}
+
+fn encode(input: &Input) -> Vec<u8> {
+    let version: u32 = match input.version {
+        Version::V3_1 => 0x0003_0001,
+        Version::V4_0 => 0x0004_0000,
+        Version::V4_1 => 0x0004_0001,
+        Version::Other(v) => v,
+    };
+    let v4 = version >> 16 >= 4;
+    let mut out = SIGNATURE.to_vec();
+    out.extend_from_slice(&version.to_le_bytes());
+
+    let fields = input.fields.iter().map(|field| match field {
+        Field::Cipher(cipher) => (
+            2,
+            uuid(cipher, &[CIPHER_AES256, CIPHER_CHACHA20, CIPHER_TWOFISH]).to_vec(),
+        ),
+        Field::Compression(flags) => (3, flags.to_le_bytes().to_vec()),
+        Field::MasterSeed(seed) => (4, seed.clone()),
+        Field::TransformSeed(seed) => (5, seed.clone()),
+        Field::TransformRounds(rounds) => (6, rounds.to_le_bytes().to_vec()),
+        Field::EncryptionIv(iv) => (7, iv.clone()),
+        Field::ProtectedStreamKey(key) => (8, key.clone()),
+        Field::StreamStartBytes(bytes) => (9, bytes.clone()),
+        Field::InnerStreamId(id) => (10, id.to_le_bytes().to_vec()),
+        Field::KdfParameters(params) => (11, dictionary(params)),
+        Field::PublicCustomData(data) => (12, dictionary(data)),
+        Field::Unknown { id, data } => (*id, data.clone()),
+    });
+    let end = (!input.truncated).then(|| (0, b"\r\n\r\n".to_vec()));
+    for (index, (id, data)) in fields.chain(end).enumerate() {
+        let mut len = data.len() as i64;
+        if let Some((field, delta)) = input.bad_length {
+            if field as usize == index {
+                len += delta as i64;
+            }
+        }
+        out.push(id);
+        if v4 {
+            out.extend_from_slice(&(len as u32).to_le_bytes());
+        } else {
+            out.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+        out.extend_from_slice(&data);
+    }
+    out.extend_from_slice(&input.trailing);
+    out
+}
+
+fuzz_target!(|input: Input| {
+    let data = encode(&input);
+    fuzz::header(&data);
+    fuzz::open(&data);
+});
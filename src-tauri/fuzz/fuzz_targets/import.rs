@@ -0,0 +1,11 @@
+// CSV and JSON exports, read by every importer
+#![no_main]
+
+use keedavault_lib::import::{self, ImportFormat};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|content: &str| {
+    for format in ImportFormat::ALL {
+        let _ = import::parse(format, content);
+    }
+});
@@ -0,0 +1,7 @@
+// Key files: XML v1/v2, raw 32 bytes, hex and arbitrary files
+#![no_main]
+
+use keedavault_lib::kdbx::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| fuzz::key_file(data));
@@ -0,0 +1,7 @@
+// Whole .kdbx files, up to the payload integrity check unless the input is a real vault
+#![no_main]
+
+use keedavault_lib::kdbx::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| fuzz::open(data));
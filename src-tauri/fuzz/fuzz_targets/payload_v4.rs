@@ -0,0 +1,7 @@
+// Decrypted KDBX 4 payload: inner header, attachments and XML
+#![no_main]
+
+use keedavault_lib::kdbx::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| fuzz::payload_v4(data));
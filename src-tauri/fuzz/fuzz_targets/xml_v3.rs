@@ -0,0 +1,7 @@
+// Decrypted KDBX 3.1 XML, including the attachment pool in Meta
+#![no_main]
+
+use keedavault_lib::kdbx::fuzz;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| fuzz::xml_v3(data));
@@ -0,0 +1,974 @@
+// Versioned vault backups with retention policies
+//
+// Backups live in `<app data>/backups/<key>/`, where `key` is the SHA-256 of the vault's
+// canonical path and its identity (root group UUID), so two vaults that were moved or
+// renamed onto the same path never share history. Each directory holds the backup files
+// plus an `index.json` with their metadata. Saving, backing up and restoring all run as
+// one transaction under a process-wide lock, and every write to the vault file goes
+// through a temp file + rename so a crash never leaves a half-written database.
+// Backups written by the earlier frontend implementation (`<name>.backup.<time>.kdbx`)
+// are neither migrated nor listed; see docs/BACKUP_MANAGEMENT.md.
+//
+// A vault can also have mirror directories (USB drive, NAS mount) that receive a copy of
+// every verified save. Mirrors are written in the background after the save completes;
+// an unmounted or failing mirror only updates its status and never fails the save.
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::history;
+use crate::logging::redact_path;
+use crate::read_only;
+use crate::settings;
+use crate::watcher::{self, WriteGuard};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{command, Emitter, Manager};
+
+const BACKUP_DIR_NAME: &str = "backups";
+const INDEX_FILE_NAME: &str = "index.json";
+const BACKUP_EXTENSION: &str = "kdbx";
+
+// KDBX file signature (little-endian 0x9AA2D903, 0xB54BFB67)
+const KDBX_SIGNATURE: [u8; 8] = [0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5];
+
+const DEFAULT_MAX_COUNT: usize = 10;
+const DEFAULT_MIRROR_MAX_COUNT: usize = 5;
+const BACKUP_STAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+
+// Serializes save/backup/restore transactions across all windows
+static TRANSACTION: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_count: Option<usize>,
+    pub max_age: Option<Duration>,
+    pub max_total_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Read the policy from the `backup` UI settings; a value of 0 disables that limit
+    pub fn from_settings(app: &tauri::AppHandle) -> Self {
+        let limit = |pointer: &str| {
+            settings::ui_setting(app, pointer)
+                .and_then(|v| v.as_u64())
+                .filter(|&v| v > 0)
+        };
+        let max_count = match settings::ui_setting(app, "/backup/maxCount").and_then(|v| v.as_u64())
+        {
+            Some(0) => None,
+            Some(count) => Some(count as usize),
+            None => Some(DEFAULT_MAX_COUNT),
+        };
+        Self {
+            max_count,
+            max_age: limit("/backup/maxAgeDays").map(|days| Duration::days(days as i64)),
+            max_total_bytes: limit("/backup/maxTotalMb").map(|mb| mb * 1024 * 1024),
+        }
+    }
+
+    /// Ids of the backups to delete, given `(id, created_at, size)` sorted newest first.
+    /// The newest backup is always kept so a policy can never leave a vault without one.
+    pub fn expired<'a>(
+        &self,
+        backups: impl IntoIterator<Item = (&'a str, DateTime<Utc>, u64)>,
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let mut total = 0u64;
+        backups
+            .into_iter()
+            .enumerate()
+            .filter(|(index, (_, created_at, size))| {
+                total += size;
+                *index > 0
+                    && (self.max_count.is_some_and(|max| *index >= max)
+                        || self.max_age.is_some_and(|age| now - *created_at > age)
+                        || self.max_total_bytes.is_some_and(|max| total > max))
+            })
+            .map(|(_, (id, _, _))| id.to_string())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub id: String,
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+    pub size: u64,
+    pub sha256: String,
+    /// Number of entries in the backed-up version, when it was saved while unlocked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_count: Option<u32>,
+}
+
+/// What we last wrote to the vault file, used to describe it once it becomes a backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedState {
+    sha256: String,
+    #[serde(default)]
+    entry_count: Option<u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupIndex {
+    original_path: String,
+    #[serde(default)]
+    vault_id: Option<String>,
+    #[serde(default)]
+    last_saved: Option<SavedState>,
+    /// Newest first
+    #[serde(default)]
+    backups: Vec<BackupInfo>,
+    #[serde(default)]
+    mirrors: Vec<Mirror>,
+}
+
+/// Mirror directory settings as edited in the database properties
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorConfig {
+    pub dir: String,
+    /// Copies to keep in this mirror; 0 = unlimited
+    #[serde(default)]
+    pub max_count: Option<usize>,
+    /// Delete copies older than this many days; 0 = keep forever
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+}
+
+impl MirrorConfig {
+    fn policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            max_count: match self.max_count {
+                Some(0) => None,
+                Some(count) => Some(count),
+                None => Some(DEFAULT_MIRROR_MAX_COUNT),
+            },
+            max_age: self
+                .max_age_days
+                .filter(|&days| days > 0)
+                .map(|days| Duration::days(days as i64)),
+            max_total_bytes: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Mirror {
+    #[serde(flatten)]
+    pub config: MirrorConfig,
+    #[serde(default)]
+    pub last_attempt: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_success: Option<DateTime<Utc>>,
+    /// Error of the last attempt; `None` when it succeeded
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveReport {
+    pub sha256: String,
+    pub size: u64,
+    pub backup: Option<BackupInfo>,
+}
+
+/// Backup store of a single vault
+pub struct VaultBackups {
+    key: String,
+    dir: PathBuf,
+    vault_path: PathBuf,
+    index: BackupIndex,
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Canonical form of a vault path; falls back to the given path if it does not exist yet
+pub fn canonical_path(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Key of a vault in the per-vault stores (backups, history)
+pub(crate) fn vault_key(canonical: &Path, vault_id: Option<&str>) -> String {
+    let mut identity = canonical.to_string_lossy().into_owned().into_bytes();
+    identity.push(0);
+    identity.extend_from_slice(vault_id.unwrap_or_default().as_bytes());
+    sha256_hex(&identity)
+}
+
+/// Write `data` to `path` via a synced temp file and an atomic rename
+pub fn write_atomic(path: &Path, data: &[u8]) -> AppResult<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| AppError::invalid_input("Path has no file name"))?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+
+    // Persist the rename itself; not supported for directories on Windows
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn check_kdbx(data: &[u8]) -> AppResult<()> {
+    if data.len() < 12 || data[..8] != KDBX_SIGNATURE {
+        return Err(AppError::invalid_input("Data is not a KDBX database"));
+    }
+    Ok(())
+}
+
+impl VaultBackups {
+    pub fn open(app: &tauri::AppHandle, path: &str, vault_id: Option<&str>) -> AppResult<Self> {
+        let key = vault_key(&canonical_path(path), vault_id);
+        let dir = app.path().app_data_dir()?.join(BACKUP_DIR_NAME).join(key);
+        Self::open_in(&dir, path, vault_id)
+    }
+
+    /// The store of the vault at `path` kept in `dir`
+    fn open_in(dir: &Path, path: &str, vault_id: Option<&str>) -> AppResult<Self> {
+        let vault_path = canonical_path(path);
+        let key = vault_key(&vault_path, vault_id);
+        let dir = dir.to_path_buf();
+
+        let index = match fs::read(dir.join(INDEX_FILE_NAME)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log::warn!("Backup index is unreadable, starting a new one: {}", e);
+                BackupIndex::default()
+            }),
+            Err(_) => BackupIndex::default(),
+        };
+
+        let mut backups = Self {
+            key,
+            dir,
+            vault_path,
+            index,
+        };
+        backups.index.original_path = backups.vault_path.to_string_lossy().into_owned();
+        backups.index.vault_id = vault_id.map(str::to_string);
+        // Files may have been removed by hand; forget them
+        backups
+            .index
+            .backups
+            .retain(|backup| Path::new(&backup.path).exists());
+        Ok(backups)
+    }
+
+    /// Directory holding this vault's backups
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn list(&self) -> &[BackupInfo] {
+        &self.index.backups
+    }
+
+    pub fn mirrors(&self) -> &[Mirror] {
+        &self.index.mirrors
+    }
+
+    /// Replace the mirror list, keeping the status of directories that stay configured
+    pub fn set_mirrors(&mut self, configs: Vec<MirrorConfig>) -> AppResult<()> {
+        let mut mirrors: Vec<Mirror> = Vec::with_capacity(configs.len());
+        for config in configs {
+            let dir = canonical_path(config.dir.trim());
+            if !dir.is_dir() {
+                return Err(AppError::invalid_input(format!(
+                    "Mirror directory does not exist: {}",
+                    config.dir
+                )));
+            }
+            if dir.starts_with(&self.dir) {
+                return Err(AppError::invalid_input(
+                    "Mirror directory cannot be inside the backup store",
+                ));
+            }
+            let dir = dir.to_string_lossy().into_owned();
+            if mirrors.iter().any(|m| m.config.dir == dir) {
+                continue;
+            }
+            let previous = self.index.mirrors.iter().find(|m| m.config.dir == dir);
+            mirrors.push(Mirror {
+                config: MirrorConfig { dir, ..config },
+                last_attempt: previous.and_then(|m| m.last_attempt),
+                last_success: previous.and_then(|m| m.last_success),
+                last_error: previous.and_then(|m| m.last_error.clone()),
+            });
+        }
+        self.index.mirrors = mirrors;
+        self.save_index()
+    }
+
+    /// Folder inside a mirror directory that holds this vault's copies
+    fn mirror_folder(&self, mirror_dir: &Path) -> PathBuf {
+        let stem = self
+            .vault_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "vault".to_string());
+        mirror_dir.join(format!("{}-{}", stem, &self.key[..8]))
+    }
+
+    fn save_index(&self) -> AppResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec_pretty(&self.index)
+            .map_err(|e| AppError::internal(format!("Failed to encode backup index: {}", e)))?;
+        write_atomic(&self.dir.join(INDEX_FILE_NAME), &json)
+    }
+
+    fn new_backup_path(&self, now: DateTime<Utc>) -> (String, PathBuf) {
+        let stamp = now.format(BACKUP_STAMP_FORMAT).to_string();
+        let mut id = stamp.clone();
+        let mut suffix = 1;
+        while self
+            .dir
+            .join(format!("{}.{}", id, BACKUP_EXTENSION))
+            .exists()
+        {
+            id = format!("{}-{}", stamp, suffix);
+            suffix += 1;
+        }
+        let path = self.dir.join(format!("{}.{}", id, BACKUP_EXTENSION));
+        (id, path)
+    }
+
+    /// Copy the current vault file into the backup store. Returns `None` when the vault
+    /// file does not exist or is identical to the newest backup.
+    fn snapshot_current(&mut self) -> AppResult<Option<BackupInfo>> {
+        let data = match fs::read(&self.vault_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let sha256 = sha256_hex(&data);
+        if self
+            .index
+            .backups
+            .first()
+            .is_some_and(|b| b.sha256 == sha256)
+        {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let now = Utc::now();
+        let (id, path) = self.new_backup_path(now);
+        write_atomic(&path, &data)?;
+
+        let entry_count = self
+            .index
+            .last_saved
+            .as_ref()
+            .filter(|saved| saved.sha256 == sha256)
+            .and_then(|saved| saved.entry_count);
+        let info = BackupInfo {
+            id,
+            path: path.to_string_lossy().into_owned(),
+            created_at: now,
+            size: data.len() as u64,
+            sha256,
+            entry_count,
+        };
+        self.index.backups.insert(0, info.clone());
+        log::info!(
+            "Backed up {} ({} bytes)",
+            redact_path(&self.vault_path.to_string_lossy()),
+            info.size
+        );
+        Ok(Some(info))
+    }
+
+    /// Drop a snapshot taken for a write that then failed, so it does not linger as a
+    /// duplicate of the unchanged vault file
+    fn discard_snapshot(&mut self, snapshot: Option<BackupInfo>) {
+        let Some(snapshot) = snapshot else {
+            return;
+        };
+        self.index.backups.retain(|backup| backup.id != snapshot.id);
+        if let Err(e) = fs::remove_file(&snapshot.path) {
+            log::warn!("Failed to remove backup {}: {}", snapshot.id, e);
+        }
+    }
+
+    /// Delete backups that fall outside `policy`; failures are logged, not returned
+    pub fn apply_retention(&mut self, policy: &RetentionPolicy) {
+        let expired = policy.expired(
+            self.index
+                .backups
+                .iter()
+                .map(|backup| (backup.id.as_str(), backup.created_at, backup.size)),
+            Utc::now(),
+        );
+        if expired.is_empty() {
+            return;
+        }
+        self.index.backups.retain(|backup| {
+            if !expired.contains(&backup.id) {
+                return true;
+            }
+            match fs::remove_file(&backup.path) {
+                Ok(()) => false,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+                Err(e) => {
+                    log::warn!("Failed to delete expired backup {}: {}", backup.id, e);
+                    true
+                }
+            }
+        });
+        log::info!("Removed {} expired backup(s)", expired.len());
+    }
+
+    /// Write `data` to the vault file and read it back. On a mismatch the previous
+    /// contents are put back and the save fails.
+    fn replace_vault_file(&self, data: &[u8]) -> AppResult<String> {
+        let previous = fs::read(&self.vault_path).ok();
+        write_atomic(&self.vault_path, data)?;
+
+        let sha256 = sha256_hex(data);
+        let written = fs::read(&self.vault_path)?;
+        if sha256_hex(&written) != sha256 {
+            if let Some(previous) = previous {
+                write_atomic(&self.vault_path, &previous)?;
+            }
+            return Err(AppError::new(
+                ErrorCode::Corrupt,
+                "Read-after-write verification failed; previous version restored",
+            ));
+        }
+        Ok(sha256)
+    }
+
+    /// Back up the current file (if requested) and replace it with `data`
+    pub fn save(
+        &mut self,
+        data: &[u8],
+        entry_count: Option<u32>,
+        create_backup: bool,
+        policy: &RetentionPolicy,
+    ) -> AppResult<SaveReport> {
+        check_kdbx(data)?;
+
+        let backup = if create_backup {
+            self.snapshot_current()?
+        } else {
+            None
+        };
+        let sha256 = match self.replace_vault_file(data) {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                self.discard_snapshot(backup);
+                return Err(e);
+            }
+        };
+
+        self.index.last_saved = Some(SavedState {
+            sha256: sha256.clone(),
+            entry_count,
+        });
+        self.apply_retention(policy);
+        if let Err(e) = self.save_index() {
+            log::warn!("Failed to write backup index: {}", e);
+        }
+
+        Ok(SaveReport {
+            sha256,
+            size: data.len() as u64,
+            backup,
+        })
+    }
+
+    /// Replace the vault file with backup `id`, backing up the current file first
+    pub fn restore(&mut self, id: &str, policy: &RetentionPolicy) -> AppResult<BackupInfo> {
+        let backup = self
+            .index
+            .backups
+            .iter()
+            .find(|backup| backup.id == id)
+            .cloned()
+            .ok_or_else(|| AppError::not_found(format!("Backup {} not found", id)))?;
+
+        let data = fs::read(&backup.path)?;
+        if sha256_hex(&data) != backup.sha256 {
+            return Err(AppError::new(
+                ErrorCode::Corrupt,
+                format!("Backup {} is damaged (checksum mismatch)", id),
+            )
+            .retryable(false));
+        }
+        check_kdbx(&data)?;
+
+        let snapshot = self.snapshot_current()?;
+        let sha256 = match self.replace_vault_file(&data) {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                self.discard_snapshot(snapshot);
+                return Err(e);
+            }
+        };
+        self.index.last_saved = Some(SavedState {
+            sha256,
+            entry_count: backup.entry_count,
+        });
+        self.apply_retention(policy);
+        self.save_index()?;
+
+        log::info!(
+            "Restored {} from backup {}",
+            redact_path(&self.vault_path.to_string_lossy()),
+            id
+        );
+        Ok(backup)
+    }
+}
+
+/// Copy `data` into one mirror and prune old copies there
+fn write_mirror(folder: &Path, mirror: &MirrorConfig, data: &[u8]) -> AppResult<()> {
+    // Never create the mirror root itself: if a drive is not mounted its mount point
+    // may still exist as an empty directory on the system disk, or not at all
+    if !Path::new(&mirror.dir).is_dir() {
+        return Err(AppError::new(
+            ErrorCode::BackendUnavailable,
+            "Mirror directory is not available (drive not mounted?)",
+        ));
+    }
+    fs::create_dir_all(folder)?;
+
+    let now = Utc::now();
+    let stamp = now.format(BACKUP_STAMP_FORMAT).to_string();
+    let path = folder.join(format!("{}.{}", stamp, BACKUP_EXTENSION));
+    write_atomic(&path, data)?;
+    if sha256_hex(&fs::read(&path)?) != sha256_hex(data) {
+        let _ = fs::remove_file(&path);
+        return Err(AppError::new(
+            ErrorCode::Corrupt,
+            "Mirror copy failed read-after-write verification",
+        ));
+    }
+
+    let mut copies: Vec<(String, DateTime<Utc>, u64)> = fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stamp = name.strip_suffix(&format!(".{}", BACKUP_EXTENSION))?;
+            let created_at =
+                chrono::NaiveDateTime::parse_from_str(stamp, BACKUP_STAMP_FORMAT).ok()?;
+            let size = entry.metadata().ok()?.len();
+            Some((name, created_at.and_utc(), size))
+        })
+        .collect();
+    // Newest first, as retention expects
+    copies.sort_by_key(|copy| std::cmp::Reverse(copy.1));
+
+    let expired = mirror.policy().expired(
+        copies
+            .iter()
+            .map(|(name, created_at, size)| (name.as_str(), *created_at, *size)),
+        now,
+    );
+    for name in expired {
+        if let Err(e) = fs::remove_file(folder.join(&name)) {
+            log::warn!("Failed to delete expired mirror copy {}: {}", name, e);
+        }
+    }
+    Ok(())
+}
+
+/// Copy a freshly saved vault to all of its mirrors on a background thread, then record
+/// each mirror's status and notify the frontend with `backup-mirrors-updated`
+fn spawn_mirror_job(
+    app: tauri::AppHandle,
+    backups: &VaultBackups,
+    vault_id: Option<String>,
+    data: Vec<u8>,
+) {
+    let jobs: Vec<(PathBuf, MirrorConfig)> = backups
+        .mirrors()
+        .iter()
+        .map(|m| {
+            (
+                backups.mirror_folder(Path::new(&m.config.dir)),
+                m.config.clone(),
+            )
+        })
+        .collect();
+    if jobs.is_empty() {
+        return;
+    }
+    let path = backups.vault_path.to_string_lossy().into_owned();
+
+    std::thread::spawn(move || {
+        let results: Vec<(String, DateTime<Utc>, AppResult<()>)> = jobs
+            .iter()
+            .map(|(folder, mirror)| {
+                let result = write_mirror(folder, mirror, &data);
+                if let Err(e) = &result {
+                    log::warn!("Mirror {} failed: {}", redact_path(&mirror.dir), e.message);
+                }
+                (mirror.dir.clone(), Utc::now(), result)
+            })
+            .collect();
+
+        let _guard = lock_transaction();
+        let mut backups = match VaultBackups::open(&app, &path, vault_id.as_deref()) {
+            Ok(backups) => backups,
+            Err(e) => {
+                log::warn!("Failed to record mirror status: {}", e);
+                return;
+            }
+        };
+        for (dir, attempted_at, result) in results {
+            if let Some(mirror) = backups
+                .index
+                .mirrors
+                .iter_mut()
+                .find(|m| m.config.dir == dir)
+            {
+                mirror.last_attempt = Some(attempted_at);
+                match result {
+                    Ok(()) => {
+                        mirror.last_success = Some(attempted_at);
+                        mirror.last_error = None;
+                    }
+                    Err(e) => mirror.last_error = Some(e.message),
+                }
+            }
+        }
+        if let Err(e) = backups.save_index() {
+            log::warn!("Failed to record mirror status: {}", e);
+        }
+        let _ = app.emit("backup-mirrors-updated", &path);
+    });
+}
+
+pub(crate) fn lock_transaction() -> std::sync::MutexGuard<'static, ()> {
+    // A panic in another transaction cannot leave the files half-written (all writes
+    // are atomic), so a poisoned lock is safe to reuse
+    TRANSACTION.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Save a serialized vault, backing up the previous version in the same transaction.
+/// Fails with `Conflict` if the file changed on disk since it was loaded, unless
+/// `overwrite` is set.
+#[command]
+#[allow(clippy::too_many_arguments)] // command arguments arrive as one IPC payload
+pub async fn save_vault(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    data: Vec<u8>,
+    vault_id: Option<String>,
+    entry_count: Option<u32>,
+    create_backup: bool,
+    overwrite: Option<bool>,
+) -> AppResult<SaveReport> {
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    let guard = if overwrite.unwrap_or(false) {
+        WriteGuard::Overwrite
+    } else {
+        WriteGuard::Loaded
+    };
+    save_transaction(
+        &app_handle,
+        window.label(),
+        &path,
+        data,
+        vault_id,
+        entry_count,
+        create_backup,
+        guard,
+    )
+}
+
+/// Body of `save_vault`, shared with backend operations that rewrite a vault file on
+/// behalf of window `label`, which then holds the written version
+#[allow(clippy::too_many_arguments)] // `save_vault`'s arguments plus the window
+pub(crate) fn save_transaction(
+    app: &tauri::AppHandle,
+    label: &str,
+    path: &str,
+    data: Vec<u8>,
+    vault_id: Option<String>,
+    entry_count: Option<u32>,
+    create_backup: bool,
+    guard: WriteGuard,
+) -> AppResult<SaveReport> {
+    let policy = RetentionPolicy::from_settings(app);
+    let _guard = lock_transaction();
+    watcher::check_write(app, path, label, &guard)?;
+    let mut backups = VaultBackups::open(app, path, vault_id.as_deref())?;
+    let report = backups.save(&data, entry_count, create_backup, &policy)?;
+    watcher::record_written(app, path, label);
+    history::record(app, path, vault_id.as_deref(), &data, entry_count);
+    spawn_mirror_job(app.clone(), &backups, vault_id, data);
+    Ok(report)
+}
+
+/// Backups of a vault, newest first
+#[command]
+pub async fn list_backups(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+) -> AppResult<Vec<BackupInfo>> {
+    let _guard = lock_transaction();
+    let backups = VaultBackups::open(&app_handle, &path, vault_id.as_deref())?;
+    Ok(backups.list().to_vec())
+}
+
+/// Replace the vault file with backup `backup_id`, unless the file changed since the
+/// window loaded it. Like after a merge, the window reloads the result.
+#[command]
+pub async fn restore_backup(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    vault_id: Option<String>,
+    backup_id: String,
+) -> AppResult<BackupInfo> {
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    let policy = RetentionPolicy::from_settings(&app_handle);
+    let _guard = lock_transaction();
+    watcher::check_write(&app_handle, &path, window.label(), &WriteGuard::Loaded)?;
+    let mut backups = VaultBackups::open(&app_handle, &path, vault_id.as_deref())?;
+    let restored = backups.restore(&backup_id, &policy)?;
+    watcher::record_written(&app_handle, &path, window.label());
+    if let Ok(data) = std::fs::read(&path) {
+        history::record(
+            &app_handle,
+            &path,
+            vault_id.as_deref(),
+            &data,
+            restored.entry_count,
+        );
+    }
+    Ok(restored)
+}
+
+#[command]
+pub async fn get_backup_mirrors(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+) -> AppResult<Vec<Mirror>> {
+    let _guard = lock_transaction();
+    let backups = VaultBackups::open(&app_handle, &path, vault_id.as_deref())?;
+    Ok(backups.mirrors().to_vec())
+}
+
+#[command]
+pub async fn set_backup_mirrors(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+    mirrors: Vec<MirrorConfig>,
+) -> AppResult<Vec<Mirror>> {
+    let _guard = lock_transaction();
+    let mut backups = VaultBackups::open(&app_handle, &path, vault_id.as_deref())?;
+    backups.set_mirrors(mirrors)?;
+    log::info!(
+        "Configured {} backup mirror(s) for {}",
+        backups.mirrors().len(),
+        redact_path(&path)
+    );
+    Ok(backups.mirrors().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy {
+            max_count: None,
+            max_age: None,
+            max_total_bytes: None,
+        }
+    }
+
+    /// Backups "0" (newest) to "5", one day apart and 1 MB each
+    fn expired(policy: RetentionPolicy) -> Vec<String> {
+        let now = Utc::now();
+        let ids = ["0", "1", "2", "3", "4", "5"];
+        let backups = ids
+            .iter()
+            .zip(0..)
+            .map(|(id, days)| (*id, now - Duration::days(days), MB));
+        policy.expired(backups, now)
+    }
+
+    #[test]
+    fn retention_without_limits_keeps_everything() {
+        assert!(expired(policy()).is_empty());
+    }
+
+    #[test]
+    fn retention_limits() {
+        let count = RetentionPolicy {
+            max_count: Some(4),
+            ..policy()
+        };
+        assert_eq!(expired(count), ["4", "5"]);
+        let age = RetentionPolicy {
+            max_age: Some(Duration::hours(60)),
+            ..policy()
+        };
+        assert_eq!(expired(age), ["3", "4", "5"]);
+        // Counted from the newest: the fourth backup brings the total past 3.5 MB
+        let size = RetentionPolicy {
+            max_total_bytes: Some(3 * MB + MB / 2),
+            ..policy()
+        };
+        assert_eq!(expired(size), ["3", "4", "5"]);
+    }
+
+    #[test]
+    fn retention_limits_combine() {
+        let combined = RetentionPolicy {
+            max_count: Some(5),
+            max_age: Some(Duration::hours(84)),
+            max_total_bytes: Some(100 * MB),
+        };
+        assert_eq!(expired(combined), ["4", "5"]);
+    }
+
+    #[test]
+    fn retention_keeps_the_newest_backup() {
+        let strict = RetentionPolicy {
+            max_count: Some(1),
+            max_age: Some(Duration::zero()),
+            max_total_bytes: Some(1),
+        };
+        assert_eq!(expired(strict), ["1", "2", "3", "4", "5"]);
+        let now = Utc::now();
+        let old = [("only", now - Duration::days(400), 50 * MB)];
+        assert!(strict.expired(old, now).is_empty());
+    }
+
+    /// A vault file and a backup store in a fresh temporary directory
+    fn temp_store() -> (PathBuf, String, VaultBackups) {
+        let dir = std::env::temp_dir().join(format!(
+            "keedavault-backup-{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        fs::create_dir_all(&dir).expect("temp dir");
+        let vault = dir.join("Vault.kdbx").to_string_lossy().into_owned();
+        let store = VaultBackups::open_in(&dir.join("backups"), &vault, None).expect("open");
+        (dir, vault, store)
+    }
+
+    /// Enough of a KDBX file to pass `check_kdbx`
+    fn kdbx(contents: &str) -> Vec<u8> {
+        [&KDBX_SIGNATURE[..], contents.as_bytes()].concat()
+    }
+
+    #[test]
+    fn save_snapshots_and_restores() {
+        let (dir, vault, mut store) = temp_store();
+        let first = store
+            .save(&kdbx("first version"), Some(1), true, &policy())
+            .expect("save");
+        assert!(first.backup.is_none(), "nothing to back up yet");
+        let second = store
+            .save(&kdbx("second version"), Some(2), true, &policy())
+            .expect("save");
+        let backup = second.backup.expect("first version backed up");
+        assert_eq!(backup.sha256, first.sha256);
+        assert_eq!(backup.entry_count, Some(1));
+        assert_eq!(fs::read(&vault).expect("vault"), kdbx("second version"));
+
+        // Reopened as the commands do
+        let mut store = VaultBackups::open_in(&dir.join("backups"), &vault, None).expect("open");
+        let restored = store.restore(&backup.id, &policy()).expect("restore");
+        assert_eq!(restored.id, backup.id);
+        assert_eq!(fs::read(&vault).expect("vault"), kdbx("first version"));
+        // The replaced version became the newest backup, so the restore can be undone
+        let shas: Vec<&str> = store.list().iter().map(|b| b.sha256.as_str()).collect();
+        assert_eq!(shas, [second.sha256.as_str(), first.sha256.as_str()]);
+        assert_eq!(store.list()[0].entry_count, Some(2));
+
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+
+    #[test]
+    fn restore_refuses_a_tampered_backup() {
+        let (dir, vault, mut store) = temp_store();
+        store
+            .save(&kdbx("first version"), None, true, &policy())
+            .expect("save");
+        let backup = store
+            .save(&kdbx("second version"), None, true, &policy())
+            .expect("save")
+            .backup
+            .expect("backup");
+        fs::write(&backup.path, kdbx("tampered version")).expect("tamper");
+
+        let error = store.restore(&backup.id, &policy()).err();
+        assert_eq!(
+            error.map(|e| (e.code, e.retryable)),
+            Some((ErrorCode::Corrupt, false))
+        );
+        assert_eq!(fs::read(&vault).expect("vault"), kdbx("second version"));
+        assert_eq!(store.list().len(), 1, "no snapshot of the unchanged file");
+
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+
+    #[test]
+    fn failed_write_removes_its_snapshot() {
+        let (dir, vault, mut store) = temp_store();
+        store
+            .save(&kdbx("first version"), None, true, &policy())
+            .expect("save");
+        // A directory where `write_atomic` wants its temp file makes the write fail
+        let temp = format!("{}.{}.tmp", vault, std::process::id());
+        fs::create_dir(&temp).expect("blocking dir");
+
+        assert!(store
+            .save(&kdbx("second version"), None, true, &policy())
+            .is_err());
+        assert!(store.list().is_empty());
+        let snapshots = fs::read_dir(store.dir())
+            .expect("backup dir")
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .is_ok_and(|entry| entry.path().extension() == Some(BACKUP_EXTENSION.as_ref()))
+            })
+            .count();
+        assert_eq!(snapshots, 0, "snapshot file left behind");
+        assert_eq!(fs::read(&vault).expect("vault"), kdbx("first version"));
+
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+}
@@ -1,8 +1,10 @@
 // Biometric authentication utilities
+use crate::error::AppResult;
 use tauri::command;
 
 #[cfg(target_os = "macos")]
 mod macos {
+    use crate::error::{AppError, ErrorCode};
     use objc::runtime::{Class, Object};
     use objc::{msg_send, sel, sel_impl};
     use objc_foundation::{INSString, NSString};
@@ -10,6 +12,19 @@ mod macos {
     use std::sync::mpsc::channel;
     use std::time::Duration;
 
+    /// Map an LAError code from the LocalAuthentication framework
+    fn la_error(code: i64, message: String) -> AppError {
+        let (kind, retryable) = match code {
+            -1 => (ErrorCode::AuthFailed, true), // LAErrorAuthenticationFailed
+            -2 | -3 | -4 | -9 => (ErrorCode::UserCancelled, false), // user/fallback/system/app cancel
+            -8..=-5 => (ErrorCode::BackendUnavailable, false), // no passcode, biometry unavailable/not enrolled/locked out
+            _ => (ErrorCode::Internal, false),
+        };
+        AppError::new(kind, message)
+            .with_os_status(code)
+            .retryable(retryable)
+    }
+
     pub fn check_biometric_available() -> bool {
         unsafe {
             let context_class = match Class::get("LAContext") {
@@ -35,30 +50,41 @@ mod macos {
         }
     }
 
-    pub fn authenticate_biometric(reason: &str) -> Result<bool, String> {
+    pub fn authenticate_biometric(reason: &str) -> Result<bool, AppError> {
         unsafe {
             let context_class = match Class::get("LAContext") {
                 Some(cls) => cls,
-                None => return Err("LAContext class not available".to_string()),
+                None => {
+                    return Err(AppError::new(
+                        ErrorCode::BackendUnavailable,
+                        "LAContext class not available",
+                    )
+                    .retryable(false))
+                }
             };
 
             let context: *mut Object = msg_send![context_class, alloc];
             let context: *mut Object = msg_send![context, init];
 
             if context.is_null() {
-                return Err("Failed to create LAContext".to_string());
+                return Err(AppError::internal("Failed to create LAContext"));
             }
 
             let policy: i64 = 2; // LAPolicyDeviceOwnerAuthenticationWithBiometrics
             let reason_nsstring = NSString::from_str(reason);
 
-            let (tx, rx) = channel::<Result<bool, String>>();
+            let (tx, rx) = channel::<Result<bool, AppError>>();
             
             // Create a block that will be called with the authentication result
             let block = ConcreteBlock::new(move |success: bool, error: *mut Object| {
                 if success {
                     let _ = tx.send(Ok(true));
                 } else {
+                    let error_code: i64 = if !error.is_null() {
+                        msg_send![error, code]
+                    } else {
+                        0
+                    };
                     let error_msg = if !error.is_null() {
                         let desc: *mut Object = msg_send![error, localizedDescription];
                         if !desc.is_null() {
@@ -76,7 +102,7 @@ mod macos {
                     } else {
                         "Authentication failed".to_string()
                     };
-                    let _ = tx.send(Err(error_msg));
+                    let _ = tx.send(Err(la_error(error_code, error_msg)));
                 }
             });
             let block = block.copy();
@@ -90,7 +116,7 @@ mod macos {
             // Wait for the result with a timeout
             let result = match rx.recv_timeout(Duration::from_secs(60)) {
                 Ok(res) => res,
-                Err(_) => Err("Authentication timeout".to_string()),
+                Err(_) => Err(AppError::new(ErrorCode::AuthFailed, "Authentication timeout")),
             };
 
             let _: () = msg_send![context, release];
@@ -101,7 +127,7 @@ mod macos {
 }
 
 #[command]
-pub async fn check_biometric_available() -> Result<bool, String> {
+pub async fn check_biometric_available() -> AppResult<bool> {
     #[cfg(target_os = "macos")]
     {
         Ok(macos::check_biometric_available())
@@ -113,13 +139,15 @@ pub async fn check_biometric_available() -> Result<bool, String> {
 }
 
 #[command]
-pub async fn authenticate_biometric(reason: String) -> Result<bool, String> {
+pub async fn authenticate_biometric(reason: String) -> AppResult<bool> {
     #[cfg(target_os = "macos")]
     {
         macos::authenticate_biometric(&reason)
     }
     #[cfg(not(target_os = "macos"))]
     {
-        Err("Biometric authentication not supported on this platform".to_string())
+        Err(crate::error::AppError::unsupported(
+            "Biometric authentication not supported on this platform",
+        ))
     }
 }
@@ -0,0 +1,485 @@
+// Sync-conflict copies next to open vaults
+//
+// When two devices change a vault before their sync client catches up, the client keeps
+// both versions by writing the losing one next to the original under a tell-tale name.
+// This module recognizes those names, reports the copies with their timestamps, and
+// archives a copy into the vault's backup directory once it was merged (or discarded),
+// so it no longer syncs around. Merging itself goes through `sync::merge_vault_file`.
+//
+// Recognized names for `Vault.kdbx`:
+//   Dropbox    `Vault (Alice's conflicted copy 2024-05-01).kdbx`
+//   Nextcloud  `Vault (conflicted copy 2024-05-01 101500).kdbx`, `Vault_conflict-20240501-101500.kdbx`
+//   Syncthing  `Vault.sync-conflict-20240501-101500-ABCDEF1.kdbx`
+//   iCloud     `Vault 2.kdbx`, and `.<name>.icloud` placeholders of any of these that
+//              are not downloaded yet
+use crate::backup::{self, canonical_path, VaultBackups};
+use crate::error::{AppError, AppResult};
+use crate::logging::redact_path;
+use crate::read_only;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::command;
+
+const ARCHIVE_DIR_NAME: &str = "conflicts";
+const ICLOUD_PLACEHOLDER_SUFFIX: &str = ".icloud";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CloudProvider {
+    ICloud,
+    Dropbox,
+    GoogleDrive,
+    OneDrive,
+    Nextcloud,
+    Syncthing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictKind {
+    Dropbox,
+    Nextcloud,
+    Syncthing,
+    ICloud,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictCopy {
+    pub path: String,
+    pub file_name: String,
+    pub kind: ConflictKind,
+    /// Device or user that produced the copy, when the name records it
+    pub device: Option<String>,
+    /// When the conflict happened, according to the file name
+    pub conflict_at: Option<DateTime<Utc>>,
+    pub modified_at: Option<DateTime<Utc>>,
+    pub size: u64,
+    /// iCloud placeholder: the copy must be downloaded before it can be merged
+    pub placeholder: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictScan {
+    /// Sync service the vault's folder belongs to, if recognized
+    pub provider: Option<CloudProvider>,
+    /// Oldest first
+    pub conflicts: Vec<ConflictCopy>,
+}
+
+/// Name components of a conflict copy
+#[derive(Debug, PartialEq)]
+struct ParsedName {
+    kind: ConflictKind,
+    device: Option<String>,
+    conflict_at: Option<DateTime<Utc>>,
+    placeholder: bool,
+}
+
+/// Sync service a path belongs to, from well-known folder names and the marker files
+/// Syncthing and Nextcloud keep at the root of a synced folder
+pub fn detect_provider(path: &Path) -> Option<CloudProvider> {
+    let normalized = path.to_string_lossy().replace('\\', "/");
+    let patterns = [
+        ("/Library/Mobile Documents/", CloudProvider::ICloud),
+        ("/iCloudDrive/", CloudProvider::ICloud),
+        ("/Dropbox/", CloudProvider::Dropbox),
+        ("/Google Drive/", CloudProvider::GoogleDrive),
+        ("/GoogleDrive/", CloudProvider::GoogleDrive),
+        ("/gdrive/", CloudProvider::GoogleDrive),
+        ("/OneDrive", CloudProvider::OneDrive),
+        ("/Nextcloud/", CloudProvider::Nextcloud),
+    ];
+    if let Some((_, provider)) = patterns.iter().find(|(p, _)| normalized.contains(p)) {
+        return Some(*provider);
+    }
+
+    for dir in path.ancestors().skip(1) {
+        if dir.join(".stfolder").exists() {
+            return Some(CloudProvider::Syncthing);
+        }
+        let nextcloud_marker = fs::read_dir(dir).ok().is_some_and(|entries| {
+            entries.flatten().any(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name == ".owncloudsync.log"
+                    || ((name.starts_with(".sync_") || name.starts_with("._sync_"))
+                        && name.ends_with(".db"))
+            })
+        });
+        if nextcloud_marker {
+            return Some(CloudProvider::Nextcloud);
+        }
+    }
+    None
+}
+
+/// Names carry the local time of the device that created the copy
+fn local_time(time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// `2024-05-01` or `2024-05-01 101500`
+fn parse_dashed_time(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    let time = match text.split_once(' ') {
+        Some((date, time)) => {
+            NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H%M%S").ok()?
+        }
+        None => NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()?
+            .and_hms_opt(0, 0, 0)?,
+    };
+    local_time(time)
+}
+
+/// `20240501-101500`
+fn parse_compact_time(text: &str) -> Option<DateTime<Utc>> {
+    local_time(NaiveDateTime::parse_from_str(text, "%Y%m%d-%H%M%S").ok()?)
+}
+
+/// Recognize `name` as a conflict copy of the vault file `vault_name`
+fn parse_conflict_name(
+    vault_name: &str,
+    name: &str,
+    provider: Option<CloudProvider>,
+) -> Option<ParsedName> {
+    if let Some(inner) = name
+        .strip_prefix('.')
+        .and_then(|n| n.strip_suffix(ICLOUD_PLACEHOLDER_SUFFIX))
+    {
+        // The vault itself can be a placeholder on another Mac, but not while it is open here
+        return parse_conflict_name(vault_name, inner, Some(CloudProvider::ICloud)).map(|parsed| {
+            ParsedName {
+                placeholder: true,
+                ..parsed
+            }
+        });
+    }
+    if name == vault_name {
+        return None;
+    }
+
+    let (stem, extension) = match vault_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (vault_name, String::new()),
+    };
+    let rest = name.strip_prefix(stem)?.strip_suffix(extension.as_str())?;
+
+    if let Some(suffix) = rest.strip_prefix(".sync-conflict-") {
+        // 20240501-101500-DEVICE
+        let device = suffix.get(15..).and_then(|d| d.strip_prefix('-'));
+        return Some(ParsedName {
+            kind: ConflictKind::Syncthing,
+            device: device.filter(|d| !d.is_empty()).map(str::to_string),
+            conflict_at: suffix.get(..15).and_then(parse_compact_time),
+            placeholder: false,
+        });
+    }
+
+    if let Some(suffix) = rest.strip_prefix("_conflict-") {
+        return Some(ParsedName {
+            kind: ConflictKind::Nextcloud,
+            device: None,
+            conflict_at: parse_compact_time(suffix),
+            placeholder: false,
+        });
+    }
+
+    if let Some(inner) = rest.strip_prefix(" (") {
+        // Dropbox appends ` (1)` when the same conflict name is taken
+        let (inner, _) = inner.split_once(')')?;
+        let (who, when) = inner.split_once("conflicted copy")?;
+        let device = who
+            .trim()
+            .strip_suffix("'s")
+            .or_else(|| who.trim().strip_suffix("\u{2019}s"))
+            .map(str::to_string);
+        let kind = if device.is_some() || provider == Some(CloudProvider::Dropbox) {
+            ConflictKind::Dropbox
+        } else {
+            ConflictKind::Nextcloud
+        };
+        return Some(ParsedName {
+            kind,
+            device,
+            conflict_at: parse_dashed_time(when),
+            placeholder: false,
+        });
+    }
+
+    // iCloud numbers the losing copy: `Vault 2.kdbx`. Other folders use that name for
+    // ordinary files too, so only trust it inside iCloud Drive.
+    if provider == Some(CloudProvider::ICloud) {
+        let number = rest.strip_prefix(' ')?;
+        if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) {
+            return Some(ParsedName {
+                kind: ConflictKind::ICloud,
+                device: None,
+                conflict_at: None,
+                placeholder: false,
+            });
+        }
+    }
+    None
+}
+
+fn file_name(path: &Path) -> AppResult<&str> {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| AppError::invalid_input("Path has no file name"))
+}
+
+/// Whether `candidate` is a conflict copy of the vault at `vault` (both canonical).
+/// `provider` is what `detect_provider` found for the vault; the watcher looks it up
+/// once per vault, since that may list every directory above it.
+pub fn is_conflict_copy(vault: &Path, candidate: &Path, provider: Option<CloudProvider>) -> bool {
+    if vault.parent() != candidate.parent() {
+        return false;
+    }
+    let (Ok(vault_name), Ok(name)) = (file_name(vault), file_name(candidate)) else {
+        return false;
+    };
+    parse_conflict_name(vault_name, name, provider).is_some()
+}
+
+/// Conflict copies of the vault at `vault` (canonical), oldest first
+pub fn scan(vault: &Path) -> AppResult<ConflictScan> {
+    let vault_name = file_name(vault)?;
+    let dir = vault
+        .parent()
+        .ok_or_else(|| AppError::invalid_input("Vault path has no parent directory"))?;
+    let provider = detect_provider(vault);
+
+    let mut conflicts = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let Some(parsed) = parse_conflict_name(vault_name, name, provider) else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        conflicts.push(ConflictCopy {
+            path: entry.path().to_string_lossy().into_owned(),
+            file_name: name.to_string(),
+            kind: parsed.kind,
+            device: parsed.device,
+            conflict_at: parsed.conflict_at,
+            modified_at: metadata.modified().ok().map(DateTime::<Utc>::from),
+            size: metadata.len(),
+            placeholder: parsed.placeholder,
+        });
+    }
+    conflicts.sort_by_key(|c| (c.conflict_at.or(c.modified_at), c.file_name.clone()));
+    Ok(ConflictScan {
+        provider,
+        conflicts,
+    })
+}
+
+/// Move `from` to `to`, copying when they are on different volumes
+fn move_file(from: &Path, to: &Path) -> AppResult<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    if let Err(e) = fs::remove_file(from) {
+        let _ = fs::remove_file(to);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Find the sync-conflict copies next to the vault at `path`
+#[command]
+pub async fn find_sync_conflicts(path: String) -> AppResult<ConflictScan> {
+    let result = scan(&canonical_path(&path))?;
+    if !result.conflicts.is_empty() {
+        log::info!(
+            "Found {} sync conflict copy(ies) of {}",
+            result.conflicts.len(),
+            redact_path(&path)
+        );
+    }
+    Ok(result)
+}
+
+/// Move a conflict copy out of the synced folder into the vault's backup directory.
+/// Returns the archived path.
+#[command]
+pub async fn archive_sync_conflict(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    vault_id: Option<String>,
+    conflict_path: String,
+) -> AppResult<String> {
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    let vault = canonical_path(&path);
+    let conflict = canonical_path(&conflict_path);
+    let vault_name = file_name(&vault)?;
+    let name = file_name(&conflict)?;
+    let parsed = (vault.parent() == conflict.parent())
+        .then(|| parse_conflict_name(vault_name, name, detect_provider(&vault)))
+        .flatten()
+        .ok_or_else(|| AppError::invalid_input("Not a sync conflict copy of this vault"))?;
+    if parsed.placeholder {
+        return Err(AppError::invalid_input(
+            "The conflict copy is not downloaded from iCloud yet",
+        ));
+    }
+
+    let _guard = backup::lock_transaction();
+    let backups = VaultBackups::open(&app_handle, &path, vault_id.as_deref())?;
+    let archive_dir = backups.dir().join(ARCHIVE_DIR_NAME);
+    fs::create_dir_all(&archive_dir)?;
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+    let target: PathBuf = archive_dir.join(format!("{}-{}", stamp, name));
+    move_file(&conflict, &target)?;
+
+    log::info!(
+        "Archived sync conflict copy {}",
+        redact_path(&conflict.to_string_lossy())
+    );
+    Ok(target.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VAULT: &str = "Vault.kdbx";
+
+    fn parsed(kind: ConflictKind, device: Option<&str>, time: Option<&str>) -> ParsedName {
+        let time =
+            time.map(|t| NaiveDateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S").expect("time"));
+        ParsedName {
+            kind,
+            device: device.map(str::to_string),
+            conflict_at: time.and_then(local_time),
+            placeholder: false,
+        }
+    }
+
+    fn placeholder(parsed: ParsedName) -> ParsedName {
+        ParsedName {
+            placeholder: true,
+            ..parsed
+        }
+    }
+
+    #[test]
+    fn conflict_names() {
+        use CloudProvider as P;
+        use ConflictKind as K;
+        let may = Some("2024-05-01 00:00:00");
+        let may_morning = Some("2024-05-01 10:15:00");
+        let cases = [
+            // Dropbox
+            (
+                "Vault (Alice's conflicted copy 2024-05-01).kdbx",
+                None,
+                Some(parsed(K::Dropbox, Some("Alice"), may)),
+            ),
+            (
+                "Vault (Alice\u{2019}s conflicted copy 2024-05-01) (1).kdbx",
+                Some(P::Dropbox),
+                Some(parsed(K::Dropbox, Some("Alice"), may)),
+            ),
+            (
+                "Vault (conflicted copy 2024-05-01 101500).kdbx",
+                Some(P::Dropbox),
+                Some(parsed(K::Dropbox, None, may_morning)),
+            ),
+            // Nextcloud
+            (
+                "Vault (conflicted copy 2024-05-01 101500).kdbx",
+                None,
+                Some(parsed(K::Nextcloud, None, may_morning)),
+            ),
+            (
+                "Vault_conflict-20240501-101500.kdbx",
+                Some(P::Nextcloud),
+                Some(parsed(K::Nextcloud, None, may_morning)),
+            ),
+            // Syncthing
+            (
+                "Vault.sync-conflict-20240501-101500-ABCDEF1.kdbx",
+                Some(P::Syncthing),
+                Some(parsed(K::Syncthing, Some("ABCDEF1"), may_morning)),
+            ),
+            (
+                "Vault.sync-conflict-20240501-101500.kdbx",
+                None,
+                Some(parsed(K::Syncthing, None, may_morning)),
+            ),
+            (
+                "Vault.sync-conflict-garbage.kdbx",
+                None,
+                Some(parsed(K::Syncthing, None, None)),
+            ),
+            // iCloud
+            (
+                "Vault 2.kdbx",
+                Some(P::ICloud),
+                Some(parsed(K::ICloud, None, None)),
+            ),
+            ("Vault 2.kdbx", None, None),
+            ("Vault 2.kdbx", Some(P::Dropbox), None),
+            (
+                ".Vault 2.kdbx.icloud",
+                None,
+                Some(placeholder(parsed(K::ICloud, None, None))),
+            ),
+            (
+                ".Vault.sync-conflict-20240501-101500-ABCDEF1.kdbx.icloud",
+                Some(P::ICloud),
+                Some(placeholder(parsed(
+                    K::Syncthing,
+                    Some("ABCDEF1"),
+                    may_morning,
+                ))),
+            ),
+            // Not conflict copies
+            (VAULT, Some(P::ICloud), None),
+            (".Vault.kdbx.icloud", Some(P::ICloud), None),
+            ("Vault.kdbx.bak", None, None),
+            ("Vault (copy).kdbx", None, None),
+            ("Vault 2 (1).kdbx", Some(P::ICloud), None),
+            (
+                "Other (Alice's conflicted copy 2024-05-01).kdbx",
+                None,
+                None,
+            ),
+            ("Vault (Alice's conflicted copy 2024-05-01).kdb", None, None),
+        ];
+        for (name, provider, expected) in cases {
+            assert_eq!(
+                parse_conflict_name(VAULT, name, provider),
+                expected,
+                "{} in {:?}",
+                name,
+                provider
+            );
+        }
+    }
+
+    #[test]
+    fn conflict_names_without_extension() {
+        let parsed = parse_conflict_name("Vault", "Vault_conflict-20240501-101500", None);
+        assert_eq!(parsed.map(|p| p.kind), Some(ConflictKind::Nextcloud));
+    }
+}
@@ -0,0 +1,108 @@
+// Changing a vault's master password and key file
+//
+// The frontend saves its in-memory state first. The backend then opens the file with
+// the current credentials (so a typo cannot lock anyone out), encrypts it with the new
+// ones, checks that the result opens with them, and writes it through the regular save
+// transaction with a backup of the previous version. Touch ID entries holding the old
+// password are removed before the write and saved again with the new password after
+// it (see `quick_unlock`), so they never unlock to a wrong-password error; when the key
+// file changes as well they stay removed until the next unlock sets them up. The window
+// then reloads the vault with the new credentials. The master key may be a password, a
+// key file or both (see `kdbx::key`).
+use crate::backup::{self, SaveReport};
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::kdbx::{Credentials, Database};
+use crate::logging::redact_path;
+use crate::quick_unlock::{self, QuickUnlock};
+use crate::read_only;
+use crate::unlock_attempts;
+use crate::watcher::WriteGuard;
+use serde::Serialize;
+use tauri::command;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialChange {
+    pub save: SaveReport,
+    pub quick_unlock: QuickUnlock,
+}
+
+/// Check credentials in the create and unlock forms, so they can say what is wrong
+/// before a key derivation runs; `new` applies the rules for a new master key
+#[command]
+pub async fn check_credentials(credentials: Credentials, new: bool) -> AppResult<()> {
+    if new {
+        credentials.validate_new()
+    } else {
+        credentials.master_key().map(|_| ())
+    }
+}
+
+/// Re-key the vault at `path` from `current` to `new` credentials. `location` is where
+/// its Touch ID entries are kept (the server URL of a remote vault); it defaults to
+/// `path`
+#[command]
+pub async fn change_credentials(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    vault_id: Option<String>,
+    location: Option<String>,
+    current: Credentials,
+    new: Credentials,
+) -> AppResult<CredentialChange> {
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    new.validate_new()?;
+
+    let label = window.label().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let location = location.unwrap_or_else(|| path.clone());
+        let data = std::fs::read(&path)?;
+        let mut database = unlock_attempts::open(&app_handle, &location, &data, &current).map_err(
+            |e| match e.code {
+                ErrorCode::WrongCredentials => AppError::new(
+                    ErrorCode::WrongCredentials,
+                    "The current password or key file is incorrect",
+                ),
+                _ => e,
+            },
+        )?;
+        if let Some(vault_id) = &vault_id {
+            if database.identity() != vault_id {
+                return Err(AppError::invalid_input(
+                    "The vault file changed since it was opened",
+                ));
+            }
+        }
+
+        database.touch_master_key();
+        let encrypted = database.save(&new)?;
+        let reopened = Database::open(&encrypted, &new)?;
+        if reopened.entry_count() != database.entry_count() {
+            return Err(AppError::internal(
+                "The re-keyed vault did not verify; the file was not changed",
+            ));
+        }
+
+        let entry_count = u32::try_from(database.entry_count()).ok();
+        let (save, quick_unlock) = quick_unlock::rekey(&location, &current, &new, || {
+            backup::save_transaction(
+                &app_handle,
+                &label,
+                &path,
+                encrypted,
+                vault_id,
+                entry_count,
+                true,
+                WriteGuard::Sha256(backup::sha256_hex(&data)),
+            )
+        })?;
+        log::info!(
+            "Changed the master key of {} (quick unlock: {:?})",
+            redact_path(&path),
+            quick_unlock
+        );
+        Ok(CredentialChange { save, quick_unlock })
+    })
+    .await?
+}
@@ -0,0 +1,125 @@
+// Changing a vault's cipher, key derivation and compression
+//
+// Like a compatibility fix, the change is made to the file on disk: the frontend saves
+// first, the backend re-encrypts the vault with the new settings (see
+// `kdbx::settings`), checks that the result opens with them and holds the same entries,
+// and writes it through the regular save transaction, which backs up the previous
+// version. The window then reloads the vault.
+//
+// Vaults in a cipher kdbxweb lacks (Twofish) keep it: `transcode_vault` gives the
+// frontend a ChaCha20 copy in memory to load, and converts what the frontend saves back
+// before it is written, so neither the file nor the server copy changes cipher.
+//
+// Every command here runs a key derivation, which takes a second or more by design, so
+// the work runs on a blocking thread rather than on the async runtime.
+use crate::backup::{self, SaveReport};
+use crate::error::{AppError, AppResult};
+use crate::kdbx::settings::{self, Cipher, CryptoSettings, KdfBenchmark};
+use crate::kdbx::{Credentials, Database};
+use crate::logging::redact_path;
+use crate::read_only;
+use crate::unlock_attempts;
+use crate::watcher::WriteGuard;
+use tauri::command;
+
+/// Re-encrypt the vault at `path` with `settings`, unless it changed since the window
+/// loaded it
+#[command]
+pub async fn set_vault_crypto(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    vault_id: Option<String>,
+    credentials: Credentials,
+    settings: CryptoSettings,
+) -> AppResult<SaveReport> {
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    let label = window.label().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        reencrypt(&app_handle, &label, &path, vault_id, &credentials, |_| {
+            settings
+        })
+    })
+    .await?
+}
+
+/// Re-encrypt vault `data` with `cipher` in memory; everything else, including key
+/// derivation parameters the settings dialog would not accept, is kept. Nothing is
+/// written: the frontend loads and saves vaults in a cipher it cannot decrypt this way.
+/// `location` is where the vault lives, for unlock throttling.
+#[command]
+pub async fn transcode_vault(
+    app_handle: tauri::AppHandle,
+    location: String,
+    data: Vec<u8>,
+    credentials: Credentials,
+    cipher: Cipher,
+) -> AppResult<Vec<u8>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut database = unlock_attempts::open(&app_handle, &location, &data, &credentials)?;
+        database.header.cipher = cipher;
+        let encrypted = database.save(&credentials)?;
+        let reopened = Database::open(&encrypted, &credentials)?;
+        if reopened.header.cipher != cipher || reopened.entry_count() != database.entry_count() {
+            return Err(AppError::internal("The re-encrypted vault did not verify"));
+        }
+        Ok(encrypted)
+    })
+    .await?
+}
+
+fn reencrypt(
+    app: &tauri::AppHandle,
+    label: &str,
+    path: &str,
+    vault_id: Option<String>,
+    credentials: &Credentials,
+    settings: impl FnOnce(&CryptoSettings) -> CryptoSettings,
+) -> AppResult<SaveReport> {
+    let data = std::fs::read(path)?;
+    let mut database = unlock_attempts::open(app, path, &data, credentials)?;
+    if let Some(vault_id) = &vault_id {
+        if database.identity() != vault_id {
+            return Err(AppError::invalid_input(
+                "The vault file changed since it was opened",
+            ));
+        }
+    }
+    let previous = settings::current(&database)?;
+
+    settings::apply(&mut database, &settings(&previous))?;
+    let encrypted = database.save(credentials)?;
+    let expected = settings::current(&database)?;
+    let reopened = Database::open(&encrypted, credentials)?;
+    if settings::current(&reopened)? != expected || reopened.entry_count() != database.entry_count()
+    {
+        return Err(AppError::internal(
+            "The re-encrypted vault did not verify; the file was not changed",
+        ));
+    }
+
+    let entry_count = u32::try_from(database.entry_count()).ok();
+    let report = backup::save_transaction(
+        app,
+        label,
+        path,
+        encrypted,
+        vault_id,
+        entry_count,
+        true,
+        WriteGuard::Sha256(backup::sha256_hex(&data)),
+    )?;
+    log::info!(
+        "Changed encryption of {}: {:?} -> {:?}",
+        redact_path(path),
+        previous,
+        expected
+    );
+    Ok(report)
+}
+
+/// Time the key derivation `settings` describe on this device
+#[command]
+pub async fn benchmark_kdf(settings: CryptoSettings) -> AppResult<KdfBenchmark> {
+    tauri::async_runtime::spawn_blocking(move || settings::benchmark(&settings)).await?
+}
@@ -0,0 +1,206 @@
+// Error type shared by all Tauri commands
+//
+// Serialized to the frontend as `{ code, message, osStatus?, retryable }` so the UI
+// can branch on `code` instead of matching message strings (see services/appError.ts).
+// Codes are part of the frontend contract: add new ones, never rename existing ones.
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ErrorCode {
+    /// The requested item (keychain entry, file, backup...) does not exist
+    NotFound,
+    /// The user dismissed a system prompt (Touch ID, keychain dialog)
+    UserCancelled,
+    /// Biometric or keychain authentication was attempted and failed
+    AuthFailed,
+    /// The platform service is missing, disabled or temporarily unusable
+    BackendUnavailable,
+    /// The operation is not implemented on this platform
+    Unsupported,
+    /// File system or network I/O failed
+    Io,
+    /// Data was read but is malformed or fails integrity checks
+    Corrupt,
+    /// The master password or key file does not open the vault
+    WrongCredentials,
+    /// The caller passed an argument the backend cannot use
+    InvalidInput,
+    /// The vault file changed on disk since it was loaded; merge or overwrite explicitly
+    Conflict,
+    /// The vault is open read-only in the calling window
+    ReadOnly,
+    /// Unlocking is paused after repeated failed attempts; `message` says for how long
+    TooManyAttempts,
+    /// An SFTP server's host key is not known yet; `message` shows its fingerprint for
+    /// the user to confirm
+    UnknownHostKey,
+    /// Anything else; `message` and `osStatus` carry the details
+    Internal,
+}
+
+impl ErrorCode {
+    /// Whether repeating the same call unchanged has a reasonable chance to succeed
+    fn default_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorCode::AuthFailed | ErrorCode::BackendUnavailable | ErrorCode::Io
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Raw OSStatus / LAError / errno from the platform, when there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_status: Option<i64>,
+    pub retryable: bool,
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            os_status: None,
+            retryable: code.default_retryable(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unsupported, message)
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidInput, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    pub fn with_os_status(mut self, status: i64) -> Self {
+        self.os_status = Some(status);
+        self
+    }
+
+    pub fn retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    /// Map a Security framework OSStatus to an error code
+    pub fn from_os_status(status: i32, context: &str) -> Self {
+        let code = match status {
+            -25300 => ErrorCode::NotFound,           // errSecItemNotFound
+            -128 => ErrorCode::UserCancelled,        // errSecUserCanceled
+            -25293 => ErrorCode::AuthFailed,         // errSecAuthFailed
+            -25308 => ErrorCode::BackendUnavailable, // errSecInteractionNotAllowed
+            -25291 => ErrorCode::BackendUnavailable, // errSecNotAvailable
+            -34018 => ErrorCode::BackendUnavailable, // errSecMissingEntitlement
+            -50 => ErrorCode::InvalidInput,          // errSecParam
+            _ => ErrorCode::Internal,
+        };
+        // A missing entitlement will not fix itself between attempts
+        let retryable = code.default_retryable() && status != -34018;
+        Self::new(code, format!("{}, status: {}", context, status))
+            .with_os_status(status as i64)
+            .retryable(retryable)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        let code = match e.kind() {
+            ErrorKind::NotFound => ErrorCode::NotFound,
+            ErrorKind::InvalidInput => ErrorCode::InvalidInput,
+            ErrorKind::InvalidData | ErrorKind::UnexpectedEof => ErrorCode::Corrupt,
+            _ => ErrorCode::Io,
+        };
+        let error = Self::new(code, e.to_string());
+        match e.raw_os_error() {
+            Some(errno) => error.with_os_status(errno as i64),
+            None => error,
+        }
+    }
+}
+
+impl From<std::ffi::NulError> for AppError {
+    fn from(e: std::ffi::NulError) -> Self {
+        Self::invalid_input(e.to_string())
+    }
+}
+
+impl From<keyring::Error> for AppError {
+    fn from(e: keyring::Error) -> Self {
+        let code = match &e {
+            keyring::Error::NoEntry => ErrorCode::NotFound,
+            keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_) => {
+                ErrorCode::BackendUnavailable
+            }
+            keyring::Error::BadEncoding(_)
+            | keyring::Error::TooLong(_, _)
+            | keyring::Error::Invalid(_, _) => ErrorCode::InvalidInput,
+            _ => ErrorCode::Internal,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        Self::internal(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        let error = Self::new(ErrorCode::Io, e.to_string());
+        match e.status() {
+            Some(status) => error.with_os_status(status.as_u16() as i64),
+            None => error,
+        }
+    }
+}
+
+impl From<ssh2::Error> for AppError {
+    fn from(e: ssh2::Error) -> Self {
+        // libssh2 session errors are negative; SFTP status codes come from the server
+        let (code, status) = match e.code() {
+            ssh2::ErrorCode::Session(-18) => (ErrorCode::AuthFailed, -18), // authentication failed
+            ssh2::ErrorCode::Session(status) => (ErrorCode::Io, status),
+            ssh2::ErrorCode::SFTP(2) => (ErrorCode::NotFound, 2), // no such file
+            ssh2::ErrorCode::SFTP(status) => (ErrorCode::Io, status),
+        };
+        Self::new(code, e.message().to_string()).with_os_status(i64::from(status))
+    }
+}
+
+impl From<git2::Error> for AppError {
+    fn from(e: git2::Error) -> Self {
+        let code = match e.code() {
+            git2::ErrorCode::NotFound | git2::ErrorCode::UnbornBranch => ErrorCode::NotFound,
+            git2::ErrorCode::Locked => ErrorCode::BackendUnavailable,
+            _ => ErrorCode::Internal,
+        };
+        Self::new(code, e.message().to_string()).with_os_status(i64::from(e.raw_code()))
+    }
+}
@@ -0,0 +1,469 @@
+// Git-backed point-in-time history of vaults (opt-in per vault)
+//
+// When enabled, every verified save is also committed to a private bare git repository
+// in `<app data>/history/<key>/`, keyed like the backup store. The vault file is
+// committed as it is on disk, still encrypted, so the repository never holds plaintext.
+// Every version is kept, without a retention limit. Each save encrypts with a fresh seed
+// and IV, so git can neither share nor compress bytes between snapshots: every snapshot
+// costs about the size of the vault file, until the history is deleted. Each commit
+// records the entry count and SHA-256 of the file as message trailers.
+//
+// Snapshots are only read back through the commands below: two snapshots are diffed
+// after opening both with the given credentials, and a snapshot is restored as a new
+// file, never over the vault itself. Recording a snapshot never fails a save.
+use crate::backup::{self, canonical_path, check_kdbx, sha256_hex, vault_key, write_atomic};
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::kdbx::diff::{self, VaultDiff};
+use crate::kdbx::Credentials;
+use crate::logging::redact_path;
+use crate::unlock_attempts;
+use chrono::{DateTime, TimeZone, Utc};
+use git2::{Commit, ObjectType, Oid, Repository, Signature, Sort};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::{command, Manager};
+
+const HISTORY_DIR_NAME: &str = "history";
+const ENABLED_KEY: &str = "keedavault.enabled";
+const VAULT_PATH_KEY: &str = "keedavault.path";
+const AUTHOR_NAME: &str = "KeedaVault";
+const AUTHOR_EMAIL: &str = "keedavault@localhost";
+const ENTRIES_TRAILER: &str = "Entries: ";
+const SHA256_TRAILER: &str = "Sha256: ";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// Commit hash
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub size: u64,
+    pub sha256: Option<String>,
+    pub entry_count: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryStatus {
+    pub enabled: bool,
+    pub snapshot_count: usize,
+}
+
+/// History repository of a single vault
+struct VaultHistory {
+    repo: Repository,
+    vault_path: PathBuf,
+}
+
+fn history_dir(app: &tauri::AppHandle, path: &str, vault_id: Option<&str>) -> AppResult<PathBuf> {
+    let key = vault_key(&canonical_path(path), vault_id);
+    Ok(app.path().app_data_dir()?.join(HISTORY_DIR_NAME).join(key))
+}
+
+fn trailer<'a>(message: &'a str, name: &str) -> Option<&'a str> {
+    message.lines().find_map(|line| line.strip_prefix(name))
+}
+
+impl VaultHistory {
+    /// The vault's history, if it was ever enabled
+    fn open(app: &tauri::AppHandle, path: &str, vault_id: Option<&str>) -> AppResult<Option<Self>> {
+        Self::open_in(&history_dir(app, path, vault_id)?, path)
+    }
+
+    fn open_in(dir: &Path, path: &str) -> AppResult<Option<Self>> {
+        if !dir.exists() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            repo: Repository::open_bare(dir)?,
+            vault_path: canonical_path(path),
+        }))
+    }
+
+    fn create(app: &tauri::AppHandle, path: &str, vault_id: Option<&str>) -> AppResult<Self> {
+        Self::create_in(&history_dir(app, path, vault_id)?, path)
+    }
+
+    fn create_in(dir: &Path, path: &str) -> AppResult<Self> {
+        let repo = Repository::init_bare(dir)?;
+        let vault_path = canonical_path(path);
+        repo.config()?
+            .set_str(VAULT_PATH_KEY, &vault_path.to_string_lossy())?;
+        Ok(Self { repo, vault_path })
+    }
+
+    fn enabled(&self) -> bool {
+        self.repo
+            .config()
+            .and_then(|config| config.get_bool(ENABLED_KEY))
+            .unwrap_or(false)
+    }
+
+    fn set_enabled(&self, enabled: bool) -> AppResult<()> {
+        Ok(self.repo.config()?.set_bool(ENABLED_KEY, enabled)?)
+    }
+
+    fn file_name(&self) -> String {
+        self.vault_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Vault.kdbx".to_string())
+    }
+
+    fn head(&self) -> Option<Commit<'_>> {
+        self.repo.head().ok()?.peel_to_commit().ok()
+    }
+
+    /// Commit `data` unless it is what the newest snapshot already holds
+    fn commit(&self, data: &[u8], entry_count: Option<u32>) -> AppResult<Option<Oid>> {
+        let blob = self.repo.blob(data)?;
+        let mut builder = self.repo.treebuilder(None)?;
+        builder.insert(self.file_name(), blob, 0o100644)?;
+        let tree = self.repo.find_tree(builder.write()?)?;
+        let parent = self.head();
+        if parent.as_ref().is_some_and(|p| p.tree_id() == tree.id()) {
+            return Ok(None);
+        }
+
+        let mut message = format!("Save {}\n\n", self.file_name());
+        if let Some(count) = entry_count {
+            message.push_str(&format!("{}{}\n", ENTRIES_TRAILER, count));
+        }
+        message.push_str(&format!("{}{}\n", SHA256_TRAILER, sha256_hex(data)));
+        let signature = Signature::now(AUTHOR_NAME, AUTHOR_EMAIL)?;
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let oid = self.repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            &message,
+            &tree,
+            &parents,
+        )?;
+        Ok(Some(oid))
+    }
+
+    /// Commit a save if history is enabled
+    fn record(&self, data: &[u8], entry_count: Option<u32>) -> AppResult<Option<Oid>> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+        self.commit(data, entry_count)
+    }
+
+    fn snapshot_of(&self, commit: &Commit) -> Snapshot {
+        let message = commit.message().unwrap_or_default();
+        let size = commit
+            .tree()
+            .ok()
+            .and_then(|tree| tree.iter().next().map(|entry| entry.id()))
+            .and_then(|blob| self.repo.odb().ok()?.read_header(blob).ok())
+            .map(|(size, _)| size as u64)
+            .unwrap_or_default();
+        Snapshot {
+            id: commit.id().to_string(),
+            created_at: Utc
+                .timestamp_opt(commit.time().seconds(), 0)
+                .single()
+                .unwrap_or_default(),
+            size,
+            sha256: trailer(message, SHA256_TRAILER).map(str::to_string),
+            entry_count: trailer(message, ENTRIES_TRAILER).and_then(|n| n.trim().parse().ok()),
+        }
+    }
+
+    /// Snapshots, newest first
+    fn list(&self) -> AppResult<Vec<Snapshot>> {
+        if self.head().is_none() {
+            return Ok(Vec::new());
+        }
+        let mut walk = self.repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        walk.push_head()?;
+        walk.map(|oid| Ok(self.snapshot_of(&self.repo.find_commit(oid?)?)))
+            .collect()
+    }
+
+    /// Vault file as committed in snapshot `id`
+    fn data(&self, id: &str) -> AppResult<Vec<u8>> {
+        let not_found = || AppError::not_found(format!("Snapshot {} not found", id));
+        let oid = Oid::from_str(id).map_err(|_| not_found())?;
+        let commit = self.repo.find_commit(oid).map_err(|_| not_found())?;
+        let tree = commit.tree()?;
+        let entry = tree.iter().next().ok_or_else(not_found)?;
+        let blob = entry.to_object(&self.repo)?.peel(ObjectType::Blob)?;
+        let data = blob.as_blob().ok_or_else(not_found)?.content().to_vec();
+
+        let snapshot = self.snapshot_of(&commit);
+        if snapshot
+            .sha256
+            .is_some_and(|sha256| sha256 != sha256_hex(&data))
+        {
+            return Err(AppError::new(
+                ErrorCode::Corrupt,
+                format!("Snapshot {} is damaged (checksum mismatch)", id),
+            )
+            .retryable(false));
+        }
+        Ok(data)
+    }
+
+    /// Write snapshot `id` to `target` as a separate vault file
+    fn restore(&self, id: &str, target: &Path) -> AppResult<()> {
+        let data = self.data(id)?;
+        check_kdbx(&data)?;
+        write_atomic(target, &data)
+    }
+}
+
+/// Commit a verified save to the vault's history when it is enabled. Runs inside the
+/// save transaction; failures are logged, never returned.
+pub(crate) fn record(
+    app: &tauri::AppHandle,
+    path: &str,
+    vault_id: Option<&str>,
+    data: &[u8],
+    entry_count: Option<u32>,
+) {
+    let result = VaultHistory::open(app, path, vault_id).and_then(|history| match history {
+        Some(history) => history.record(data, entry_count),
+        None => Ok(None),
+    });
+    match result {
+        Ok(Some(oid)) => log::debug!("Recorded history snapshot {}", oid),
+        Ok(None) => {}
+        Err(e) => log::warn!(
+            "Failed to record history of {}: {}",
+            redact_path(path),
+            e.message
+        ),
+    }
+}
+
+#[command]
+pub async fn get_vault_history(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+) -> AppResult<HistoryStatus> {
+    let _guard = backup::lock_transaction();
+    Ok(
+        match VaultHistory::open(&app_handle, &path, vault_id.as_deref())? {
+            Some(history) => HistoryStatus {
+                enabled: history.enabled(),
+                snapshot_count: history.list()?.len(),
+            },
+            None => HistoryStatus {
+                enabled: false,
+                snapshot_count: 0,
+            },
+        },
+    )
+}
+
+/// Turn history on or off for a vault. Turning it on records the current file as the
+/// first snapshot; turning it off keeps the snapshots taken so far.
+#[command]
+pub async fn set_vault_history(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+    enabled: bool,
+) -> AppResult<HistoryStatus> {
+    let _guard = backup::lock_transaction();
+    let history = match VaultHistory::open(&app_handle, &path, vault_id.as_deref())? {
+        Some(history) => history,
+        None if enabled => VaultHistory::create(&app_handle, &path, vault_id.as_deref())?,
+        None => {
+            return Ok(HistoryStatus {
+                enabled: false,
+                snapshot_count: 0,
+            })
+        }
+    };
+    history.set_enabled(enabled)?;
+    if enabled {
+        let data = std::fs::read(&path)?;
+        check_kdbx(&data)?;
+        history.commit(&data, None)?;
+    }
+    log::info!(
+        "History {} for {}",
+        if enabled { "enabled" } else { "disabled" },
+        redact_path(&path)
+    );
+    Ok(HistoryStatus {
+        enabled,
+        snapshot_count: history.list()?.len(),
+    })
+}
+
+/// Delete a vault's history repository, all snapshots included
+#[command]
+pub async fn delete_vault_history(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+) -> AppResult<()> {
+    let _guard = backup::lock_transaction();
+    let dir = history_dir(&app_handle, &path, vault_id.as_deref())?;
+    match std::fs::remove_dir_all(&dir) {
+        Ok(()) => {
+            log::info!("Deleted history of {}", redact_path(&path));
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Snapshots of a vault, newest first
+#[command]
+pub async fn list_vault_snapshots(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+) -> AppResult<Vec<Snapshot>> {
+    let _guard = backup::lock_transaction();
+    match VaultHistory::open(&app_handle, &path, vault_id.as_deref())? {
+        Some(history) => history.list(),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Entry-level changes from snapshot `from` to snapshot `to`. `to_credentials` defaults
+/// to `credentials`, for snapshots taken before the master key changed.
+#[command]
+#[allow(clippy::too_many_arguments)] // command arguments arrive as one IPC payload
+pub async fn diff_vault_snapshots(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+    from: String,
+    to: String,
+    credentials: Credentials,
+    to_credentials: Option<Credentials>,
+) -> AppResult<VaultDiff> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let (from_data, to_data) = {
+            let _guard = backup::lock_transaction();
+            let history = VaultHistory::open(&app_handle, &path, vault_id.as_deref())?
+                .ok_or_else(|| AppError::not_found("This vault has no history"))?;
+            (history.data(&from)?, history.data(&to)?)
+        };
+        let a = unlock_attempts::open(&app_handle, &path, &from_data, &credentials)?;
+        let b = unlock_attempts::open(
+            &app_handle,
+            &path,
+            &to_data,
+            to_credentials.as_ref().unwrap_or(&credentials),
+        )?;
+        Ok(diff::diff(&a, &b))
+    })
+    .await?
+}
+
+/// Write snapshot `id` to `target_path` as a separate vault file
+#[command]
+pub async fn restore_vault_snapshot(
+    app_handle: tauri::AppHandle,
+    path: String,
+    vault_id: Option<String>,
+    snapshot_id: String,
+    target_path: String,
+) -> AppResult<()> {
+    if canonical_path(&target_path) == canonical_path(&path) {
+        return Err(AppError::invalid_input(
+            "Restore the snapshot as a new file, not over the open vault",
+        ));
+    }
+    let _guard = backup::lock_transaction();
+    let history = VaultHistory::open(&app_handle, &path, vault_id.as_deref())?
+        .ok_or_else(|| AppError::not_found("This vault has no history"))?;
+    history.restore(&snapshot_id, Path::new(&target_path))?;
+    log::info!(
+        "Restored snapshot {} of {} to {}",
+        snapshot_id,
+        redact_path(&path),
+        redact_path(&target_path)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::diff::ChangeKind;
+    use crate::kdbx::tests::{sample_vault, MAIL};
+    use crate::kdbx::Database;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            password: Some("history".to_string()),
+            key_file: None,
+            challenge_response: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn history_records_lists_diffs_and_restores() {
+        let dir = std::env::temp_dir().join(format!(
+            "keedavault-history-{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        let repo_dir = dir.join("history");
+        let vault = dir.join("Vault.kdbx").to_string_lossy().into_owned();
+        let history = VaultHistory::create_in(&repo_dir, &vault).expect("create");
+
+        let mut database = sample_vault();
+        let first = database.save(&credentials()).expect("save");
+        let first_count = u32::try_from(database.entry_count()).ok();
+        let recorded = history.record(&first, first_count).expect("record");
+        assert_eq!(recorded, None, "history is off until enabled");
+        assert!(history.list().expect("list").is_empty());
+
+        history.set_enabled(true).expect("enable");
+        let first_id = history.record(&first, first_count).expect("record");
+        let unchanged = history.record(&first, first_count).expect("record");
+        assert_eq!(unchanged, None, "the same file is recorded once");
+        database.root.take_entry(MAIL).expect("Mail entry");
+        let second = database.save(&credentials()).expect("save");
+        let second_count = u32::try_from(database.entry_count()).ok();
+        let second_id = history.record(&second, second_count).expect("record");
+
+        // Reopened as the commands do, newest first
+        let history = VaultHistory::open_in(&repo_dir, &vault)
+            .expect("open")
+            .expect("history exists");
+        assert!(history.enabled());
+        let snapshots = history.list().expect("list");
+        let ids: Vec<Option<String>> = snapshots.iter().map(|s| Some(s.id.clone())).collect();
+        assert_eq!(
+            ids,
+            [second_id, first_id].map(|id| id.map(|oid| oid.to_string()))
+        );
+        let (newest, oldest) = (&snapshots[0], &snapshots[1]);
+        assert_eq!(newest.entry_count, second_count);
+        assert_eq!(oldest.entry_count, first_count);
+        assert_eq!(oldest.size, first.len() as u64);
+        assert_eq!(oldest.sha256, Some(sha256_hex(&first)));
+
+        // Diffs open both snapshots with the vault's credentials
+        let open = |id: &str| {
+            let data = history.data(id).expect("snapshot data");
+            Database::open(&data, &credentials()).expect("open snapshot")
+        };
+        let changes = diff::diff(&open(&oldest.id), &open(&newest.id));
+        assert_eq!(changes.entries.len(), 1, "{:?}", changes.entries);
+        assert_eq!(changes.entries[0].uuid, MAIL);
+        assert_eq!(changes.entries[0].kind, ChangeKind::Removed);
+
+        let restored = dir.join("Restored.kdbx");
+        history.restore(&oldest.id, &restored).expect("restore");
+        assert_eq!(std::fs::read(&restored).expect("restored file"), first);
+        let missing = history.restore(&"0".repeat(40), &restored).err();
+        assert_eq!(missing.map(|e| e.code), Some(ErrorCode::NotFound));
+
+        std::fs::remove_dir_all(&dir).expect("clean up");
+    }
+}
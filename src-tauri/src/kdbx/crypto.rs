@@ -0,0 +1,368 @@
+// Key derivation, payload ciphers and the inner stream used for protected values
+use super::corrupt;
+use super::header::{KdfParams, VarValue};
+use crate::error::{AppError, AppResult};
+use aes::cipher::{
+    block_padding::{NoPadding, Pkcs7},
+    BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit, StreamCipher,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+
+pub const CIPHER_AES256: [u8; 16] = [
+    0x31, 0xc1, 0xf2, 0xe6, 0xbf, 0x71, 0x43, 0x50, 0xbe, 0x58, 0x05, 0x21, 0x6a, 0xfc, 0x5a, 0xff,
+];
+pub const CIPHER_CHACHA20: [u8; 16] = [
+    0xd6, 0x03, 0x8a, 0x2b, 0x8b, 0x6f, 0x4c, 0xb5, 0xa5, 0x24, 0x33, 0x9a, 0x31, 0xdb, 0xb5, 0x9a,
+];
+// Added by the KeePass TwofishCipher plugin; KeePassXC writes it too
+pub const CIPHER_TWOFISH: [u8; 16] = [
+    0xad, 0x68, 0xf2, 0x9f, 0x57, 0x6f, 0x4b, 0xb9, 0xa3, 0x6a, 0xd4, 0x7a, 0xf9, 0x65, 0x34, 0x6c,
+];
+
+pub const KDF_AES: [u8; 16] = [
+    0xc9, 0xd9, 0xf3, 0x9a, 0x62, 0x8a, 0x44, 0x60, 0xbf, 0x74, 0x0d, 0x08, 0xc1, 0x8a, 0x4f, 0xea,
+];
+pub const KDF_ARGON2D: [u8; 16] = [
+    0xef, 0x63, 0x6d, 0xdf, 0x8c, 0x29, 0x44, 0x4b, 0x91, 0xf7, 0xa9, 0xa4, 0x03, 0xe3, 0x0a, 0x0c,
+];
+pub const KDF_ARGON2ID: [u8; 16] = [
+    0x9e, 0x29, 0x8b, 0x19, 0x56, 0xdb, 0x47, 0x73, 0xb2, 0x3d, 0xfc, 0x3e, 0xc6, 0xf0, 0xa1, 0xe6,
+];
+
+// Costliest key derivation a file may ask for. Vaults set up in KeePass, KeePassXC or
+// here stay far below; without a limit a crafted file would tie up the machine for
+// hours, or abort it, before the password is even checked. Fuzzing builds derive
+// cheaply so that the limit itself is what the fuzzer runs into
+#[cfg(not(fuzzing))]
+const MAX_AES_ROUNDS: u64 = 1 << 32;
+#[cfg(not(fuzzing))]
+const MAX_ARGON2_MEMORY: u64 = 4 * 1024 * 1024 * 1024;
+// Argon2 memory times iterations, in bytes: some 20 minutes of work
+#[cfg(not(fuzzing))]
+const MAX_ARGON2_WORK: u64 = 1 << 40;
+#[cfg(fuzzing)]
+const MAX_AES_ROUNDS: u64 = 1000;
+#[cfg(fuzzing)]
+const MAX_ARGON2_MEMORY: u64 = 1024 * 1024;
+#[cfg(fuzzing)]
+const MAX_ARGON2_WORK: u64 = 4 * 1024 * 1024;
+
+// Fixed nonce KeePass uses for the Salsa20 inner stream
+const SALSA20_NONCE: [u8; 8] = [0xe8, 0x30, 0x09, 0x4b, 0x97, 0x20, 0x5d, 0x2a];
+
+pub type Key32 = Zeroizing<[u8; 32]>;
+
+pub fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+pub fn sha512(parts: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+pub fn random_bytes<const N: usize>() -> AppResult<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| AppError::internal(format!("System random generator failed: {}", e)))?;
+    Ok(bytes)
+}
+
+/// Fail with `Unsupported` if the KDF described by `params` costs more than this app
+/// derives
+pub fn check_kdf_cost(params: &KdfParams) -> AppResult<()> {
+    let uuid = params.uuid()?;
+    if uuid == KDF_AES {
+        let rounds = params.u64("R")?;
+        if rounds > MAX_AES_ROUNDS {
+            return Err(AppError::unsupported(format!(
+                "AES-KDF with {} rounds is more than this app derives (at most {})",
+                rounds, MAX_AES_ROUNDS
+            )));
+        }
+    } else if uuid == KDF_ARGON2D || uuid == KDF_ARGON2ID {
+        let memory = params.u64("M")?;
+        if memory > MAX_ARGON2_MEMORY {
+            return Err(AppError::unsupported(format!(
+                "Argon2 with {} MiB of memory is more than this app derives (at most {} MiB)",
+                memory >> 20,
+                MAX_ARGON2_MEMORY >> 20
+            )));
+        }
+        if memory.saturating_mul(params.u64("I")?) > MAX_ARGON2_WORK {
+            return Err(AppError::unsupported(
+                "Argon2 with these iterations and memory takes longer than this app derives",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Run the KDF described by `params` over the composite key
+pub fn transform_key(composite: &[u8; 32], params: &KdfParams) -> AppResult<Key32> {
+    check_kdf_cost(params)?;
+    let uuid = params.uuid()?;
+    if uuid == KDF_AES {
+        let seed = params.bytes("S")?;
+        let rounds = params.u64("R")?;
+        aes_kdf(composite, seed, rounds)
+    } else if uuid == KDF_ARGON2D || uuid == KDF_ARGON2ID {
+        let algorithm = if uuid == KDF_ARGON2D {
+            argon2::Algorithm::Argon2d
+        } else {
+            argon2::Algorithm::Argon2id
+        };
+        argon2_kdf(composite, algorithm, params)
+    } else {
+        Err(AppError::unsupported("Unknown key derivation function"))
+    }
+}
+
+fn aes_kdf(composite: &[u8; 32], seed: &[u8], rounds: u64) -> AppResult<Key32> {
+    if seed.len() != 32 {
+        return Err(corrupt("Invalid AES-KDF seed"));
+    }
+    let cipher = aes::Aes256::new(seed.into());
+    let mut left = aes::Block::clone_from_slice(&composite[..16]);
+    let mut right = aes::Block::clone_from_slice(&composite[16..]);
+    for _ in 0..rounds {
+        cipher.encrypt_block(&mut left);
+        cipher.encrypt_block(&mut right);
+    }
+    let mut transformed = Zeroizing::new([0u8; 32]);
+    transformed[..16].copy_from_slice(&left);
+    transformed[16..].copy_from_slice(&right);
+    Ok(Zeroizing::new(sha256(&[transformed.as_slice()])))
+}
+
+fn argon2_kdf(
+    composite: &[u8; 32],
+    algorithm: argon2::Algorithm,
+    params: &KdfParams,
+) -> AppResult<Key32> {
+    let invalid = |e: argon2::Error| corrupt(format!("Invalid Argon2 parameters: {}", e));
+
+    let salt = params.bytes("S")?;
+    let memory_kib = params.u64("M")? / 1024;
+    let iterations = params.u64("I")?;
+    let parallelism = params.u32("P")?;
+    let version = match params.u32("V")? {
+        0x10 => argon2::Version::V0x10,
+        0x13 => argon2::Version::V0x13,
+        other => {
+            return Err(AppError::unsupported(format!(
+                "Unsupported Argon2 version 0x{:x}",
+                other
+            )))
+        }
+    };
+
+    let mut builder = argon2::ParamsBuilder::new();
+    builder
+        .m_cost(u32::try_from(memory_kib).map_err(|_| corrupt("Argon2 memory is too large"))?)
+        .t_cost(u32::try_from(iterations).map_err(|_| corrupt("Argon2 iterations are too large"))?)
+        .p_cost(parallelism)
+        .output_len(32);
+    if let Some(VarValue::Bytes(data)) = params.get("A") {
+        builder.data(argon2::AssociatedData::new(data).map_err(invalid)?);
+    }
+    let argon2_params = builder.build().map_err(invalid)?;
+
+    // The memory size comes from the file; allocate it fallibly so an absurd value is
+    // an error instead of an abort
+    let block_count = argon2_params.block_count();
+    let mut memory = Vec::new();
+    memory.try_reserve_exact(block_count).map_err(|_| {
+        AppError::internal(format!(
+            "Not enough memory for Argon2 ({} MiB)",
+            block_count / 1024
+        ))
+    })?;
+    memory.resize(block_count, argon2::Block::default());
+
+    let context = match params.get("K") {
+        Some(VarValue::Bytes(secret)) => {
+            argon2::Argon2::new_with_secret(secret, algorithm, version, argon2_params)
+                .map_err(invalid)?
+        }
+        _ => argon2::Argon2::new(algorithm, version, argon2_params),
+    };
+    let mut out = Zeroizing::new([0u8; 32]);
+    context
+        .hash_password_into_with_memory(composite, salt, out.as_mut_slice(), &mut memory)
+        .map_err(invalid)?;
+    Ok(out)
+}
+
+/// Payload cipher named by the outer header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cipher {
+    #[serde(rename = "AES-256")]
+    Aes256,
+    ChaCha20,
+    Twofish,
+}
+
+impl Cipher {
+    pub fn from_uuid(uuid: &[u8]) -> AppResult<Self> {
+        if uuid == CIPHER_AES256 {
+            Ok(Cipher::Aes256)
+        } else if uuid == CIPHER_CHACHA20 {
+            Ok(Cipher::ChaCha20)
+        } else if uuid == CIPHER_TWOFISH {
+            Ok(Cipher::Twofish)
+        } else {
+            Err(AppError::unsupported("Unsupported database cipher"))
+        }
+    }
+
+    pub fn uuid(self) -> [u8; 16] {
+        match self {
+            Cipher::Aes256 => CIPHER_AES256,
+            Cipher::ChaCha20 => CIPHER_CHACHA20,
+            Cipher::Twofish => CIPHER_TWOFISH,
+        }
+    }
+
+    pub fn iv_len(self) -> usize {
+        match self {
+            Cipher::Aes256 | Cipher::Twofish => 16,
+            Cipher::ChaCha20 => 12,
+        }
+    }
+
+    pub fn decrypt(self, key: &[u8; 32], iv: &[u8], data: &[u8]) -> AppResult<Vec<u8>> {
+        if iv.len() != self.iv_len() {
+            return Err(corrupt("Invalid encryption IV length"));
+        }
+        match self {
+            Cipher::Aes256 => cbc::Decryptor::<aes::Aes256>::new(key.into(), iv.into())
+                .decrypt_padded_vec_mut::<Pkcs7>(data)
+                .map_err(|_| corrupt("Payload decryption failed")),
+            Cipher::Twofish => cbc::Decryptor::<twofish::Twofish>::new(key.into(), iv.into())
+                .decrypt_padded_vec_mut::<Pkcs7>(data)
+                .map_err(|_| corrupt("Payload decryption failed")),
+            Cipher::ChaCha20 => {
+                let mut out = data.to_vec();
+                chacha20::ChaCha20::new(key.into(), iv.into()).apply_keystream(&mut out);
+                Ok(out)
+            }
+        }
+    }
+
+    /// Decrypt as much of a cut-off payload as the cipher allows, without checking
+    /// padding: CBC keeps the whole blocks, a stream cipher keeps every byte
+    pub fn decrypt_prefix(self, key: &[u8; 32], iv: &[u8], data: &[u8]) -> AppResult<Vec<u8>> {
+        if iv.len() != self.iv_len() {
+            return Err(corrupt("Invalid encryption IV length"));
+        }
+        match self {
+            Cipher::Aes256 => {
+                let whole = &data[..data.len() - data.len() % 16];
+                cbc::Decryptor::<aes::Aes256>::new(key.into(), iv.into())
+                    .decrypt_padded_vec_mut::<NoPadding>(whole)
+                    .map_err(|_| corrupt("Payload decryption failed"))
+            }
+            Cipher::Twofish => {
+                let whole = &data[..data.len() - data.len() % 16];
+                cbc::Decryptor::<twofish::Twofish>::new(key.into(), iv.into())
+                    .decrypt_padded_vec_mut::<NoPadding>(whole)
+                    .map_err(|_| corrupt("Payload decryption failed"))
+            }
+            Cipher::ChaCha20 => self.decrypt(key, iv, data),
+        }
+    }
+
+    pub fn encrypt(self, key: &[u8; 32], iv: &[u8], data: &[u8]) -> AppResult<Vec<u8>> {
+        if iv.len() != self.iv_len() {
+            return Err(AppError::internal("Invalid encryption IV length"));
+        }
+        match self {
+            Cipher::Aes256 => Ok(cbc::Encryptor::<aes::Aes256>::new(key.into(), iv.into())
+                .encrypt_padded_vec_mut::<Pkcs7>(data)),
+            Cipher::Twofish => Ok(
+                cbc::Encryptor::<twofish::Twofish>::new(key.into(), iv.into())
+                    .encrypt_padded_vec_mut::<Pkcs7>(data),
+            ),
+            Cipher::ChaCha20 => {
+                let mut out = data.to_vec();
+                chacha20::ChaCha20::new(key.into(), iv.into()).apply_keystream(&mut out);
+                Ok(out)
+            }
+        }
+    }
+}
+
+pub const INNER_STREAM_SALSA20: u32 = 2;
+pub const INNER_STREAM_CHACHA20: u32 = 3;
+
+/// Keystream XORed over protected values, in document order
+pub enum InnerStream {
+    Salsa20(salsa20::Salsa20),
+    ChaCha20(chacha20::ChaCha20),
+}
+
+impl InnerStream {
+    pub fn new(id: u32, key: &[u8]) -> AppResult<Self> {
+        match id {
+            INNER_STREAM_SALSA20 => {
+                let key = sha256(&[key]);
+                Ok(InnerStream::Salsa20(salsa20::Salsa20::new(
+                    (&key).into(),
+                    (&SALSA20_NONCE).into(),
+                )))
+            }
+            INNER_STREAM_CHACHA20 => {
+                let hash = Zeroizing::new(sha512(&[key]));
+                Ok(InnerStream::ChaCha20(chacha20::ChaCha20::new(
+                    hash[..32].into(),
+                    hash[32..44].into(),
+                )))
+            }
+            _ => Err(AppError::unsupported(
+                "Unsupported protected value stream (ArcFour or unknown)",
+            )),
+        }
+    }
+
+    pub fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            InnerStream::Salsa20(stream) => stream.apply_keystream(data),
+            InnerStream::ChaCha20(stream) => stream.apply_keystream(data),
+        }
+    }
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Per-block HMAC key of the KDBX 4 block stream (`u64::MAX` is used for the header)
+pub fn block_hmac_key(hmac_key: &[u8; 64], index: u64) -> Zeroizing<[u8; 64]> {
+    Zeroizing::new(sha512(&[&index.to_le_bytes(), hmac_key]))
+}
+
+pub fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Constant-time comparison for MACs and hashes
+pub fn verify_hmac(key: &[u8], parts: &[&[u8]], expected: &[u8]) -> bool {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.verify_slice(expected).is_ok()
+}
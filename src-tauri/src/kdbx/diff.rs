@@ -0,0 +1,452 @@
+// Entry-level comparison of two vaults
+//
+// Groups and entries are matched by UUID wherever they live in the tree, as the merge
+// does. An object only in `b` is added, only in `a` removed; a matched object is moved
+// when its parent group differs and modified when any of its user-visible properties
+// or its history differ. Access times and usage counts are ignored: opening an entry is
+// not a change anyone wants to review.
+//
+// Changes are reported field by field with their old and new values, so the frontend
+// can show them without unlocking either vault. Protected values (passwords, protected
+// custom fields) are never included, only flagged as masked; attachments are described
+// by size and digest.
+use super::crypto::sha256;
+use super::model::{Entry, Group, Node};
+use super::xml::Element;
+use super::Database;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Entry properties compared as whole elements, besides strings and attachments
+const ENTRY_PROPERTIES: &[&str] = &[
+    "IconID",
+    "CustomIconUUID",
+    "ForegroundColor",
+    "BackgroundColor",
+    "OverrideURL",
+    "Tags",
+    "AutoType",
+];
+const GROUP_PROPERTIES: &[&str] = &[
+    "Name",
+    "Notes",
+    "IconID",
+    "CustomIconUUID",
+    "IsExpanded",
+    "DefaultAutoTypeSequence",
+    "EnableAutoType",
+    "EnableSearching",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// Present in both; moved, modified or both
+    Changed,
+}
+
+impl ChangeKind {
+    fn of<T>(before: Option<T>, after: Option<T>) -> Self {
+        match (before, after) {
+            (None, _) => Self::Added,
+            (_, None) => Self::Removed,
+            _ => Self::Changed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldKind {
+    /// `<String>` field: Title, UserName, Password, URL, Notes or a custom field
+    String,
+    Attachment,
+    /// Icon, colors, tags, auto-type, expiry and the like
+    Property,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDiff {
+    pub name: String,
+    pub kind: FieldKind,
+    pub change: ChangeKind,
+    /// Value in `a`; `None` when absent, masked or not representable as text
+    pub before: Option<String>,
+    /// Value in `b`, by the same rules
+    pub after: Option<String>,
+    /// A protected value whose contents were left out
+    pub masked: bool,
+}
+
+/// Previous versions of an entry, matched by modification time
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryDiff {
+    /// Versions only in `b`, normally one per edit
+    pub added: usize,
+    /// Versions only in `a`, dropped by the history limits or deleted by hand
+    pub removed: usize,
+    pub count_before: usize,
+    pub count_after: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryDiff {
+    pub uuid: String,
+    pub title: String,
+    pub kind: ChangeKind,
+    /// Group path in `b`, or in `a` for removed entries
+    pub path: String,
+    /// Group path in `a` when the entry moved
+    pub moved_from: Option<String>,
+    /// Fields that differ; all fields of an added or removed entry
+    pub fields: Vec<FieldDiff>,
+    pub history: Option<HistoryDiff>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupDiff {
+    pub uuid: String,
+    pub name: String,
+    pub kind: ChangeKind,
+    /// Path of the parent group in `b`, or in `a` for removed groups
+    pub path: String,
+    pub moved_from: Option<String>,
+    pub fields: Vec<FieldDiff>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultDiff {
+    pub entries: Vec<EntryDiff>,
+    pub groups: Vec<GroupDiff>,
+}
+
+/// Where an object sits: path of its parent group (names joined with `/`) and the
+/// parent's UUID, which decides whether it moved. The root group has neither
+struct Placed<'a, T> {
+    node: &'a T,
+    parent: &'a str,
+    path: String,
+}
+
+struct Index<'a> {
+    entries: HashMap<&'a str, Placed<'a, Entry>>,
+    groups: HashMap<&'a str, Placed<'a, Group>>,
+}
+
+fn index(root: &Group) -> Index<'_> {
+    fn walk<'a>(group: &'a Group, path: &str, index: &mut Index<'a>) {
+        let path = if path.is_empty() {
+            group.name().to_string()
+        } else {
+            format!("{}/{}", path, group.name())
+        };
+        for entry in &group.entries {
+            index.entries.insert(
+                entry.uuid(),
+                Placed {
+                    node: entry,
+                    parent: group.uuid(),
+                    path: path.clone(),
+                },
+            );
+        }
+        for child in &group.groups {
+            index.groups.insert(
+                child.uuid(),
+                Placed {
+                    node: child,
+                    parent: group.uuid(),
+                    path: path.clone(),
+                },
+            );
+            walk(child, &path, index);
+        }
+    }
+    let mut index = Index {
+        entries: HashMap::new(),
+        groups: HashMap::new(),
+    };
+    index.groups.insert(
+        root.uuid(),
+        Placed {
+            node: root,
+            parent: "",
+            path: String::new(),
+        },
+    );
+    walk(root, "", &mut index);
+    index
+}
+
+/// Keys present on either side whose values differ
+fn changed_keys<'a, V: PartialEq>(
+    a: &BTreeMap<&'a str, V>,
+    b: &BTreeMap<&'a str, V>,
+) -> Vec<&'a str> {
+    let keys: BTreeSet<&str> = a.keys().chain(b.keys()).copied().collect();
+    keys.into_iter()
+        .filter(|key| a.get(key) != b.get(key))
+        .collect()
+}
+
+/// `<String>` fields of an entry element by key, with whether the value is protected
+fn strings(element: &Element) -> BTreeMap<&str, (&str, bool)> {
+    element
+        .children_named("String")
+        .filter_map(|s| {
+            let value = s.child("Value");
+            let text = value.map(|v| v.text.as_str()).unwrap_or_default();
+            let protected = value.is_some_and(Element::is_protected);
+            Some((s.child_text("Key")?, (text, protected)))
+        })
+        .collect()
+}
+
+fn string_fields(a: &Element, b: &Element) -> Vec<FieldDiff> {
+    let (a, b) = (strings(a), strings(b));
+    changed_keys(&a, &b)
+        .into_iter()
+        .map(|key| {
+            let (before, after) = (a.get(key), b.get(key));
+            // A value protected on either side is masked on both
+            let masked = before.is_some_and(|v| v.1) || after.is_some_and(|v| v.1);
+            let value = |v: Option<&(&str, bool)>| v.filter(|_| !masked).map(|v| v.0.to_string());
+            FieldDiff {
+                name: key.to_string(),
+                kind: FieldKind::String,
+                change: ChangeKind::of(before, after),
+                before: value(before),
+                after: value(after),
+                masked,
+            }
+        })
+        .collect()
+}
+
+/// Attachments of an entry element by name, resolved to their data in the pool
+fn attachments<'a>(element: &'a Element, db: &'a Database) -> BTreeMap<&'a str, Option<&'a [u8]>> {
+    element
+        .children_named("Binary")
+        .filter_map(|b| {
+            let name = b.child_text("Key")?;
+            let data = b
+                .child("Value")
+                .and_then(|v| v.attr("Ref"))
+                .and_then(|r| r.parse::<usize>().ok())
+                .and_then(|i| db.binaries.get(i))
+                .map(|binary| binary.data.as_slice());
+            Some((name, data))
+        })
+        .collect()
+}
+
+fn describe_attachment(data: Option<&[u8]>) -> String {
+    match data {
+        Some(data) => {
+            let digest: String = sha256(&[data])[..4]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            format!("{} bytes, sha256 {}", data.len(), digest)
+        }
+        None => "missing from the attachment pool".to_string(),
+    }
+}
+
+fn attachment_fields(a: &Element, a_db: &Database, b: &Element, b_db: &Database) -> Vec<FieldDiff> {
+    let (a, b) = (attachments(a, a_db), attachments(b, b_db));
+    changed_keys(&a, &b)
+        .into_iter()
+        .map(|name| {
+            let (before, after) = (a.get(name), b.get(name));
+            FieldDiff {
+                name: name.to_string(),
+                kind: FieldKind::Attachment,
+                change: ChangeKind::of(before, after),
+                before: before.map(|data| describe_attachment(*data)),
+                after: after.map(|data| describe_attachment(*data)),
+                masked: false,
+            }
+        })
+        .collect()
+}
+
+/// Text of a simple property element; structured ones (auto-type) have none
+fn property_text(element: Option<&Element>) -> Option<String> {
+    element
+        .filter(|e| e.children.is_empty())
+        .map(|e| e.text.clone())
+}
+
+fn property_fields(a: &Element, b: &Element, names: &[&str]) -> Vec<FieldDiff> {
+    names
+        .iter()
+        .filter(|name| a.child(name) != b.child(name))
+        .map(|name| {
+            let (before, after) = (a.child(name), b.child(name));
+            FieldDiff {
+                name: name.to_string(),
+                kind: FieldKind::Property,
+                change: ChangeKind::of(before, after),
+                before: property_text(before),
+                after: property_text(after),
+                masked: false,
+            }
+        })
+        .collect()
+}
+
+/// Expiry date, or `Never`
+fn expiry(element: &Element) -> String {
+    let expires = element
+        .child("Times")
+        .and_then(|t| t.child_text("Expires"))
+        .is_some_and(|v| v.eq_ignore_ascii_case("true"));
+    match expires.then(|| element.time("ExpiryTime")).flatten() {
+        Some(time) => time.to_rfc3339(),
+        None => "Never".to_string(),
+    }
+}
+
+fn entry_fields(a: &Element, a_db: &Database, b: &Element, b_db: &Database) -> Vec<FieldDiff> {
+    let mut fields = string_fields(a, b);
+    fields.extend(attachment_fields(a, a_db, b, b_db));
+    fields.extend(property_fields(a, b, ENTRY_PROPERTIES));
+    let (before, after) = (expiry(a), expiry(b));
+    if before != after {
+        fields.push(FieldDiff {
+            name: "Expiry".to_string(),
+            kind: FieldKind::Property,
+            change: ChangeKind::Changed,
+            before: Some(before),
+            after: Some(after),
+            masked: false,
+        });
+    }
+    fields
+}
+
+fn history_diff(a: &[Element], b: &[Element]) -> Option<HistoryDiff> {
+    let times = |items: &[Element]| -> BTreeSet<Option<DateTime<Utc>>> {
+        items.iter().map(|item| item.last_modified()).collect()
+    };
+    let (a_times, b_times) = (times(a), times(b));
+    let diff = HistoryDiff {
+        added: b_times.difference(&a_times).count(),
+        removed: a_times.difference(&b_times).count(),
+        count_before: a.len(),
+        count_after: b.len(),
+    };
+    (diff.added > 0 || diff.removed > 0 || diff.count_before != diff.count_after).then_some(diff)
+}
+
+/// Compare two vaults, `a` being the older one
+pub fn diff(a: &Database, b: &Database) -> VaultDiff {
+    let (a_index, b_index) = (index(&a.root), index(&b.root));
+    let mut result = VaultDiff::default();
+
+    // Added and removed objects are compared against an empty element, listing all
+    // their fields
+    let empty = Element::new("Entry");
+    for (uuid, new) in &b_index.entries {
+        let title = new.node.title().to_string();
+        match a_index.entries.get(uuid) {
+            None => result.entries.push(EntryDiff {
+                uuid: uuid.to_string(),
+                title,
+                kind: ChangeKind::Added,
+                path: new.path.clone(),
+                moved_from: None,
+                fields: entry_fields(&empty, a, &new.node.element, b),
+                history: history_diff(&[], &new.node.history),
+            }),
+            Some(old) => {
+                let moved_from = (old.parent != new.parent).then(|| old.path.clone());
+                let fields = entry_fields(&old.node.element, a, &new.node.element, b);
+                let history = history_diff(&old.node.history, &new.node.history);
+                if moved_from.is_some() || !fields.is_empty() || history.is_some() {
+                    result.entries.push(EntryDiff {
+                        uuid: uuid.to_string(),
+                        title,
+                        kind: ChangeKind::Changed,
+                        path: new.path.clone(),
+                        moved_from,
+                        fields,
+                        history,
+                    });
+                }
+            }
+        }
+    }
+    for (uuid, old) in &a_index.entries {
+        if !b_index.entries.contains_key(uuid) {
+            result.entries.push(EntryDiff {
+                uuid: uuid.to_string(),
+                title: old.node.title().to_string(),
+                kind: ChangeKind::Removed,
+                path: old.path.clone(),
+                moved_from: None,
+                fields: entry_fields(&old.node.element, a, &empty, b),
+                history: history_diff(&old.node.history, &[]),
+            });
+        }
+    }
+
+    let empty = Element::new("Group");
+    for (uuid, new) in &b_index.groups {
+        let name = new.node.name().to_string();
+        match a_index.groups.get(uuid) {
+            None => result.groups.push(GroupDiff {
+                uuid: uuid.to_string(),
+                name,
+                kind: ChangeKind::Added,
+                path: new.path.clone(),
+                moved_from: None,
+                fields: property_fields(&empty, &new.node.element, GROUP_PROPERTIES),
+            }),
+            Some(old) => {
+                let moved_from = (old.parent != new.parent).then(|| old.path.clone());
+                let fields =
+                    property_fields(&old.node.element, &new.node.element, GROUP_PROPERTIES);
+                if moved_from.is_some() || !fields.is_empty() {
+                    result.groups.push(GroupDiff {
+                        uuid: uuid.to_string(),
+                        name,
+                        kind: ChangeKind::Changed,
+                        path: new.path.clone(),
+                        moved_from,
+                        fields,
+                    });
+                }
+            }
+        }
+    }
+    for (uuid, old) in &a_index.groups {
+        if !b_index.groups.contains_key(uuid) {
+            result.groups.push(GroupDiff {
+                uuid: uuid.to_string(),
+                name: old.node.name().to_string(),
+                kind: ChangeKind::Removed,
+                path: old.path.clone(),
+                moved_from: None,
+                fields: property_fields(&old.node.element, &empty, GROUP_PROPERTIES),
+            });
+        }
+    }
+
+    result
+        .entries
+        .sort_by(|x, y| (&x.path, &x.title, &x.uuid).cmp(&(&y.path, &y.title, &y.uuid)));
+    result
+        .groups
+        .sort_by(|x, y| (&x.path, &x.name, &x.uuid).cmp(&(&y.path, &y.name, &y.uuid)));
+    result
+}
@@ -0,0 +1,109 @@
+// Entry points for the cargo-fuzz targets in `src-tauri/fuzz`
+//
+// Only built with `--cfg fuzzing`. Random bytes never get past the payload HMAC or the
+// CBC padding, so besides whole files there are entry points for each stage behind
+// decryption. Each one must return or fail with an `AppError`; a panic is a finding.
+// Whatever loads must also save, and the saved file must open again. Fuzzing builds
+// lower the engine's key derivation limits (see crypto.rs), so whole files derive
+// cheaply or are refused like any costlier file.
+use super::crypto::{Cipher, InnerStream, INNER_STREAM_SALSA20, KDF_AES};
+use super::header::{Header, VarDictionary, VarValue};
+use super::key::key_file_key;
+use super::salvage::{gunzip_prefix, SalvageReport};
+use super::{gunzip, read_inner_header, Credentials, Database, Payload};
+
+fn credentials() -> Credentials {
+    Credentials {
+        password: Some("fuzz".to_string()),
+        key_file: None,
+        challenge_response: Vec::new(),
+    }
+}
+
+/// Outer header. A header that parses must serialize to bytes that parse back to the
+/// same header.
+pub fn header(data: &[u8]) {
+    let Ok((header, _)) = Header::parse(data) else {
+        return;
+    };
+    let Ok(bytes) = header.to_bytes() else {
+        return;
+    };
+    let (reparsed, len) = Header::parse(&bytes).expect("written header parses");
+    assert_eq!(len, bytes.len());
+    assert_eq!(reparsed.to_bytes().ok(), Some(bytes));
+}
+
+/// Whole file, opened with the password `fuzz`
+pub fn open(data: &[u8]) {
+    if let Ok(database) = Database::open(data, &credentials()) {
+        save_and_reopen(database);
+    }
+}
+
+/// Key file in any of the supported formats
+pub fn key_file(data: &[u8]) {
+    let _ = key_file_key(data);
+}
+
+/// Decrypted KDBX 4 payload: inner header with attachments, then the XML
+pub fn payload_v4(data: &[u8]) {
+    if let Ok(payload) = read_inner_header(data) {
+        load(bare_header(0x0004_0001), payload);
+    }
+}
+
+/// Compressed KDBX 4 payload, decompressed by the strict reader and by salvage. Both
+/// stop at the size limit however far the data would expand
+pub fn compressed(data: &[u8]) {
+    let salvaged = gunzip_prefix(data, &mut SalvageReport::default());
+    assert!(salvaged.len() as u64 <= super::MAX_DECOMPRESSED_SIZE);
+    if let Ok(plain) = gunzip(data) {
+        payload_v4(&plain);
+    }
+}
+
+/// Decrypted KDBX 3.1 XML, with attachments in `Meta/Binaries`
+pub fn xml_v3(data: &[u8]) {
+    let header = bare_header(0x0003_0001);
+    let stream = InnerStream::new(INNER_STREAM_SALSA20, &header.protected_stream_key)
+        .expect("Salsa20 stream");
+    let payload = Payload {
+        xml: data.to_vec(),
+        stream,
+        binaries: Vec::new(),
+    };
+    load(header, payload);
+}
+
+/// Header for payloads that skip the outer layer, with the cheapest AES-KDF
+fn bare_header(version: u32) -> Header {
+    let mut kdf = VarDictionary::default();
+    kdf.set("$UUID", VarValue::Bytes(KDF_AES.to_vec()));
+    kdf.set("S", VarValue::Bytes(vec![0; 32]));
+    kdf.set("R", VarValue::U64(1));
+    Header {
+        version,
+        cipher: Cipher::Aes256,
+        compression: false,
+        master_seed: vec![0; 32],
+        iv: vec![0; 16],
+        kdf,
+        public_custom_data: None,
+        protected_stream_key: vec![0; 32],
+        stream_start_bytes: vec![0; 32],
+        inner_stream_id: INNER_STREAM_SALSA20,
+    }
+}
+
+fn load(header: Header, payload: Payload) {
+    if let Ok(database) = Database::from_payload(header, payload) {
+        save_and_reopen(database);
+    }
+}
+
+fn save_and_reopen(mut database: Database) {
+    let saved = database.save(&credentials()).expect("loaded database saves");
+    let reopened = Database::open(&saved, &credentials()).expect("saved database opens");
+    assert_eq!(reopened.entry_count(), database.entry_count());
+}
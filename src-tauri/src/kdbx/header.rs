@@ -0,0 +1,386 @@
+// Outer header of KDBX 3.1 and 4.x files, including the KDBX 4 variant dictionary
+use super::corrupt;
+use super::crypto::{Cipher, INNER_STREAM_SALSA20, KDF_AES};
+use crate::error::{AppError, AppResult};
+
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+
+const END_OF_HEADER: &[u8] = b"\r\n\r\n";
+
+mod field {
+    pub const END: u8 = 0;
+    pub const CIPHER_ID: u8 = 2;
+    pub const COMPRESSION: u8 = 3;
+    pub const MASTER_SEED: u8 = 4;
+    pub const TRANSFORM_SEED: u8 = 5;
+    pub const TRANSFORM_ROUNDS: u8 = 6;
+    pub const ENCRYPTION_IV: u8 = 7;
+    pub const PROTECTED_STREAM_KEY: u8 = 8;
+    pub const STREAM_START_BYTES: u8 = 9;
+    pub const INNER_STREAM_ID: u8 = 10;
+    pub const KDF_PARAMETERS: u8 = 11;
+    pub const PUBLIC_CUSTOM_DATA: u8 = 12;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VarValue {
+    U32(u32),
+    U64(u64),
+    Bool(bool),
+    I32(i32),
+    I64(i64),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+impl VarValue {
+    fn type_id(&self) -> u8 {
+        match self {
+            VarValue::U32(_) => 0x04,
+            VarValue::U64(_) => 0x05,
+            VarValue::Bool(_) => 0x08,
+            VarValue::I32(_) => 0x0c,
+            VarValue::I64(_) => 0x0d,
+            VarValue::Str(_) => 0x18,
+            VarValue::Bytes(_) => 0x42,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            VarValue::U32(v) => v.to_le_bytes().to_vec(),
+            VarValue::U64(v) => v.to_le_bytes().to_vec(),
+            VarValue::Bool(v) => vec![*v as u8],
+            VarValue::I32(v) => v.to_le_bytes().to_vec(),
+            VarValue::I64(v) => v.to_le_bytes().to_vec(),
+            VarValue::Str(v) => v.as_bytes().to_vec(),
+            VarValue::Bytes(v) => v.clone(),
+        }
+    }
+}
+
+/// KDBX 4 `VariantDictionary`; keeps the original key order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VarDictionary {
+    entries: Vec<(String, VarValue)>,
+}
+
+pub type KdfParams = VarDictionary;
+
+impl VarDictionary {
+    pub fn get(&self, key: &str) -> Option<&VarValue> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn set(&mut self, key: &str, value: VarValue) {
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn bytes(&self, key: &str) -> AppResult<&[u8]> {
+        match self.get(key) {
+            Some(VarValue::Bytes(v)) => Ok(v),
+            _ => Err(corrupt(format!("KDF parameter '{}' is missing", key))),
+        }
+    }
+
+    pub fn u64(&self, key: &str) -> AppResult<u64> {
+        match self.get(key) {
+            Some(VarValue::U64(v)) => Ok(*v),
+            Some(VarValue::U32(v)) => Ok(*v as u64),
+            _ => Err(corrupt(format!("KDF parameter '{}' is missing", key))),
+        }
+    }
+
+    pub fn u32(&self, key: &str) -> AppResult<u32> {
+        match self.get(key) {
+            Some(VarValue::U32(v)) => Ok(*v),
+            Some(VarValue::U64(v)) => u32::try_from(*v)
+                .map_err(|_| corrupt(format!("KDF parameter '{}' is out of range", key))),
+            _ => Err(corrupt(format!("KDF parameter '{}' is missing", key))),
+        }
+    }
+
+    /// `$UUID` of a KDF parameter dictionary
+    pub fn uuid(&self) -> AppResult<[u8; 16]> {
+        self.bytes("$UUID")?
+            .try_into()
+            .map_err(|_| corrupt("Invalid KDF UUID"))
+    }
+
+    pub fn parse(data: &[u8]) -> AppResult<Self> {
+        let mut reader = Reader::new(data);
+        let version = reader.u16()?;
+        if version >> 8 != 1 {
+            return Err(AppError::unsupported(
+                "Unsupported variant dictionary version",
+            ));
+        }
+        let mut entries = Vec::new();
+        loop {
+            let type_id = reader.u8()?;
+            if type_id == 0 {
+                break;
+            }
+            let name_len = reader.u32()? as usize;
+            let name = String::from_utf8(reader.take(name_len)?.to_vec())
+                .map_err(|_| corrupt("Invalid variant dictionary key"))?;
+            let value_len = reader.u32()? as usize;
+            let value = reader.take(value_len)?;
+            let fixed = |len: usize| {
+                if value.len() == len {
+                    Ok(value)
+                } else {
+                    Err(corrupt(format!("Invalid length for '{}'", name)))
+                }
+            };
+            let value = match type_id {
+                0x04 => VarValue::U32(u32::from_le_bytes(fixed(4)?.try_into().unwrap())),
+                0x05 => VarValue::U64(u64::from_le_bytes(fixed(8)?.try_into().unwrap())),
+                0x08 => VarValue::Bool(fixed(1)?[0] != 0),
+                0x0c => VarValue::I32(i32::from_le_bytes(fixed(4)?.try_into().unwrap())),
+                0x0d => VarValue::I64(i64::from_le_bytes(fixed(8)?.try_into().unwrap())),
+                0x18 => VarValue::Str(
+                    String::from_utf8(value.to_vec())
+                        .map_err(|_| corrupt("Invalid variant dictionary string"))?,
+                ),
+                0x42 => VarValue::Bytes(value.to_vec()),
+                _ => return Err(corrupt("Unknown variant dictionary value type")),
+            };
+            entries.push((name, value));
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = 0x0100u16.to_le_bytes().to_vec();
+        for (name, value) in &self.entries {
+            let data = value.to_bytes();
+            out.push(value.type_id());
+            out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&data);
+        }
+        out.push(0);
+        out
+    }
+}
+
+/// Little-endian cursor over a byte slice
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+    pub fn take(&mut self, len: usize) -> AppResult<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| corrupt("Unexpected end of data"))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    pub fn u8(&mut self) -> AppResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> AppResult<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> AppResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub version: u32,
+    pub cipher: Cipher,
+    pub compression: bool,
+    pub master_seed: Vec<u8>,
+    pub iv: Vec<u8>,
+    /// KDBX 3.1 files are mapped onto an AES-KDF dictionary
+    pub kdf: KdfParams,
+    pub public_custom_data: Option<VarDictionary>,
+    // KDBX 3.1 only; KDBX 4 keeps these in the inner header
+    pub protected_stream_key: Vec<u8>,
+    pub stream_start_bytes: Vec<u8>,
+    pub inner_stream_id: u32,
+}
+
+impl Header {
+    pub fn is_v4(&self) -> bool {
+        self.version >> 16 >= 4
+    }
+
+    /// Parse the outer header; returns it with the number of bytes it occupies
+    pub fn parse(data: &[u8]) -> AppResult<(Self, usize)> {
+        let mut reader = Reader::new(data);
+        if reader.u32().ok() != Some(SIGNATURE_1) || reader.u32().ok() != Some(SIGNATURE_2) {
+            return Err(AppError::invalid_input("Not a KeePass database"));
+        }
+        let version = reader.u32()?;
+        let major = version >> 16;
+        if !(3..=4).contains(&major) {
+            return Err(AppError::unsupported(format!(
+                "Unsupported KDBX version {}.{}",
+                major,
+                version & 0xffff
+            )));
+        }
+        let v4 = major >= 4;
+
+        let mut cipher = None;
+        let mut compression = false;
+        let mut master_seed = Vec::new();
+        let mut iv = Vec::new();
+        let mut kdf = None;
+        let mut transform_seed = None;
+        let mut transform_rounds = None;
+        let mut public_custom_data = None;
+        let mut protected_stream_key = Vec::new();
+        let mut stream_start_bytes = Vec::new();
+        let mut inner_stream_id = INNER_STREAM_SALSA20;
+
+        loop {
+            let id = reader.u8()?;
+            let len = if v4 {
+                reader.u32()? as usize
+            } else {
+                reader.u16()? as usize
+            };
+            let value = reader.take(len)?;
+            match id {
+                field::END => break,
+                field::CIPHER_ID => cipher = Some(Cipher::from_uuid(value)?),
+                field::COMPRESSION => {
+                    let flags = u32::from_le_bytes(
+                        value
+                            .try_into()
+                            .map_err(|_| corrupt("Invalid compression flags"))?,
+                    );
+                    if flags > 1 {
+                        return Err(AppError::unsupported("Unsupported compression algorithm"));
+                    }
+                    compression = flags == 1;
+                }
+                field::MASTER_SEED => master_seed = value.to_vec(),
+                field::ENCRYPTION_IV => iv = value.to_vec(),
+                field::KDF_PARAMETERS => kdf = Some(VarDictionary::parse(value)?),
+                field::PUBLIC_CUSTOM_DATA => {
+                    public_custom_data = Some(VarDictionary::parse(value)?)
+                }
+                field::TRANSFORM_SEED => transform_seed = Some(value.to_vec()),
+                field::TRANSFORM_ROUNDS => {
+                    transform_rounds = Some(u64::from_le_bytes(
+                        value
+                            .try_into()
+                            .map_err(|_| corrupt("Invalid transform rounds"))?,
+                    ))
+                }
+                field::PROTECTED_STREAM_KEY => protected_stream_key = value.to_vec(),
+                field::STREAM_START_BYTES => stream_start_bytes = value.to_vec(),
+                field::INNER_STREAM_ID => {
+                    inner_stream_id = u32::from_le_bytes(
+                        value
+                            .try_into()
+                            .map_err(|_| corrupt("Invalid inner stream id"))?,
+                    )
+                }
+                // Unknown fields are ignored, as KeePass does
+                _ => {}
+            }
+        }
+
+        if master_seed.len() != 32 {
+            return Err(corrupt("Invalid master seed"));
+        }
+        let kdf = match (v4, kdf, transform_seed, transform_rounds) {
+            (true, Some(kdf), _, _) => kdf,
+            (false, _, Some(seed), Some(rounds)) => {
+                let mut kdf = VarDictionary::default();
+                kdf.set("$UUID", VarValue::Bytes(KDF_AES.to_vec()));
+                kdf.set("R", VarValue::U64(rounds));
+                kdf.set("S", VarValue::Bytes(seed));
+                kdf
+            }
+            _ => return Err(corrupt("Key derivation parameters are missing")),
+        };
+
+        let header = Self {
+            version,
+            cipher: cipher.ok_or_else(|| corrupt("Cipher is missing"))?,
+            compression,
+            master_seed,
+            iv,
+            kdf,
+            public_custom_data,
+            protected_stream_key,
+            stream_start_bytes,
+            inner_stream_id,
+        };
+        Ok((header, reader.position()))
+    }
+
+    pub fn to_bytes(&self) -> AppResult<Vec<u8>> {
+        let v4 = self.is_v4();
+        let mut out = Vec::new();
+        out.extend_from_slice(&SIGNATURE_1.to_le_bytes());
+        out.extend_from_slice(&SIGNATURE_2.to_le_bytes());
+        out.extend_from_slice(&self.version.to_le_bytes());
+
+        let mut write_field = |id: u8, value: &[u8]| {
+            out.push(id);
+            if v4 {
+                out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            } else {
+                out.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            }
+            out.extend_from_slice(value);
+        };
+
+        write_field(field::CIPHER_ID, &self.cipher.uuid());
+        write_field(field::COMPRESSION, &(self.compression as u32).to_le_bytes());
+        write_field(field::MASTER_SEED, &self.master_seed);
+        if v4 {
+            write_field(field::ENCRYPTION_IV, &self.iv);
+            write_field(field::KDF_PARAMETERS, &self.kdf.to_bytes());
+            if let Some(data) = &self.public_custom_data {
+                write_field(field::PUBLIC_CUSTOM_DATA, &data.to_bytes());
+            }
+        } else {
+            if self.kdf.uuid()? != KDF_AES {
+                return Err(AppError::invalid_input("KDBX 3.1 only supports AES-KDF"));
+            }
+            write_field(field::TRANSFORM_SEED, self.kdf.bytes("S")?);
+            write_field(field::TRANSFORM_ROUNDS, &self.kdf.u64("R")?.to_le_bytes());
+            write_field(field::ENCRYPTION_IV, &self.iv);
+            write_field(field::PROTECTED_STREAM_KEY, &self.protected_stream_key);
+            write_field(field::STREAM_START_BYTES, &self.stream_start_bytes);
+            write_field(field::INNER_STREAM_ID, &self.inner_stream_id.to_le_bytes());
+        }
+        write_field(field::END, END_OF_HEADER);
+        Ok(out)
+    }
+}
@@ -0,0 +1,275 @@
+// Master key: the components it is built from and the keys derived from it
+//
+// Each static component (password, key file) contributes a 32-byte hash, and their
+// concatenation is hashed into the composite key the KDF transforms. KeePassXC also
+// accepts challenge-response components: a hardware key answers a challenge that
+// changes with every save. In KDBX 4 the challenge is the KDF seed and the hashed
+// answers join the composite key; in KDBX 3 it is the master seed and they join the
+// final cipher key. Without such components both formulas reduce to the plain ones.
+// Opening and saving only see `MasterKey`, so a new kind of component (a YubiKey slot
+// behind `HmacSha1Slot`) needs no change to the unlock flow.
+use super::crypto::{sha256, Key32};
+use super::header::Header;
+use super::xml::{self, Element};
+use crate::error::{AppError, AppResult};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use std::sync::Arc;
+use zeroize::{Zeroize, Zeroizing};
+
+// Challenges KeePassXC sends to HMAC-SHA1 slots are padded to this length
+const HMAC_SHA1_BLOCK: usize = 64;
+
+/// One part of a master key
+pub trait KeyComponent: Send + Sync {
+    /// Shown in error messages, e.g. "YubiKey 5 (slot 2)"
+    fn name(&self) -> String;
+
+    /// Key material for `challenge`; static components ignore it and return their hash
+    fn key(&self, challenge: &[u8]) -> AppResult<Zeroizing<Vec<u8>>>;
+
+    /// Whether `key` answers the challenge rather than returning a fixed hash
+    fn is_challenge_response(&self) -> bool {
+        false
+    }
+}
+
+/// Master key components as sent by the frontend
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Credentials {
+    pub password: Option<String>,
+    /// Raw contents of the key file
+    pub key_file: Option<Vec<u8>>,
+    /// Hardware keys, hashed after the password and key file in this order
+    #[serde(skip)]
+    pub challenge_response: Vec<Arc<dyn KeyComponent>>,
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        self.password.zeroize();
+        self.key_file.zeroize();
+    }
+}
+
+impl Credentials {
+    /// Components of the master key, in the order KeePass hashes them. Fails with a
+    /// message the unlock form can show when none is given or the key file is unusable
+    pub fn master_key(&self) -> AppResult<MasterKey> {
+        let mut components: Vec<Arc<dyn KeyComponent>> = Vec::new();
+        if let Some(password) = &self.password {
+            components.push(Arc::new(StaticKey {
+                name: "password",
+                key: Zeroizing::new(sha256(&[password.as_bytes()])),
+            }));
+        }
+        if let Some(key_file) = &self.key_file {
+            components.push(Arc::new(StaticKey {
+                name: "key file",
+                key: key_file_key(key_file)?,
+            }));
+        }
+        components.extend(self.challenge_response.iter().cloned());
+        if components.is_empty() {
+            return Err(AppError::invalid_input(
+                "Enter a password or choose a key file",
+            ));
+        }
+        Ok(MasterKey { components })
+    }
+
+    /// Check credentials about to become a vault's master key. Stricter than unlocking:
+    /// an empty password only counts with another component, since KeePass clients
+    /// would still ask for it
+    pub fn validate_new(&self) -> AppResult<()> {
+        let master_key = self.master_key()?;
+        if self.password.as_deref() == Some("") && master_key.components.len() == 1 {
+            return Err(AppError::invalid_input(
+                "The password is empty; enter one or add a key file",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Password or key file hash
+struct StaticKey {
+    name: &'static str,
+    key: Key32,
+}
+
+impl KeyComponent for StaticKey {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn key(&self, _challenge: &[u8]) -> AppResult<Zeroizing<Vec<u8>>> {
+        Ok(Zeroizing::new(self.key.to_vec()))
+    }
+}
+
+/// Validated master key components of one vault
+pub struct MasterKey {
+    components: Vec<Arc<dyn KeyComponent>>,
+}
+
+impl MasterKey {
+    /// SHA-256 over the answers of the challenge-response components, or nothing
+    /// without any
+    fn challenge(&self, challenge: &[u8]) -> AppResult<Vec<u8>> {
+        let mut answers = Vec::new();
+        for component in self.components.iter().filter(|c| c.is_challenge_response()) {
+            let answer = component.key(challenge).map_err(|e| {
+                AppError::new(e.code, format!("{}: {}", component.name(), e.message))
+            })?;
+            answers.push(answer);
+        }
+        if answers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let slices: Vec<&[u8]> = answers.iter().map(|a| a.as_slice()).collect();
+        Ok(sha256(&slices).to_vec())
+    }
+
+    /// Key the KDF transforms
+    pub(super) fn composite(&self, header: &Header) -> AppResult<Key32> {
+        let mut parts = Vec::new();
+        for component in self
+            .components
+            .iter()
+            .filter(|c| !c.is_challenge_response())
+        {
+            parts.push(component.key(&[])?);
+        }
+        if header.is_v4() {
+            let seed = header.kdf.bytes("S")?;
+            parts.push(Zeroizing::new(self.challenge(seed)?));
+        }
+        let slices: Vec<&[u8]> = parts.iter().map(|p| p.as_slice()).collect();
+        Ok(Zeroizing::new(sha256(&slices)))
+    }
+
+    /// Key of the payload cipher, from the transformed composite key
+    pub(super) fn cipher_key(&self, header: &Header, transformed: &Key32) -> AppResult<Key32> {
+        let answer = if header.is_v4() {
+            Vec::new()
+        } else {
+            self.challenge(&header.master_seed)?
+        };
+        Ok(Zeroizing::new(sha256(&[
+            &header.master_seed,
+            &answer,
+            transformed.as_slice(),
+        ])))
+    }
+}
+
+/// A device slot computing HMAC-SHA1 with a secret it keeps, such as a YubiKey
+/// configured for challenge-response
+pub trait HmacSha1Slot: Send + Sync {
+    /// Shown in error messages
+    fn name(&self) -> String;
+
+    /// HMAC-SHA1 of `challenge`
+    fn hmac_sha1(&self, challenge: &[u8; HMAC_SHA1_BLOCK]) -> AppResult<[u8; 20]>;
+}
+
+/// Challenge-response component answered by an HMAC-SHA1 slot, compatible with
+/// KeePassXC
+pub struct HmacSha1ChallengeResponse<S>(pub S);
+
+impl<S: HmacSha1Slot> KeyComponent for HmacSha1ChallengeResponse<S> {
+    fn name(&self) -> String {
+        self.0.name()
+    }
+
+    fn key(&self, challenge: &[u8]) -> AppResult<Zeroizing<Vec<u8>>> {
+        // KeePassXC pads the challenge PKCS#7-style to a full block; slots configured
+        // for variable-length input strip the padding again
+        let padding = HMAC_SHA1_BLOCK
+            .checked_sub(challenge.len())
+            .filter(|&p| p > 0)
+            .ok_or_else(|| {
+                AppError::unsupported("The vault's seed is too long for a challenge-response key")
+            })?;
+        let mut block = Zeroizing::new([padding as u8; HMAC_SHA1_BLOCK]);
+        block[..challenge.len()].copy_from_slice(challenge);
+        let answer = Zeroizing::new(self.0.hmac_sha1(&block)?);
+        Ok(Zeroizing::new(answer.to_vec()))
+    }
+
+    fn is_challenge_response(&self) -> bool {
+        true
+    }
+}
+
+/// 32-byte key derived from a key file (XML v1/v2, raw 32 bytes, 64 hex chars or any
+/// other file hashed with SHA-256)
+pub(super) fn key_file_key(data: &[u8]) -> AppResult<Key32> {
+    if data.is_empty() {
+        return Err(AppError::invalid_input("The key file is empty"));
+    }
+    if let Ok(document) = xml::parse_document(data, None) {
+        if document.name == "KeyFile" {
+            return xml_key_file_key(&document);
+        }
+    }
+    if data.len() == 32 {
+        let mut key = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(data);
+        return Ok(key);
+    }
+    if data.len() == 64 {
+        if let Some(bytes) = std::str::from_utf8(data).ok().and_then(decode_hex) {
+            let bytes = Zeroizing::new(bytes);
+            let mut key = Zeroizing::new([0u8; 32]);
+            key.copy_from_slice(&bytes);
+            return Ok(key);
+        }
+    }
+    Ok(Zeroizing::new(sha256(&[data])))
+}
+
+fn xml_key_file_key(document: &Element) -> AppResult<Key32> {
+    let invalid = || AppError::invalid_input("Invalid key file");
+    let version = document
+        .child("Meta")
+        .and_then(|m| m.child_text("Version"))
+        .unwrap_or("1.0");
+    let data = document
+        .child("Key")
+        .and_then(|k| k.child("Data"))
+        .ok_or_else(invalid)?;
+
+    let bytes = Zeroizing::new(if version.starts_with('2') {
+        let hex: String = data.text.split_whitespace().collect();
+        let bytes = decode_hex(&hex).ok_or_else(invalid)?;
+        if let Some(hash) = data.attr("Hash") {
+            let expected = decode_hex(hash).ok_or_else(invalid)?;
+            if sha256(&[&bytes])[..4] != expected[..] {
+                return Err(AppError::invalid_input("Key file checksum mismatch"));
+            }
+        }
+        bytes
+    } else {
+        BASE64.decode(data.text.trim()).map_err(|_| invalid())?
+    });
+    if bytes.len() != 32 {
+        return Err(invalid());
+    }
+    let mut key = Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
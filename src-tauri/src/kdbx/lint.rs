@@ -0,0 +1,363 @@
+// Compatibility lint for values other KeePass clients reject or misread
+//
+// kdbxweb (and older KeedaVault versions) can write values that KeePassXC refuses to
+// load ("Invalid EnableSearching value", "Invalid bool value") or that KeePass 2 reads
+// differently: empty or `null` booleans, unknown tri-states, icon ids outside the
+// standard set, colors that are not `#RRGGBB` and custom icons that no longer exist.
+// Every issue has an auto-fix that keeps the meaning the value had in KeedaVault: an
+// unreadable tri-state becomes `null` (inherit from the parent group) instead of being
+// removed, and an unreadable boolean becomes the default KeePass assumes when it is
+// missing.
+use super::model::{Entry, Group, Node};
+use super::verify::Severity;
+use super::xml::Element;
+use super::Database;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+// KeePass ships icons 0-68; anything else has no image in other clients
+const STANDARD_ICONS: RangeInclusive<i64> = 0..=68;
+const ENTRY_ICON: &str = "0";
+const GROUP_ICON: &str = "48";
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintIssue {
+    /// Errors make KeePassXC refuse the file; warnings are read differently or dropped
+    pub severity: Severity,
+    /// Rule that produced the issue, e.g. `triState` or `iconId`
+    pub check: &'static str,
+    pub message: String,
+    /// What the auto-fix does
+    pub fix: String,
+    /// Base64 UUID of the group or entry concerned, `None` for database settings
+    pub uuid: Option<String>,
+    pub fixed: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintReport {
+    pub issues: Vec<LintIssue>,
+    /// Issues fixed in the database
+    pub fixed: usize,
+}
+
+/// Scan `database` for compatibility issues; with `fix`, repair them in place
+pub fn lint(database: &mut Database, fix: bool) -> LintReport {
+    let custom_icons = database
+        .meta
+        .child("CustomIcons")
+        .map(|icons| {
+            icons
+                .children_named("Icon")
+                .filter_map(|icon| icon.child_text("UUID"))
+                .map(|uuid| uuid.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut linter = Linter {
+        report: LintReport::default(),
+        fix,
+        custom_icons,
+    };
+    linter.meta(&mut database.meta);
+    linter.group(&mut database.root, "");
+    linter.report.fixed = linter.report.issues.iter().filter(|i| i.fixed).count();
+    linter.report
+}
+
+/// `AutoType/Enabled`, `Times/Expires`; other values are named on their own
+fn display_name(parent: &Element, name: &str) -> String {
+    match parent.name.as_str() {
+        "AutoType" | "Times" => format!("{}/{}", parent.name, name),
+        _ => name.to_string(),
+    }
+}
+
+/// Where a value lives, for messages
+struct Location<'a> {
+    label: &'a str,
+    uuid: Option<&'a str>,
+}
+
+struct Linter {
+    report: LintReport,
+    fix: bool,
+    custom_icons: HashSet<String>,
+}
+
+impl Linter {
+    fn add(
+        &mut self,
+        severity: Severity,
+        check: &'static str,
+        at: &Location,
+        message: String,
+        fix: String,
+    ) {
+        self.report.issues.push(LintIssue {
+            severity,
+            check,
+            message: format!("{}: {}", at.label, message),
+            fix,
+            uuid: at.uuid.map(str::to_string),
+            fixed: self.fix,
+        });
+    }
+
+    fn meta(&mut self, meta: &mut Element) {
+        let at = Location {
+            label: "Database settings",
+            uuid: None,
+        };
+        self.boolean(meta, "RecycleBinEnabled", true, &at);
+        self.integer(meta, "HistoryMaxItems", -1..=i64::from(i32::MAX), "10", &at);
+        self.integer(meta, "HistoryMaxSize", -1..=i64::MAX, "6291456", &at);
+        self.integer(
+            meta,
+            "MaintenanceHistoryDays",
+            0..=i64::from(i32::MAX),
+            "365",
+            &at,
+        );
+        if let Some(protection) = meta.child_mut("MemoryProtection") {
+            for (name, default) in [
+                ("ProtectTitle", false),
+                ("ProtectUserName", false),
+                ("ProtectPassword", true),
+                ("ProtectURL", false),
+                ("ProtectNotes", false),
+            ] {
+                self.boolean(protection, name, default, &at);
+            }
+        }
+    }
+
+    fn group(&mut self, group: &mut Group, parent_path: &str) {
+        let path = if parent_path.is_empty() {
+            group.name().to_string()
+        } else {
+            format!("{}/{}", parent_path, group.name())
+        };
+        let label = format!("Group \"{}\"", path);
+        let uuid = group.uuid().to_string();
+        let at = Location {
+            label: &label,
+            uuid: Some(&uuid),
+        };
+        let element = &mut group.element;
+        self.boolean(element, "IsExpanded", true, &at);
+        self.tri_state(element, "EnableAutoType", &at);
+        self.tri_state(element, "EnableSearching", &at);
+        self.common(element, GROUP_ICON, &at);
+
+        for entry in &mut group.entries {
+            self.entry(entry, &path);
+        }
+        for child in &mut group.groups {
+            self.group(child, &path);
+        }
+    }
+
+    fn entry(&mut self, entry: &mut Entry, path: &str) {
+        let title = Some(entry.title())
+            .filter(|t| !t.is_empty())
+            .unwrap_or("(untitled)");
+        let label = format!("Entry \"{}\" in \"{}\"", title, path);
+        let history_label = format!("{} (history)", label);
+        let uuid = entry.uuid().to_string();
+        self.entry_fields(
+            &mut entry.element,
+            &Location {
+                label: &label,
+                uuid: Some(&uuid),
+            },
+        );
+        for item in &mut entry.history {
+            self.entry_fields(
+                item,
+                &Location {
+                    label: &history_label,
+                    uuid: Some(&uuid),
+                },
+            );
+        }
+    }
+
+    /// Entry or history item
+    fn entry_fields(&mut self, element: &mut Element, at: &Location) {
+        self.common(element, ENTRY_ICON, at);
+        for name in ["ForegroundColor", "BackgroundColor"] {
+            self.color(element, name, at);
+        }
+        if let Some(auto_type) = element.child_mut("AutoType") {
+            self.boolean(auto_type, "Enabled", true, at);
+            self.integer(auto_type, "DataTransferObfuscation", 0..=1, "0", at);
+        }
+    }
+
+    /// Values groups and entries share: icons and `Times`
+    fn common(&mut self, element: &mut Element, default_icon: &str, at: &Location) {
+        self.integer(element, "IconID", STANDARD_ICONS, default_icon, at);
+        self.custom_icon(element, at);
+        if let Some(times) = element.child_mut("Times") {
+            self.boolean(times, "Expires", false, at);
+            self.integer(times, "UsageCount", 0..=i64::from(i32::MAX), "0", at);
+        }
+    }
+
+    /// `True`/`False` in any case. `1`/`0` keep their meaning; anything else becomes the
+    /// value KeePass assumes when the element is missing.
+    fn boolean(&mut self, parent: &mut Element, name: &str, default: bool, at: &Location) {
+        let field = display_name(parent, name);
+        let Some(child) = parent.child_mut(name) else {
+            return;
+        };
+        let text = child.text.trim();
+        if text.eq_ignore_ascii_case("true") || text.eq_ignore_ascii_case("false") {
+            return;
+        }
+        let value = match text {
+            "1" => true,
+            "0" => false,
+            _ => default,
+        };
+        let canonical = if value { "True" } else { "False" };
+        let message = format!("{} is \"{}\", which is not a boolean", field, text);
+        if self.fix {
+            child.text = canonical.to_string();
+        }
+        self.add(
+            Severity::Error,
+            "boolean",
+            at,
+            message,
+            format!("Set {} to {}", field, canonical),
+        );
+    }
+
+    /// Group setting that is `true`, `false` or `null` (inherit from the parent group).
+    /// Unreadable values become `null`, which is what the group did in KeedaVault.
+    fn tri_state(&mut self, parent: &mut Element, name: &str, at: &Location) {
+        let field = display_name(parent, name);
+        let Some(child) = parent.child_mut(name) else {
+            return;
+        };
+        let text = child.text.trim();
+        let valid = ["true", "false", "null"]
+            .iter()
+            .any(|v| text.eq_ignore_ascii_case(v));
+        if valid {
+            return;
+        }
+        let (canonical, meaning) = match text {
+            "1" => ("true", "enabled"),
+            "0" => ("false", "disabled"),
+            _ => ("null", "inherit from the parent group"),
+        };
+        let message = format!("{} is \"{}\", which KeePassXC rejects", field, text);
+        if self.fix {
+            child.text = canonical.to_string();
+        }
+        self.add(
+            Severity::Error,
+            "triState",
+            at,
+            message,
+            format!("Set {} to {} ({})", field, canonical, meaning),
+        );
+    }
+
+    fn integer(
+        &mut self,
+        parent: &mut Element,
+        name: &str,
+        range: RangeInclusive<i64>,
+        default: &str,
+        at: &Location,
+    ) {
+        let field = display_name(parent, name);
+        let Some(child) = parent.child_mut(name) else {
+            return;
+        };
+        let text = child.text.trim();
+        let (severity, check, message) = match text.parse::<i64>() {
+            Ok(value) if range.contains(&value) => return,
+            Ok(value) => (
+                Severity::Warning,
+                if name == "IconID" { "iconId" } else { "range" },
+                format!(
+                    "{} is {}, outside {}-{}",
+                    field,
+                    value,
+                    range.start(),
+                    range.end()
+                ),
+            ),
+            Err(_) => (
+                Severity::Error,
+                "number",
+                format!("{} is \"{}\", which is not a number", field, text),
+            ),
+        };
+        if self.fix {
+            child.text = default.to_string();
+        }
+        self.add(
+            severity,
+            check,
+            at,
+            message,
+            format!("Set {} to {}", field, default),
+        );
+    }
+
+    /// Empty or `#RRGGBB`; KeePassXC drops anything else
+    fn color(&mut self, parent: &mut Element, name: &str, at: &Location) {
+        let field = display_name(parent, name);
+        let Some(child) = parent.child_mut(name) else {
+            return;
+        };
+        let text = child.text.trim();
+        let valid = text.is_empty()
+            || (text.len() == 7
+                && text.starts_with('#')
+                && text[1..].chars().all(|c| c.is_ascii_hexdigit()));
+        if valid {
+            return;
+        }
+        let message = format!("{} is \"{}\", which is not a #RRGGBB color", field, text);
+        if self.fix {
+            child.text.clear();
+        }
+        self.add(
+            Severity::Warning,
+            "color",
+            at,
+            message,
+            format!("Clear {}", field),
+        );
+    }
+
+    fn custom_icon(&mut self, element: &mut Element, at: &Location) {
+        let Some(uuid) = element.child_text("CustomIconUUID").map(str::trim) else {
+            return;
+        };
+        if uuid.is_empty() || self.custom_icons.contains(uuid) {
+            return;
+        }
+        let message = format!("Custom icon {} does not exist in this database", uuid);
+        if self.fix {
+            element.remove_children("CustomIconUUID");
+        }
+        self.add(
+            Severity::Warning,
+            "customIcon",
+            at,
+            message,
+            "Use the standard icon".to_string(),
+        );
+    }
+}
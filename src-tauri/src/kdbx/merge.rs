@@ -0,0 +1,353 @@
+// Database merge with KeePassXC "Synchronize" semantics
+//
+// Groups and entries are matched by UUID wherever they live in the tree. For each
+// pair the copy with the newer LastModificationTime wins and the other one is kept
+// in the entry history; LocationChanged decides which side's placement wins. Deletions
+// from both sides are applied afterwards unless the object changed after it was deleted.
+use super::model::{Entry, Group, Node};
+use super::xml::Element;
+use super::{Database, DeletedObject};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeSummary {
+    pub entries_added: u32,
+    pub entries_updated: u32,
+    pub entries_moved: u32,
+    pub entries_deleted: u32,
+    pub groups_added: u32,
+    pub groups_updated: u32,
+    pub groups_moved: u32,
+    pub groups_deleted: u32,
+    pub icons_added: u32,
+    /// Human-readable log of every change, in the order they were applied
+    pub changes: Vec<String>,
+}
+
+impl MergeSummary {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Merge `source` into `target`
+pub fn merge(target: &mut Database, mut source: Database) -> MergeSummary {
+    import_binaries(target, &mut source);
+
+    let mut merger = Merger {
+        history_max_items: target.history_max_items(),
+        target,
+        summary: MergeSummary::default(),
+    };
+    let target_root = merger.target.root.uuid().to_string();
+    merger.merge_group(&source.root, &target_root);
+    merger.merge_deletions(&source.deleted_objects);
+    merger.merge_meta(&source.meta);
+    merger.summary
+}
+
+/// Point the source's attachment references at (possibly new) slots of the target pool
+fn import_binaries(target: &mut Database, source: &mut Database) {
+    let remap: Vec<usize> = source
+        .binaries
+        .iter()
+        .map(|binary| target.intern_binary(binary))
+        .collect();
+    source.root.for_each_entry_mut(&mut |entry| {
+        entry.for_each_binary_ref(&mut |reference| {
+            if let Some(index) = reference.parse::<usize>().ok().and_then(|i| remap.get(i)) {
+                *reference = index.to_string();
+            }
+        })
+    });
+}
+
+/// Times are compared at second precision, as KDBX 4 stores them
+fn seconds(time: Option<DateTime<Utc>>) -> Option<i64> {
+    time.map(|t| t.timestamp())
+}
+
+fn label(title: &str, uuid: &str) -> String {
+    if title.is_empty() {
+        format!("[{}]", uuid)
+    } else {
+        format!("{} [{}]", title, uuid)
+    }
+}
+
+/// Copy the `Times/LocationChanged` value of `from` onto `to`
+fn copy_location_changed(to: &mut Element, from: &Element) {
+    if let Some(time) = from.child("Times").and_then(|t| t.child("LocationChanged")) {
+        to.child_or_insert("Times")
+            .set_child_text("LocationChanged", time.text.clone());
+    }
+}
+
+/// Combine history items, dropping duplicates (same modification time) and versions
+/// equal in time to the current one, oldest first and trimmed to `max_items`
+fn merge_history(
+    current: &Element,
+    lists: &[&[Element]],
+    max_items: Option<usize>,
+) -> Vec<Element> {
+    let current_time = seconds(current.last_modified());
+    let mut merged: Vec<Element> = Vec::new();
+    for item in lists.iter().flat_map(|list| list.iter()) {
+        let time = seconds(item.last_modified());
+        if time == current_time || merged.iter().any(|m| seconds(m.last_modified()) == time) {
+            continue;
+        }
+        merged.push(item.clone());
+    }
+    merged.sort_by_key(|item| seconds(item.last_modified()));
+    if let Some(max) = max_items {
+        let excess = merged.len().saturating_sub(max);
+        merged.drain(..excess);
+    }
+    merged
+}
+
+struct Merger<'a> {
+    target: &'a mut Database,
+    history_max_items: Option<usize>,
+    summary: MergeSummary,
+}
+
+impl Merger<'_> {
+    fn log(&mut self, change: String) {
+        log::debug!("Merge: {}", change);
+        self.summary.changes.push(change);
+    }
+
+    fn group_mut(&mut self, uuid: &str) -> &mut Group {
+        self.target
+            .root
+            .find_group_mut(uuid)
+            .expect("merge target group exists")
+    }
+
+    /// Merge the children of `source` into target group `target_uuid`
+    fn merge_group(&mut self, source: &Group, target_uuid: &str) {
+        for entry in &source.entries {
+            self.merge_entry(entry, target_uuid);
+        }
+
+        for child in &source.groups {
+            let uuid = child.uuid();
+            match self.target.root.find_group(uuid) {
+                None => {
+                    self.group_mut(target_uuid).groups.push(Group {
+                        element: child.element.clone(),
+                        entries: Vec::new(),
+                        groups: Vec::new(),
+                    });
+                    self.summary.groups_added += 1;
+                    self.log(format!("Added group {}", label(child.name(), uuid)));
+                }
+                Some(existing) => {
+                    let moved_here = seconds(child.location_changed())
+                        > seconds(existing.location_changed())
+                        && !existing.is_ancestor_of(target_uuid)
+                        && self.target.root.parent_of_group(uuid) != Some(target_uuid);
+                    let newer = seconds(child.last_modified()) > seconds(existing.last_modified());
+
+                    if moved_here {
+                        if let Some(mut group) = self.target.root.take_group(uuid) {
+                            copy_location_changed(&mut group.element, &child.element);
+                            self.group_mut(target_uuid).groups.push(group);
+                            self.summary.groups_moved += 1;
+                            self.log(format!("Moved group {}", label(child.name(), uuid)));
+                        }
+                    }
+                    if newer {
+                        let group = self.group_mut(uuid);
+                        let location = group.element.clone();
+                        group.element = child.element.clone();
+                        if !moved_here {
+                            copy_location_changed(&mut group.element, &location);
+                        }
+                        self.summary.groups_updated += 1;
+                        self.log(format!("Updated group {}", label(child.name(), uuid)));
+                    }
+                }
+            }
+            self.merge_group(child, uuid);
+        }
+    }
+
+    fn merge_entry(&mut self, source: &Entry, target_uuid: &str) {
+        let uuid = source.uuid();
+        let Some(existing) = self.target.root.find_entry(uuid) else {
+            self.group_mut(target_uuid).entries.push(source.clone());
+            self.summary.entries_added += 1;
+            self.log(format!("Added entry {}", label(source.title(), uuid)));
+            return;
+        };
+
+        let moved_here = seconds(source.location_changed()) > seconds(existing.location_changed())
+            && self.target.root.parent_of_entry(uuid) != Some(target_uuid);
+        if moved_here {
+            if let Some(mut entry) = self.target.root.take_entry(uuid) {
+                copy_location_changed(&mut entry.element, &source.element);
+                self.group_mut(target_uuid).entries.push(entry);
+                self.summary.entries_moved += 1;
+                self.log(format!("Moved entry {}", label(source.title(), uuid)));
+            }
+        }
+
+        let max_items = self.history_max_items;
+        let target = self
+            .target
+            .root
+            .find_entry_mut(uuid)
+            .expect("merge target entry exists");
+        if target.element == source.element && target.history == source.history {
+            return;
+        }
+
+        if seconds(source.last_modified()) > seconds(target.last_modified()) {
+            // Source wins; the target's current version becomes history
+            let mut element = source.element.clone();
+            copy_location_changed(&mut element, &target.element);
+            let history = merge_history(
+                &element,
+                &[
+                    &source.history,
+                    &target.history,
+                    std::slice::from_ref(&target.element),
+                ],
+                max_items,
+            );
+            *target = Entry { element, history };
+            self.summary.entries_updated += 1;
+            self.log(format!("Updated entry {}", label(source.title(), uuid)));
+        } else {
+            // Target wins (or ties); keep its data and absorb the source's versions
+            let history = merge_history(
+                &target.element,
+                &[
+                    &target.history,
+                    &source.history,
+                    std::slice::from_ref(&source.element),
+                ],
+                max_items,
+            );
+            if history != target.history {
+                target.history = history;
+                let title = target.title().to_string();
+                self.log(format!("Merged history of entry {}", label(&title, uuid)));
+            }
+        }
+    }
+
+    /// Union both deletion lists and remove objects that were not modified after
+    /// being deleted; groups are only removed once they are empty
+    fn merge_deletions(&mut self, source: &[DeletedObject]) {
+        let mut deletions: HashMap<String, DateTime<Utc>> = HashMap::new();
+        for object in self.target.deleted_objects.iter().chain(source) {
+            let time = deletions
+                .entry(object.uuid.clone())
+                .or_insert(object.deleted_at);
+            *time = (*time).max(object.deleted_at);
+        }
+
+        let root_uuid = self.target.root.uuid().to_string();
+        let mut revived = Vec::new();
+        for (uuid, deleted_at) in &deletions {
+            let Some(entry) = self.target.root.find_entry(uuid) else {
+                continue;
+            };
+            if seconds(entry.last_modified()) < Some(deleted_at.timestamp()) {
+                let entry = self.target.root.take_entry(uuid).expect("entry exists");
+                self.summary.entries_deleted += 1;
+                self.log(format!("Deleted entry {}", label(entry.title(), uuid)));
+            } else {
+                revived.push(uuid.clone());
+            }
+        }
+
+        // Children before parents so a group emptied by the deletions can go too
+        let mut groups = Vec::new();
+        collect_groups_post_order(&self.target.root, &mut groups);
+        for uuid in groups {
+            let Some(deleted_at) = deletions.get(&uuid) else {
+                continue;
+            };
+            let group = self.target.root.find_group(&uuid).expect("group exists");
+            let deletable = uuid != root_uuid
+                && group.entries.is_empty()
+                && group.groups.is_empty()
+                && seconds(group.last_modified()) < Some(deleted_at.timestamp());
+            if deletable {
+                let group = self.target.root.take_group(&uuid).expect("group exists");
+                self.summary.groups_deleted += 1;
+                self.log(format!("Deleted group {}", label(group.name(), &uuid)));
+            } else {
+                revived.push(uuid);
+            }
+        }
+
+        for uuid in revived {
+            deletions.remove(&uuid);
+        }
+        let mut deleted_objects: Vec<DeletedObject> = deletions
+            .into_iter()
+            .map(|(uuid, deleted_at)| DeletedObject { uuid, deleted_at })
+            .collect();
+        deleted_objects.sort_by(|a, b| a.deleted_at.cmp(&b.deleted_at).then(a.uuid.cmp(&b.uuid)));
+        self.target.deleted_objects = deleted_objects;
+    }
+
+    /// Custom icons are added by UUID; custom data items are added, or replaced when
+    /// the source item is newer
+    fn merge_meta(&mut self, source: &Element) {
+        if let Some(icons) = source.child("CustomIcons") {
+            for icon in icons.children_named("Icon") {
+                let uuid = icon.child_text("UUID").unwrap_or_default();
+                let target_icons = self.target.meta.child_or_insert("CustomIcons");
+                if target_icons
+                    .children_named("Icon")
+                    .any(|i| i.child_text("UUID") == Some(uuid))
+                {
+                    continue;
+                }
+                target_icons.children.push(icon.clone());
+                self.summary.icons_added += 1;
+                self.log(format!("Added custom icon [{}]", uuid));
+            }
+        }
+
+        if let Some(data) = source.child("CustomData") {
+            for item in data.children_named("Item") {
+                let key = item.child_text("Key").unwrap_or_default().to_string();
+                let target_data = self.target.meta.child_or_insert("CustomData");
+                let existing = target_data
+                    .children
+                    .iter_mut()
+                    .find(|i| i.name == "Item" && i.child_text("Key") == Some(key.as_str()));
+                match existing {
+                    None => target_data.children.push(item.clone()),
+                    Some(existing)
+                        if existing != item
+                            && seconds(item.child_time("LastModificationTime"))
+                                > seconds(existing.child_time("LastModificationTime")) =>
+                    {
+                        *existing = item.clone()
+                    }
+                    Some(_) => continue,
+                }
+                self.log(format!("Merged custom data {}", key));
+            }
+        }
+    }
+}
+
+fn collect_groups_post_order(group: &Group, out: &mut Vec<String>) {
+    for child in &group.groups {
+        collect_groups_post_order(child, out);
+    }
+    out.push(group.uuid().to_string());
+}
@@ -0,0 +1,569 @@
+// Backend KDBX 3.1 / 4.x codec
+//
+// The frontend edits vaults with kdbxweb. Everything the backend does to a vault file
+// goes through this reader and writer instead: merging, diffing, verification, linting
+// and repair, re-encryption and re-keying, and formats kdbxweb cannot read (Twofish).
+// Decoding keeps the XML as a lossless DOM so a load/save cycle preserves everything
+// other KeePass clients wrote.
+mod crypto;
+pub mod diff;
+#[cfg(fuzzing)]
+pub mod fuzz;
+mod header;
+pub mod key;
+pub mod lint;
+pub mod merge;
+mod model;
+pub mod salvage;
+pub mod settings;
+#[cfg(test)]
+pub(crate) mod tests;
+pub mod verify;
+mod xml;
+
+use crate::error::{AppError, AppResult, ErrorCode};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use crypto::{sha256, sha512, Cipher, InnerStream, INNER_STREAM_CHACHA20};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use header::{Header, Reader, VarValue};
+use model::{Group, Node};
+use std::io::{Read, Write};
+use xml::Element;
+use zeroize::Zeroizing;
+
+pub use key::Credentials;
+
+// Size of the HMAC blocks written for KDBX 4 (the format allows any size)
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+// Largest decompressed payload or attachment; a few kilobytes of gzip can otherwise
+// expand into gigabytes. Fuzzing runs with far less memory
+#[cfg(not(fuzzing))]
+const MAX_DECOMPRESSED_SIZE: u64 = 1024 * 1024 * 1024;
+#[cfg(fuzzing)]
+const MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
+
+mod inner_field {
+    pub const END: u8 = 0;
+    pub const STREAM_ID: u8 = 1;
+    pub const STREAM_KEY: u8 = 2;
+    pub const BINARY: u8 = 3;
+}
+
+pub(crate) fn corrupt(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorCode::Corrupt, message)
+}
+
+fn wrong_credentials() -> AppError {
+    AppError::new(ErrorCode::WrongCredentials, "Invalid password or key file")
+}
+
+fn gunzip(data: &[u8]) -> AppResult<Vec<u8>> {
+    gunzip_at_most(data, MAX_DECOMPRESSED_SIZE)
+}
+
+/// Decompress `data`, failing once the output would exceed `limit` bytes
+fn gunzip_at_most(data: &[u8], limit: u64) -> AppResult<Vec<u8>> {
+    let mut out = Vec::new();
+    GzDecoder::new(data)
+        .take(limit + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| corrupt(format!("Decompression failed: {}", e)))?;
+    if out.len() as u64 > limit {
+        return Err(corrupt("The decompressed data is implausibly large"));
+    }
+    Ok(out)
+}
+
+fn gzip(data: &[u8]) -> AppResult<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+/// Attachment stored once in the binary pool and referenced by index from entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binary {
+    pub data: Vec<u8>,
+    pub protected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletedObject {
+    pub uuid: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+pub struct Database {
+    header: Header,
+    /// `KeePassFile/Meta`, without the KDBX 3 binary pool
+    pub meta: Element,
+    pub root: Group,
+    pub deleted_objects: Vec<DeletedObject>,
+    pub binaries: Vec<Binary>,
+}
+
+/// Decrypted payload of either format version
+struct Payload {
+    xml: Vec<u8>,
+    stream: InnerStream,
+    binaries: Vec<Binary>,
+}
+
+impl Database {
+    pub fn open(data: &[u8], credentials: &Credentials) -> AppResult<Self> {
+        let (header, header_len) = Header::parse(data)?;
+        let master_key = credentials.master_key()?;
+        let composite = master_key.composite(&header)?;
+        let transformed = crypto::transform_key(&composite, &header.kdf)?;
+        let cipher_key = master_key.cipher_key(&header, &transformed)?;
+
+        let payload = if header.is_v4() {
+            read_v4(data, header_len, &header, &cipher_key, &transformed)?
+        } else {
+            read_v3(&data[header_len..], &header, &cipher_key)?
+        };
+        Self::from_payload(header, payload)
+    }
+
+    /// Parse the decrypted XML and attachments into a database
+    fn from_payload(header: Header, payload: Payload) -> AppResult<Self> {
+        let Payload {
+            xml,
+            mut stream,
+            mut binaries,
+        } = payload;
+        let xml = Zeroizing::new(xml);
+        let mut document = xml::parse_document(&xml, Some(&mut stream))?;
+        if document.name != "KeePassFile" {
+            return Err(corrupt("Database XML has no KeePassFile element"));
+        }
+
+        let mut meta = document
+            .remove_children("Meta")
+            .pop()
+            .ok_or_else(|| corrupt("Database XML has no Meta element"))?;
+        let mut root = document
+            .remove_children("Root")
+            .pop()
+            .ok_or_else(|| corrupt("Database XML has no Root element"))?;
+        let mut root_group = root
+            .remove_children("Group")
+            .into_iter()
+            .next()
+            .map(Group::from_element)
+            .ok_or_else(|| corrupt("Database has no root group"))?;
+
+        if !header.is_v4() {
+            // KDBX 3 keeps attachments in Meta with IDs; map them onto pool indices
+            let mut ids = Vec::new();
+            for pool in meta.remove_children("Binaries") {
+                for binary in pool.children_named("Binary") {
+                    let mut data = BASE64
+                        .decode(binary.text.trim())
+                        .map_err(|_| corrupt("Invalid attachment data"))?;
+                    if binary
+                        .attr("Compressed")
+                        .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+                    {
+                        data = gunzip(&data)?;
+                    }
+                    ids.push(binary.attr("ID").unwrap_or_default().to_string());
+                    binaries.push(Binary {
+                        data,
+                        protected: false,
+                    });
+                }
+            }
+            root_group.for_each_entry_mut(&mut |entry| {
+                entry.for_each_binary_ref(&mut |reference| {
+                    if let Some(index) = ids.iter().position(|id| id == reference) {
+                        *reference = index.to_string();
+                    }
+                })
+            });
+        }
+
+        Ok(Self {
+            header,
+            meta,
+            root: root_group,
+            deleted_objects: deleted_objects(&root),
+            binaries,
+        })
+    }
+
+    /// Serialize with fresh seeds and IV, keeping the original format version,
+    /// cipher and KDF settings
+    pub fn save(&mut self, credentials: &Credentials) -> AppResult<Vec<u8>> {
+        self.compact_binaries();
+        let v4 = self.header.is_v4();
+
+        let mut header = self.header.clone();
+        header.master_seed = crypto::random_bytes::<32>()?.to_vec();
+        header.iv = if header.cipher == Cipher::ChaCha20 {
+            crypto::random_bytes::<12>()?.to_vec()
+        } else {
+            crypto::random_bytes::<16>()?.to_vec()
+        };
+        header
+            .kdf
+            .set("S", VarValue::Bytes(crypto::random_bytes::<32>()?.to_vec()));
+        let stream_key: Zeroizing<Vec<u8>> = if v4 {
+            header.inner_stream_id = INNER_STREAM_CHACHA20;
+            Zeroizing::new(crypto::random_bytes::<64>()?.to_vec())
+        } else {
+            header.protected_stream_key = crypto::random_bytes::<32>()?.to_vec();
+            header.stream_start_bytes = crypto::random_bytes::<32>()?.to_vec();
+            Zeroizing::new(header.protected_stream_key.clone())
+        };
+        let header_bytes = header.to_bytes()?;
+
+        let master_key = credentials.master_key()?;
+        let composite = master_key.composite(&header)?;
+        let transformed = crypto::transform_key(&composite, &header.kdf)?;
+        let cipher_key = master_key.cipher_key(&header, &transformed)?;
+
+        let document = self.to_document(v4, &header_bytes)?;
+        let mut stream = InnerStream::new(header.inner_stream_id, &stream_key)?;
+        let xml = Zeroizing::new(xml::write_document(&document, &mut stream));
+
+        let mut out = header_bytes.clone();
+        if v4 {
+            let mut inner = Zeroizing::new(Vec::new());
+            write_inner_field(
+                &mut inner,
+                inner_field::STREAM_ID,
+                &[&header.inner_stream_id.to_le_bytes()],
+            );
+            write_inner_field(&mut inner, inner_field::STREAM_KEY, &[&stream_key]);
+            for binary in &self.binaries {
+                write_inner_field(
+                    &mut inner,
+                    inner_field::BINARY,
+                    &[&[binary.protected as u8], &binary.data],
+                );
+            }
+            write_inner_field(&mut inner, inner_field::END, &[]);
+            inner.extend_from_slice(&xml);
+
+            let plain = if header.compression {
+                Zeroizing::new(gzip(&inner)?)
+            } else {
+                inner
+            };
+            let encrypted = header.cipher.encrypt(&cipher_key, &header.iv, &plain)?;
+
+            let hmac_key =
+                Zeroizing::new(sha512(&[&header.master_seed, transformed.as_slice(), &[1]]));
+            out.extend_from_slice(&sha256(&[&header_bytes]));
+            out.extend_from_slice(&crypto::hmac_sha256(
+                crypto::block_hmac_key(&hmac_key, u64::MAX).as_slice(),
+                &[&header_bytes],
+            ));
+            let chunks = encrypted.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..]));
+            for (index, block) in chunks.enumerate() {
+                let index = index as u64;
+                let len = (block.len() as u32).to_le_bytes();
+                out.extend_from_slice(&crypto::hmac_sha256(
+                    crypto::block_hmac_key(&hmac_key, index).as_slice(),
+                    &[&index.to_le_bytes(), &len, block],
+                ));
+                out.extend_from_slice(&len);
+                out.extend_from_slice(block);
+            }
+        } else {
+            let plain = if header.compression {
+                Zeroizing::new(gzip(&xml)?)
+            } else {
+                xml
+            };
+            let mut blocks = Zeroizing::new(header.stream_start_bytes.clone());
+            let chunks = plain.chunks(BLOCK_SIZE).chain(std::iter::once(&[][..]));
+            for (index, block) in chunks.enumerate() {
+                blocks.extend_from_slice(&(index as u32).to_le_bytes());
+                if block.is_empty() {
+                    blocks.extend_from_slice(&[0u8; 32]);
+                } else {
+                    blocks.extend_from_slice(&sha256(&[block]));
+                }
+                blocks.extend_from_slice(&(block.len() as u32).to_le_bytes());
+                blocks.extend_from_slice(block);
+            }
+            out.extend_from_slice(&header.cipher.encrypt(&cipher_key, &header.iv, &blocks)?);
+        }
+
+        self.header = header;
+        Ok(out)
+    }
+
+    /// Base64 UUID of the root group, the identity used for backups and sessions
+    pub fn identity(&self) -> &str {
+        self.root.uuid()
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.root.entry_count()
+    }
+
+    /// Record a master key change in `Meta/MasterKeyChanged`; the next `save` encrypts
+    /// with whatever credentials it is given
+    pub fn touch_master_key(&mut self) {
+        self.meta
+            .set_child_text("MasterKeyChanged", xml::format_time(Utc::now(), false));
+    }
+
+    /// `Meta/HistoryMaxItems`; negative values mean unlimited
+    pub fn history_max_items(&self) -> Option<usize> {
+        self.meta
+            .child_text("HistoryMaxItems")
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .and_then(|v| usize::try_from(v).ok())
+    }
+
+    /// Add `binary` to the pool (reusing an identical one) and return its index
+    pub fn intern_binary(&mut self, binary: &Binary) -> usize {
+        match self.binaries.iter().position(|b| b.data == binary.data) {
+            Some(index) => {
+                self.binaries[index].protected |= binary.protected;
+                index
+            }
+            None => {
+                self.binaries.push(binary.clone());
+                self.binaries.len() - 1
+            }
+        }
+    }
+
+    /// Drop attachments no entry refers to anymore and renumber the rest
+    fn compact_binaries(&mut self) {
+        let old = std::mem::take(&mut self.binaries);
+        let mut remap: Vec<Option<usize>> = vec![None; old.len()];
+        let mut pool: Vec<Binary> = Vec::new();
+        self.root.for_each_entry_mut(&mut |entry| {
+            entry.for_each_binary_ref(&mut |reference| {
+                let Some(index) = reference.parse::<usize>().ok().filter(|i| *i < old.len()) else {
+                    return;
+                };
+                let new_index = *remap[index].get_or_insert_with(|| {
+                    match pool.iter().position(|b| b.data == old[index].data) {
+                        Some(existing) => existing,
+                        None => {
+                            pool.push(old[index].clone());
+                            pool.len() - 1
+                        }
+                    }
+                });
+                *reference = new_index.to_string();
+            })
+        });
+        self.binaries = pool;
+    }
+
+    fn to_document(&self, v4: bool, header_bytes: &[u8]) -> AppResult<Element> {
+        let mut meta = self.meta.clone();
+        meta.remove_children("Binaries");
+        if v4 {
+            meta.remove_children("HeaderHash");
+        } else {
+            meta.set_child_text("HeaderHash", BASE64.encode(sha256(&[header_bytes])));
+            let mut pool = Element::new("Binaries");
+            for (index, binary) in self.binaries.iter().enumerate() {
+                let mut element = Element::new("Binary");
+                element.set_attr("ID", index.to_string());
+                if self.header.compression {
+                    element.set_attr("Compressed", "True");
+                    element.text = BASE64.encode(gzip(&binary.data)?);
+                } else {
+                    element.text = BASE64.encode(&binary.data);
+                }
+                pool.children.push(element);
+            }
+            meta.children.push(pool);
+        }
+
+        let mut deleted = Element::new("DeletedObjects");
+        for object in &self.deleted_objects {
+            let mut item = Element::new("DeletedObject");
+            item.children
+                .push(Element::with_text("UUID", object.uuid.clone()));
+            item.children.push(Element::with_text(
+                "DeletionTime",
+                xml::format_time(object.deleted_at, v4),
+            ));
+            deleted.children.push(item);
+        }
+
+        let mut root = Element::new("Root");
+        root.children.push(self.root.to_element());
+        root.children.push(deleted);
+
+        let mut document = Element::new("KeePassFile");
+        document.children.push(meta);
+        document.children.push(root);
+        xml::normalize_times(&mut document, v4);
+        Ok(document)
+    }
+}
+
+/// `Root/DeletedObjects`, skipping items without a UUID or time
+fn deleted_objects(root: &Element) -> Vec<DeletedObject> {
+    root.child("DeletedObjects")
+        .map(|list| {
+            list.children_named("DeletedObject")
+                .filter_map(|item| {
+                    Some(DeletedObject {
+                        uuid: item.child_text("UUID")?.to_string(),
+                        deleted_at: item.child_time("DeletionTime")?,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn write_inner_field(out: &mut Vec<u8>, id: u8, parts: &[&[u8]]) {
+    let len: usize = parts.iter().map(|p| p.len()).sum();
+    out.push(id);
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+}
+
+fn read_v4(
+    data: &[u8],
+    header_len: usize,
+    header: &Header,
+    cipher_key: &[u8; 32],
+    transformed: &[u8; 32],
+) -> AppResult<Payload> {
+    let header_bytes = &data[..header_len];
+    let mut reader = Reader::new(&data[header_len..]);
+    let stored_hash = reader.take(32)?;
+    let stored_hmac = reader.take(32)?;
+    if sha256(&[header_bytes])[..] != stored_hash[..] {
+        return Err(corrupt("Header checksum mismatch"));
+    }
+    let hmac_key = Zeroizing::new(sha512(&[&header.master_seed, transformed, &[1]]));
+    if !crypto::verify_hmac(
+        crypto::block_hmac_key(&hmac_key, u64::MAX).as_slice(),
+        &[header_bytes],
+        stored_hmac,
+    ) {
+        return Err(wrong_credentials());
+    }
+
+    let mut encrypted = Vec::new();
+    for index in 0u64.. {
+        let mac = reader.take(32)?;
+        let len_bytes = reader.take(4)?;
+        let len = u32::from_le_bytes(len_bytes.try_into().expect("4 bytes")) as usize;
+        let block = reader.take(len)?;
+        if !crypto::verify_hmac(
+            crypto::block_hmac_key(&hmac_key, index).as_slice(),
+            &[&index.to_le_bytes(), len_bytes, block],
+            mac,
+        ) {
+            return Err(corrupt(format!(
+                "Block {} failed its integrity check",
+                index
+            )));
+        }
+        if len == 0 {
+            break;
+        }
+        encrypted.extend_from_slice(block);
+    }
+
+    let plain = Zeroizing::new(header.cipher.decrypt(cipher_key, &header.iv, &encrypted)?);
+    let plain = if header.compression {
+        Zeroizing::new(gunzip(&plain)?)
+    } else {
+        plain
+    };
+    read_inner_header(&plain)
+}
+
+/// Split a decrypted KDBX 4 payload into its inner header and the XML
+fn read_inner_header(plain: &[u8]) -> AppResult<Payload> {
+    let mut reader = Reader::new(plain);
+    let mut stream_id = None;
+    let mut stream_key = Zeroizing::new(Vec::new());
+    let mut binaries = Vec::new();
+    loop {
+        let id = reader.u8()?;
+        let len = reader.u32()? as usize;
+        let value = reader.take(len)?;
+        match id {
+            inner_field::END => break,
+            inner_field::STREAM_ID => {
+                stream_id = Some(u32::from_le_bytes(
+                    value
+                        .try_into()
+                        .map_err(|_| corrupt("Invalid inner stream id"))?,
+                ))
+            }
+            inner_field::STREAM_KEY => stream_key = Zeroizing::new(value.to_vec()),
+            inner_field::BINARY => {
+                let (flags, data) = value
+                    .split_first()
+                    .ok_or_else(|| corrupt("Invalid attachment"))?;
+                binaries.push(Binary {
+                    data: data.to_vec(),
+                    protected: flags & 1 != 0,
+                });
+            }
+            _ => {}
+        }
+    }
+    let stream_id = stream_id.ok_or_else(|| corrupt("Inner stream id is missing"))?;
+    Ok(Payload {
+        xml: reader.remaining().to_vec(),
+        stream: InnerStream::new(stream_id, &stream_key)?,
+        binaries,
+    })
+}
+
+fn read_v3(encrypted: &[u8], header: &Header, cipher_key: &[u8; 32]) -> AppResult<Payload> {
+    // With CBC a wrong key almost always shows up as bad padding
+    let plain = Zeroizing::new(
+        header
+            .cipher
+            .decrypt(cipher_key, &header.iv, encrypted)
+            .map_err(|_| wrong_credentials())?,
+    );
+    if plain.len() < 32 || plain[..32] != header.stream_start_bytes[..] {
+        return Err(wrong_credentials());
+    }
+
+    let mut reader = Reader::new(&plain[32..]);
+    let mut content = Zeroizing::new(Vec::new());
+    loop {
+        let _index = reader.u32()?;
+        let hash = reader.take(32)?;
+        let len = reader.u32()? as usize;
+        if len == 0 {
+            break;
+        }
+        let block = reader.take(len)?;
+        if sha256(&[block])[..] != hash[..] {
+            return Err(corrupt("Block hash mismatch"));
+        }
+        content.extend_from_slice(block);
+    }
+
+    let xml = if header.compression {
+        gunzip(&content)?
+    } else {
+        content.to_vec()
+    };
+    Ok(Payload {
+        xml,
+        stream: InnerStream::new(header.inner_stream_id, &header.protected_stream_key)?,
+        binaries: Vec::new(),
+    })
+}
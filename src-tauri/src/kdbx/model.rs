@@ -0,0 +1,227 @@
+// Group/entry tree built on top of the XML DOM
+//
+// Only the structure (nesting, history) is modelled; every property stays in the
+// underlying element so unknown fields survive a load/save cycle.
+use super::xml::Element;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Entry element without its `History`
+    pub element: Element,
+    /// Previous versions, oldest first
+    pub history: Vec<Element>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    /// Group element without its child entries and groups
+    pub element: Element,
+    pub entries: Vec<Entry>,
+    pub groups: Vec<Group>,
+}
+
+/// Accessors shared by entries, history items and groups
+pub trait Node {
+    fn element(&self) -> &Element;
+
+    fn uuid(&self) -> &str {
+        self.element().child_text("UUID").unwrap_or_default()
+    }
+
+    fn time(&self, name: &str) -> Option<DateTime<Utc>> {
+        self.element().child("Times")?.child_time(name)
+    }
+
+    fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.time("LastModificationTime")
+    }
+
+    fn location_changed(&self) -> Option<DateTime<Utc>> {
+        self.time("LocationChanged")
+    }
+}
+
+impl Node for Element {
+    fn element(&self) -> &Element {
+        self
+    }
+}
+
+impl Node for Entry {
+    fn element(&self) -> &Element {
+        &self.element
+    }
+}
+
+impl Node for Group {
+    fn element(&self) -> &Element {
+        &self.element
+    }
+}
+
+/// Value of a `<String>` field (Title, UserName, ...) of an entry element
+pub fn string_field<'a>(entry: &'a Element, key: &str) -> Option<&'a str> {
+    entry
+        .children_named("String")
+        .find(|s| s.child_text("Key") == Some(key))
+        .and_then(|s| s.child_text("Value"))
+}
+
+/// Apply `f` to every binary reference (`<Binary><Value Ref="n"/>`) inside `element`
+pub fn for_each_binary_ref(element: &mut Element, f: &mut dyn FnMut(&mut String)) {
+    if element.name == "Binary" {
+        if let Some(value) = element.child_mut("Value") {
+            if let Some((_, reference)) = value.attrs.iter_mut().find(|(k, _)| k == "Ref") {
+                f(reference);
+            }
+        }
+    }
+    for child in &mut element.children {
+        for_each_binary_ref(child, f);
+    }
+}
+
+impl Entry {
+    pub fn from_element(mut element: Element) -> Self {
+        let history = element
+            .remove_children("History")
+            .into_iter()
+            .flat_map(|h| h.children.into_iter().filter(|c| c.name == "Entry"))
+            .collect();
+        Self { element, history }
+    }
+
+    pub fn to_element(&self) -> Element {
+        let mut element = self.element.clone();
+        let mut history = Element::new("History");
+        history.children = self.history.clone();
+        element.children.push(history);
+        element
+    }
+
+    pub fn title(&self) -> &str {
+        string_field(&self.element, "Title").unwrap_or_default()
+    }
+
+    pub fn for_each_binary_ref(&mut self, f: &mut dyn FnMut(&mut String)) {
+        for_each_binary_ref(&mut self.element, f);
+        for item in &mut self.history {
+            for_each_binary_ref(item, f);
+        }
+    }
+}
+
+impl Group {
+    pub fn from_element(mut element: Element) -> Self {
+        let entries = element
+            .remove_children("Entry")
+            .into_iter()
+            .map(Entry::from_element)
+            .collect();
+        let groups = element
+            .remove_children("Group")
+            .into_iter()
+            .map(Group::from_element)
+            .collect();
+        Self {
+            element,
+            entries,
+            groups,
+        }
+    }
+
+    pub fn to_element(&self) -> Element {
+        let mut element = self.element.clone();
+        element
+            .children
+            .extend(self.entries.iter().map(Entry::to_element));
+        element
+            .children
+            .extend(self.groups.iter().map(Group::to_element));
+        element
+    }
+
+    pub fn name(&self) -> &str {
+        self.element.child_text("Name").unwrap_or_default()
+    }
+
+    pub fn find_entry(&self, uuid: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|e| e.uuid() == uuid)
+            .or_else(|| self.groups.iter().find_map(|g| g.find_entry(uuid)))
+    }
+
+    pub fn find_entry_mut(&mut self, uuid: &str) -> Option<&mut Entry> {
+        if let Some(index) = self.entries.iter().position(|e| e.uuid() == uuid) {
+            return Some(&mut self.entries[index]);
+        }
+        self.groups.iter_mut().find_map(|g| g.find_entry_mut(uuid))
+    }
+
+    pub fn find_group(&self, uuid: &str) -> Option<&Group> {
+        if self.uuid() == uuid {
+            return Some(self);
+        }
+        self.groups.iter().find_map(|g| g.find_group(uuid))
+    }
+
+    pub fn find_group_mut(&mut self, uuid: &str) -> Option<&mut Group> {
+        if self.uuid() == uuid {
+            return Some(self);
+        }
+        self.groups.iter_mut().find_map(|g| g.find_group_mut(uuid))
+    }
+
+    /// UUID of the group directly containing entry `uuid`
+    pub fn parent_of_entry(&self, uuid: &str) -> Option<&str> {
+        if self.entries.iter().any(|e| e.uuid() == uuid) {
+            return Some(self.uuid());
+        }
+        self.groups.iter().find_map(|g| g.parent_of_entry(uuid))
+    }
+
+    /// UUID of the group directly containing group `uuid`
+    pub fn parent_of_group(&self, uuid: &str) -> Option<&str> {
+        if self.groups.iter().any(|g| g.uuid() == uuid) {
+            return Some(self.uuid());
+        }
+        self.groups.iter().find_map(|g| g.parent_of_group(uuid))
+    }
+
+    /// Remove entry `uuid` from wherever it is in this subtree
+    pub fn take_entry(&mut self, uuid: &str) -> Option<Entry> {
+        if let Some(index) = self.entries.iter().position(|e| e.uuid() == uuid) {
+            return Some(self.entries.remove(index));
+        }
+        self.groups.iter_mut().find_map(|g| g.take_entry(uuid))
+    }
+
+    /// Remove group `uuid` (with its contents) from this subtree
+    pub fn take_group(&mut self, uuid: &str) -> Option<Group> {
+        if let Some(index) = self.groups.iter().position(|g| g.uuid() == uuid) {
+            return Some(self.groups.remove(index));
+        }
+        self.groups.iter_mut().find_map(|g| g.take_group(uuid))
+    }
+
+    pub fn is_ancestor_of(&self, uuid: &str) -> bool {
+        self.groups
+            .iter()
+            .any(|g| g.uuid() == uuid || g.is_ancestor_of(uuid))
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.entries.len() + self.groups.iter().map(Group::entry_count).sum::<usize>()
+    }
+
+    pub fn for_each_entry_mut(&mut self, f: &mut dyn FnMut(&mut Entry)) {
+        for entry in &mut self.entries {
+            f(entry);
+        }
+        for group in &mut self.groups {
+            group.for_each_entry_mut(f);
+        }
+    }
+}
@@ -0,0 +1,622 @@
+// Salvage of vaults the strict reader rejects
+//
+// With the right credentials, payload blocks are decrypted up to the first one that is
+// cut off or fails its HMAC (KDBX 4) or hash (KDBX 3); that block is decrypted as well
+// but flagged as unverified. The XML is read until it ends or turns malformed, closing
+// whatever is still open. Groups and entries that lost their UUID are dropped, as are
+// incomplete fields and references to attachments that did not survive. Every loss is
+// written to the report so the user knows what to look for in a backup.
+use super::crypto::{self, sha256, sha512, InnerStream};
+use super::header::{Header, Reader};
+use super::model::{for_each_binary_ref, string_field, Group};
+use super::xml::{self, Element};
+use super::{
+    corrupt, deleted_objects, gunzip, inner_field, wrong_credentials, Binary, Credentials,
+    Database, MAX_DECOMPRESSED_SIZE,
+};
+use crate::error::AppResult;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use std::io::Read;
+use zeroize::Zeroizing;
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SalvageReport {
+    /// Payload blocks that verified and were decrypted
+    pub blocks_recovered: usize,
+    /// False when the payload was cut off or a block failed verification
+    pub payload_complete: bool,
+    /// Bytes of the first damaged block that were decrypted without verification
+    pub unverified_bytes: usize,
+    pub xml_complete: bool,
+    pub groups: usize,
+    pub entries: usize,
+    pub history_items: usize,
+    pub attachments: usize,
+    /// Human-readable list of what could not be recovered
+    pub lost: Vec<String>,
+}
+
+impl SalvageReport {
+    /// Nothing was damaged; the vault would have opened normally
+    pub fn is_clean(&self) -> bool {
+        self.payload_complete && self.xml_complete && self.lost.is_empty()
+    }
+}
+
+/// Payload as far as it could be decrypted
+struct Recovered {
+    xml: Vec<u8>,
+    /// None when the inner header did not survive
+    stream: Option<InnerStream>,
+    binaries: Vec<Binary>,
+}
+
+/// Recover what can be trusted from a damaged vault
+pub fn salvage(data: &[u8], credentials: &Credentials) -> AppResult<(Database, SalvageReport)> {
+    let (header, header_len) = Header::parse(data)?;
+    let master_key = credentials.master_key()?;
+    let composite = master_key.composite(&header)?;
+    let transformed = crypto::transform_key(&composite, &header.kdf)?;
+    let cipher_key = master_key.cipher_key(&header, &transformed)?;
+
+    let mut report = SalvageReport::default();
+    let Recovered {
+        xml,
+        mut stream,
+        mut binaries,
+    } = if header.is_v4() {
+        recover_v4(
+            data,
+            header_len,
+            &header,
+            &cipher_key,
+            &transformed,
+            &mut report,
+        )?
+    } else {
+        recover_v3(&data[header_len..], &header, &cipher_key, &mut report)?
+    };
+
+    let xml = Zeroizing::new(xml);
+    let partial = xml::parse_partial(&xml, stream.as_mut());
+    report.xml_complete = partial.error.is_none() && partial.unclosed.is_empty();
+    if let Some(error) = &partial.error {
+        report
+            .lost
+            .push(format!("{}; nothing after that point could be read", error));
+    } else if !partial.unclosed.is_empty() {
+        report
+            .lost
+            .push("The database XML ends early; nothing after that point could be read".into());
+    }
+    let mut document = partial
+        .root
+        .filter(|root| root.name == "KeePassFile")
+        .ok_or_else(|| corrupt("None of the database XML could be read"))?;
+    // Names of the still-open elements below KeePassFile
+    let unclosed = partial.unclosed.get(1..).unwrap_or_default();
+
+    let mut meta = match document.remove_children("Meta").pop() {
+        Some(meta) => meta,
+        None => {
+            report
+                .lost
+                .push("The database settings were lost; defaults are used".into());
+            Element::new("Meta")
+        }
+    };
+    if unclosed.first().is_some_and(|name| name == "Meta") {
+        report
+            .lost
+            .push("The database settings were cut short; missing ones use defaults".into());
+    }
+
+    let mut root = document
+        .remove_children("Root")
+        .pop()
+        .ok_or_else(|| corrupt("No groups or entries could be recovered"))?;
+    let root_cut = unclosed.first().is_some_and(|name| name == "Root");
+    if root_cut && root.child("DeletedObjects").is_none() {
+        report.lost.push(
+            "The list of deleted objects was lost; entries deleted on another device may \
+             come back when syncing"
+                .into(),
+        );
+    }
+
+    if !header.is_v4() {
+        binaries = v3_binaries(&mut meta, &mut root, &mut report);
+    }
+
+    let group_count = root.children_named("Group").count();
+    let mut root_element = root
+        .remove_children("Group")
+        .into_iter()
+        .next()
+        .ok_or_else(|| corrupt("No groups or entries could be recovered"))?;
+    if group_count > 1 {
+        report.lost.push(format!(
+            "{} extra top-level groups were dropped",
+            group_count - 1
+        ));
+    }
+    // Only the last child can have been cut, and only if Root itself was
+    let group_cut = (root_cut && group_count == 1)
+        .then(|| {
+            unclosed
+                .get(1..)
+                .filter(|rest| rest.first().is_some_and(|n| n == "Group"))
+        })
+        .flatten()
+        .map(|rest| &rest[1..]);
+    if root_element.child_text("UUID").is_none() {
+        return Err(corrupt("The root group could not be recovered"));
+    }
+    salvage_group(
+        &mut root_element,
+        "",
+        group_cut,
+        binaries.len(),
+        &mut report,
+    );
+
+    let mut database = Database {
+        header,
+        meta,
+        root: Group::from_element(root_element),
+        deleted_objects: deleted_objects(&root),
+        binaries,
+    };
+    database.compact_binaries();
+    report.groups = count_groups(&database.root);
+    report.entries = database.entry_count();
+    report.history_items = count_history(&database.root);
+    report.attachments = database.binaries.len();
+    Ok((database, report))
+}
+
+fn count_groups(group: &Group) -> usize {
+    1 + group.groups.iter().map(count_groups).sum::<usize>()
+}
+
+fn count_history(group: &Group) -> usize {
+    group.entries.iter().map(|e| e.history.len()).sum::<usize>()
+        + group.groups.iter().map(count_history).sum::<usize>()
+}
+
+/// Next KDBX 4 block as (mac, length field, data); the data is shorter than the length
+/// field says when the file ends inside the block
+fn next_hmac_block<'a>(reader: &mut Reader<'a>) -> Option<(&'a [u8], &'a [u8], &'a [u8])> {
+    let mac = reader.take(32).ok()?;
+    let len_bytes = reader.take(4).ok()?;
+    let len = u32::from_le_bytes(len_bytes.try_into().ok()?) as usize;
+    let available = len.min(reader.remaining().len());
+    Some((mac, len_bytes, reader.take(available).ok()?))
+}
+
+fn recover_v4(
+    data: &[u8],
+    header_len: usize,
+    header: &Header,
+    cipher_key: &[u8; 32],
+    transformed: &[u8; 32],
+    report: &mut SalvageReport,
+) -> AppResult<Recovered> {
+    let header_bytes = &data[..header_len];
+    let mut reader = Reader::new(&data[header_len..]);
+    let (Ok(stored_hash), Ok(stored_hmac)) = (reader.take(32), reader.take(32)) else {
+        return Err(corrupt("The file ends right after its header"));
+    };
+    let hmac_key = Zeroizing::new(sha512(&[&header.master_seed, transformed, &[1]]));
+    if !crypto::verify_hmac(
+        crypto::block_hmac_key(&hmac_key, u64::MAX).as_slice(),
+        &[header_bytes],
+        stored_hmac,
+    ) {
+        // A damaged header changes the derived keys, so nothing can be decrypted
+        if sha256(&[header_bytes])[..] != stored_hash[..] {
+            return Err(corrupt(
+                "The header is damaged; the payload cannot be decrypted",
+            ));
+        }
+        return Err(wrong_credentials());
+    }
+
+    // The first block that cannot be verified is still decrypted: with 1 MiB blocks most
+    // vaults are a single block, and a cut-off file would otherwise lose everything.
+    // Decompression and the XML parser stop where its data turns to garbage.
+    let mut encrypted = Vec::new();
+    loop {
+        let index = report.blocks_recovered as u64;
+        let Some((mac, len_bytes, block)) = next_hmac_block(&mut reader) else {
+            report.lost.push(format!(
+                "The file ends before payload block {}; the rest of the payload is missing",
+                index
+            ));
+            break;
+        };
+        let cut_off =
+            block.len() < u32::from_le_bytes(len_bytes.try_into().expect("4 bytes")) as usize;
+        if cut_off
+            || !crypto::verify_hmac(
+                crypto::block_hmac_key(&hmac_key, index).as_slice(),
+                &[&index.to_le_bytes(), len_bytes, block],
+                mac,
+            )
+        {
+            report.lost.push(if cut_off {
+                format!(
+                    "The file ends inside payload block {}; its {} readable bytes were \
+                     recovered without verification",
+                    index,
+                    block.len()
+                )
+            } else {
+                format!(
+                    "Payload block {} failed its integrity check; it was recovered without \
+                     verification and the {} bytes after it were discarded",
+                    index,
+                    reader.remaining().len()
+                )
+            });
+            report.unverified_bytes = block.len();
+            encrypted.extend_from_slice(block);
+            break;
+        }
+        if block.is_empty() {
+            report.payload_complete = true;
+            break;
+        }
+        encrypted.extend_from_slice(block);
+        report.blocks_recovered += 1;
+    }
+    if encrypted.is_empty() {
+        return Err(corrupt("The file ends before its payload"));
+    }
+
+    let plain = Zeroizing::new(if report.payload_complete {
+        header.cipher.decrypt(cipher_key, &header.iv, &encrypted)?
+    } else {
+        header
+            .cipher
+            .decrypt_prefix(cipher_key, &header.iv, &encrypted)?
+    });
+    let plain = if header.compression {
+        gunzip_prefix(&plain, report)
+    } else {
+        plain
+    };
+
+    let mut reader = Reader::new(&plain);
+    let mut stream_id = None;
+    let mut stream_key = Zeroizing::new(Vec::new());
+    let mut binaries = Vec::new();
+    let mut complete = false;
+    while let (Ok(id), Ok(len)) = (reader.u8(), reader.u32()) {
+        let Ok(value) = reader.take(len as usize) else {
+            break;
+        };
+        match id {
+            inner_field::END => {
+                complete = true;
+                break;
+            }
+            inner_field::STREAM_ID => {
+                stream_id = value.try_into().ok().map(u32::from_le_bytes);
+            }
+            inner_field::STREAM_KEY => stream_key = Zeroizing::new(value.to_vec()),
+            inner_field::BINARY => {
+                if let Some((flags, data)) = value.split_first() {
+                    binaries.push(Binary {
+                        data: data.to_vec(),
+                        protected: flags & 1 != 0,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    if !complete {
+        report.lost.push(format!(
+            "The payload ends inside the attachment pool after {} attachments; \
+             no entries could be read",
+            binaries.len()
+        ));
+    }
+    Ok(Recovered {
+        xml: if complete {
+            reader.remaining().to_vec()
+        } else {
+            Vec::new()
+        },
+        stream: stream_id.and_then(|id| InnerStream::new(id, &stream_key).ok()),
+        binaries,
+    })
+}
+
+/// Next KDBX 3 block as (hash, length, data); the data is shorter than `length` when the
+/// payload ends inside the block
+fn next_hashed_block<'a>(reader: &mut Reader<'a>) -> Option<(&'a [u8], usize, &'a [u8])> {
+    let _index = reader.u32().ok()?;
+    let hash = reader.take(32).ok()?;
+    let len = reader.u32().ok()? as usize;
+    let available = len.min(reader.remaining().len());
+    Some((hash, len, reader.take(available).ok()?))
+}
+
+fn recover_v3(
+    encrypted: &[u8],
+    header: &Header,
+    cipher_key: &[u8; 32],
+    report: &mut SalvageReport,
+) -> AppResult<Recovered> {
+    // Bad padding means a wrong key or a cut-off file; the start bytes tell them apart
+    let plain = Zeroizing::new(
+        match header.cipher.decrypt(cipher_key, &header.iv, encrypted) {
+            Ok(plain) => plain,
+            Err(_) => header
+                .cipher
+                .decrypt_prefix(cipher_key, &header.iv, encrypted)?,
+        },
+    );
+    if plain.len() < 32 {
+        return Err(corrupt("The payload is too short to recover anything"));
+    }
+    if plain[..32] != header.stream_start_bytes[..] {
+        return Err(wrong_credentials());
+    }
+
+    let mut reader = Reader::new(&plain[32..]);
+    let mut content = Zeroizing::new(Vec::new());
+    loop {
+        let index = report.blocks_recovered;
+        let Some((hash, len, block)) = next_hashed_block(&mut reader) else {
+            report.lost.push(format!(
+                "The file ends before payload block {}; the rest of the payload is missing",
+                index
+            ));
+            break;
+        };
+        if len == 0 {
+            report.payload_complete = true;
+            break;
+        }
+        // Kept without verification like the KDBX 4 block above
+        let cut_off = block.len() < len;
+        if cut_off || sha256(&[block])[..] != hash[..] {
+            report.lost.push(if cut_off {
+                format!(
+                    "The file ends inside payload block {}; its {} readable bytes were \
+                     recovered without verification",
+                    index,
+                    block.len()
+                )
+            } else {
+                format!(
+                    "Payload block {} failed its hash check; it was recovered without \
+                     verification and the {} bytes after it were discarded",
+                    index,
+                    reader.remaining().len()
+                )
+            });
+            report.unverified_bytes = block.len();
+            content.extend_from_slice(block);
+            break;
+        }
+        content.extend_from_slice(block);
+        report.blocks_recovered += 1;
+    }
+
+    let xml = if header.compression {
+        gunzip_prefix(&content, report).to_vec()
+    } else {
+        content.to_vec()
+    };
+    Ok(Recovered {
+        xml,
+        stream: InnerStream::new(header.inner_stream_id, &header.protected_stream_key).ok(),
+        binaries: Vec::new(),
+    })
+}
+
+/// Decompress as far as the data goes; a cut-off stream still yields its beginning.
+/// Output beyond the engine's size limit is dropped like a cut-off stream
+pub(super) fn gunzip_prefix(data: &[u8], report: &mut SalvageReport) -> Zeroizing<Vec<u8>> {
+    let mut out = Zeroizing::new(Vec::new());
+    let result = GzDecoder::new(data)
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut out);
+    if out.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        out.truncate(MAX_DECOMPRESSED_SIZE as usize);
+        report.lost.push(format!(
+            "Decompressed data beyond {} MiB was discarded",
+            MAX_DECOMPRESSED_SIZE / (1024 * 1024)
+        ));
+    } else if let Err(e) = result {
+        // Expected when blocks were already dropped, and reported there
+        if report.payload_complete {
+            report
+                .lost
+                .push(format!("Decompression stopped early ({})", e));
+        }
+    }
+    out
+}
+
+/// Take the KDBX 3 attachment pool out of Meta and point references at pool indices;
+/// references to attachments that did not survive are left unresolvable
+fn v3_binaries(meta: &mut Element, root: &mut Element, report: &mut SalvageReport) -> Vec<Binary> {
+    let mut ids = Vec::new();
+    let mut binaries = Vec::new();
+    for pool in meta.remove_children("Binaries") {
+        for binary in pool.children_named("Binary") {
+            let id = binary.attr("ID").unwrap_or_default();
+            let mut data = BASE64.decode(binary.text.trim()).ok();
+            if binary
+                .attr("Compressed")
+                .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+            {
+                data = data.and_then(|d| gunzip(&d).ok());
+            }
+            match data {
+                Some(data) => {
+                    ids.push(id.to_string());
+                    binaries.push(Binary {
+                        data,
+                        protected: false,
+                    });
+                }
+                None => report.lost.push(format!(
+                    "Attachment {} in the attachment pool is unreadable",
+                    id
+                )),
+            }
+        }
+    }
+    for_each_binary_ref(root, &mut |reference| {
+        *reference = match ids.iter().position(|id| id == reference) {
+            Some(index) => index.to_string(),
+            None => String::new(),
+        };
+    });
+    binaries
+}
+
+/// `cut` is `Some` when the element was still open where the XML stopped, holding the
+/// names of its open descendants
+fn salvage_group(
+    element: &mut Element,
+    parent_path: &str,
+    cut: Option<&[String]>,
+    pool_len: usize,
+    report: &mut SalvageReport,
+) {
+    let name = element.child_text("Name").unwrap_or_default();
+    let path = if parent_path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent_path, name)
+    };
+    let child_cut = cut.and_then(|rest| Some((rest.first()?.as_str(), &rest[1..])));
+    if cut.is_some() && !cut.is_some_and(|rest| rest.iter().any(|n| n == "Group")) {
+        report.lost.push(format!(
+            "Group \"{}\" was cut short; entries and subgroups stored after the recovered \
+             ones are missing",
+            path
+        ));
+    }
+
+    let last = element.children.len().saturating_sub(1);
+    let children = std::mem::take(&mut element.children);
+    for (index, mut child) in children.into_iter().enumerate() {
+        let cut = child_cut
+            .filter(|(name, _)| index == last && *name == child.name)
+            .map(|(_, rest)| rest);
+        let keep = match child.name.as_str() {
+            "Group" if child.child_text("UUID").is_none() => {
+                report.lost.push(format!(
+                    "A group in \"{}\" was cut off before its UUID and was dropped",
+                    path
+                ));
+                false
+            }
+            "Group" => {
+                salvage_group(&mut child, &path, cut, pool_len, report);
+                true
+            }
+            "Entry" => salvage_entry(&mut child, &path, cut, pool_len, report),
+            _ => true,
+        };
+        if keep {
+            element.children.push(child);
+        }
+    }
+}
+
+/// Returns false when the entry has to be dropped
+fn salvage_entry(
+    element: &mut Element,
+    path: &str,
+    cut: Option<&[String]>,
+    pool_len: usize,
+    report: &mut SalvageReport,
+) -> bool {
+    if element.child_text("UUID").is_none() {
+        report.lost.push(format!(
+            "An entry in \"{}\" was cut off before its UUID and was dropped",
+            path
+        ));
+        return false;
+    }
+    let title = string_field(element, "Title")
+        .filter(|t| !t.is_empty())
+        .unwrap_or("(untitled)")
+        .to_string();
+    let label = format!("Entry \"{}\" in \"{}\"", title, path);
+
+    if let Some(rest) = cut {
+        if rest.first().is_some_and(|n| n == "History") {
+            let history = element.child_or_insert("History");
+            // The last version was still being read
+            if rest.get(1).is_some_and(|n| n == "Entry") {
+                history.children.pop();
+            }
+            report.lost.push(format!(
+                "{}: its history was cut short; {} older versions were kept",
+                label,
+                history.children_named("Entry").count()
+            ));
+        } else {
+            report.lost.push(format!(
+                "{} was cut short; fields stored after the recovered ones are missing",
+                label
+            ));
+        }
+    }
+
+    salvage_fields(element, &label, pool_len, report);
+    if let Some(history) = element.child_mut("History") {
+        history.children.retain(|item| item.name == "Entry");
+        for item in &mut history.children {
+            salvage_fields(item, &format!("{} (history)", label), pool_len, report);
+        }
+    }
+    true
+}
+
+/// Drop incomplete fields and references to attachments that are not in the pool
+fn salvage_fields(element: &mut Element, label: &str, pool_len: usize, report: &mut SalvageReport) {
+    element.children.retain(|child| match child.name.as_str() {
+        "String" => {
+            let complete = child.child("Key").is_some() && child.child("Value").is_some();
+            if !complete {
+                report.lost.push(format!(
+                    "{}: field \"{}\" was incomplete and dropped",
+                    label,
+                    child.child_text("Key").unwrap_or("?")
+                ));
+            }
+            complete
+        }
+        "Binary" => {
+            let present = child
+                .child("Value")
+                .and_then(|value| value.attr("Ref"))
+                .and_then(|r| r.parse::<usize>().ok())
+                .is_some_and(|index| index < pool_len);
+            if !present {
+                report.lost.push(format!(
+                    "{}: attachment \"{}\" was lost",
+                    label,
+                    child.child_text("Key").unwrap_or("?")
+                ));
+            }
+            present
+        }
+        _ => true,
+    });
+}
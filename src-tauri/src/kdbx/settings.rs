@@ -0,0 +1,218 @@
+// Encryption settings of a vault: payload cipher, key derivation and compression
+//
+// Changing them only touches the outer header; the next save re-encrypts the payload
+// with fresh seeds. KDBX 3.1 has no field for Argon2, so choosing it upgrades the vault
+// to KDBX 4.0 like KeePassXC does; a vault is never downgraded. The benchmark times a
+// short key derivation on this device and extrapolates, so the frontend can show how
+// long unlocking will take and suggest parameters for a one-second delay.
+use super::crypto::{self, KDF_AES, KDF_ARGON2D, KDF_ARGON2ID};
+use super::header::{KdfParams, VarDictionary, VarValue};
+use super::Database;
+use crate::error::{AppError, AppResult};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+pub use super::crypto::Cipher;
+
+const VERSION_4_0: u32 = 0x0004_0000;
+const ARGON2_VERSION: u32 = 0x13;
+
+// Accepted Argon2 parameters; the upper limits keep a vault openable on ordinary
+// hardware (and within what KeePassXC offers)
+const MIN_ARGON2_MEMORY: u64 = 1024 * 1024;
+const MAX_ARGON2_MEMORY: u64 = 4 * 1024 * 1024 * 1024;
+const MAX_ARGON2_PARALLELISM: u32 = 64;
+
+// Shortest run the benchmark measures before extrapolating
+const BENCHMARK_MIN_TIME: Duration = Duration::from_millis(100);
+const TARGET_TIME: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kdf {
+    #[serde(rename = "AES-KDF")]
+    Aes,
+    Argon2d,
+    Argon2id,
+}
+
+impl Kdf {
+    fn uuid(self) -> [u8; 16] {
+        match self {
+            Kdf::Aes => KDF_AES,
+            Kdf::Argon2d => KDF_ARGON2D,
+            Kdf::Argon2id => KDF_ARGON2ID,
+        }
+    }
+
+    fn from_params(params: &KdfParams) -> AppResult<Self> {
+        match params.uuid()? {
+            KDF_AES => Ok(Kdf::Aes),
+            KDF_ARGON2D => Ok(Kdf::Argon2d),
+            KDF_ARGON2ID => Ok(Kdf::Argon2id),
+            _ => Err(AppError::unsupported("Unknown key derivation function")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CryptoSettings {
+    pub cipher: Cipher,
+    pub kdf: Kdf,
+    /// AES-KDF rounds or Argon2 iterations
+    pub iterations: u64,
+    /// Argon2 memory in bytes
+    #[serde(default)]
+    pub memory: Option<u64>,
+    /// Argon2 lanes
+    #[serde(default)]
+    pub parallelism: Option<u32>,
+    /// GZip the payload
+    pub compression: bool,
+}
+
+impl CryptoSettings {
+    /// Reject values other clients cannot open; Argon2 memory and parallelism are
+    /// required for Argon2 and dropped for AES-KDF
+    fn validated(&self) -> AppResult<Self> {
+        if self.iterations == 0 {
+            return Err(AppError::invalid_input(
+                "Key derivation needs at least one iteration",
+            ));
+        }
+        if self.kdf == Kdf::Aes {
+            return Ok(Self {
+                memory: None,
+                parallelism: None,
+                ..self.clone()
+            });
+        }
+
+        if u32::try_from(self.iterations).is_err() {
+            return Err(AppError::invalid_input("Too many Argon2 iterations"));
+        }
+        let (Some(memory), Some(parallelism)) = (self.memory, self.parallelism) else {
+            return Err(AppError::invalid_input(
+                "Argon2 needs a memory size and parallelism",
+            ));
+        };
+        if !(1..=MAX_ARGON2_PARALLELISM).contains(&parallelism) {
+            return Err(AppError::invalid_input(format!(
+                "Argon2 parallelism must be between 1 and {}",
+                MAX_ARGON2_PARALLELISM
+            )));
+        }
+        if !(MIN_ARGON2_MEMORY..=MAX_ARGON2_MEMORY).contains(&memory) {
+            return Err(AppError::invalid_input(format!(
+                "Argon2 memory must be between {} MiB and {} MiB",
+                MIN_ARGON2_MEMORY >> 20,
+                MAX_ARGON2_MEMORY >> 20
+            )));
+        }
+        if !memory.is_multiple_of(1024) {
+            return Err(AppError::invalid_input(
+                "Argon2 memory must be a whole number of KiB",
+            ));
+        }
+        Ok(self.clone())
+    }
+
+    /// KDF parameter dictionary for these settings; `current` keeps its extra entries
+    /// (Argon2 secret and associated data) when the KDF stays the same
+    fn kdf_params(&self, current: Option<&KdfParams>) -> AppResult<KdfParams> {
+        let mut params = match current {
+            Some(current) if Kdf::from_params(current).ok() == Some(self.kdf) => current.clone(),
+            _ => {
+                let mut params = VarDictionary::default();
+                params.set("$UUID", VarValue::Bytes(self.kdf.uuid().to_vec()));
+                params
+            }
+        };
+        params.set("S", VarValue::Bytes(crypto::random_bytes::<32>()?.to_vec()));
+        match (self.kdf, self.memory, self.parallelism) {
+            (Kdf::Aes, _, _) => params.set("R", VarValue::U64(self.iterations)),
+            (_, Some(memory), Some(parallelism)) => {
+                params.set("P", VarValue::U32(parallelism));
+                params.set("M", VarValue::U64(memory));
+                params.set("I", VarValue::U64(self.iterations));
+                params.set("V", VarValue::U32(ARGON2_VERSION));
+            }
+            _ => return Err(AppError::internal("Argon2 settings were not validated")),
+        }
+        // Never write a vault that could not be opened again
+        crypto::check_kdf_cost(&params).map_err(|e| AppError::invalid_input(e.message))?;
+        Ok(params)
+    }
+}
+
+/// Estimated cost of a key derivation on this device
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfBenchmark {
+    /// Time to derive the key with the benchmarked settings
+    pub estimated_millis: u64,
+    /// AES-KDF rounds or Argon2 iterations that take about one second, with the same
+    /// memory and parallelism
+    pub one_second_iterations: u64,
+}
+
+/// Encryption settings `database` was opened with
+pub fn current(database: &Database) -> AppResult<CryptoSettings> {
+    let header = &database.header;
+    let kdf = Kdf::from_params(&header.kdf)?;
+    let (iterations, memory, parallelism) = match kdf {
+        Kdf::Aes => (header.kdf.u64("R")?, None, None),
+        Kdf::Argon2d | Kdf::Argon2id => (
+            header.kdf.u64("I")?,
+            Some(header.kdf.u64("M")?),
+            Some(header.kdf.u32("P")?),
+        ),
+    };
+    Ok(CryptoSettings {
+        cipher: header.cipher,
+        kdf,
+        iterations,
+        memory,
+        parallelism,
+        compression: header.compression,
+    })
+}
+
+/// Switch `database` to `settings`; they take effect with the next save
+pub fn apply(database: &mut Database, settings: &CryptoSettings) -> AppResult<()> {
+    let settings = settings.validated()?;
+    let header = &mut database.header;
+    header.kdf = settings.kdf_params(Some(&header.kdf))?;
+    header.cipher = settings.cipher;
+    header.compression = settings.compression;
+    if settings.kdf != Kdf::Aes && !header.is_v4() {
+        header.version = VERSION_4_0;
+    }
+    Ok(())
+}
+
+/// Time the key derivation `settings` describe: the iteration count doubles until a run
+/// takes `BENCHMARK_MIN_TIME`, and the result is scaled from there
+pub fn benchmark(settings: &CryptoSettings) -> AppResult<KdfBenchmark> {
+    let settings = settings.validated()?;
+    let composite = [0u8; 32];
+    let mut probe = CryptoSettings {
+        iterations: if settings.kdf == Kdf::Aes { 10_000 } else { 1 },
+        ..settings.clone()
+    };
+    let per_iteration = loop {
+        let params = probe.kdf_params(None)?;
+        let started = Instant::now();
+        crypto::transform_key(&composite, &params)?;
+        let elapsed = started.elapsed();
+        if elapsed >= BENCHMARK_MIN_TIME {
+            break elapsed.as_secs_f64() / probe.iterations as f64;
+        }
+        probe.iterations = probe.iterations.saturating_mul(2);
+    };
+
+    Ok(KdfBenchmark {
+        estimated_millis: (per_iteration * settings.iterations as f64 * 1000.0) as u64,
+        one_second_iterations: ((TARGET_TIME.as_secs_f64() / per_iteration) as u64).max(1),
+    })
+}
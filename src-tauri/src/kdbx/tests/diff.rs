@@ -0,0 +1,221 @@
+// Diffs between the sample vault and edited copies of it
+use super::*;
+use crate::kdbx::diff::{diff, ChangeKind, EntryDiff, FieldDiff, FieldKind, GroupDiff, VaultDiff};
+use crate::kdbx::model::{Entry, Group};
+
+const NEW_ENTRY: &str = "Dg4ODg4ODg4ODg4ODg4ODg==";
+const NEW_GROUP: &str = "Dw8PDw8PDw8PDw8PDw8PDw==";
+
+fn sample() -> Database {
+    sample_database(VERSION_4_0, Cipher::Aes256, KDF_AES)
+}
+
+fn entry<'a>(diff: &'a VaultDiff, uuid: &str) -> &'a EntryDiff {
+    diff.entries
+        .iter()
+        .find(|e| e.uuid == uuid)
+        .unwrap_or_else(|| panic!("no diff for entry {}", uuid))
+}
+
+fn group<'a>(diff: &'a VaultDiff, uuid: &str) -> &'a GroupDiff {
+    diff.groups
+        .iter()
+        .find(|g| g.uuid == uuid)
+        .unwrap_or_else(|| panic!("no diff for group {}", uuid))
+}
+
+fn field<'a>(fields: &'a [FieldDiff], name: &str) -> &'a FieldDiff {
+    fields
+        .iter()
+        .find(|f| f.name == name)
+        .unwrap_or_else(|| panic!("no diff for field {}", name))
+}
+
+fn mail_mut(database: &mut Database) -> &mut Entry {
+    database.root.find_entry_mut(MAIL).expect("Mail entry")
+}
+
+#[test]
+fn diff_of_identical_vaults_is_empty() {
+    let result = diff(&sample(), &sample());
+    assert!(result.entries.is_empty(), "{:?}", result.entries);
+    assert!(result.groups.is_empty(), "{:?}", result.groups);
+}
+
+#[test]
+fn diff_added_removed_and_moved() {
+    let mut b = sample();
+    let mut added = b.root.find_entry(FORUM).expect("Forum entry").clone();
+    added.element.set_child_text("UUID", NEW_ENTRY);
+    set_string(&mut added.element, "Title", "New");
+    b.root.entries.push(added);
+    b.root.take_entry(DELETED_LATER).expect("entry");
+    b.root.take_group(EMAIL).expect("eMail group");
+    let mut new_group = Element::new("Group");
+    new_group
+        .children
+        .push(Element::with_text("UUID", NEW_GROUP));
+    new_group
+        .children
+        .push(Element::with_text("Name", "Banking"));
+    b.root
+        .find_group_mut(INTERNET)
+        .expect("Internet group")
+        .groups
+        .push(Group::from_element(new_group));
+    move_to(&mut b, FORUM, ROOT, "2024-05-06T10:00:00Z");
+    move_to(&mut b, RECYCLE_BIN, INTERNET, "2024-05-06T10:00:00Z");
+
+    let result = diff(&sample(), &b);
+    let new = entry(&result, NEW_ENTRY);
+    assert_eq!((new.kind, new.path.as_str()), (ChangeKind::Added, "Root"));
+    let title = field(&new.fields, "Title");
+    assert_eq!(
+        (title.before.as_deref(), title.after.as_deref()),
+        (None, Some("New"))
+    );
+    let removed = entry(&result, DELETED_LATER);
+    assert_eq!(removed.kind, ChangeKind::Removed);
+    assert_eq!(removed.path, "Root/Recycle Bin");
+    let forum = entry(&result, FORUM);
+    assert_eq!(forum.kind, ChangeKind::Changed);
+    assert_eq!(forum.path, "Root");
+    assert_eq!(forum.moved_from.as_deref(), Some("Root/Internet"));
+    assert!(forum.fields.is_empty(), "{:?}", forum.fields);
+    assert_eq!(result.entries.len(), 3, "Mail is unchanged");
+
+    let email = group(&result, EMAIL);
+    assert_eq!(
+        (email.kind, email.path.as_str()),
+        (ChangeKind::Removed, "Root")
+    );
+    let banking = group(&result, NEW_GROUP);
+    assert_eq!(banking.kind, ChangeKind::Added);
+    assert_eq!(banking.path, "Root/Internet");
+    assert_eq!(
+        field(&banking.fields, "Name").after.as_deref(),
+        Some("Banking")
+    );
+    let bin = group(&result, RECYCLE_BIN);
+    assert_eq!(bin.kind, ChangeKind::Changed);
+    assert_eq!(bin.path, "Root/Internet");
+    assert_eq!(bin.moved_from.as_deref(), Some("Root"));
+    assert_eq!(result.groups.len(), 3);
+}
+
+#[test]
+fn diff_includes_the_root_group() {
+    let mut b = sample();
+    b.root.element.set_child_text("Name", "Vault");
+    let result = diff(&sample(), &b);
+    let root = group(&result, ROOT);
+    assert_eq!((root.kind, root.path.as_str()), (ChangeKind::Changed, ""));
+    assert_eq!(root.moved_from, None);
+    let name = field(&root.fields, "Name");
+    assert_eq!(
+        (name.before.as_deref(), name.after.as_deref()),
+        (Some("Root"), Some("Vault"))
+    );
+    assert_eq!(result.groups.len(), 1, "children only changed path");
+    assert!(result.entries.is_empty());
+}
+
+#[test]
+fn diff_masks_protected_values() {
+    let mut a = sample();
+    let mut b = sample();
+    set_string(&mut mail_mut(&mut a).element, "PIN", "1234");
+    let mail = mail_mut(&mut b);
+    set_string(&mut mail.element, "Password", "new password");
+    set_string(&mut mail.element, "UserName", "alice@example.org");
+    // Protected in `b` only: the old value must not leak either
+    set_string(&mut mail.element, "PIN", "5678");
+    mail.element
+        .children
+        .iter_mut()
+        .find(|s| s.child_text("Key") == Some("PIN"))
+        .and_then(|s| s.child_mut("Value"))
+        .expect("PIN value")
+        .set_attr("Protected", "True");
+
+    let result = diff(&a, &b);
+    let mail = entry(&result, MAIL);
+    for name in ["Password", "PIN"] {
+        let masked = field(&mail.fields, name);
+        assert!(masked.masked, "{}", name);
+        assert_eq!(
+            (masked.before.as_deref(), masked.after.as_deref()),
+            (None, None)
+        );
+        assert_eq!(masked.change, ChangeKind::Changed);
+    }
+    let user = field(&mail.fields, "UserName");
+    assert!(!user.masked);
+    assert_eq!(user.before.as_deref(), Some("alice@example.com"));
+    assert_eq!(user.after.as_deref(), Some("alice@example.org"));
+
+    let json = serde_json::to_string(&result).expect("JSON");
+    for secret in ["p<a&s", "new password", "1234", "5678"] {
+        assert!(!json.contains(secret), "{} leaked", secret);
+    }
+}
+
+#[test]
+fn diff_describes_attachments_by_digest() {
+    let a = sample();
+    let mut b = sample();
+    let logo = b
+        .binaries
+        .iter_mut()
+        .find(|binary| binary.data.len() == 3000)
+        .expect("logo.bin");
+    logo.data = b"new logo".to_vec();
+
+    let result = diff(&a, &b);
+    let mail = entry(&result, MAIL);
+    assert_eq!(mail.fields.len(), 1, "{:?}", mail.fields);
+    let logo = field(&mail.fields, "logo.bin");
+    assert_eq!(logo.kind, FieldKind::Attachment);
+    let digest = |data: &[u8]| hex(&sha256(&[data])[..4]).to_lowercase();
+    let old: Vec<u8> = (0..=255u8).cycle().take(3000).collect();
+    assert_eq!(
+        logo.before.as_deref(),
+        Some(format!("3000 bytes, sha256 {}", digest(&old)).as_str())
+    );
+    assert_eq!(
+        logo.after.as_deref(),
+        Some(format!("8 bytes, sha256 {}", digest(b"new logo")).as_str())
+    );
+}
+
+#[test]
+fn diff_counts_history_changes() {
+    let mut b = sample();
+    let mail = mail_mut(&mut b);
+    let mut previous = mail.element.clone();
+    set_time(
+        &mut previous,
+        "LastModificationTime",
+        "2024-05-03T10:00:00Z",
+    );
+    mail.history.remove(0);
+    mail.history.push(previous);
+    set_time(
+        &mut mail.element,
+        "LastModificationTime",
+        "2024-05-06T10:00:00Z",
+    );
+
+    let result = diff(&sample(), &b);
+    let history = entry(&result, MAIL).history.as_ref().expect("history diff");
+    assert_eq!((history.added, history.removed), (1, 1));
+    assert_eq!((history.count_before, history.count_after), (2, 2));
+
+    // Added and removed entries count all their versions
+    let mut c = sample();
+    c.root.take_entry(MAIL).expect("Mail entry");
+    let result = diff(&sample(), &c);
+    let history = entry(&result, MAIL).history.as_ref().expect("history diff");
+    assert_eq!((history.added, history.removed), (0, 2));
+    assert_eq!((history.count_before, history.count_after), (2, 0));
+}
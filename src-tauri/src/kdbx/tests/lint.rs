@@ -0,0 +1,170 @@
+// Compatibility lint of the sample vault with values other clients reject
+use super::*;
+use crate::kdbx::lint::{lint, LintIssue, LintReport};
+use crate::kdbx::verify::Severity;
+
+const MISSING_ICON: &str = "ZGRkZGRkZGRkZGRkZGRkZA==";
+
+fn sample() -> Database {
+    sample_database(VERSION_4_0, Cipher::Aes256, KDF_AES)
+}
+
+fn issues<'a>(report: &'a LintReport, check: &str) -> Vec<&'a LintIssue> {
+    report.issues.iter().filter(|i| i.check == check).collect()
+}
+
+fn group_text<'a>(database: &'a Database, uuid: &str, name: &str) -> Option<&'a str> {
+    let group = if uuid == ROOT {
+        &database.root
+    } else {
+        database.root.find_group(uuid).expect("group")
+    };
+    group.element.child_text(name)
+}
+
+/// Lint with fixes, then check the result saves, reopens and lints clean
+fn fix_and_reopen(database: &mut Database) -> (LintReport, Database) {
+    let report = lint(database, true);
+    assert_eq!(report.fixed, report.issues.len());
+    let credentials = Credentials {
+        password: Some("lint".to_string()),
+        key_file: None,
+        challenge_response: Vec::new(),
+    };
+    let data = database.save(&credentials).expect("save");
+    let mut reopened = Database::open(&data, &credentials).expect("open");
+    let again = lint(&mut reopened, false);
+    assert!(again.issues.is_empty(), "{:?}", again.issues);
+    (report, reopened)
+}
+
+#[test]
+fn lint_sample_is_clean() {
+    let report = lint(&mut sample(), false);
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+    assert_eq!(report.fixed, 0);
+}
+
+#[test]
+fn lint_unreadable_tri_states_become_null() {
+    let mut database = sample();
+    database.root.element.set_child_text("EnableSearching", "");
+    database
+        .root
+        .element
+        .set_child_text("EnableAutoType", "null");
+    let internet = database.root.find_group_mut(INTERNET).expect("Internet");
+    internet
+        .element
+        .set_child_text("EnableSearching", "garbage");
+    internet.element.set_child_text("EnableAutoType", "1");
+    let bin = database.root.find_group_mut(RECYCLE_BIN).expect("bin");
+    bin.element.set_child_text("EnableAutoType", " NULL ");
+
+    // Without fixes only the report changes
+    let report = lint(&mut database, false);
+    let tri_states = issues(&report, "triState");
+    assert_eq!(tri_states.len(), 3, "{:?}", report.issues);
+    assert!(tri_states
+        .iter()
+        .all(|i| i.severity == Severity::Error && !i.fixed));
+    assert_eq!(report.fixed, 0);
+    assert_eq!(
+        group_text(&database, INTERNET, "EnableSearching"),
+        Some("garbage")
+    );
+
+    let (report, reopened) = fix_and_reopen(&mut database);
+    let empty = &issues(&report, "triState")[0];
+    assert_eq!(empty.uuid.as_deref(), Some(ROOT));
+    assert_eq!(
+        empty.fix,
+        "Set EnableSearching to null (inherit from the parent group)"
+    );
+    for (uuid, name, value) in [
+        (ROOT, "EnableSearching", "null"),
+        (ROOT, "EnableAutoType", "null"),
+        (INTERNET, "EnableSearching", "null"),
+        (INTERNET, "EnableAutoType", "true"),
+        (RECYCLE_BIN, "EnableAutoType", " NULL "),
+    ] {
+        // Present, never removed: a missing element is not the same as null everywhere
+        assert_eq!(group_text(&reopened, uuid, name), Some(value), "{}", name);
+    }
+}
+
+#[test]
+fn lint_fixes_icons_and_colors() {
+    let mut database = sample();
+    let internet = database.root.find_group_mut(INTERNET).expect("Internet");
+    internet.element.set_child_text("IconID", "99");
+    let forum = database.root.find_entry_mut(FORUM).expect("Forum entry");
+    forum.element.set_child_text("IconID", "-1");
+    forum.element.set_child_text("ForegroundColor", "red");
+    forum.element.set_child_text("BackgroundColor", "#12345G");
+    forum.element.set_child_text("CustomIconUUID", MISSING_ICON);
+    let mail = database.root.find_entry_mut(MAIL).expect("Mail entry");
+    mail.element.set_child_text("IconID", "key");
+
+    let (report, reopened) = fix_and_reopen(&mut database);
+    let icons = issues(&report, "iconId");
+    assert_eq!(icons.len(), 2, "{:?}", report.issues);
+    assert!(icons.iter().all(|i| i.severity == Severity::Warning));
+    assert_eq!(
+        icons[0].message,
+        "Group \"Root/Internet\": IconID is 99, outside 0-68"
+    );
+    let number = &issues(&report, "number")[0];
+    assert_eq!(number.severity, Severity::Error);
+    assert_eq!(number.uuid.as_deref(), Some(MAIL));
+    assert_eq!(issues(&report, "color").len(), 2);
+    let custom = &issues(&report, "customIcon")[0];
+    assert!(custom.message.contains(MISSING_ICON));
+
+    assert_eq!(group_text(&reopened, INTERNET, "IconID"), Some("48"));
+    let forum = reopened.root.find_entry(FORUM).expect("Forum entry");
+    assert_eq!(forum.element.child_text("IconID"), Some("0"));
+    assert_eq!(forum.element.child_text("ForegroundColor"), Some(""));
+    assert_eq!(forum.element.child_text("BackgroundColor"), Some(""));
+    assert_eq!(forum.element.child_text("CustomIconUUID"), None);
+    let mail = reopened.root.find_entry(MAIL).expect("Mail entry");
+    assert_eq!(mail.element.child_text("IconID"), Some("0"));
+    assert_eq!(
+        mail.element.child_text("CustomIconUUID"),
+        Some("Y2NjY2NjY2NjY2NjY2NjYw=="),
+        "existing custom icons are kept"
+    );
+}
+
+#[test]
+fn lint_checks_history_items() {
+    let mut database = sample();
+    let mail = database.root.find_entry_mut(MAIL).expect("Mail entry");
+    mail.history[0].set_child_text("BackgroundColor", "blue");
+    let times = mail.history[1].child_mut("Times").expect("history times");
+    times.set_child_text("Expires", "");
+
+    let (report, reopened) = fix_and_reopen(&mut database);
+    assert_eq!(report.issues.len(), 2, "{:?}", report.issues);
+    for issue in &report.issues {
+        assert!(
+            issue
+                .message
+                .starts_with("Entry \"Mail – ünïcödé ✉\" in \"Root\" (history)"),
+            "{}",
+            issue.message
+        );
+        assert_eq!(issue.uuid.as_deref(), Some(MAIL));
+    }
+    assert_eq!(
+        issues(&report, "boolean")[0].fix,
+        "Set Times/Expires to False"
+    );
+
+    let mail = reopened.root.find_entry(MAIL).expect("Mail entry");
+    assert_eq!(mail.history[0].child_text("BackgroundColor"), Some(""));
+    let expires = mail.history[1]
+        .child("Times")
+        .and_then(|t| t.child_text("Expires"));
+    assert_eq!(expires, Some("False"));
+}
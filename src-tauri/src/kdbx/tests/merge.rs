@@ -0,0 +1,221 @@
+// Merge with KeePassXC "Synchronize" semantics: two copies of the sample vault that
+// diverged in one way each, merged source into target
+use super::*;
+use crate::kdbx::merge::merge;
+use crate::kdbx::model::{string_field, Node};
+use crate::kdbx::DeletedObject;
+
+const LATER: &str = "2024-05-06T10:00:00Z";
+const LATEST: &str = "2024-05-07T10:00:00Z";
+
+/// Target and source, both the sample vault as last synchronized
+fn pair() -> (Database, Database) {
+    (
+        sample_database(VERSION_4_0, Cipher::Aes256, KDF_AES),
+        sample_database(VERSION_4_0, Cipher::Aes256, KDF_AES),
+    )
+}
+
+/// Edit the Mail entry's title at `time` without keeping a history item
+fn edit_mail(database: &mut Database, title: &str, time: &str) {
+    let entry = database.root.find_entry_mut(MAIL).expect("Mail entry");
+    set_string(&mut entry.element, "Title", title);
+    set_time(&mut entry.element, "LastModificationTime", time);
+}
+
+fn history_times(database: &Database, uuid: &str) -> Vec<String> {
+    let entry = database.root.find_entry(uuid).expect("entry");
+    entry
+        .history
+        .iter()
+        .map(|item| item.last_modified().expect("history time").to_rfc3339())
+        .collect()
+}
+
+fn deleted(database: &Database) -> Vec<&str> {
+    database
+        .deleted_objects
+        .iter()
+        .map(|object| object.uuid.as_str())
+        .collect()
+}
+
+fn meta_item<'a>(database: &'a mut Database, key: &str) -> Option<&'a mut Element> {
+    database
+        .meta
+        .child_mut("CustomData")?
+        .children
+        .iter_mut()
+        .find(|item| item.child_text("Key") == Some(key))
+}
+
+#[test]
+fn merge_matches_moved_groups_and_entries_by_uuid() {
+    let (mut target, mut source) = pair();
+    move_to(&mut source, EMAIL, INTERNET, LATER);
+    move_to(&mut source, FORUM, ROOT, LATER);
+
+    let summary = merge(&mut target, source);
+    assert_eq!(summary.groups_moved, 1, "{:?}", summary.changes);
+    assert_eq!(summary.entries_moved, 1, "{:?}", summary.changes);
+    assert_eq!(summary.groups_added + summary.entries_added, 0);
+    assert_eq!(target.root.parent_of_group(EMAIL), Some(INTERNET));
+    assert_eq!(target.root.parent_of_entry(FORUM), Some(ROOT));
+    assert_eq!(target.entry_count(), 3, "moved objects were duplicated");
+    let email = target.root.find_group(EMAIL).expect("eMail group");
+    assert_eq!(
+        email.location_changed(),
+        xml::parse_time(LATER),
+        "the move time comes along"
+    );
+
+    // A later move in the target wins over an earlier one in the source
+    let (mut target, mut source) = pair();
+    move_to(&mut source, EMAIL, INTERNET, LATER);
+    move_to(&mut target, EMAIL, RECYCLE_BIN, LATEST);
+    let summary = merge(&mut target, source);
+    assert_eq!(summary.groups_moved, 0);
+    assert_eq!(target.root.parent_of_group(EMAIL), Some(RECYCLE_BIN));
+}
+
+#[test]
+fn merge_keeps_the_newer_entry_and_both_histories() {
+    // Source newer: its version wins and the target's becomes history
+    let (mut target, mut source) = pair();
+    edit_mail(&mut source, "Mail (source)", "2024-05-05T10:00:00Z");
+    let summary = merge(&mut target, source);
+    assert_eq!(summary.entries_updated, 1);
+    let mail = target.root.find_entry(MAIL).expect("Mail entry");
+    assert_eq!(string_field(&mail.element, "Title"), Some("Mail (source)"));
+    assert_eq!(
+        history_times(&target, MAIL),
+        [
+            "2024-05-01T10:00:00+00:00",
+            "2024-05-02T10:00:00+00:00",
+            "2024-05-03T10:00:00+00:00"
+        ]
+    );
+
+    // Target newer: it keeps its data and takes the source's version into history
+    let (mut target, mut source) = pair();
+    edit_mail(&mut source, "Mail (source)", "2024-05-05T10:00:00Z");
+    edit_mail(&mut target, "Mail (target)", LATER);
+    let summary = merge(&mut target, source);
+    assert_eq!(summary.entries_updated, 0);
+    let mail = target.root.find_entry(MAIL).expect("Mail entry");
+    assert_eq!(string_field(&mail.element, "Title"), Some("Mail (target)"));
+    let absorbed = mail.history.last().expect("history");
+    assert_eq!(string_field(absorbed, "Title"), Some("Mail (source)"));
+    assert_eq!(history_times(&target, MAIL).len(), 3);
+
+    // Identical versions are not duplicated
+    let (mut target, source) = pair();
+    let summary = merge(&mut target, source);
+    assert!(summary.is_empty(), "{:?}", summary.changes);
+    assert_eq!(history_times(&target, MAIL).len(), 2);
+}
+
+#[test]
+fn merge_trims_history_to_the_target_limit() {
+    let (mut target, mut source) = pair();
+    target.meta.set_child_text("HistoryMaxItems", "2");
+    edit_mail(&mut source, "Mail (source)", "2024-05-05T10:00:00Z");
+    merge(&mut target, source);
+    assert_eq!(
+        history_times(&target, MAIL),
+        ["2024-05-02T10:00:00+00:00", "2024-05-03T10:00:00+00:00"],
+        "the oldest versions go first"
+    );
+}
+
+#[test]
+fn merge_propagates_deletions_unless_changed_later() {
+    let delete = |database: &mut Database| {
+        database.root.take_entry(FORUM).expect("Forum entry");
+        database.root.take_group(EMAIL).expect("eMail group");
+        for uuid in [FORUM, EMAIL] {
+            database.deleted_objects.push(DeletedObject {
+                uuid: uuid.to_string(),
+                deleted_at: xml::parse_time(LATER).expect("time"),
+            });
+        }
+    };
+
+    let (mut target, mut source) = pair();
+    delete(&mut source);
+    let summary = merge(&mut target, source);
+    assert_eq!(summary.entries_deleted, 1, "{:?}", summary.changes);
+    assert_eq!(summary.groups_deleted, 1, "{:?}", summary.changes);
+    assert!(target.root.find_entry(FORUM).is_none());
+    assert!(target.root.find_group(EMAIL).is_none());
+    let mut recorded = deleted(&target);
+    recorded.sort_unstable();
+    assert_eq!(
+        recorded,
+        [EMAIL, FORUM, "DQ0NDQ0NDQ0NDQ0NDQ0NDQ=="],
+        "deletions of both sides are kept"
+    );
+
+    // Changed after the deletion: the object stays and the deletion is dropped
+    let (mut target, mut source) = pair();
+    delete(&mut source);
+    let forum = target.root.find_entry_mut(FORUM).expect("Forum entry");
+    set_time(&mut forum.element, "LastModificationTime", LATEST);
+    let entry = target.root.take_entry(DELETED_LATER).expect("entry");
+    target
+        .root
+        .find_group_mut(EMAIL)
+        .expect("eMail group")
+        .entries
+        .push(entry);
+    let summary = merge(&mut target, source);
+    assert_eq!(summary.entries_deleted + summary.groups_deleted, 0);
+    assert!(target.root.find_entry(FORUM).is_some());
+    assert!(
+        target.root.find_group(EMAIL).is_some(),
+        "a group that is not empty stays"
+    );
+    assert_eq!(deleted(&target), ["DQ0NDQ0NDQ0NDQ0NDQ0NDQ=="]);
+}
+
+#[test]
+fn merge_custom_data_and_icons() {
+    let (mut target, mut source) = pair();
+    let item = meta_item(&mut source, "KPXC_DECRYPTION_TIME_PREFERENCE").expect("item");
+    item.set_child_text("Value", "2000");
+    item.set_child_text("LastModificationTime", LATER);
+    let mut added = Element::new("Item");
+    added
+        .children
+        .push(Element::with_text("Key", "keedavault_new"));
+    added.children.push(Element::with_text("Value", "yes"));
+    source
+        .meta
+        .child_mut("CustomData")
+        .expect("custom data")
+        .children
+        .push(added);
+    let icons = source.meta.child_mut("CustomIcons").expect("custom icons");
+    let mut icon = icons.children[0].clone();
+    icon.set_child_text("UUID", "ZGRkZGRkZGRkZGRkZGRkZA==");
+    icons.children.push(icon);
+
+    let summary = merge(&mut target, source);
+    assert_eq!(summary.icons_added, 1);
+    let item = meta_item(&mut target, "KPXC_DECRYPTION_TIME_PREFERENCE").expect("item");
+    assert_eq!(item.child_text("Value"), Some("2000"), "newer item wins");
+    let item = meta_item(&mut target, "keedavault_new").expect("added item");
+    assert_eq!(item.child_text("Value"), Some("yes"));
+    let icons = target.meta.child("CustomIcons").expect("custom icons");
+    assert_eq!(icons.children_named("Icon").count(), 2);
+
+    // An older source item does not replace the target's
+    let (mut target, mut source) = pair();
+    let item = meta_item(&mut source, "KPXC_DECRYPTION_TIME_PREFERENCE").expect("item");
+    item.set_child_text("Value", "3000");
+    item.set_child_text("LastModificationTime", "2024-04-01T10:00:00Z");
+    let summary = merge(&mut target, source);
+    assert!(summary.is_empty(), "{:?}", summary.changes);
+    let item = meta_item(&mut target, "KPXC_DECRYPTION_TIME_PREFERENCE").expect("item");
+    assert_eq!(item.child_text("Value"), Some("1000"));
+}
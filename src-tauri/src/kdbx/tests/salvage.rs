@@ -0,0 +1,188 @@
+// Salvage of damaged copies of the sample vault
+//
+// The vaults are saved uncompressed with ChaCha20, a stream cipher, so every byte of
+// the XML sits at a known offset in the file and can be cut exactly where a test needs.
+// Whatever salvage recovers must save and open again with the strict reader.
+use super::*;
+use crate::kdbx::model::string_field;
+use crate::kdbx::salvage::{salvage, SalvageReport};
+use crate::kdbx::Binary;
+
+// HMAC and length field of the empty block that ends a KDBX 4 payload
+const END_BLOCK: usize = 36;
+const BLOCK: usize = crate::kdbx::BLOCK_SIZE;
+
+fn credentials() -> Credentials {
+    Credentials {
+        password: Some("salvage".to_string()),
+        key_file: None,
+        challenge_response: Vec::new(),
+    }
+}
+
+fn uncompressed_sample() -> Database {
+    let mut database = sample_database(VERSION_4_0, Cipher::ChaCha20, KDF_AES);
+    database.header.compression = false;
+    database
+}
+
+/// Save `database`; returns the file and the offset of its XML in it. Protected values
+/// differ between saves, but not in length
+fn save_with_xml(database: &mut Database) -> (Vec<u8>, Vec<u8>, usize) {
+    let data = database.save(&credentials()).expect("save");
+    let document = database.to_document(true, &[]).expect("document");
+    let mut stream = InnerStream::new(INNER_STREAM_CHACHA20, &[0; 64]).expect("stream");
+    let xml = xml::write_document(&document, &mut stream);
+    let offset = data.len() - END_BLOCK - xml.len();
+    (data, xml, offset)
+}
+
+fn find(xml: &[u8], text: &str) -> usize {
+    xml.windows(text.len())
+        .position(|window| window == text.as_bytes())
+        .unwrap_or_else(|| panic!("{} not in the XML", text))
+}
+
+fn assert_lost(report: &SalvageReport, text: &str) {
+    assert!(
+        report.lost.iter().any(|lost| lost.contains(text)),
+        "{:?} does not mention {:?}",
+        report.lost,
+        text
+    );
+}
+
+/// Save what salvage recovered and open it with the strict reader
+fn reopen(mut database: Database) -> Database {
+    let saved = database
+        .save(&credentials())
+        .expect("recovered vault saves");
+    Database::open(&saved, &credentials()).expect("recovered vault opens")
+}
+
+#[test]
+fn salvage_file_cut_mid_entry() {
+    let (data, xml, offset) = save_with_xml(&mut uncompressed_sample());
+    // Forum has its title and password, and is cut inside its third field's value
+    let cut = offset + find(&xml, "First pet?") + 3;
+    let error = Database::open(&data[..cut], &credentials()).err();
+    assert_eq!(error.map(|e| e.code), Some(ErrorCode::Corrupt));
+
+    let (database, report) = salvage(&data[..cut], &credentials()).expect("salvage");
+    assert!(!report.payload_complete && !report.xml_complete);
+    assert_eq!(report.blocks_recovered, 0);
+    assert_eq!((report.groups, report.entries), (2, 2));
+    assert_eq!(report.history_items, 2);
+    assert_eq!(report.attachments, 2);
+    assert_lost(&report, "The file ends inside payload block 0");
+    assert_lost(&report, "Group \"Root/Internet\" was cut short");
+    assert_lost(
+        &report,
+        "Entry \"Forum\" in \"Root/Internet\" was cut short; fields stored after",
+    );
+    assert_lost(
+        &report,
+        "Entry \"Forum\" in \"Root/Internet\": field \"Security question\" was incomplete",
+    );
+    assert_lost(&report, "The list of deleted objects was lost");
+
+    let forum = database.root.find_entry(FORUM).expect("Forum entry");
+    assert_eq!(string_field(&forum.element, "Title"), Some("Forum"));
+    assert_eq!(string_field(&forum.element, "Password"), Some(""));
+    assert_eq!(string_field(&forum.element, "Security question"), None);
+    assert!(database.root.find_group(RECYCLE_BIN).is_none());
+    assert!(database.root.find_group(EMAIL).is_none());
+
+    let reopened = reopen(database);
+    assert_eq!(reopened.entry_count(), 2);
+    let mail = reopened.root.find_entry(MAIL).expect("Mail entry");
+    assert_eq!(string_field(&mail.element, "Password"), Some("p<a&s\"s"));
+}
+
+#[test]
+fn salvage_file_cut_mid_history() {
+    let (data, xml, offset) = save_with_xml(&mut uncompressed_sample());
+    // Inside the second of Mail's two history items
+    let cut = offset + find(&xml, "Mail (old)");
+    let (database, report) = salvage(&data[..cut], &credentials()).expect("salvage");
+    assert_eq!((report.groups, report.entries), (1, 1));
+    assert_eq!(report.history_items, 1);
+    assert_lost(
+        &report,
+        "Entry \"Mail – ünïcödé ✉\" in \"Root\": its history was cut short; \
+         1 older versions were kept",
+    );
+    assert_lost(&report, "Group \"Root\" was cut short");
+    assert!(database.root.find_group(INTERNET).is_none());
+
+    let reopened = reopen(database);
+    let mail = reopened.root.find_entry(MAIL).expect("Mail entry");
+    assert_eq!(mail.history.len(), 1);
+    assert_eq!(
+        string_field(&mail.history[0], "Password"),
+        Some("first password")
+    );
+    assert_eq!(
+        reopened.binaries.len(),
+        2,
+        "attachments are in the inner header"
+    );
+}
+
+#[test]
+fn salvage_corrupted_block() {
+    // An attachment larger than a block puts the XML into the second one
+    let mut database = uncompressed_sample();
+    let index = database.intern_binary(&Binary {
+        data: vec![0x42; 3 * BLOCK / 2],
+        protected: false,
+    });
+    let forum = database.root.find_entry_mut(FORUM).expect("Forum entry");
+    let mut reference = Element::new("Binary");
+    reference
+        .children
+        .push(Element::with_text("Key", "large.bin"));
+    let mut value = Element::new("Value");
+    value.set_attr("Ref", index.to_string());
+    reference.children.push(value);
+    forum.element.children.push(reference);
+    let data = database.save(&credentials()).expect("save");
+    let (_, header_len) = Header::parse(&data).expect("header");
+    let first_block = header_len + 64;
+    let second_block = first_block + 36 + BLOCK;
+
+    // Second block's HMAC: its data still decrypts, only the end marker is discarded
+    let mut damaged = data.clone();
+    damaged[second_block] ^= 1;
+    let error = Database::open(&damaged, &credentials()).err();
+    assert_eq!(
+        error.map(|e| e.message),
+        Some("Block 1 failed its integrity check".to_string())
+    );
+    let (recovered, report) = salvage(&damaged, &credentials()).expect("salvage");
+    assert!(!report.payload_complete);
+    assert!(report.xml_complete);
+    assert_eq!(report.blocks_recovered, 1);
+    assert_eq!(report.unverified_bytes, data.len() - second_block - 2 * 36);
+    assert_eq!(report.entries, 3);
+    assert_eq!(
+        report.lost,
+        [
+            "Payload block 1 failed its integrity check; it was recovered without \
+          verification and the 36 bytes after it were discarded"
+        ]
+    );
+    let reopened = reopen(recovered);
+    assert_eq!(reopened.entry_count(), 3);
+    let large = reopened
+        .binaries
+        .iter()
+        .find(|b| b.data.len() == 3 * BLOCK / 2);
+    assert!(large.is_some_and(|b| b.data.iter().all(|&byte| byte == 0x42)));
+
+    // First block's data: the attachment pool never ends, so no XML is left
+    let mut damaged = data;
+    damaged[first_block + 36 + 1000] ^= 1;
+    let error = salvage(&damaged, &credentials()).err();
+    assert_eq!(error.map(|e| e.code), Some(ErrorCode::Corrupt));
+}
@@ -0,0 +1,249 @@
+// Deep verification: one damaged copy of the sample vault per kind of finding
+use super::*;
+use crate::kdbx::crypto::{block_hmac_key, hmac_sha256, sha512, transform_key};
+use crate::kdbx::header::Reader;
+use crate::kdbx::verify::{verify, Finding, Severity, VerifyReport};
+use crate::kdbx::DeletedObject;
+
+fn credentials() -> Credentials {
+    Credentials {
+        password: Some("verify".to_string()),
+        key_file: None,
+        challenge_response: Vec::new(),
+    }
+}
+
+fn uncompressed_sample() -> Database {
+    let mut database = sample_vault();
+    database.header.compression = false;
+    database
+}
+
+fn verify_saved(mut database: Database) -> VerifyReport {
+    let data = database.save(&credentials()).expect("save");
+    verify(&data, &credentials()).expect("verify")
+}
+
+/// The only finding of `check`, which must have `severity`
+fn finding<'a>(report: &'a VerifyReport, check: &str, severity: Severity) -> &'a Finding {
+    let found: Vec<&Finding> = report
+        .findings
+        .iter()
+        .filter(|f| f.check == check)
+        .collect();
+    assert_eq!(found.len(), 1, "{:?}", report.findings);
+    assert_eq!(found[0].severity, severity, "{:?}", found[0]);
+    found[0]
+}
+
+/// Decrypt a saved uncompressed KDBX 4 file, let `edit` change the plaintext (inner
+/// header and XML) and encrypt it again with valid HMACs: damage only a buggy writer
+/// could produce
+fn reseal(data: &[u8], edit: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+    let (header, header_len) = Header::parse(data).expect("header");
+    let master_key = credentials().master_key().expect("master key");
+    let composite = master_key.composite(&header).expect("composite key");
+    let transformed = transform_key(&composite, &header.kdf).expect("KDF");
+    let cipher_key = master_key.cipher_key(&header, &transformed).expect("key");
+    let hmac_key = sha512(&[&header.master_seed, transformed.as_slice(), &[1]]);
+
+    let mut reader = Reader::new(&data[header_len + 64..]);
+    reader.take(32).expect("block HMAC");
+    let len = reader.u32().expect("block length") as usize;
+    let encrypted = reader.take(len).expect("block");
+    let mut plain = header
+        .cipher
+        .decrypt(&cipher_key, &header.iv, encrypted)
+        .expect("decrypt");
+    edit(&mut plain);
+    let encrypted = header
+        .cipher
+        .encrypt(&cipher_key, &header.iv, &plain)
+        .expect("encrypt");
+
+    let mut out = data[..header_len + 64].to_vec();
+    for (index, block) in [&encrypted[..], &[]].into_iter().enumerate() {
+        let index = index as u64;
+        let len = (block.len() as u32).to_le_bytes();
+        out.extend_from_slice(&hmac_sha256(
+            block_hmac_key(&hmac_key, index).as_slice(),
+            &[&index.to_le_bytes(), &len, block],
+        ));
+        out.extend_from_slice(&len);
+        out.extend_from_slice(block);
+    }
+    out
+}
+
+#[test]
+fn verify_sample_has_no_findings() {
+    let report = verify_saved(uncompressed_sample());
+    assert!(report.findings.is_empty(), "{:?}", report.findings);
+    assert_eq!(report.format, "KDBX 4.0");
+    assert_eq!(
+        (report.groups, report.entries, report.attachments),
+        (4, 3, 2)
+    );
+    assert_eq!(report.blocks, 1);
+}
+
+#[test]
+fn verify_damaged_header() {
+    let data = uncompressed_sample().save(&credentials()).expect("save");
+    let (_, header_len) = Header::parse(&data).expect("header");
+    // A byte of the public custom data: the header still parses
+    let offset = data[..header_len]
+        .windows(6)
+        .position(|w| w == b"sample")
+        .expect("custom data in the header");
+    let mut damaged = data.clone();
+    damaged[offset] ^= 0x20;
+    let report = verify(&damaged, &credentials()).expect("verify");
+    finding(&report, "headerHash", Severity::Error);
+    finding(&report, "headerHmac", Severity::Error);
+    assert!(!report.is_valid());
+
+    // Only the stored HMAC differs: that is what wrong credentials look like
+    let mut damaged = data;
+    damaged[header_len + 40] ^= 1;
+    let error = verify(&damaged, &credentials()).err();
+    assert_eq!(error.map(|e| e.code), Some(ErrorCode::WrongCredentials));
+}
+
+#[test]
+fn verify_damaged_block() {
+    let data = uncompressed_sample().save(&credentials()).expect("save");
+    let (_, header_len) = Header::parse(&data).expect("header");
+    let mut damaged = data;
+    damaged[header_len + 64 + 36 + 100] ^= 1;
+    let report = verify(&damaged, &credentials()).expect("verify");
+    let block = finding(&report, "blockHmac", Severity::Error);
+    assert_eq!(block.message, "Payload block 0 failed its integrity check");
+    assert_eq!(report.blocks, 0);
+    assert_eq!(report.entries, 0, "nothing past a bad block is decoded");
+}
+
+#[test]
+fn verify_duplicate_uuid() {
+    let mut database = uncompressed_sample();
+    let copy = database
+        .root
+        .find_entry(FORUM)
+        .expect("Forum entry")
+        .clone();
+    database
+        .root
+        .find_group_mut(EMAIL)
+        .expect("eMail group")
+        .entries
+        .push(copy);
+    let report = verify_saved(database);
+    let duplicate = finding(&report, "duplicateUuid", Severity::Error);
+    assert_eq!(duplicate.uuid.as_deref(), Some(FORUM));
+}
+
+#[test]
+fn verify_dangling_attachment() {
+    let mut database = uncompressed_sample();
+    let forum = database.root.find_entry_mut(FORUM).expect("Forum entry");
+    let mut binary = Element::new("Binary");
+    binary.children.push(Element::with_text("Key", "gone.txt"));
+    let mut value = Element::new("Value");
+    value.set_attr("Ref", "7");
+    binary.children.push(value);
+    forum.element.children.push(binary);
+    let report = verify_saved(database);
+    let dangling = finding(&report, "danglingAttachment", Severity::Error);
+    assert_eq!(dangling.uuid.as_deref(), Some(FORUM));
+    assert!(dangling
+        .message
+        .contains("\"gone.txt\" refers to pool item 7"));
+}
+
+#[test]
+fn verify_orphaned_attachment() {
+    let data = uncompressed_sample().save(&credentials()).expect("save");
+    // One more attachment just before the end of the inner header
+    let data = reseal(&data, |plain| {
+        let mut reader = Reader::new(plain);
+        loop {
+            let start = reader.position();
+            let id = reader.u8().expect("field id");
+            let len = reader.u32().expect("field length") as usize;
+            reader.take(len).expect("field");
+            if id == 0 {
+                let mut field = vec![3];
+                field.extend_from_slice(&6u32.to_le_bytes());
+                field.extend_from_slice(b"\x00extra");
+                plain.splice(start..start, field);
+                break;
+            }
+        }
+    });
+    let report = verify(&data, &credentials()).expect("verify");
+    let orphan = finding(&report, "orphanedAttachment", Severity::Info);
+    assert!(orphan.message.starts_with("Attachment 2 (5 bytes)"));
+    assert!(report.is_valid());
+    assert_eq!(report.attachments, 2);
+}
+
+#[test]
+fn verify_recycle_bin() {
+    let with_bin = |uuid: &str, enabled: bool| {
+        let mut database = uncompressed_sample();
+        database.meta.set_child_text("RecycleBinUUID", uuid);
+        let enabled = if enabled { "True" } else { "False" };
+        database.meta.set_child_text("RecycleBinEnabled", enabled);
+        verify_saved(database)
+    };
+
+    let report = with_bin(ROOT, true);
+    let root = finding(&report, "recycleBin", Severity::Error);
+    assert!(root.message.contains("is the root group"));
+    let report = with_bin(MAIL, true);
+    let entry = finding(&report, "recycleBin", Severity::Warning);
+    assert!(entry.message.contains("points at an entry"));
+    let missing = "ERERERERERERERERERERQ==";
+    let report = with_bin(missing, true);
+    let gone = finding(&report, "recycleBin", Severity::Warning);
+    assert_eq!(gone.uuid.as_deref(), Some(missing));
+    let report = with_bin(missing, false);
+    finding(&report, "recycleBin", Severity::Info);
+    let report = with_bin("AAAAAAAAAAAAAAAAAAAAAA==", true);
+    assert!(report.findings.is_empty(), "no recycle bin is fine");
+}
+
+#[test]
+fn verify_deleted_objects_that_still_exist() {
+    let mut database = uncompressed_sample();
+    database.deleted_objects.push(DeletedObject {
+        uuid: FORUM.to_string(),
+        deleted_at: xml::parse_time("2024-05-06T10:00:00Z").expect("time"),
+    });
+    let report = verify_saved(database);
+    let deleted = finding(&report, "deletedObjects", Severity::Warning);
+    assert_eq!(deleted.uuid.as_deref(), Some(FORUM));
+}
+
+#[test]
+fn verify_future_timestamps() {
+    let mut database = uncompressed_sample();
+    let forum = database.root.find_entry_mut(FORUM).expect("Forum entry");
+    set_time(
+        &mut forum.element,
+        "LastModificationTime",
+        "2999-01-01T00:00:00Z",
+    );
+    // Expiry is meant to lie ahead
+    set_time(&mut forum.element, "ExpiryTime", "2999-01-01T00:00:00Z");
+    let report = verify_saved(database);
+    let future = finding(&report, "futureTime", Severity::Warning);
+    assert_eq!(future.uuid.as_deref(), Some(FORUM));
+    assert!(
+        future
+            .message
+            .ends_with("timestamps in the future: LastModificationTime"),
+        "{}",
+        future.message
+    );
+}
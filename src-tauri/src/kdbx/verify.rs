@@ -0,0 +1,711 @@
+// Deep verification of a vault file
+//
+// `Database::open` stops at the first problem; this goes through the whole file and
+// reports every finding with a severity. The header and each payload block are checked
+// on their own, then the inner header, the XML structure and the consistency of the
+// decoded tree: UUIDs, attachment references, the recycle bin and timestamps.
+// Errors mean data is damaged or other clients may refuse the file; warnings mean the
+// file loads but something is off; infos are harmless and fixed by the next save.
+use super::crypto::{self, sha256, sha512, InnerStream};
+use super::header::{Header, Reader};
+use super::model::{Entry, Group, Node};
+use super::xml::{self, Element};
+use super::{gunzip, inner_field, read_inner_header, wrong_credentials, Credentials};
+use super::{Database, Payload};
+use crate::error::AppResult;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use zeroize::Zeroizing;
+
+// KeePass writes an all-zero UUID when there is no recycle bin
+const ZERO_UUID: &str = "AAAAAAAAAAAAAAAAAAAAAA==";
+
+// Clock skew between devices tolerated before a timestamp counts as in the future
+const FUTURE_TOLERANCE_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub severity: Severity,
+    /// Check that produced the finding, e.g. `blockHmac` or `duplicateUuid`
+    pub check: &'static str,
+    pub message: String,
+    /// Base64 UUID of the group or entry concerned
+    pub uuid: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyReport {
+    /// e.g. "KDBX 4.1"; empty when the header could not be read
+    pub format: String,
+    /// Payload blocks that passed their HMAC or hash check
+    pub blocks: usize,
+    pub groups: usize,
+    pub entries: usize,
+    pub attachments: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl VerifyReport {
+    /// No errors; warnings and infos are allowed
+    pub fn is_valid(&self) -> bool {
+        !self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+
+    fn add(
+        &mut self,
+        severity: Severity,
+        check: &'static str,
+        message: impl Into<String>,
+        uuid: Option<&str>,
+    ) {
+        self.findings.push(Finding {
+            severity,
+            check,
+            message: message.into(),
+            uuid: uuid.map(str::to_string),
+        });
+    }
+}
+
+/// Verify every layer of the vault file `data`. Wrong credentials are an error (so the
+/// caller can ask again); everything wrong with the file itself is a finding.
+pub fn verify(data: &[u8], credentials: &Credentials) -> AppResult<VerifyReport> {
+    let mut report = VerifyReport::default();
+    let (header, header_len) = match Header::parse(data) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            report.add(Severity::Error, "header", e.message, None);
+            return Ok(report);
+        }
+    };
+    report.format = format!("KDBX {}.{}", header.version >> 16, header.version & 0xffff);
+
+    let master_key = credentials.master_key()?;
+    let composite = master_key.composite(&header)?;
+    let transformed = match crypto::transform_key(&composite, &header.kdf) {
+        Ok(key) => key,
+        Err(e) => {
+            report.add(Severity::Error, "header", e.message, None);
+            return Ok(report);
+        }
+    };
+    let cipher_key = master_key.cipher_key(&header, &transformed)?;
+
+    let payload = if header.is_v4() {
+        check_v4(
+            data,
+            header_len,
+            &header,
+            &cipher_key,
+            &transformed,
+            &mut report,
+        )?
+    } else {
+        check_v3(&data[header_len..], &header, &cipher_key, &mut report)?
+    };
+    let Some(payload) = payload else {
+        return Ok(report);
+    };
+    match Database::from_payload(header, payload) {
+        Ok(database) => check_database(&database, &mut report),
+        Err(e) => report.add(Severity::Error, "xml", e.message, None),
+    }
+    Ok(report)
+}
+
+/// Header and block checks of KDBX 4; the decrypted payload if every block verified
+fn check_v4(
+    data: &[u8],
+    header_len: usize,
+    header: &Header,
+    cipher_key: &[u8; 32],
+    transformed: &[u8; 32],
+    report: &mut VerifyReport,
+) -> AppResult<Option<Payload>> {
+    let header_bytes = &data[..header_len];
+    let mut reader = Reader::new(&data[header_len..]);
+    let (Ok(stored_hash), Ok(stored_hmac)) = (reader.take(32), reader.take(32)) else {
+        report.add(
+            Severity::Error,
+            "header",
+            "The file ends right after its header",
+            None,
+        );
+        return Ok(None);
+    };
+    let hash_ok = sha256(&[header_bytes])[..] == stored_hash[..];
+    if !hash_ok {
+        report.add(
+            Severity::Error,
+            "headerHash",
+            "Header checksum mismatch",
+            None,
+        );
+    }
+    let hmac_key = Zeroizing::new(sha512(&[&header.master_seed, transformed, &[1]]));
+    if !crypto::verify_hmac(
+        crypto::block_hmac_key(&hmac_key, u64::MAX).as_slice(),
+        &[header_bytes],
+        stored_hmac,
+    ) {
+        if hash_ok {
+            return Err(wrong_credentials());
+        }
+        report.add(
+            Severity::Error,
+            "headerHmac",
+            "The header is damaged, so the payload cannot be checked",
+            None,
+        );
+        return Ok(None);
+    }
+
+    // Every block carries its own HMAC, so a bad block does not stop the check
+    let mut encrypted = Vec::new();
+    let mut damaged = false;
+    for index in 0u64.. {
+        let (Ok(mac), Ok(len_bytes)) = (reader.take(32), reader.take(4)) else {
+            report.add(
+                Severity::Error,
+                "blockHmac",
+                format!("The file ends before payload block {}", index),
+                None,
+            );
+            damaged = true;
+            break;
+        };
+        let len = u32::from_le_bytes(len_bytes.try_into().expect("4 bytes")) as usize;
+        let Ok(block) = reader.take(len) else {
+            report.add(
+                Severity::Error,
+                "blockHmac",
+                format!(
+                    "The file ends inside payload block {} ({} of {} bytes present)",
+                    index,
+                    reader.remaining().len(),
+                    len
+                ),
+                None,
+            );
+            damaged = true;
+            break;
+        };
+        if !crypto::verify_hmac(
+            crypto::block_hmac_key(&hmac_key, index).as_slice(),
+            &[&index.to_le_bytes(), len_bytes, block],
+            mac,
+        ) {
+            report.add(
+                Severity::Error,
+                "blockHmac",
+                format!("Payload block {} failed its integrity check", index),
+                None,
+            );
+            damaged = true;
+            if len == 0 {
+                break;
+            }
+            continue;
+        }
+        if len == 0 {
+            break;
+        }
+        report.blocks += 1;
+        encrypted.extend_from_slice(block);
+    }
+    if !damaged && !reader.remaining().is_empty() {
+        report.add(
+            Severity::Warning,
+            "blockHmac",
+            format!(
+                "{} bytes of unknown data follow the payload",
+                reader.remaining().len()
+            ),
+            None,
+        );
+    }
+    if damaged {
+        return Ok(None);
+    }
+
+    let plain = match header.cipher.decrypt(cipher_key, &header.iv, &encrypted) {
+        Ok(plain) => Zeroizing::new(plain),
+        Err(e) => {
+            report.add(Severity::Error, "payload", e.message, None);
+            return Ok(None);
+        }
+    };
+    let plain = if header.compression {
+        match gunzip(&plain) {
+            Ok(plain) => Zeroizing::new(plain),
+            Err(e) => {
+                report.add(Severity::Error, "payload", e.message, None);
+                return Ok(None);
+            }
+        }
+    } else {
+        plain
+    };
+
+    check_inner_header(&plain, report);
+    match read_inner_header(&plain) {
+        Ok(payload) => Ok(Some(payload)),
+        Err(e) => {
+            report.add(Severity::Error, "innerHeader", e.message, None);
+            Ok(None)
+        }
+    }
+}
+
+/// Findings `read_inner_header` does not fail on
+fn check_inner_header(plain: &[u8], report: &mut VerifyReport) {
+    let mut reader = Reader::new(plain);
+    let mut binaries = 0;
+    while let (Ok(id), Ok(len)) = (reader.u8(), reader.u32()) {
+        let Ok(value) = reader.take(len as usize) else {
+            return;
+        };
+        match id {
+            inner_field::END => return,
+            inner_field::STREAM_ID => {
+                let known = value
+                    .try_into()
+                    .ok()
+                    .map(u32::from_le_bytes)
+                    .is_some_and(|id| InnerStream::new(id, &[0; 64]).is_ok());
+                if !known {
+                    report.add(
+                        Severity::Error,
+                        "innerHeader",
+                        "Protected values use an unsupported stream cipher",
+                        None,
+                    );
+                }
+            }
+            inner_field::STREAM_KEY => {}
+            inner_field::BINARY => {
+                if value.len() > 1 && value[0] & !1 != 0 {
+                    report.add(
+                        Severity::Warning,
+                        "innerHeader",
+                        format!(
+                            "Attachment {} has unknown flags 0x{:02x}",
+                            binaries, value[0]
+                        ),
+                        None,
+                    );
+                }
+                binaries += 1;
+            }
+            other => report.add(
+                Severity::Warning,
+                "innerHeader",
+                format!(
+                    "Unknown inner header field {} ({} bytes) is ignored",
+                    other, len
+                ),
+                None,
+            ),
+        }
+    }
+}
+
+/// Header and block checks of KDBX 3; the decrypted payload if every block verified
+fn check_v3(
+    encrypted: &[u8],
+    header: &Header,
+    cipher_key: &[u8; 32],
+    report: &mut VerifyReport,
+) -> AppResult<Option<Payload>> {
+    // Decrypt without the padding check first: the start bytes tell a wrong key from
+    // a payload that is cut off
+    let plain = match header.cipher.decrypt(cipher_key, &header.iv, encrypted) {
+        Ok(plain) => Zeroizing::new(plain),
+        Err(_) => {
+            let plain = Zeroizing::new(
+                header
+                    .cipher
+                    .decrypt_prefix(cipher_key, &header.iv, encrypted)?,
+            );
+            if plain.len() < 32 || plain[..32] != header.stream_start_bytes[..] {
+                return Err(wrong_credentials());
+            }
+            report.add(
+                Severity::Error,
+                "payload",
+                "The encrypted payload is cut off or its padding is damaged",
+                None,
+            );
+            plain
+        }
+    };
+    if plain.len() < 32 || plain[..32] != header.stream_start_bytes[..] {
+        return Err(wrong_credentials());
+    }
+
+    // Block hashes are independent of each other, so keep checking past a bad one
+    let mut reader = Reader::new(&plain[32..]);
+    let mut content = Zeroizing::new(Vec::new());
+    let mut damaged = !report.is_valid();
+    for expected in 0u32.. {
+        let (Ok(index), Ok(hash), Ok(len)) = (reader.u32(), reader.take(32), reader.u32()) else {
+            report.add(
+                Severity::Error,
+                "blockHash",
+                format!("The payload ends before block {}", expected),
+                None,
+            );
+            damaged = true;
+            break;
+        };
+        if index != expected {
+            report.add(
+                Severity::Warning,
+                "blockHash",
+                format!("Payload block {} is numbered {}", expected, index),
+                None,
+            );
+        }
+        if len == 0 {
+            break;
+        }
+        let Ok(block) = reader.take(len as usize) else {
+            report.add(
+                Severity::Error,
+                "blockHash",
+                format!("The payload ends inside block {}", expected),
+                None,
+            );
+            damaged = true;
+            break;
+        };
+        if sha256(&[block])[..] != hash[..] {
+            report.add(
+                Severity::Error,
+                "blockHash",
+                format!("Payload block {} failed its hash check", expected),
+                None,
+            );
+            damaged = true;
+            continue;
+        }
+        report.blocks += 1;
+        content.extend_from_slice(block);
+    }
+    if damaged {
+        return Ok(None);
+    }
+
+    let xml = if header.compression {
+        match gunzip(&content) {
+            Ok(xml) => xml,
+            Err(e) => {
+                report.add(Severity::Error, "payload", e.message, None);
+                return Ok(None);
+            }
+        }
+    } else {
+        content.to_vec()
+    };
+    match InnerStream::new(header.inner_stream_id, &header.protected_stream_key) {
+        Ok(stream) => Ok(Some(Payload {
+            xml,
+            stream,
+            binaries: Vec::new(),
+        })),
+        Err(e) => {
+            report.add(Severity::Error, "header", e.message, None);
+            Ok(None)
+        }
+    }
+}
+
+/// Consistency checks on the decoded tree
+fn check_database(database: &Database, report: &mut VerifyReport) {
+    let mut checker = TreeChecker {
+        report,
+        seen: HashMap::new(),
+        referenced: HashSet::new(),
+        pool_len: database.binaries.len(),
+        future: Utc::now() + Duration::minutes(FUTURE_TOLERANCE_MINUTES),
+    };
+    checker.group(&database.root, "");
+
+    let TreeChecker {
+        report,
+        seen,
+        referenced,
+        ..
+    } = checker;
+    for (uuid, count) in &seen {
+        if *count > 1 {
+            report.add(
+                Severity::Error,
+                "duplicateUuid",
+                format!("{} groups or entries share the UUID {}", count, uuid),
+                Some(uuid),
+            );
+        }
+    }
+    for index in (0..database.binaries.len()).filter(|i| !referenced.contains(i)) {
+        report.add(
+            Severity::Info,
+            "orphanedAttachment",
+            format!(
+                "Attachment {} ({} bytes) is not used by any entry and will be dropped on the \
+                 next save",
+                index,
+                database.binaries[index].data.len()
+            ),
+            None,
+        );
+    }
+
+    check_recycle_bin(database, report);
+    for object in &database.deleted_objects {
+        if seen.contains_key(&object.uuid) {
+            report.add(
+                Severity::Warning,
+                "deletedObjects",
+                format!(
+                    "{} is listed as deleted but still exists; syncing may delete it",
+                    object.uuid
+                ),
+                Some(&object.uuid),
+            );
+        }
+    }
+
+    report.groups = count_groups(&database.root);
+    report.entries = database.entry_count();
+    report.attachments = referenced.len();
+}
+
+fn count_groups(group: &Group) -> usize {
+    1 + group.groups.iter().map(count_groups).sum::<usize>()
+}
+
+fn check_recycle_bin(database: &Database, report: &mut VerifyReport) {
+    let enabled = database
+        .meta
+        .child_text("RecycleBinEnabled")
+        .is_none_or(|v| v.eq_ignore_ascii_case("true"));
+    let Some(uuid) = database
+        .meta
+        .child_text("RecycleBinUUID")
+        .map(str::trim)
+        .filter(|u| !u.is_empty() && *u != ZERO_UUID)
+    else {
+        return;
+    };
+    if database.root.uuid() == uuid {
+        report.add(
+            Severity::Error,
+            "recycleBin",
+            "The recycle bin is the root group; deleting an entry would delete everything",
+            Some(uuid),
+        );
+    } else if database.root.find_group(uuid).is_none() {
+        let message = if database.root.find_entry(uuid).is_some() {
+            "The recycle bin points at an entry instead of a group"
+        } else if enabled {
+            "The recycle bin group no longer exists; a new one is created on the next delete"
+        } else {
+            "The disabled recycle bin points at a group that no longer exists"
+        };
+        let severity = if enabled {
+            Severity::Warning
+        } else {
+            Severity::Info
+        };
+        report.add(severity, "recycleBin", message, Some(uuid));
+    }
+}
+
+struct TreeChecker<'a> {
+    report: &'a mut VerifyReport,
+    /// Occurrences of each group and entry UUID (history items excluded)
+    seen: HashMap<String, usize>,
+    /// Attachment pool indices used by entries or their history
+    referenced: HashSet<usize>,
+    pool_len: usize,
+    future: chrono::DateTime<Utc>,
+}
+
+impl TreeChecker<'_> {
+    fn group(&mut self, group: &Group, parent_path: &str) {
+        let path = if parent_path.is_empty() {
+            group.name().to_string()
+        } else {
+            format!("{}/{}", parent_path, group.name())
+        };
+        let label = format!("Group \"{}\"", path);
+        self.uuid(group, &label);
+        if group.element.child("Name").is_none() {
+            self.report.add(
+                Severity::Warning,
+                "schema",
+                format!("{} has no name", label),
+                None,
+            );
+        }
+        self.times(&group.element, &label);
+
+        for entry in &group.entries {
+            self.entry(entry, &path);
+        }
+        for child in &group.groups {
+            self.group(child, &path);
+        }
+    }
+
+    fn entry(&mut self, entry: &Entry, path: &str) {
+        let title = Some(entry.title())
+            .filter(|t| !t.is_empty())
+            .unwrap_or("(untitled)");
+        let label = format!("Entry \"{}\" in \"{}\"", title, path);
+        self.uuid(entry, &label);
+        self.times(&entry.element, &label);
+        self.fields(&entry.element, entry.uuid(), &label);
+        for item in &entry.history {
+            let label = format!("{} (history)", label);
+            if item.uuid() != entry.uuid() {
+                self.report.add(
+                    Severity::Warning,
+                    "schema",
+                    format!("{} has a different UUID than its entry", label),
+                    Some(entry.uuid()),
+                );
+            }
+            self.times(item, &label);
+            self.fields(item, entry.uuid(), &label);
+        }
+    }
+
+    /// Presence and format of a group or entry UUID, counted for the duplicate check
+    fn uuid(&mut self, node: &dyn Node, label: &str) {
+        let Some(text) = node.element().child_text("UUID") else {
+            self.report.add(
+                Severity::Error,
+                "schema",
+                format!("{} has no UUID", label),
+                None,
+            );
+            return;
+        };
+        let valid = BASE64
+            .decode(text.trim())
+            .is_ok_and(|bytes| bytes.len() == 16);
+        if !valid {
+            self.report.add(
+                Severity::Error,
+                "schema",
+                format!("{} has an invalid UUID", label),
+                Some(text),
+            );
+        }
+        *self.seen.entry(text.to_string()).or_default() += 1;
+    }
+
+    fn times(&mut self, element: &Element, label: &str) {
+        let Some(times) = element.child("Times") else {
+            return;
+        };
+        let uuid = element.child_text("UUID");
+        let mut future = Vec::new();
+        for child in &times.children {
+            let is_time = matches!(
+                child.name.as_str(),
+                "CreationTime" | "LastModificationTime" | "LastAccessTime" | "LocationChanged"
+            );
+            if !is_time && child.name != "ExpiryTime" {
+                continue;
+            }
+            match xml::parse_time(&child.text) {
+                None => self.report.add(
+                    Severity::Error,
+                    "schema",
+                    format!("{} has an unreadable {}", label, child.name),
+                    uuid,
+                ),
+                // Expiry is meant to be in the future
+                Some(time) if is_time && time > self.future => future.push(child.name.as_str()),
+                Some(_) => {}
+            }
+        }
+        if !future.is_empty() {
+            self.report.add(
+                Severity::Warning,
+                "futureTime",
+                format!(
+                    "{} has timestamps in the future: {}",
+                    label,
+                    future.join(", ")
+                ),
+                uuid,
+            );
+        }
+    }
+
+    /// `<String>` and `<Binary>` fields of an entry or history item
+    fn fields(&mut self, element: &Element, uuid: &str, label: &str) {
+        let mut keys = HashSet::new();
+        for string in element.children_named("String") {
+            match (string.child_text("Key"), string.child("Value")) {
+                (Some(key), Some(_)) => {
+                    if !keys.insert(key) {
+                        self.report.add(
+                            Severity::Error,
+                            "schema",
+                            format!("{} has field \"{}\" more than once", label, key),
+                            Some(uuid),
+                        );
+                    }
+                }
+                _ => self.report.add(
+                    Severity::Error,
+                    "schema",
+                    format!("{} has a field without a name or value", label),
+                    Some(uuid),
+                ),
+            }
+        }
+        for binary in element.children_named("Binary") {
+            let key = binary.child_text("Key").unwrap_or("?");
+            let reference = binary.child("Value").and_then(|v| v.attr("Ref"));
+            match reference.and_then(|r| r.trim().parse::<usize>().ok()) {
+                Some(index) if index < self.pool_len => {
+                    self.referenced.insert(index);
+                }
+                Some(index) => self.report.add(
+                    Severity::Error,
+                    "danglingAttachment",
+                    format!(
+                        "{}: attachment \"{}\" refers to pool item {}, which does not exist",
+                        label, key, index
+                    ),
+                    Some(uuid),
+                ),
+                None => self.report.add(
+                    Severity::Error,
+                    "schema",
+                    format!("{}: attachment \"{}\" has no valid reference", label, key),
+                    Some(uuid),
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+// Minimal DOM for the KeePass XML payload
+//
+// Elements we do not model are kept verbatim so that saving never drops data written
+// by other KeePass clients. Protected values are decrypted while parsing and
+// re-encrypted while writing, both in document order as the inner stream requires.
+use super::corrupt;
+use super::crypto::InnerStream;
+use crate::error::AppResult;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+// Deepest element nesting accepted. Real vaults stay far below (each group level adds
+// one); the limit keeps the recursive walks over the tree within the stack.
+const MAX_DEPTH: usize = 512;
+
+// Seconds between 0001-01-01 (KDBX 4 epoch) and 1970-01-01
+const KDBX4_EPOCH_OFFSET: i64 = 62_135_596_800;
+
+/// Elements whose text is a timestamp; their format differs between KDBX 3 and 4
+const TIME_FIELDS: &[&str] = &[
+    "CreationTime",
+    "LastModificationTime",
+    "LastAccessTime",
+    "ExpiryTime",
+    "LocationChanged",
+    "DeletionTime",
+    "DatabaseNameChanged",
+    "DatabaseDescriptionChanged",
+    "DefaultUserNameChanged",
+    "MasterKeyChanged",
+    "RecycleBinChanged",
+    "EntryTemplatesGroupChanged",
+    "SettingsChanged",
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_text(name: &str, text: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_attr(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+        match self.attrs.iter_mut().find(|(k, _)| k == name) {
+            Some(attr) => attr.1 = value,
+            None => self.attrs.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.attr("Protected")
+            .is_some_and(|v| v.eq_ignore_ascii_case("true"))
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.children.iter_mut().find(|c| c.name == name)
+    }
+
+    /// Existing child `name`, appended if missing
+    pub fn child_or_insert(&mut self, name: &str) -> &mut Element {
+        match self.children.iter().position(|c| c.name == name) {
+            Some(index) => &mut self.children[index],
+            None => {
+                self.children.push(Element::new(name));
+                self.children.last_mut().unwrap()
+            }
+        }
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|c| c.text.as_str())
+    }
+
+    pub fn set_child_text(&mut self, name: &str, text: impl Into<String>) {
+        self.child_or_insert(name).text = text.into();
+    }
+
+    pub fn remove_children(&mut self, name: &str) -> Vec<Element> {
+        let (removed, kept) = std::mem::take(&mut self.children)
+            .into_iter()
+            .partition(|c| c.name == name);
+        self.children = kept;
+        removed
+    }
+
+    /// Timestamp stored in child `name` (either KDBX 3 or KDBX 4 encoding)
+    pub fn child_time(&self, name: &str) -> Option<DateTime<Utc>> {
+        self.child_text(name).and_then(parse_time)
+    }
+}
+
+pub fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if text.contains('-') || text.contains(':') {
+        return DateTime::parse_from_rfc3339(text)
+            .map(|t| t.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S")
+                    .ok()
+                    .map(|t| t.and_utc())
+            });
+    }
+    let bytes: [u8; 8] = BASE64.decode(text).ok()?.try_into().ok()?;
+    let seconds = i64::from_le_bytes(bytes).checked_sub(KDBX4_EPOCH_OFFSET)?;
+    DateTime::from_timestamp(seconds, 0)
+}
+
+pub fn format_time(time: DateTime<Utc>, v4: bool) -> String {
+    if v4 {
+        let seconds = time.timestamp() + KDBX4_EPOCH_OFFSET;
+        BASE64.encode(seconds.to_le_bytes())
+    } else {
+        time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    }
+}
+
+/// Rewrite every timestamp in `element` to the encoding of the target format
+pub fn normalize_times(element: &mut Element, v4: bool) {
+    if element.children.is_empty() && TIME_FIELDS.contains(&element.name.as_str()) {
+        if let Some(time) = parse_time(&element.text) {
+            element.text = format_time(time, v4);
+        }
+    }
+    for child in &mut element.children {
+        normalize_times(child, v4);
+    }
+}
+
+fn xml_error(e: impl std::fmt::Display) -> crate::error::AppError {
+    corrupt(format!("Invalid database XML: {}", e))
+}
+
+/// Parse an XML document, decrypting protected values with `stream` (the database
+/// payload) or rejecting them when there is none (key files)
+pub fn parse_document(xml: &[u8], stream: Option<&mut InnerStream>) -> AppResult<Element> {
+    let xml = xml.strip_prefix(b"\xef\xbb\xbf").unwrap_or(xml);
+    let xml = std::str::from_utf8(xml).map_err(xml_error)?;
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    parse_into(xml, stream, &mut stack, &mut root)?;
+
+    if !stack.is_empty() {
+        return Err(xml_error("unexpected end of document"));
+    }
+    root.ok_or_else(|| xml_error("document is empty"))
+}
+
+/// What could be read from a damaged document
+pub struct PartialDocument {
+    pub root: Option<Element>,
+    /// Why parsing stopped early, if it did
+    pub error: Option<String>,
+    /// Elements still open where parsing stopped, outermost first; each one was kept as
+    /// the last child of its parent. An innermost open element without children is
+    /// dropped instead, since its text may be cut short
+    pub unclosed: Vec<String>,
+}
+
+/// Parse as much of `xml` as is well-formed and close whatever is still open at the
+/// first error or at the end of the data
+pub fn parse_partial(xml: &[u8], stream: Option<&mut InnerStream>) -> PartialDocument {
+    let xml = xml.strip_prefix(b"\xef\xbb\xbf").unwrap_or(xml);
+    let (xml, mut error) = match std::str::from_utf8(xml) {
+        Ok(text) => (text, None),
+        Err(e) => (
+            // Checked by from_utf8 just above
+            std::str::from_utf8(&xml[..e.valid_up_to()]).unwrap_or_default(),
+            Some(format!("invalid UTF-8 at byte {}", e.valid_up_to())),
+        ),
+    };
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    if let Err(e) = parse_into(xml, stream, &mut stack, &mut root) {
+        error.get_or_insert(e.message);
+    }
+
+    if stack.last().is_some_and(|e| e.children.is_empty()) {
+        stack.pop();
+    }
+    let unclosed = stack.iter().map(|e| e.name.clone()).collect();
+    while let Some(mut element) = stack.pop() {
+        element.text.clear();
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => root = Some(element),
+        }
+    }
+    PartialDocument {
+        root,
+        error,
+        unclosed,
+    }
+}
+
+/// Event loop shared by the strict and partial parsers; closed elements are attached to
+/// their parent (or `root`) as they finish, so `stack` holds whatever is still open
+fn parse_into(
+    xml: &str,
+    mut stream: Option<&mut InnerStream>,
+    stack: &mut Vec<Element>,
+    root: &mut Option<Element>,
+) -> AppResult<()> {
+    let mut reader = Reader::from_str(xml);
+
+    let open = |e: &quick_xml::events::BytesStart| -> AppResult<Element> {
+        let mut element = Element::new(&String::from_utf8_lossy(e.name().as_ref()));
+        for attr in e.attributes() {
+            let attr = attr.map_err(xml_error)?;
+            element.attrs.push((
+                String::from_utf8_lossy(attr.key.as_ref()).into_owned(),
+                attr.unescape_value().map_err(xml_error)?.into_owned(),
+            ));
+        }
+        Ok(element)
+    };
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => {
+                if stack.len() >= MAX_DEPTH {
+                    return Err(xml_error("elements are nested too deeply"));
+                }
+                stack.push(open(&e)?)
+            }
+            Event::Empty(e) => {
+                let element = close(open(&e)?, stream.as_deref_mut())?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => *root = Some(element),
+                }
+            }
+            Event::Text(t) => {
+                if let Some(current) = stack.last_mut() {
+                    current.text.push_str(&t.unescape().map_err(xml_error)?);
+                }
+            }
+            Event::CData(c) => {
+                if let Some(current) = stack.last_mut() {
+                    current
+                        .text
+                        .push_str(&String::from_utf8_lossy(&c.into_inner()));
+                }
+            }
+            Event::End(_) => {
+                let element = stack.pop().ok_or_else(|| xml_error("unbalanced tags"))?;
+                let element = close(element, stream.as_deref_mut())?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => *root = Some(element),
+                }
+            }
+            Event::Eof => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Finish an element: drop formatting whitespace and decrypt protected text
+fn close(mut element: Element, stream: Option<&mut InnerStream>) -> AppResult<Element> {
+    if !element.children.is_empty() {
+        element.text.clear();
+    } else if element.is_protected() {
+        let stream = stream.ok_or_else(|| corrupt("Unexpected protected value"))?;
+        let mut data = BASE64
+            .decode(element.text.trim())
+            .map_err(|_| corrupt("Invalid protected value"))?;
+        stream.apply(&mut data);
+        element.text =
+            String::from_utf8(data).map_err(|_| corrupt("Protected value is not valid UTF-8"))?;
+    }
+    Ok(element)
+}
+
+fn escape(text: &str, attribute: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '\r' => out.push_str("&#xD;"),
+            '\n' if attribute => out.push_str("&#xA;"),
+            // Characters XML 1.0 cannot represent; KeePass strips them as well
+            c if (c as u32) < 0x20 && c != '\t' && c != '\n' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Serialize the document, encrypting protected values with `stream`
+pub fn write_document(root: &Element, stream: &mut InnerStream) -> Vec<u8> {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n");
+    write_element(root, 0, stream, &mut out);
+    out.into_bytes()
+}
+
+fn write_element(element: &Element, depth: usize, stream: &mut InnerStream, out: &mut String) {
+    for _ in 0..depth {
+        out.push('\t');
+    }
+    out.push('<');
+    out.push_str(&element.name);
+    for (key, value) in &element.attrs {
+        out.push(' ');
+        out.push_str(key);
+        out.push_str("=\"");
+        out.push_str(&escape(value, true));
+        out.push('"');
+    }
+
+    if !element.children.is_empty() {
+        out.push_str(">\n");
+        for child in &element.children {
+            write_element(child, depth + 1, stream, out);
+        }
+        for _ in 0..depth {
+            out.push('\t');
+        }
+    } else if element.is_protected() {
+        let mut data = element.text.as_bytes().to_vec();
+        stream.apply(&mut data);
+        out.push('>');
+        out.push_str(&BASE64.encode(&data));
+    } else if element.text.is_empty() {
+        out.push_str(" />\n");
+        return;
+    } else {
+        out.push('>');
+        out.push_str(&escape(&element.text, false));
+    }
+    out.push_str("</");
+    out.push_str(&element.name);
+    out.push_str(">\n");
+}
@@ -0,0 +1,5 @@
+// Vault engine and file parsers, built as a library so the fuzz targets in
+// `src-tauri/fuzz` can link them without the app
+pub mod error;
+pub mod import;
+pub mod kdbx;
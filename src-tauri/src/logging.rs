@@ -0,0 +1,249 @@
+// Application logging with on-disk rotation and redaction of sensitive values
+//
+// Records are written to `<app data>/logs/keedavault.log` and rotated by size.
+// Vault paths are redacted unless the user opts into verbose logging from
+// Settings. Secrets (passwords, key material) must never be passed to the logger.
+use crate::error::AppResult;
+use crate::settings;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::{command, Manager};
+
+const LOG_DIR_NAME: &str = "logs";
+const LOG_FILE_NAME: &str = "keedavault.log";
+const MAX_LOG_SIZE: u64 = 1024 * 1024; // 1 MiB per file
+const MAX_LOG_FILES: usize = 5; // current file + 4 rotated ones
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static LOGGER: OnceLock<FileLogger> = OnceLock::new();
+
+struct FileLogger {
+    dir: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileLogger {
+    fn current_path(&self) -> PathBuf {
+        self.dir.join(LOG_FILE_NAME)
+    }
+
+    fn open(&self) -> Option<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.current_path())
+            .ok()
+    }
+
+    /// Shift keedavault.log -> keedavault.1.log -> ... and drop the oldest file
+    fn rotate(&self) {
+        let oldest = rotated_path(&self.dir, MAX_LOG_FILES - 1);
+        let _ = fs::remove_file(oldest);
+        for index in (1..MAX_LOG_FILES - 1).rev() {
+            let from = rotated_path(&self.dir, index);
+            if from.exists() {
+                let _ = fs::rename(&from, rotated_path(&self.dir, index + 1));
+            }
+        }
+        let _ = fs::rename(self.current_path(), rotated_path(&self.dir, 1));
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let target = record.target();
+        let target = target.strip_prefix("keedavault::").unwrap_or(target);
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            record.level(),
+            target,
+            record.args()
+        );
+
+        #[cfg(debug_assertions)]
+        print!("{}", line);
+
+        let mut guard = match self.file.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let needs_rotation = guard
+            .as_ref()
+            .and_then(|f| f.metadata().ok())
+            .map(|m| m.len() >= MAX_LOG_SIZE)
+            .unwrap_or(false);
+        if needs_rotation {
+            *guard = None;
+            self.rotate();
+        }
+
+        if guard.is_none() {
+            *guard = self.open();
+        }
+
+        if let Some(file) = guard.as_mut() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut guard) = self.file.lock() {
+            if let Some(file) = guard.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+fn rotated_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("keedavault.{}.log", index))
+}
+
+fn level_filter(verbose: bool) -> LevelFilter {
+    if verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    }
+}
+
+pub fn log_dir(app: &tauri::AppHandle) -> AppResult<PathBuf> {
+    Ok(app.path().app_data_dir()?.join(LOG_DIR_NAME))
+}
+
+/// Install the file logger. Must run after the store plugin is registered.
+pub fn init(app: &tauri::AppHandle) {
+    let dir = match log_dir(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("[Logging] Failed to resolve log directory: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("[Logging] Failed to create log directory: {}", e);
+        return;
+    }
+
+    let verbose = settings::ui_setting(app, "/security/verboseLogging")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    VERBOSE.store(verbose, Ordering::Relaxed);
+
+    let logger = LOGGER.get_or_init(|| FileLogger {
+        dir,
+        file: Mutex::new(None),
+    });
+    if log::set_logger(logger).is_err() {
+        return;
+    }
+    log::set_max_level(level_filter(verbose));
+
+    // Route panics through the logger so they end up in diagnostics bundles
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{}", info);
+        default_hook(info);
+    }));
+
+    log::info!(
+        "KeedaVault {} starting (verbose logging: {})",
+        app.package_info().version,
+        verbose
+    );
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// A path that renders as a stable fingerprint unless verbose logging is on
+pub struct RedactedPath<'a>(&'a str);
+
+impl fmt::Display for RedactedPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if is_verbose() {
+            return f.write_str(self.0);
+        }
+        let mut hasher = DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        write!(f, "<path:{:08x}>", hasher.finish() as u32)
+    }
+}
+
+pub fn redact_path(path: &str) -> RedactedPath<'_> {
+    RedactedPath(path)
+}
+
+#[command]
+pub fn set_verbose_logging(enabled: bool) {
+    VERBOSE.store(enabled, Ordering::Relaxed);
+    log::set_max_level(level_filter(enabled));
+    log::info!("Verbose logging {}", if enabled { "enabled" } else { "disabled" });
+}
+
+/// Write a plain-text bundle of environment info and recent logs for bug reports.
+/// Plain text keeps it attachable to GitHub issues and reviewable by the user.
+#[command]
+pub fn export_diagnostics(app_handle: tauri::AppHandle, destination: String) -> AppResult<()> {
+    let dir = log_dir(&app_handle)?;
+    log::logger().flush();
+
+    let package = app_handle.package_info();
+    let mut report = String::new();
+    report.push_str("KeedaVault Diagnostics\n");
+    report.push_str("======================\n\n");
+    report.push_str(&format!("Generated:   {}\n", chrono::Utc::now().to_rfc3339()));
+    report.push_str(&format!("App:         {} {}\n", package.name, package.version));
+    report.push_str(&format!("Tauri:       {}\n", tauri::VERSION));
+    report.push_str(&format!(
+        "Platform:    {} ({}, {})\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::FAMILY
+    ));
+    report.push_str(&format!("Verbose log: {}\n", is_verbose()));
+    if !is_verbose() {
+        report.push_str("Paths in this report are redacted.\n");
+    }
+
+    // Oldest rotated file first so the bundle reads chronologically
+    let mut files: Vec<PathBuf> = (1..MAX_LOG_FILES)
+        .rev()
+        .map(|index| rotated_path(&dir, index))
+        .collect();
+    files.push(dir.join(LOG_FILE_NAME));
+
+    for file in files.into_iter().filter(|f| f.exists()) {
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        report.push_str(&format!("\n----- {} -----\n", name));
+        match fs::read(&file) {
+            Ok(bytes) => report.push_str(&String::from_utf8_lossy(&bytes)),
+            Err(e) => report.push_str(&format!("<unreadable: {}>\n", e)),
+        }
+    }
+
+    fs::write(&destination, report)?;
+    log::info!("Diagnostics exported to {}", redact_path(&destination));
+    Ok(())
+}
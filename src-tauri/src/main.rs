@@ -1,25 +1,41 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod backup;
 mod biometric;
+mod conflicts;
+mod credentials;
+mod encryption;
+mod history;
+mod logging;
 mod native_keychain;
 mod native_keychain_modern;
+mod quick_unlock;
+mod read_only;
+mod remote;
+mod repair;
 mod secure_storage;
-
+mod settings;
+mod sync;
+mod unlock_attempts;
+mod updater;
+mod vault_lock;
+mod watcher;
+
+use keedavault_lib::{error, import, kdbx};
 use tauri::menu::IsMenuItem;
 use tauri::window::Color;
 use tauri::Listener;
 use tauri::{Emitter, Manager};
 
 #[tauri::command]
-fn reveal_in_finder(path: String) -> Result<(), String> {
+fn reveal_in_finder(path: String) -> error::AppResult<()> {
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
             .arg("-R")
             .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+            .spawn()?;
     }
 
     #[cfg(target_os = "windows")]
@@ -27,8 +43,7 @@ fn reveal_in_finder(path: String) -> Result<(), String> {
         std::process::Command::new("explorer")
             .arg("/select,")
             .arg(&path)
-            .spawn()
-            .map_err(|e| e.to_string())?;
+            .spawn()?;
     }
 
     #[cfg(target_os = "linux")]
@@ -52,40 +67,51 @@ fn reveal_in_finder(path: String) -> Result<(), String> {
                         .parent()
                         .unwrap_or(std::path::Path::new(&path)),
                 )
-                .spawn()
-                .map_err(|e| e.to_string())?;
+                .spawn()?;
         }
     }
 
     Ok(())
 }
 
+/// Parse a CSV or JSON export of another password manager
+#[tauri::command]
+fn parse_import(
+    format: import::ImportFormat,
+    content: String,
+) -> error::AppResult<Vec<import::ImportedEntry>> {
+    import::parse(format, &content)
+}
+
 #[tauri::command]
-fn set_database_menu_state(app_handle: tauri::AppHandle, unlocked: bool) {
+fn set_database_menu_state(app_handle: tauri::AppHandle, unlocked: bool, read_only: Option<bool>) {
     #[cfg(target_os = "macos")]
     {
+        // Items that change the vault stay disabled for read-only vaults
+        let writable = unlocked && !read_only.unwrap_or(false);
         if let Some(menu) = app_handle.menu() {
             let items = menu.items().unwrap_or_default();
             for item in items {
                 if let tauri::menu::MenuItemKind::Submenu(submenu) = item {
                     let text = submenu.text().unwrap_or_default();
-                    if text == "Database" {
-                        // Items to enable/disable based on vault state
-                        // Note: password_generator is NOT in this list, so it stays enabled
-                        let db_items = [
-                            "create_entry",
-                            "lock_database",
-                            "change_credentials",
-                            "database_setting",
-                        ];
-                        for id in db_items {
-                            if let Some(db_item) = submenu.get(id) {
-                                if let tauri::menu::MenuItemKind::MenuItem(i) = db_item {
-                                    let _ = i.set_enabled(unlocked);
-                                }
-                            }
+                    // Items to enable/disable based on vault state
+                    // Note: password_generator is NOT in this list, so it stays enabled
+                    let menu_items: &[(&str, bool)] = match text.as_str() {
+                        "Database" => &[
+                            ("create_entry", writable),
+                            ("lock_database", unlocked),
+                            ("change_credentials", writable),
+                            ("database_setting", unlocked),
+                            ("merge_database", writable),
+                            ("sync_conflicts", writable),
+                        ],
+                        "File" => &[("import_database", !read_only.unwrap_or(false))],
+                        _ => continue,
+                    };
+                    for (id, enabled) in menu_items {
+                        if let Some(tauri::menu::MenuItemKind::MenuItem(i)) = submenu.get(id) {
+                            let _ = i.set_enabled(*enabled);
                         }
-                        return;
                     }
                 }
             }
@@ -158,6 +184,11 @@ fn update_window_menu(app_handle: tauri::AppHandle) {
 
                         // Check unlocked state before moving vault_windows
                         let is_unlocked = !vault_windows.is_empty();
+                        // Keep the focused window's read-only state across the rebuild
+                        let is_read_only = app_handle.webview_windows().values().any(|w| {
+                            w.is_focused().unwrap_or(false)
+                                && read_only::is_window_read_only(&app_handle, w.label())
+                        });
 
                         // Add vault windows if any exist
                         if !vault_windows.is_empty() {
@@ -183,8 +214,12 @@ fn update_window_menu(app_handle: tauri::AppHandle) {
                         if let Ok(new_window_menu) = window_menu_builder.build() {
                             // Rebuild entire menu with updated Window menu
                             // If there are vault windows, we consider the database unlocked
-                            let _ =
-                                rebuild_menu_with_window_menu(handle, new_window_menu, is_unlocked);
+                            let _ = rebuild_menu_with_window_menu(
+                                handle,
+                                new_window_menu,
+                                is_unlocked,
+                                is_read_only,
+                            );
                         }
 
                         return;
@@ -199,6 +234,7 @@ fn rebuild_menu_with_window_menu(
     handle: &tauri::AppHandle,
     window_menu: tauri::menu::Submenu<tauri::Wry>,
     is_unlocked: bool,
+    is_read_only: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     #[cfg(target_os = "macos")]
     {
@@ -236,7 +272,11 @@ fn rebuild_menu_with_window_menu(
                     .build(handle)?,
             )
             .separator()
-            .item(&MenuItemBuilder::with_id("import_database", "Import...").build(handle)?)
+            .item(
+                &MenuItemBuilder::with_id("import_database", "Import...")
+                    .enabled(!is_read_only)
+                    .build(handle)?,
+            )
             .item(&MenuItemBuilder::with_id("export_database", "Export Database...").build(handle)?)
             .item(&MenuItemBuilder::with_id("export_selected", "Export Selected...").build(handle)?)
             .separator()
@@ -261,7 +301,7 @@ fn rebuild_menu_with_window_menu(
             .item(
                 &MenuItemBuilder::with_id("create_entry", "Create Entry")
                     .accelerator("CmdOrCtrl+I")
-                    .enabled(is_unlocked)
+                    .enabled(is_unlocked && !is_read_only)
                     .build(handle)?,
             )
             .separator()
@@ -273,7 +313,7 @@ fn rebuild_menu_with_window_menu(
             )
             .item(
                 &MenuItemBuilder::with_id("change_credentials", "Change Credentials")
-                    .enabled(is_unlocked)
+                    .enabled(is_unlocked && !is_read_only)
                     .build(handle)?,
             )
             .item(
@@ -281,6 +321,16 @@ fn rebuild_menu_with_window_menu(
                     .enabled(is_unlocked)
                     .build(handle)?,
             )
+            .item(
+                &MenuItemBuilder::with_id("merge_database", "Merge From Database...")
+                    .enabled(is_unlocked && !is_read_only)
+                    .build(handle)?,
+            )
+            .item(
+                &MenuItemBuilder::with_id("sync_conflicts", "Resolve Sync Conflicts...")
+                    .enabled(is_unlocked && !is_read_only)
+                    .build(handle)?,
+            )
             .build()?;
 
         let menu = MenuBuilder::new(handle)
@@ -360,8 +410,50 @@ fn main() {
         .plugin(tauri_plugin_store::Builder::new().build())
         .invoke_handler(tauri::generate_handler![
             reveal_in_finder,
+            parse_import,
             set_database_menu_state,
             update_window_menu,
+            logging::set_verbose_logging,
+            logging::export_diagnostics,
+            updater::check_updates,
+            backup::save_vault,
+            backup::list_backups,
+            backup::restore_backup,
+            backup::get_backup_mirrors,
+            backup::set_backup_mirrors,
+            history::get_vault_history,
+            history::set_vault_history,
+            history::delete_vault_history,
+            history::list_vault_snapshots,
+            history::diff_vault_snapshots,
+            history::restore_vault_snapshot,
+            sync::merge_vault_file,
+            sync::merge_vault_data,
+            sync::diff_vaults,
+            repair::verify_vault_deep,
+            repair::lint_vault,
+            repair::repair_vault,
+            encryption::set_vault_crypto,
+            encryption::transcode_vault,
+            encryption::benchmark_kdf,
+            credentials::check_credentials,
+            credentials::change_credentials,
+            unlock_attempts::check_unlock_allowed,
+            unlock_attempts::record_failed_unlock,
+            unlock_attempts::record_successful_unlock,
+            conflicts::find_sync_conflicts,
+            conflicts::archive_sync_conflict,
+            watcher::watch_vault,
+            watcher::unwatch_vault,
+            vault_lock::acquire_vault_lock,
+            vault_lock::release_vault_lock,
+            read_only::mark_vault_read_only,
+            remote::open_remote_vault,
+            remote::push_remote_vault,
+            remote::set_remote_credentials,
+            remote::delete_remote_credentials,
+            remote::remote_host_key,
+            remote::trust_remote_host_key,
             biometric::check_biometric_available,
             biometric::authenticate_biometric,
             secure_storage::secure_store_password,
@@ -378,6 +470,12 @@ fn main() {
             native_keychain_modern::secure_has_password_modern
         ])
         .setup(|app| {
+            logging::init(app.handle());
+            app.manage(watcher::VaultWatchers::default());
+            app.manage(vault_lock::VaultLocks::default());
+            app.manage(read_only::ReadOnlySessions::default());
+            app.manage(remote::RemoteSyncs::default());
+
             #[cfg(target_os = "macos")]
             app.set_activation_policy(tauri::ActivationPolicy::Regular);
 
@@ -508,6 +606,16 @@ fn main() {
                             .enabled(false)
                             .build(handle)?,
                     )
+                    .item(
+                        &MenuItemBuilder::with_id("merge_database", "Merge From Database...")
+                            .enabled(false)
+                            .build(handle)?,
+                    )
+                    .item(
+                        &MenuItemBuilder::with_id("sync_conflicts", "Resolve Sync Conflicts...")
+                            .enabled(false)
+                            .build(handle)?,
+                    )
                     .build()?;
 
                 let menu = MenuBuilder::new(handle)
@@ -606,6 +714,12 @@ fn main() {
                     "database_setting" => {
                         let _ = app_handle.emit("database-setting", ());
                     }
+                    "merge_database" => {
+                        let _ = app_handle.emit("merge-database", ());
+                    }
+                    "sync_conflicts" => {
+                        let _ = app_handle.emit("sync-conflicts", ());
+                    }
                     "about" => {
                         // Open About window
                         if let Some(window) = app_handle.get_webview_window("about") {
@@ -735,13 +849,17 @@ fn main() {
             // Listen for vault unlock/lock events to update menu state
             let app_handle = app.handle().clone();
             let app_handle_unlock = app_handle.clone();
-            let _unlock_listener = app_handle.listen("vault-unlocked", move |_event| {
-                set_database_menu_state(app_handle_unlock.clone(), true);
+            let _unlock_listener = app_handle.listen("vault-unlocked", move |event| {
+                // Payload: { "readOnly": bool }
+                let read_only = serde_json::from_str::<serde_json::Value>(event.payload())
+                    .ok()
+                    .and_then(|payload| payload.get("readOnly")?.as_bool());
+                set_database_menu_state(app_handle_unlock.clone(), true, read_only);
             });
 
             let app_handle_lock = app_handle.clone();
             let _lock_listener = app_handle.listen("vault-locked", move |_event| {
-                set_database_menu_state(app_handle_lock.clone(), false);
+                set_database_menu_state(app_handle_lock.clone(), false, None);
             });
 
             Ok(())
@@ -750,17 +868,21 @@ fn main() {
         .expect("error while building tauri application")
         .run(|app_handle, event| {
             match event {
-                tauri::RunEvent::WindowEvent {
-                    label: _label,
-                    event,
-                    ..
-                } => {
+                tauri::RunEvent::WindowEvent { label, event, .. } => {
                     if let tauri::WindowEvent::CloseRequested { api: _, .. } = event {
                         // Let all windows close normally (destroy)
                         // This allows the app to quit when all windows are closed
                         // or stay in dock if there are other windows still open
+                    } else if let tauri::WindowEvent::Destroyed = event {
+                        watcher::forget_window(app_handle, &label);
+                        vault_lock::release_window(app_handle, &label);
+                        read_only::forget_window(app_handle, &label);
                     }
                 }
+                tauri::RunEvent::Exit => {
+                    // Windows may not get a Destroyed event when the app quits
+                    vault_lock::release_all(app_handle);
+                }
                 #[cfg(target_os = "macos")]
                 tauri::RunEvent::Reopen { .. } => {
                     // Priority order when clicking dock icon:
@@ -1,4 +1,6 @@
 // macOS Keychain implementation using Security Framework directly
+use crate::error::{AppError, AppResult};
+use crate::logging::redact_path;
 use std::ffi::CString;
 use std::os::raw::{c_char, c_void};
 use tauri::command;
@@ -40,11 +42,11 @@ mod macos_keychain {
     const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
     const ERR_SEC_DUPLICATE_ITEM: i32 = -25299;
 
-    pub fn store_password(service: &str, account: &str, password: &str) -> Result<(), String> {
+    pub fn store_password(service: &str, account: &str, password: &str) -> AppResult<()> {
         unsafe {
-            let service_cstr = CString::new(service).map_err(|e| e.to_string())?;
-            let account_cstr = CString::new(account).map_err(|e| e.to_string())?;
-            let password_cstr = CString::new(password).map_err(|e| e.to_string())?;
+            let service_cstr = CString::new(service)?;
+            let account_cstr = CString::new(account)?;
+            let password_cstr = CString::new(password)?;
 
             // Try to delete existing item first
             let mut item_ref: *const c_void = std::ptr::null();
@@ -78,15 +80,15 @@ mod macos_keychain {
             if status == ERR_SEC_SUCCESS {
                 Ok(())
             } else {
-                Err(format!("Failed to store password, status: {}", status))
+                Err(AppError::from_os_status(status, "Failed to store password"))
             }
         }
     }
 
-    pub fn get_password(service: &str, account: &str) -> Result<String, String> {
+    pub fn get_password(service: &str, account: &str) -> AppResult<String> {
         unsafe {
-            let service_cstr = CString::new(service).map_err(|e| e.to_string())?;
-            let account_cstr = CString::new(account).map_err(|e| e.to_string())?;
+            let service_cstr = CString::new(service)?;
+            let account_cstr = CString::new(account)?;
 
             let mut password_length: u32 = 0;
             let mut password_data: *const c_void = std::ptr::null();
@@ -115,17 +117,17 @@ mod macos_keychain {
 
                 Ok(password)
             } else if status == ERR_SEC_ITEM_NOT_FOUND {
-                Err("Password not found".to_string())
+                Err(AppError::not_found("Password not found").with_os_status(status as i64))
             } else {
-                Err(format!("Failed to get password, status: {}", status))
+                Err(AppError::from_os_status(status, "Failed to get password"))
             }
         }
     }
 
-    pub fn delete_password(service: &str, account: &str) -> Result<(), String> {
+    pub fn delete_password(service: &str, account: &str) -> AppResult<()> {
         unsafe {
-            let service_cstr = CString::new(service).map_err(|e| e.to_string())?;
-            let account_cstr = CString::new(account).map_err(|e| e.to_string())?;
+            let service_cstr = CString::new(service)?;
+            let account_cstr = CString::new(account)?;
 
             let mut item_ref: *const c_void = std::ptr::null();
 
@@ -145,15 +147,15 @@ mod macos_keychain {
                 if delete_status == ERR_SEC_SUCCESS {
                     Ok(())
                 } else {
-                    Err(format!(
-                        "Failed to delete password, status: {}",
-                        delete_status
+                    Err(AppError::from_os_status(
+                        delete_status,
+                        "Failed to delete password",
                     ))
                 }
             } else if find_status == ERR_SEC_ITEM_NOT_FOUND {
                 Ok(()) // Already deleted
             } else {
-                Err(format!("Failed to find password, status: {}", find_status))
+                Err(AppError::from_os_status(find_status, "Failed to find password"))
             }
         }
     }
@@ -165,80 +167,84 @@ mod macos_keychain {
 
 const SERVICE_NAME: &str = "keedavault-biometric";
 
+// Entry access for `quick_unlock`, with the account already encoded
+#[cfg(target_os = "macos")]
+pub(crate) fn entry_exists(account: &str) -> bool {
+    macos_keychain::has_password(SERVICE_NAME, account)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn store_entry(account: &str, password: &str) -> AppResult<()> {
+    macos_keychain::store_password(SERVICE_NAME, account, password)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn delete_entry(account: &str) -> AppResult<()> {
+    macos_keychain::delete_password(SERVICE_NAME, account)
+}
+
 #[command]
 pub async fn secure_store_password_native(
     vault_path: String,
     password: String,
-) -> Result<(), String> {
-    println!(
-        "[Native Keychain] Storing password for path: {}",
-        vault_path
-    );
+) -> AppResult<()> {
+    log::debug!("Storing password for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         macos_keychain::store_password(SERVICE_NAME, &vault_path, &password)?;
-        println!("[Native Keychain] Password stored successfully");
+        log::info!("Password stored for {}", redact_path(&vault_path));
         Ok(())
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        Err("Native keychain only supported on macOS".to_string())
+        Err(AppError::unsupported("Native keychain only supported on macOS"))
     }
 }
 
 #[command]
-pub async fn secure_get_password_native(vault_path: String) -> Result<String, String> {
-    println!(
-        "[Native Keychain] Getting password for path: {}",
-        vault_path
-    );
+pub async fn secure_get_password_native(vault_path: String) -> AppResult<String> {
+    log::debug!("Getting password for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         let password = macos_keychain::get_password(SERVICE_NAME, &vault_path)?;
-        println!("[Native Keychain] Password retrieved successfully");
+        log::info!("Password retrieved for {}", redact_path(&vault_path));
         Ok(password)
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        Err("Native keychain only supported on macOS".to_string())
+        Err(AppError::unsupported("Native keychain only supported on macOS"))
     }
 }
 
 #[command]
-pub async fn secure_delete_password_native(vault_path: String) -> Result<(), String> {
-    println!(
-        "[Native Keychain] Deleting password for path: {}",
-        vault_path
-    );
+pub async fn secure_delete_password_native(vault_path: String) -> AppResult<()> {
+    log::debug!("Deleting password for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         macos_keychain::delete_password(SERVICE_NAME, &vault_path)?;
-        println!("[Native Keychain] Password deleted successfully");
+        log::info!("Password deleted for {}", redact_path(&vault_path));
         Ok(())
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        Err("Native keychain only supported on macOS".to_string())
+        Err(AppError::unsupported("Native keychain only supported on macOS"))
     }
 }
 
 #[command]
-pub async fn secure_has_password_native(vault_path: String) -> Result<bool, String> {
-    println!(
-        "[Native Keychain] Checking if password exists for path: {}",
-        vault_path
-    );
+pub async fn secure_has_password_native(vault_path: String) -> AppResult<bool> {
+    log::debug!("Checking if password exists for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         let has = macos_keychain::has_password(SERVICE_NAME, &vault_path);
-        println!("[Native Keychain] Password exists: {}", has);
+        log::debug!("Password exists for {}: {}", redact_path(&vault_path), has);
         Ok(has)
     }
 
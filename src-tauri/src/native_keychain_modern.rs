@@ -1,5 +1,7 @@
 // Modern macOS Keychain implementation using SecItemAdd with SecAccessControl
 // This approach does NOT prompt for macOS password when saving passwords
+use crate::error::{AppError, AppResult};
+use crate::logging::redact_path;
 use std::ffi::CString;
 use std::os::raw::{c_char, c_void};
 use std::ptr;
@@ -8,6 +10,7 @@ use tauri::command;
 #[cfg(target_os = "macos")]
 mod macos_keychain_modern {
     use super::*;
+    use crate::error::ErrorCode;
 
     // Core Foundation types
     type CFTypeRef = *const c_void;
@@ -107,30 +110,30 @@ mod macos_keychain_modern {
     const K_CF_STRING_ENCODING_UTF8: u32 = 0x08000100;
 
     /// Create a CFString from a Rust string
-    unsafe fn create_cfstring(s: &str) -> Result<CFStringRef, String> {
-        let c_str = CString::new(s).map_err(|e| e.to_string())?;
+    unsafe fn create_cfstring(s: &str) -> AppResult<CFStringRef> {
+        let c_str = CString::new(s)?;
         let cf_str = CFStringCreateWithCString(
             kCFAllocatorDefault,
             c_str.as_ptr(),
             K_CF_STRING_ENCODING_UTF8,
         );
         if cf_str.is_null() {
-            return Err("Failed to create CFString".to_string());
+            return Err(AppError::internal("Failed to create CFString"));
         }
         Ok(cf_str)
     }
 
     /// Create CFData from bytes
-    unsafe fn create_cfdata(data: &[u8]) -> Result<CFDataRef, String> {
+    unsafe fn create_cfdata(data: &[u8]) -> AppResult<CFDataRef> {
         let cf_data = CFDataCreate(kCFAllocatorDefault, data.as_ptr(), data.len() as CFIndex);
         if cf_data.is_null() {
-            return Err("Failed to create CFData".to_string());
+            return Err(AppError::internal("Failed to create CFData"));
         }
         Ok(cf_data)
     }
 
     /// Store password with biometric protection (NO password prompt!)
-    pub fn store_password(service: &str, account: &str, password: &str) -> Result<(), String> {
+    pub fn store_password(service: &str, account: &str, password: &str) -> AppResult<()> {
         unsafe {
             // Create access control with biometric authentication
             let mut error: CFErrorRef = ptr::null();
@@ -142,7 +145,7 @@ mod macos_keychain_modern {
             );
 
             if access_control.is_null() {
-                let err_msg = if !error.is_null() {
+                let err = if !error.is_null() {
                     let desc = CFErrorCopyDescription(error);
                     let c_str = CFStringGetCStringPtr(desc, K_CF_STRING_ENCODING_UTF8);
                     let msg = if !c_str.is_null() {
@@ -154,11 +157,11 @@ mod macos_keychain_modern {
                     };
                     CFRelease(desc as CFTypeRef);
                     CFRelease(error as CFTypeRef);
-                    msg
+                    AppError::new(ErrorCode::BackendUnavailable, msg)
                 } else {
-                    "Failed to create access control".to_string()
+                    AppError::internal("Failed to create access control")
                 };
-                return Err(err_msg);
+                return Err(err);
             }
 
             // Create service and account CFStrings
@@ -179,7 +182,7 @@ mod macos_keychain_modern {
                 CFRelease(service_cfstr as CFTypeRef);
                 CFRelease(account_cfstr as CFTypeRef);
                 CFRelease(password_data as CFTypeRef);
-                return Err("Failed to create query dictionary".to_string());
+                return Err(AppError::internal("Failed to create query dictionary"));
             }
 
             // Set query attributes
@@ -255,13 +258,13 @@ mod macos_keychain_modern {
             if status == ERR_SEC_SUCCESS {
                 Ok(())
             } else {
-                Err(format!("Failed to store password, status: {}", status))
+                Err(AppError::from_os_status(status, "Failed to store password"))
             }
         }
     }
 
     /// Retrieve password (will trigger biometric authentication)
-    pub fn get_password(service: &str, account: &str) -> Result<String, String> {
+    pub fn get_password(service: &str, account: &str) -> AppResult<String> {
         unsafe {
             let service_cfstr = create_cfstring(service)?;
             let account_cfstr = create_cfstring(account)?;
@@ -277,7 +280,7 @@ mod macos_keychain_modern {
             if query.is_null() {
                 CFRelease(service_cfstr as CFTypeRef);
                 CFRelease(account_cfstr as CFTypeRef);
-                return Err("Failed to create query dictionary".to_string());
+                return Err(AppError::internal("Failed to create query dictionary"));
             }
 
             // Set query attributes
@@ -325,15 +328,15 @@ mod macos_keychain_modern {
                 CFRelease(result);
                 Ok(password)
             } else if status == ERR_SEC_ITEM_NOT_FOUND {
-                Err("Password not found".to_string())
+                Err(AppError::not_found("Password not found").with_os_status(status as i64))
             } else {
-                Err(format!("Failed to get password, status: {}", status))
+                Err(AppError::from_os_status(status, "Failed to get password"))
             }
         }
     }
 
     /// Delete password
-    pub fn delete_password(service: &str, account: &str) -> Result<(), String> {
+    pub fn delete_password(service: &str, account: &str) -> AppResult<()> {
         unsafe {
             let service_cfstr = create_cfstring(service)?;
             let account_cfstr = create_cfstring(account)?;
@@ -348,7 +351,7 @@ mod macos_keychain_modern {
             if query.is_null() {
                 CFRelease(service_cfstr as CFTypeRef);
                 CFRelease(account_cfstr as CFTypeRef);
-                return Err("Failed to create query dictionary".to_string());
+                return Err(AppError::internal("Failed to create query dictionary"));
             }
 
             CFDictionarySetValue(
@@ -376,7 +379,7 @@ mod macos_keychain_modern {
             if status == ERR_SEC_SUCCESS || status == ERR_SEC_ITEM_NOT_FOUND {
                 Ok(())
             } else {
-                Err(format!("Failed to delete password, status: {}", status))
+                Err(AppError::from_os_status(status, "Failed to delete password"))
             }
         }
     }
@@ -455,80 +458,84 @@ mod macos_keychain_modern {
 
 const SERVICE_NAME: &str = "keedavault-biometric";
 
+// Entry access for `quick_unlock`, with the account already encoded
+#[cfg(target_os = "macos")]
+pub(crate) fn entry_exists(account: &str) -> bool {
+    macos_keychain_modern::has_password(SERVICE_NAME, account)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn store_entry(account: &str, password: &str) -> AppResult<()> {
+    macos_keychain_modern::store_password(SERVICE_NAME, account, password)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn delete_entry(account: &str) -> AppResult<()> {
+    macos_keychain_modern::delete_password(SERVICE_NAME, account)
+}
+
 #[command]
 pub async fn secure_store_password_modern(
     vault_path: String,
     password: String,
-) -> Result<(), String> {
-    println!(
-        "[Modern Keychain] Storing password for path: {}",
-        vault_path
-    );
+) -> AppResult<()> {
+    log::debug!("Storing password for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         macos_keychain_modern::store_password(SERVICE_NAME, &vault_path, &password)?;
-        println!("[Modern Keychain] Password stored successfully (NO password prompt!)");
+        log::info!("Password stored for {}", redact_path(&vault_path));
         Ok(())
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        Err("Modern keychain only supported on macOS".to_string())
+        Err(AppError::unsupported("Modern keychain only supported on macOS"))
     }
 }
 
 #[command]
-pub async fn secure_get_password_modern(vault_path: String) -> Result<String, String> {
-    println!(
-        "[Modern Keychain] Getting password for path: {}",
-        vault_path
-    );
+pub async fn secure_get_password_modern(vault_path: String) -> AppResult<String> {
+    log::debug!("Getting password for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         let password = macos_keychain_modern::get_password(SERVICE_NAME, &vault_path)?;
-        println!("[Modern Keychain] Password retrieved successfully");
+        log::info!("Password retrieved for {}", redact_path(&vault_path));
         Ok(password)
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        Err("Modern keychain only supported on macOS".to_string())
+        Err(AppError::unsupported("Modern keychain only supported on macOS"))
     }
 }
 
 #[command]
-pub async fn secure_delete_password_modern(vault_path: String) -> Result<(), String> {
-    println!(
-        "[Modern Keychain] Deleting password for path: {}",
-        vault_path
-    );
+pub async fn secure_delete_password_modern(vault_path: String) -> AppResult<()> {
+    log::debug!("Deleting password for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         macos_keychain_modern::delete_password(SERVICE_NAME, &vault_path)?;
-        println!("[Modern Keychain] Password deleted successfully");
+        log::info!("Password deleted for {}", redact_path(&vault_path));
         Ok(())
     }
 
     #[cfg(not(target_os = "macos"))]
     {
-        Err("Modern keychain only supported on macOS".to_string())
+        Err(AppError::unsupported("Modern keychain only supported on macOS"))
     }
 }
 
 #[command]
-pub async fn secure_has_password_modern(vault_path: String) -> Result<bool, String> {
-    println!(
-        "[Modern Keychain] Checking if password exists for path: {}",
-        vault_path
-    );
+pub async fn secure_has_password_modern(vault_path: String) -> AppResult<bool> {
+    log::debug!("Checking if password exists for {}", redact_path(&vault_path));
 
     #[cfg(target_os = "macos")]
     {
         let has = macos_keychain_modern::has_password(SERVICE_NAME, &vault_path);
-        println!("[Modern Keychain] Password exists: {}", has);
+        log::debug!("Password exists for {}: {}", redact_path(&vault_path), has);
         Ok(has)
     }
 
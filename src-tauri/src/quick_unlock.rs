@@ -0,0 +1,344 @@
+// Quick-unlock passwords saved for Touch ID
+//
+// The frontend keeps a vault's master password in the system secret store, under the
+// Base64 of the vault's location (file path, or server URL for a remote vault). When
+// the master key changes these entries have to change with it, or Touch ID would
+// unlock to a wrong-password error. `rekey` takes them out of every store before the
+// vault file is rewritten; afterwards it puts the new password (or, if the rewrite
+// failed, the old one) back where an entry was. Touch ID supplies only the password, so
+// when the key file or hardware keys change too the entries stay removed.
+use crate::error::{AppResult, ErrorCode};
+use crate::kdbx::Credentials;
+use crate::logging::redact_path;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+
+/// A secret store quick-unlock entries can live in
+struct Store {
+    name: &'static str,
+    exists: fn(&str) -> bool,
+    store: fn(&str, &str) -> AppResult<()>,
+    delete: fn(&str) -> AppResult<()>,
+}
+
+// On macOS the keyring crate writes the same items as the native keychain
+#[cfg(target_os = "macos")]
+const STORES: &[Store] = &[
+    Store {
+        name: "keychain",
+        exists: crate::native_keychain::entry_exists,
+        store: crate::native_keychain::store_entry,
+        delete: crate::native_keychain::delete_entry,
+    },
+    Store {
+        name: "biometric keychain",
+        exists: crate::native_keychain_modern::entry_exists,
+        store: crate::native_keychain_modern::store_entry,
+        delete: crate::native_keychain_modern::delete_entry,
+    },
+];
+
+#[cfg(not(target_os = "macos"))]
+const STORES: &[Store] = &[Store {
+    name: "keyring",
+    exists: crate::secure_storage::entry_exists,
+    store: crate::secure_storage::store_entry,
+    delete: crate::secure_storage::delete_entry,
+}];
+
+/// What happened to a vault's quick-unlock entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuickUnlock {
+    /// The vault had none
+    None,
+    /// They hold the given password
+    Updated,
+    /// They were removed; Touch ID is set up again on the next unlock with a password
+    Removed,
+}
+
+/// Account of `location` in the secret stores, as `encodeVaultPath` in
+/// `biometricService.ts` builds it
+fn account(location: &str) -> String {
+    let location = location.trim();
+    let location = location
+        .strip_prefix('"')
+        .and_then(|l| l.strip_suffix('"'))
+        .unwrap_or(location);
+    BASE64.encode(location)
+}
+
+/// Whether a password saved for `current` still unlocks with `new` once the password
+/// is replaced: every other component must stay the same
+fn same_components(current: &Credentials, new: &Credentials) -> bool {
+    let hardware_keys = |credentials: &Credentials| {
+        credentials
+            .challenge_response
+            .iter()
+            .map(|key| key.name())
+            .collect::<Vec<_>>()
+    };
+    current.key_file == new.key_file && hardware_keys(current) == hardware_keys(new)
+}
+
+/// Quick-unlock entries of one vault, taken out of their stores
+#[must_use = "the entries are gone until restored"]
+struct Suspended {
+    location: String,
+    account: String,
+    stores: Vec<&'static Store>,
+}
+
+/// Rewrite a vault with `write` while its master key changes from `current` to `new`,
+/// with the quick-unlock entries of `location` suspended. Fails without calling `write`
+/// if an entry cannot be removed; the entries that remain still match the unchanged
+/// vault
+pub fn rekey<T>(
+    location: &str,
+    current: &Credentials,
+    new: &Credentials,
+    write: impl FnOnce() -> AppResult<T>,
+) -> AppResult<(T, QuickUnlock)> {
+    rekey_in(STORES, location, current, new, write)
+}
+
+fn rekey_in<T>(
+    stores: &'static [Store],
+    location: &str,
+    current: &Credentials,
+    new: &Credentials,
+    write: impl FnOnce() -> AppResult<T>,
+) -> AppResult<(T, QuickUnlock)> {
+    let suspended = suspend(stores, location)?;
+    match write() {
+        Ok(written) => {
+            let password = new
+                .password
+                .as_deref()
+                .filter(|_| same_components(current, new));
+            Ok((written, suspended.restore(password)))
+        }
+        Err(e) => {
+            // The file still opens with the current credentials
+            suspended.restore(current.password.as_deref());
+            Err(e)
+        }
+    }
+}
+
+/// Remove the quick-unlock entries of `location` from `stores`
+fn suspend(stores: &'static [Store], location: &str) -> AppResult<Suspended> {
+    let account = account(location);
+    let mut suspended = Vec::new();
+    for store in stores {
+        // Entries the lookup misses are removed too; removing a missing one is a no-op
+        let existed = (store.exists)(&account);
+        match (store.delete)(&account) {
+            Ok(()) if existed => suspended.push(store),
+            Ok(()) => {}
+            // Without a working store there is nothing to remove
+            Err(e) if !existed && e.code == ErrorCode::BackendUnavailable => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Suspended {
+        location: location.to_string(),
+        account,
+        stores: suspended,
+    })
+}
+
+impl Suspended {
+    /// Save `password` in every store that had an entry; without one (a key-file only
+    /// vault, or other components changed) the entries stay removed
+    fn restore(self, password: Option<&str>) -> QuickUnlock {
+        if self.stores.is_empty() {
+            return QuickUnlock::None;
+        }
+        let Some(password) = password else {
+            return QuickUnlock::Removed;
+        };
+        let mut result = QuickUnlock::Updated;
+        for store in self.stores {
+            if let Err(e) = (store.store)(&self.account, password) {
+                log::warn!(
+                    "Failed to save the quick-unlock password of {} in the {}: {}",
+                    redact_path(&self.location),
+                    store.name,
+                    e.message
+                );
+                result = QuickUnlock::Removed;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    const LOCATION: &str = "/vaults/Vault.kdbx";
+
+    thread_local! {
+        static ENTRIES: RefCell<HashMap<String, String>> = RefCell::default();
+        /// What the stores and the write saw, in order
+        static EVENTS: RefCell<Vec<String>> = RefCell::default();
+    }
+
+    fn event(text: String) {
+        EVENTS.with(|events| events.borrow_mut().push(text));
+    }
+
+    fn events() -> Vec<String> {
+        EVENTS.with(|events| events.take())
+    }
+
+    fn entry() -> Option<String> {
+        ENTRIES.with(|entries| entries.borrow().get(&account(LOCATION)).cloned())
+    }
+
+    fn fake_exists(account: &str) -> bool {
+        ENTRIES.with(|entries| entries.borrow().contains_key(account))
+    }
+
+    fn fake_store(account: &str, password: &str) -> AppResult<()> {
+        event(format!("store {}", password));
+        ENTRIES.with(|entries| entries.borrow_mut().insert(account.into(), password.into()));
+        Ok(())
+    }
+
+    fn fake_delete(account: &str) -> AppResult<()> {
+        event("delete".to_string());
+        ENTRIES.with(|entries| entries.borrow_mut().remove(account));
+        Ok(())
+    }
+
+    fn locked_delete(_: &str) -> AppResult<()> {
+        Err(AppError::new(ErrorCode::AuthFailed, "locked"))
+    }
+
+    const FAKE: &[Store] = &[Store {
+        name: "fake store",
+        exists: fake_exists,
+        store: fake_store,
+        delete: fake_delete,
+    }];
+
+    const LOCKED: &[Store] = &[Store {
+        name: "locked store",
+        exists: fake_exists,
+        store: fake_store,
+        delete: locked_delete,
+    }];
+
+    fn credentials(password: Option<&str>, key_file: Option<&[u8]>) -> Credentials {
+        Credentials {
+            password: password.map(str::to_string),
+            key_file: key_file.map(<[u8]>::to_vec),
+            challenge_response: Vec::new(),
+        }
+    }
+
+    /// Store `password` for the vault, then re-key it from `current` to `new`
+    fn rekey_with(
+        stores: &'static [Store],
+        password: Option<&str>,
+        current: Credentials,
+        new: Credentials,
+        fail: bool,
+    ) -> AppResult<QuickUnlock> {
+        if let Some(password) = password {
+            ENTRIES.with(|e| e.borrow_mut().insert(account(LOCATION), password.into()));
+        }
+        let result = rekey_in(stores, LOCATION, &current, &new, || {
+            event(format!("write, entry {:?}", entry()));
+            if fail {
+                return Err(AppError::new(ErrorCode::Conflict, "changed on disk"));
+            }
+            Ok(())
+        });
+        result.map(|((), quick_unlock)| quick_unlock)
+    }
+
+    #[test]
+    fn rekey_saves_the_new_password_after_the_write() {
+        let old = credentials(Some("old"), None);
+        let result = rekey_with(
+            FAKE,
+            Some("old"),
+            old,
+            credentials(Some("new"), None),
+            false,
+        );
+        assert_eq!(result.ok(), Some(QuickUnlock::Updated));
+        assert_eq!(events(), ["delete", "write, entry None", "store new"]);
+        assert_eq!(entry().as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn rekey_restores_the_old_password_when_the_write_fails() {
+        let old = credentials(Some("old"), Some(b"key"));
+        let new = credentials(Some("new"), None);
+        let result = rekey_with(FAKE, Some("old"), old, new, true);
+        assert_eq!(result.err().map(|e| e.code), Some(ErrorCode::Conflict));
+        assert_eq!(events(), ["delete", "write, entry None", "store old"]);
+        assert_eq!(entry().as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn rekey_removes_entries_when_other_components_change() {
+        let cases = [
+            (None, Some(&b"key"[..])),
+            (Some(&b"key"[..]), Some(&b"other key"[..])),
+            (Some(&b"key"[..]), None),
+        ];
+        for (current_key, new_key) in cases {
+            let old = credentials(Some("old"), current_key);
+            let new = credentials(Some("new"), new_key);
+            let result = rekey_with(FAKE, Some("old"), old, new, false);
+            assert_eq!(result.ok(), Some(QuickUnlock::Removed), "{:?}", new_key);
+            assert_eq!(events(), ["delete", "write, entry None"]);
+            assert_eq!(entry(), None);
+        }
+
+        // A key-file only vault has no password to save
+        let old = credentials(Some("old"), Some(b"key"));
+        let new = credentials(None, Some(b"key"));
+        let result = rekey_with(FAKE, Some("old"), old, new, false);
+        assert_eq!(result.ok(), Some(QuickUnlock::Removed));
+        assert_eq!(entry(), None);
+    }
+
+    #[test]
+    fn rekey_without_entries_saves_nothing() {
+        let old = credentials(Some("old"), None);
+        let result = rekey_with(FAKE, None, old, credentials(Some("new"), None), false);
+        assert_eq!(result.ok(), Some(QuickUnlock::None));
+        assert_eq!(events(), ["delete", "write, entry None"]);
+        assert_eq!(entry(), None);
+    }
+
+    #[test]
+    fn rekey_does_not_write_when_an_entry_stays() {
+        let old = credentials(Some("old"), None);
+        let result = rekey_with(
+            LOCKED,
+            Some("old"),
+            old,
+            credentials(Some("new"), None),
+            false,
+        );
+        assert_eq!(
+            result.err().map(|e| e.code),
+            Some(ErrorCode::AuthFailed)
+        );
+        assert!(events().is_empty(), "the vault was not rewritten");
+        assert_eq!(entry().as_deref(), Some("old"));
+    }
+}
@@ -0,0 +1,202 @@
+// Read-only vault sessions
+//
+// A window may only write a vault whose lock it holds (see vault_lock.rs). Every
+// command that writes the vault file or changes what sits next to it checks
+// `ensure_writable` first, so a window that opened the vault without the lock, or lost
+// the lock, cannot save even if the frontend tries to. A window that opens a vault
+// read-only (explicitly, or because another window, instance or machine holds its
+// lock) also registers the session here. Nor can it lift the flag: the session only ends when the window closes or acquires the vault's lock, that
+// is when the vault is reopened for editing after its lock holder released it or the
+// user took the lock over. A window whose lock another window takes over becomes
+// read-only the same way.
+use crate::backup::canonical_path;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::logging::redact_path;
+use crate::vault_lock;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{command, Manager};
+
+/// Read-only sessions as (canonical vault path, window label) (managed state)
+#[derive(Default)]
+pub struct ReadOnlySessions(Mutex<HashSet<(PathBuf, String)>>);
+
+impl ReadOnlySessions {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashSet<(PathBuf, String)>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn mark(&self, canonical: PathBuf, window: &str) -> bool {
+        self.lock().insert((canonical, window.to_string()))
+    }
+
+    fn end(&self, canonical: &Path, window: &str) -> bool {
+        self.lock()
+            .remove(&(canonical.to_path_buf(), window.to_string()))
+    }
+
+    fn ensure_writable(&self, window: &str, canonical: &Path, holds_lock: bool) -> AppResult<()> {
+        if self
+            .lock()
+            .contains(&(canonical.to_path_buf(), window.to_string()))
+        {
+            return Err(AppError::new(
+                ErrorCode::ReadOnly,
+                "The vault is open read-only in this window",
+            ));
+        }
+        if !holds_lock {
+            return Err(AppError::new(
+                ErrorCode::ReadOnly,
+                "This window does not hold the vault's lock; reopen the vault to edit it",
+            ));
+        }
+        Ok(())
+    }
+
+    fn forget_window(&self, label: &str) {
+        self.lock().retain(|(_, window)| window != label);
+    }
+}
+
+/// Fail with `ReadOnly` if `window` has the vault at `path` open read-only or does not
+/// hold its lock
+pub fn ensure_writable(app: &tauri::AppHandle, window: &str, path: &str) -> AppResult<()> {
+    let canonical = canonical_path(path);
+    let holds_lock = vault_lock::holds_lock(app, window, &canonical);
+    app.state::<ReadOnlySessions>()
+        .ensure_writable(window, &canonical, holds_lock)
+}
+
+/// Whether any vault in the window is open read-only (menu state)
+pub fn is_window_read_only(app: &tauri::AppHandle, window: &str) -> bool {
+    app.try_state::<ReadOnlySessions>()
+        .is_some_and(|sessions| sessions.lock().iter().any(|(_, label)| label == window))
+}
+
+/// Drop the sessions of a window that was closed
+pub fn forget_window(app: &tauri::AppHandle, label: &str) {
+    if let Some(sessions) = app.try_state::<ReadOnlySessions>() {
+        sessions.forget_window(label);
+    }
+}
+
+/// End the read-only session of `window` on the vault at `canonical`, once the window
+/// holds the vault's lock (see vault_lock::acquire_vault_lock)
+pub(crate) fn end_session(app: &tauri::AppHandle, window: &str, canonical: &Path) {
+    if let Some(sessions) = app.try_state::<ReadOnlySessions>() {
+        if sessions.end(canonical, window) {
+            log::info!(
+                "{} is writable again",
+                redact_path(&canonical.to_string_lossy())
+            );
+        }
+    }
+}
+
+/// Make the session of `window` on the vault at `canonical` read-only, once another
+/// window took the vault's lock over (see vault_lock::acquire_vault_lock)
+pub(crate) fn begin_session(app: &tauri::AppHandle, window: &str, canonical: &Path) {
+    if let Some(sessions) = app.try_state::<ReadOnlySessions>() {
+        if sessions.mark(canonical.to_path_buf(), window) {
+            log::info!(
+                "{} is read-only in window {}",
+                redact_path(&canonical.to_string_lossy()),
+                window
+            );
+        }
+    }
+}
+
+/// Mark this window's session on the vault at `path` read-only. There is no command to
+/// undo it: the session ends when the window closes or acquires the vault's lock.
+#[command]
+pub async fn mark_vault_read_only(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+) -> AppResult<()> {
+    let sessions = app_handle.state::<ReadOnlySessions>();
+    if sessions.mark(canonical_path(&path), window.label()) {
+        log::info!("Opened {} read-only", redact_path(&path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault(name: &str) -> PathBuf {
+        PathBuf::from("/vaults").join(name)
+    }
+
+    fn code(result: AppResult<()>) -> Option<ErrorCode> {
+        result.err().map(|e| e.code)
+    }
+
+    #[test]
+    fn ensure_writable_refuses_read_only_sessions() {
+        let sessions = ReadOnlySessions::default();
+        assert!(sessions
+            .ensure_writable("main", &vault("a.kdbx"), true)
+            .is_ok());
+        assert!(sessions.mark(vault("a.kdbx"), "main"));
+        assert!(!sessions.mark(vault("a.kdbx"), "main"), "marked once");
+
+        assert_eq!(
+            code(sessions.ensure_writable("main", &vault("a.kdbx"), true)),
+            Some(ErrorCode::ReadOnly)
+        );
+        // Other vaults of the window, and other windows on the vault, stay writable
+        assert!(sessions
+            .ensure_writable("main", &vault("b.kdbx"), true)
+            .is_ok());
+        assert!(sessions
+            .ensure_writable("vault-2", &vault("a.kdbx"), true)
+            .is_ok());
+    }
+
+    #[test]
+    fn ensure_writable_requires_the_lock() {
+        let sessions = ReadOnlySessions::default();
+        assert_eq!(
+            code(sessions.ensure_writable("main", &vault("a.kdbx"), false)),
+            Some(ErrorCode::ReadOnly)
+        );
+        // Acquiring the lock ends the read-only session, and only then can the window write
+        sessions.mark(vault("a.kdbx"), "main");
+        sessions.end(&vault("a.kdbx"), "main");
+        assert!(sessions
+            .ensure_writable("main", &vault("a.kdbx"), true)
+            .is_ok());
+    }
+
+    #[test]
+    fn read_only_sessions_end_per_window_and_vault() {
+        let sessions = ReadOnlySessions::default();
+        sessions.mark(vault("a.kdbx"), "main");
+        sessions.mark(vault("b.kdbx"), "main");
+        sessions.mark(vault("a.kdbx"), "vault-2");
+
+        assert!(!sessions.end(&vault("a.kdbx"), "vault-3"));
+        assert!(sessions.end(&vault("a.kdbx"), "main"));
+        assert!(sessions
+            .ensure_writable("main", &vault("a.kdbx"), true)
+            .is_ok());
+        assert_eq!(
+            code(sessions.ensure_writable("vault-2", &vault("a.kdbx"), true)),
+            Some(ErrorCode::ReadOnly)
+        );
+
+        sessions.forget_window("main");
+        assert!(sessions
+            .ensure_writable("main", &vault("b.kdbx"), true)
+            .is_ok());
+        assert_eq!(
+            code(sessions.ensure_writable("vault-2", &vault("a.kdbx"), true)),
+            Some(ErrorCode::ReadOnly)
+        );
+    }
+}
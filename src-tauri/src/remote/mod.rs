@@ -0,0 +1,455 @@
+// Vaults stored on a server (WebDAV, S3-compatible object storage, SFTP)
+//
+// A remote vault is downloaded into a local cache file that the rest of the app opens,
+// saves, backs up, locks and watches like any local vault. After each save the window
+// pushes the cache to the server with a conditional write against the version it was
+// downloaded at. When the server copy changed in the meantime, the cache is merged into
+// it (see kdbx::merge), the result is uploaded instead and the window reloads it.
+//
+// Caches live in `<app data>/remote/<key>/`, where `key` is derived from the vault URL,
+// next to a `state.json` with the server version and whether saves are still waiting to
+// be uploaded. A cache with pending saves is never replaced by a download. Server logins
+// are kept in the secret store, keyed by vault URL.
+pub mod s3;
+pub mod sftp;
+#[cfg(test)]
+mod test_server;
+pub mod webdav;
+
+use crate::backup::{self, write_atomic};
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::kdbx::merge::{self, MergeSummary};
+use crate::kdbx::{Credentials, Database};
+use crate::logging::redact_path;
+use crate::read_only;
+use crate::unlock_attempts;
+use crate::watcher::{self, WriteGuard};
+use chrono::{DateTime, Utc};
+use keyring::Entry;
+use s3::S3;
+use serde::{Deserialize, Serialize};
+use sftp::{HostKey, Sftp};
+use std::fs;
+use std::path::PathBuf;
+use tauri::async_runtime::Mutex;
+use tauri::{command, Manager};
+use webdav::WebDav;
+use zeroize::Zeroize;
+
+const CACHE_DIR_NAME: &str = "remote";
+const STATE_FILE_NAME: &str = "state.json";
+/// Host keys of SFTP servers first seen by the app, next to the caches
+const KNOWN_HOSTS_FILE_NAME: &str = "known_hosts";
+const SECRET_SERVICE: &str = "keedavault-remote";
+
+/// Login for a server, as stored in the secret store; for S3 the access key ID and
+/// secret access key, for SFTP a private key file and its passphrase
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteAuth {
+    pub username: String,
+    pub secret: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<String>,
+}
+
+impl Drop for RemoteAuth {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+pub enum Fetched {
+    /// The server still has the version the caller knows
+    Unchanged,
+    Data {
+        data: Vec<u8>,
+        version: Option<String>,
+    },
+}
+
+/// Storage service behind a vault URL
+enum Backend {
+    WebDav(WebDav),
+    S3(S3),
+    Sftp(Sftp),
+}
+
+impl Backend {
+    fn connect(app: &tauri::AppHandle, url: &str, auth: Option<RemoteAuth>) -> AppResult<Self> {
+        let scheme = url
+            .split_once("://")
+            .map(|(scheme, _)| scheme.to_ascii_lowercase());
+        match scheme.as_deref() {
+            Some("http" | "https" | "webdav" | "webdavs") => {
+                Ok(Backend::WebDav(WebDav::new(url, auth)?))
+            }
+            Some("s3") => Ok(Backend::S3(S3::new(url, auth)?)),
+            Some("sftp") => {
+                let known_hosts = app
+                    .path()
+                    .app_data_dir()?
+                    .join(CACHE_DIR_NAME)
+                    .join(KNOWN_HOSTS_FILE_NAME);
+                Ok(Backend::Sftp(Sftp::new(url, auth, known_hosts)?))
+            }
+            _ => Err(AppError::unsupported(
+                "Unsupported vault location; use a WebDAV, s3:// or sftp:// URL",
+            )),
+        }
+    }
+
+    async fn fetch(&self, known: Option<&str>) -> AppResult<Fetched> {
+        match self {
+            Backend::WebDav(webdav) => webdav.fetch(known).await,
+            Backend::S3(s3) => s3.fetch(known).await,
+            Backend::Sftp(sftp) => sftp.fetch(known).await,
+        }
+    }
+
+    async fn store(&self, data: Vec<u8>, expected: Option<&str>) -> AppResult<Option<String>> {
+        match self {
+            Backend::WebDav(webdav) => webdav.store(data, expected).await,
+            Backend::S3(s3) => s3.store(data, expected).await,
+            Backend::Sftp(sftp) => sftp.store(data, expected).await,
+        }
+    }
+}
+
+/// Serializes pushes, so two saves in a row cannot race each other to the server
+/// (managed state)
+#[derive(Default)]
+pub struct RemoteSyncs(Mutex<()>);
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheState {
+    url: String,
+    /// Server version the cache was last downloaded or uploaded at
+    version: Option<String>,
+    /// The cache holds saves that are not on the server yet
+    pending: bool,
+    synced_at: Option<DateTime<Utc>>,
+}
+
+struct Cache {
+    dir: PathBuf,
+    file: PathBuf,
+    state: CacheState,
+}
+
+/// Percent-decode one URL component
+fn percent_decode(component: &str) -> Vec<u8> {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Last path segment of the URL, percent-decoded, as the cache file name
+fn file_name_of(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let segment = path.rsplit('/').next().unwrap_or_default();
+    let name = String::from_utf8_lossy(&percent_decode(segment)).replace(['/', '\\', '\0'], "_");
+    match name.trim() {
+        "" | "." | ".." => "Vault.kdbx".to_string(),
+        name => name.to_string(),
+    }
+}
+
+impl Cache {
+    fn open(app: &tauri::AppHandle, url: &str) -> AppResult<Self> {
+        let key = &backup::sha256_hex(url.as_bytes())[..32];
+        let dir = app.path().app_data_dir()?.join(CACHE_DIR_NAME).join(key);
+        fs::create_dir_all(&dir)?;
+        let state = match fs::read(dir.join(STATE_FILE_NAME)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                log::warn!("Remote cache state is unreadable, starting over: {}", e);
+                CacheState::default()
+            }),
+            Err(_) => CacheState::default(),
+        };
+        Ok(Self {
+            file: dir.join(file_name_of(url)),
+            dir,
+            state: CacheState {
+                url: url.to_string(),
+                ..state
+            },
+        })
+    }
+
+    fn path(&self) -> String {
+        self.file.to_string_lossy().into_owned()
+    }
+
+    fn save_state(&self) -> AppResult<()> {
+        let json = serde_json::to_vec_pretty(&self.state)
+            .map_err(|e| AppError::internal(e.to_string()))?;
+        write_atomic(&self.dir.join(STATE_FILE_NAME), &json)
+    }
+
+    fn synced(&mut self, version: Option<String>) -> AppResult<()> {
+        self.state.version = version;
+        self.state.pending = false;
+        self.state.synced_at = Some(Utc::now());
+        self.save_state()
+    }
+}
+
+/// The stored login for `url`; servers that need one answer `AuthFailed` without it
+fn load_auth(url: &str) -> Option<RemoteAuth> {
+    let stored = Entry::new(SECRET_SERVICE, url).and_then(|entry| entry.get_password());
+    match stored {
+        Ok(json) => serde_json::from_str(&json)
+            .map_err(|e| log::warn!("Stored server login is unreadable: {}", e))
+            .ok(),
+        Err(keyring::Error::NoEntry) => None,
+        Err(e) => {
+            log::warn!("Secret store unavailable: {}", e);
+            None
+        }
+    }
+}
+
+/// A remote vault as opened by a window
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteVault {
+    pub url: String,
+    /// Local cache file the window opens and saves
+    pub cache_path: String,
+    /// The server could not be reached; the cached copy is opened
+    pub offline: bool,
+    /// The cache holds saves that are not on the server yet; push after unlocking
+    pub pending: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushReport {
+    /// Set when the server copy had changed and was merged with the cache; the window
+    /// reloads the cache. `None` as well when the merge was uploaded but the cache had
+    /// changed meanwhile, so it was left alone
+    pub merge: Option<MergeSummary>,
+}
+
+/// Keep the server login for the vault at `url` in the secret store
+#[command]
+pub async fn set_remote_credentials(
+    url: String,
+    username: String,
+    secret: String,
+    key_file: Option<String>,
+) -> AppResult<()> {
+    let auth = RemoteAuth {
+        username,
+        secret,
+        key_file,
+    };
+    let json = serde_json::to_string(&auth).map_err(|e| AppError::internal(e.to_string()))?;
+    Entry::new(SECRET_SERVICE, &url)?.set_password(&json)?;
+    log::info!("Stored server login for {}", redact_path(&url));
+    Ok(())
+}
+
+#[command]
+pub async fn delete_remote_credentials(url: String) -> AppResult<()> {
+    match Entry::new(SECRET_SERVICE, &url)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The SFTP client for `url`; only SFTP servers have host keys to confirm
+fn connect_sftp(app: &tauri::AppHandle, url: &str) -> AppResult<Sftp> {
+    match Backend::connect(app, url, load_auth(url))? {
+        Backend::Sftp(sftp) => Ok(sftp),
+        _ => Err(AppError::invalid_input("Only SFTP servers have host keys")),
+    }
+}
+
+/// Host key of the SFTP server behind `url`, for the user to confirm after a connection
+/// failed with `UnknownHostKey`
+#[command]
+pub async fn remote_host_key(app_handle: tauri::AppHandle, url: String) -> AppResult<HostKey> {
+    connect_sftp(&app_handle, &url)?.host_key().await
+}
+
+/// Trust the host key of the SFTP server behind `url`, provided it still has the
+/// `fingerprint` the user confirmed
+#[command]
+pub async fn trust_remote_host_key(
+    app_handle: tauri::AppHandle,
+    url: String,
+    fingerprint: String,
+) -> AppResult<()> {
+    connect_sftp(&app_handle, &url)?
+        .trust_host_key(&fingerprint)
+        .await
+}
+
+/// Download the vault at `url` into its cache, unless the cache is current or holds saves
+/// that are not uploaded yet. Falls back to the cache when the server is unreachable.
+#[command]
+pub async fn open_remote_vault(
+    app_handle: tauri::AppHandle,
+    url: String,
+) -> AppResult<RemoteVault> {
+    let mut cache = Cache::open(&app_handle, &url)?;
+    let cached = cache.file.exists();
+    let mut vault = RemoteVault {
+        url: url.clone(),
+        cache_path: cache.path(),
+        offline: false,
+        pending: cached && cache.state.pending,
+    };
+    if vault.pending {
+        // Downloading would throw those saves away; the next push merges instead
+        log::info!("Opening {} from cache with unsent saves", redact_path(&url));
+        return Ok(vault);
+    }
+
+    let backend = Backend::connect(&app_handle, &url, load_auth(&url))?;
+    let known = cache.state.version.as_deref().filter(|_| cached);
+    match backend.fetch(known).await {
+        Ok(Fetched::Unchanged) => {}
+        Ok(Fetched::Data { data, version }) => {
+            backup::check_kdbx(&data)?;
+            {
+                let _guard = backup::lock_transaction();
+                write_atomic(&cache.file, &data)?;
+            }
+            cache.synced(version)?;
+            log::info!("Downloaded {}", redact_path(&url));
+        }
+        Err(e) if cached && matches!(e.code, ErrorCode::Io | ErrorCode::BackendUnavailable) => {
+            log::warn!("Opening {} from cache: {}", redact_path(&url), e.message);
+            vault.offline = true;
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(vault)
+}
+
+/// Upload the cache of the vault at `url` after a save. When the server copy changed
+/// since the last sync, the cache is merged into it, uploaded and written back to the
+/// cache; the window then reloads. Once the merge is uploaded the push succeeds even if
+/// the cache changed meanwhile: that is left to the next push and the file watcher.
+#[command]
+pub async fn push_remote_vault(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    url: String,
+    vault_id: Option<String>,
+    credentials: Credentials,
+) -> AppResult<PushReport> {
+    let syncs = app_handle.state::<RemoteSyncs>();
+    let _push = syncs.0.lock().await;
+
+    let mut cache = Cache::open(&app_handle, &url)?;
+    let path = cache.path();
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    let data = fs::read(&cache.file)?;
+    let backend = Backend::connect(&app_handle, &url, load_auth(&url))?;
+
+    // Until the upload succeeds, the cache must not be replaced by a download
+    cache.state.pending = true;
+    cache.save_state()?;
+    if cache.state.version.is_none() {
+        log::warn!(
+            "{} has no server version; uploading without a conflict check",
+            redact_path(&url)
+        );
+    }
+    match backend
+        .store(data.clone(), cache.state.version.as_deref())
+        .await
+    {
+        Ok(version) => {
+            cache.synced(version)?;
+            log::info!("Uploaded {}", redact_path(&url));
+            return Ok(PushReport { merge: None });
+        }
+        Err(e) if e.code == ErrorCode::Conflict => {}
+        Err(e) => return Err(e),
+    }
+
+    // Someone else uploaded since our last sync: merge our copy into theirs
+    let Fetched::Data {
+        data: server_data,
+        version,
+    } = backend.fetch(None).await?
+    else {
+        return Err(AppError::internal("The server sent no data"));
+    };
+    // The key derivations and the merge run on a blocking thread
+    let (app, location, local) = (app_handle.clone(), url.clone(), data.clone());
+    let (summary, merged, entry_count) = tauri::async_runtime::spawn_blocking(move || {
+        let mut target = unlock_attempts::open(&app, &location, &server_data, &credentials)?;
+        let summary = merge::merge(&mut target, Database::open(&local, &credentials)?);
+        let merged = if summary.is_empty() {
+            server_data
+        } else {
+            target.save(&credentials)?
+        };
+        AppResult::Ok((summary, merged, u32::try_from(target.entry_count()).ok()))
+    })
+    .await??;
+    log::info!(
+        "Merged the local copy into {}: {} change(s)",
+        redact_path(&url),
+        summary.changes.len()
+    );
+    let version = if summary.is_empty() {
+        version
+    } else {
+        backend.store(merged.clone(), version.as_deref()).await?
+    };
+
+    // Bring the cache to the merged state, unless it changed meanwhile (the window saved
+    // again). The upload stands; the cache keeps the old version so the next push merges
+    // once more, and windows that do not hold the new file are told it changed
+    match backup::save_transaction(
+        &app_handle,
+        window.label(),
+        &path,
+        merged,
+        vault_id,
+        entry_count,
+        true,
+        WriteGuard::Sha256(backup::sha256_hex(&data)),
+    ) {
+        Ok(_) => {}
+        Err(e) if e.code == ErrorCode::Conflict => {
+            log::warn!(
+                "Uploaded the merge of {}, but its cache changed meanwhile: {}",
+                redact_path(&url),
+                e.message
+            );
+            watcher::flag_changed(&app_handle, &path);
+            return Ok(PushReport { merge: None });
+        }
+        Err(e) => return Err(e),
+    }
+    cache.synced(version)?;
+    Ok(PushReport {
+        merge: Some(summary),
+    })
+}
@@ -0,0 +1,594 @@
+// SFTP client for remote vaults (any OpenSSH server)
+//
+// Vault locations look like `sftp://user@host[:port]/path/Vault.kdbx`; the path is
+// absolute unless it starts with `/~/`, which is relative to the login directory. Logins
+// use the key file stored for the URL, else the keys in ssh-agent. Host keys are checked
+// against `~/.ssh/known_hosts` and the app's own known_hosts file. A host seen for the
+// first time fails with `UnknownHostKey` and its fingerprint; it is only added to the
+// app's file once the user confirmed that fingerprint. A changed host key is refused.
+//
+// The version is the file's modification time and size. Uploads check it first and fail
+// with `Conflict` when the file changed, then write a temporary file next to the vault
+// and rename it over the vault, so readers never see a half-written file.
+//
+// libssh2 is blocking, so every operation runs on a blocking thread with its own session.
+use super::{percent_decode, Fetched, RemoteAuth};
+use crate::error::{AppError, AppResult, ErrorCode};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use reqwest::Url;
+use serde::Serialize;
+use ssh2::{
+    CheckResult, FileStat, HashType, KnownHostFileKind, KnownHostKeyFormat, Session,
+    Sftp as SftpChannel,
+};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 22;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(20);
+const SESSION_TIMEOUT_MS: u32 = 60_000;
+
+#[derive(Clone)]
+pub struct Sftp {
+    host: String,
+    port: u16,
+    user: String,
+    path: PathBuf,
+    auth: Option<RemoteAuth>,
+    /// known_hosts file the app adds first-seen hosts to
+    known_hosts: PathBuf,
+}
+
+/// Host key of an SFTP server, for the user to compare before trusting it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostKey {
+    /// `host`, or `[host]:port` for other ports, as written to known_hosts
+    pub host: String,
+    /// `SHA256:` fingerprint, as `ssh-keygen -l` prints it
+    pub fingerprint: String,
+    /// The key is already in a known_hosts file
+    pub known: bool,
+}
+
+/// Version token of a remote file: modification time and size
+fn version_of(stat: &FileStat) -> String {
+    format!(
+        "{}:{}",
+        stat.mtime.unwrap_or_default(),
+        stat.size.unwrap_or_default()
+    )
+}
+
+/// `SHA256:` fingerprint of the session's host key
+fn fingerprint(session: &Session) -> AppResult<String> {
+    let hash = session
+        .host_key_hash(HashType::Sha256)
+        .ok_or_else(|| AppError::internal("The SFTP server sent no host key"))?;
+    Ok(format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+}
+
+/// Quote an argument for the remote POSIX shell
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+fn default_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+impl Sftp {
+    /// `url` is `sftp://user@host[:port]/path`; `known_hosts` is the app's own file
+    pub fn new(url: &str, auth: Option<RemoteAuth>, known_hosts: PathBuf) -> AppResult<Self> {
+        let invalid =
+            |reason: &str| AppError::invalid_input(format!("Invalid SFTP URL: {}", reason));
+        let parsed = Url::parse(url).map_err(|e| invalid(&e.to_string()))?;
+        let host = parsed
+            .host_str()
+            .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| invalid("no host"))?
+            .to_string();
+        let user = match String::from_utf8_lossy(&percent_decode(parsed.username())) {
+            user if !user.is_empty() => user.into_owned(),
+            _ => auth
+                .as_ref()
+                .map(|auth| auth.username.clone())
+                .filter(|user| !user.is_empty())
+                .unwrap_or_else(default_user),
+        };
+        let path = String::from_utf8_lossy(&percent_decode(parsed.path())).into_owned();
+        let path = match path.strip_prefix("/~/") {
+            Some(relative) => PathBuf::from(relative),
+            None => PathBuf::from(path),
+        };
+        if path.file_name().is_none() {
+            return Err(invalid("no file path"));
+        }
+        Ok(Self {
+            host,
+            port: parsed.port().unwrap_or(DEFAULT_PORT),
+            user,
+            path,
+            auth,
+            known_hosts,
+        })
+    }
+
+    /// Download the vault; `known` skips the download when the file still has that version
+    pub async fn fetch(&self, known: Option<&str>) -> AppResult<Fetched> {
+        let this = self.clone();
+        let known = known.map(str::to_string);
+        tauri::async_runtime::spawn_blocking(move || this.fetch_blocking(known.as_deref())).await?
+    }
+
+    /// Upload the vault if the file still has version `expected`, failing with `Conflict`
+    /// otherwise; `None` uploads unconditionally. Returns the new version.
+    pub async fn store(&self, data: Vec<u8>, expected: Option<&str>) -> AppResult<Option<String>> {
+        let this = self.clone();
+        let expected = expected.map(str::to_string);
+        tauri::async_runtime::spawn_blocking(move || {
+            this.store_blocking(&data, expected.as_deref())
+        })
+        .await?
+    }
+
+    /// The server's host key and whether it is known; a changed key is an error
+    pub async fn host_key(&self) -> AppResult<HostKey> {
+        let this = self.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let session = this.handshake()?;
+            let known = match this.check_host_key(&session) {
+                Ok(()) => true,
+                Err(e) if e.code == ErrorCode::UnknownHostKey => false,
+                Err(e) => return Err(e),
+            };
+            Ok(HostKey {
+                host: this.known_hosts_name(),
+                fingerprint: fingerprint(&session)?,
+                known,
+            })
+        })
+        .await?
+    }
+
+    /// Add the server's host key to the app's known_hosts, if it still has the
+    /// `fingerprint` the user confirmed
+    pub async fn trust_host_key(&self, fingerprint: &str) -> AppResult<()> {
+        let this = self.clone();
+        let confirmed = fingerprint.to_string();
+        tauri::async_runtime::spawn_blocking(move || {
+            let session = this.handshake()?;
+            match this.check_host_key(&session) {
+                Err(e) if e.code == ErrorCode::UnknownHostKey => {}
+                known => return known,
+            }
+            if self::fingerprint(&session)? != confirmed {
+                return Err(AppError::new(
+                    ErrorCode::Conflict,
+                    format!(
+                        "The host key of {} is not the one that was confirmed",
+                        this.host
+                    ),
+                )
+                .retryable(false));
+            }
+            this.add_host_key(&session)
+        })
+        .await?
+    }
+
+    fn fetch_blocking(&self, known: Option<&str>) -> AppResult<Fetched> {
+        let session = self.connect()?;
+        let sftp = session.sftp()?;
+        let version = version_of(&sftp.stat(&self.path)?);
+        if known == Some(version.as_str()) {
+            return Ok(Fetched::Unchanged);
+        }
+        let mut data = Vec::new();
+        sftp.open(&self.path)?.read_to_end(&mut data)?;
+        Ok(Fetched::Data {
+            data,
+            version: Some(version),
+        })
+    }
+
+    fn store_blocking(&self, data: &[u8], expected: Option<&str>) -> AppResult<Option<String>> {
+        let session = self.connect()?;
+        let sftp = session.sftp()?;
+        let current = match sftp.stat(&self.path) {
+            Ok(stat) => Some(stat),
+            Err(e) if expected.is_none() => {
+                log::debug!("No remote file to replace: {}", e);
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let (Some(expected), Some(current)) = (expected, &current) {
+            if version_of(current) != expected {
+                return Err(AppError::new(
+                    ErrorCode::Conflict,
+                    "The vault changed on the SFTP server",
+                ));
+            }
+        }
+
+        let file_name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let temp = self.path.with_file_name(format!(
+            ".{}.{}.tmp",
+            file_name,
+            uuid::Uuid::new_v4().simple()
+        ));
+        let written = (|| -> AppResult<()> {
+            let mut file = sftp.create(&temp)?;
+            file.write_all(data)?;
+            // fsync@openssh.com; older servers do without
+            if let Err(e) = file.fsync() {
+                log::debug!("SFTP server cannot fsync: {}", e);
+            }
+            drop(file);
+            // Keep the vault's permissions (usually 0600) rather than the umask default
+            if let Some(perm) = current.as_ref().and_then(|stat| stat.perm) {
+                sftp.setstat(
+                    &temp,
+                    FileStat {
+                        size: None,
+                        uid: None,
+                        gid: None,
+                        perm: Some(perm),
+                        atime: None,
+                        mtime: None,
+                    },
+                )?;
+            }
+            self.replace(&session, &sftp, &temp)
+        })();
+        if let Err(e) = written {
+            let _ = sftp.unlink(&temp);
+            return Err(e);
+        }
+        Ok(Some(version_of(&sftp.stat(&self.path)?)))
+    }
+
+    /// Rename `temp` over the vault
+    fn replace(&self, session: &Session, sftp: &SftpChannel, temp: &Path) -> AppResult<()> {
+        // SFTP v5+ servers overwrite atomically; OpenSSH speaks v3 and only renames onto
+        // paths that do not exist yet
+        if sftp.rename(temp, &self.path, None).is_ok() {
+            return Ok(());
+        }
+        // rename(2) through the shell is atomic, where the account may run commands
+        match self.shell_rename(session, temp) {
+            // A server forcing `internal-sftp` also exits 0, so check the file really moved
+            Ok(()) if sftp.stat(temp).is_err() => return Ok(()),
+            Ok(()) => log::debug!("Remote mv had no effect, swapping files"),
+            Err(e) => log::debug!("Remote mv unavailable, swapping files: {}", e.message),
+        }
+        // SFTP-only accounts: move the vault aside first. Between the two renames the
+        // vault is missing, but never half-written.
+        let old = temp.with_extension("old");
+        sftp.rename(&self.path, &old, None)?;
+        if let Err(e) = sftp.rename(temp, &self.path, None) {
+            let _ = sftp.rename(&old, &self.path, None);
+            return Err(e.into());
+        }
+        let _ = sftp.unlink(&old);
+        Ok(())
+    }
+
+    fn shell_rename(&self, session: &Session, temp: &Path) -> AppResult<()> {
+        let mut channel = session.channel_session()?;
+        channel.exec(&format!(
+            "mv -f -- {} {}",
+            shell_quote(temp),
+            shell_quote(&self.path)
+        ))?;
+        channel.send_eof()?;
+        let mut output = String::new();
+        channel.read_to_string(&mut output)?;
+        channel.wait_close()?;
+        match channel.exit_status()? {
+            0 => Ok(()),
+            status => Err(
+                AppError::new(ErrorCode::Io, format!("mv exited with {}", status))
+                    .with_os_status(i64::from(status)),
+            ),
+        }
+    }
+
+    fn connect(&self) -> AppResult<Session> {
+        let session = self.handshake()?;
+        self.check_host_key(&session)?;
+        self.authenticate(&session)?;
+        Ok(session)
+    }
+
+    /// Open the SSH connection, without trusting or logging in yet
+    fn handshake(&self) -> AppResult<Session> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| AppError::new(ErrorCode::Io, "The SFTP host name did not resolve"))?;
+        let tcp = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.set_timeout(SESSION_TIMEOUT_MS);
+        session.handshake()?;
+        Ok(session)
+    }
+
+    fn known_hosts_name(&self) -> String {
+        match self.port {
+            DEFAULT_PORT => self.host.clone(),
+            port => format!("[{}]:{}", self.host, port),
+        }
+    }
+
+    /// Accept known host keys; refuse changed and unknown ones
+    fn check_host_key(&self, session: &Session) -> AppResult<()> {
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| AppError::internal("The SFTP server sent no host key"))?;
+        let mut known = session.known_hosts()?;
+        let user_file = std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".ssh").join("known_hosts"));
+        for file in user_file.iter().chain([&self.known_hosts]) {
+            if file.exists() {
+                if let Err(e) = known.read_file(file, KnownHostFileKind::OpenSSH) {
+                    log::warn!("Skipping unreadable known_hosts file: {}", e);
+                }
+            }
+        }
+
+        match known.check_port(&self.host, self.port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(AppError::new(
+                ErrorCode::AuthFailed,
+                format!(
+                    "The host key of {} changed; if that is expected, remove its old key \
+                     from known_hosts",
+                    self.host
+                ),
+            )
+            .retryable(false)),
+            CheckResult::Failure => Err(AppError::internal("Could not check the SFTP host key")),
+            CheckResult::NotFound => Err(AppError::new(
+                ErrorCode::UnknownHostKey,
+                format!(
+                    "{} is not a known host. Its key fingerprint is {}; trust it only if \
+                     that is the fingerprint of the server",
+                    self.known_hosts_name(),
+                    fingerprint(session)?
+                ),
+            )),
+        }
+    }
+
+    /// Write the server's host key to the app's known_hosts file
+    fn add_host_key(&self, session: &Session) -> AppResult<()> {
+        let (key, key_type) = session
+            .host_key()
+            .ok_or_else(|| AppError::internal("The SFTP server sent no host key"))?;
+        // Only the new host goes into the app's file, not the user's entries
+        let mut ours = session.known_hosts()?;
+        if self.known_hosts.exists() {
+            ours.read_file(&self.known_hosts, KnownHostFileKind::OpenSSH)?;
+        }
+        let name = self.known_hosts_name();
+        ours.add(&name, key, "keedavault", KnownHostKeyFormat::from(key_type))?;
+        ours.write_file(&self.known_hosts, KnownHostFileKind::OpenSSH)?;
+        log::info!("Trusting the confirmed host key of {}", name);
+        Ok(())
+    }
+
+    fn authenticate(&self, session: &Session) -> AppResult<()> {
+        match self.auth.as_ref().and_then(|auth| auth.key_file.as_deref()) {
+            Some(key_file) => {
+                let passphrase = self
+                    .auth
+                    .as_ref()
+                    .map(|auth| auth.secret.as_str())
+                    .filter(|secret| !secret.is_empty());
+                // A wrong key or passphrase is reported by the check below
+                if let Err(e) =
+                    session.userauth_pubkey_file(&self.user, None, Path::new(key_file), passphrase)
+                {
+                    log::debug!("Key file login failed: {}", e);
+                }
+            }
+            None => {
+                let mut agent = session.agent()?;
+                agent.connect().map_err(|e| {
+                    AppError::new(
+                        ErrorCode::AuthFailed,
+                        format!("No ssh-agent is running; choose a key file ({})", e),
+                    )
+                })?;
+                agent.list_identities()?;
+                for identity in agent.identities()? {
+                    if agent.userauth(&self.user, &identity).is_ok() {
+                        break;
+                    }
+                }
+                let _ = agent.disconnect();
+            }
+        }
+        if session.authenticated() {
+            Ok(())
+        } else {
+            Err(AppError::new(
+                ErrorCode::AuthFailed,
+                format!("The SFTP server did not accept the key for {}", self.user),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::net::TcpListener;
+    use std::process::{Child, Command, Stdio};
+
+    /// scripts/sftp-standin.sh serving a temporary directory, stopped when dropped
+    struct StandIn {
+        dir: PathBuf,
+        port: u16,
+        sshd: Child,
+    }
+
+    impl Drop for StandIn {
+        fn drop(&mut self) {
+            let _ = self.sshd.kill();
+            let _ = self.sshd.wait();
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn keygen(path: &Path) {
+        let status = Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(path)
+            .status()
+            .expect("ssh-keygen");
+        assert!(status.success());
+    }
+
+    /// Start the stand-in with a new client key; `None` where OpenSSH's sshd is not
+    /// installed, unless `KEEDAVAULT_REQUIRE_SFTP` is set (as in CI)
+    fn start() -> Option<StandIn> {
+        let installed = Command::new("sh")
+            .args(["-c", "command -v sshd || test -x /usr/sbin/sshd"])
+            .stdout(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !installed {
+            assert!(
+                std::env::var_os("KEEDAVAULT_REQUIRE_SFTP").is_none(),
+                "sshd is not installed"
+            );
+            eprintln!("Skipping the SFTP stand-in test: sshd is not installed");
+            return None;
+        }
+
+        let dir =
+            std::env::temp_dir().join(format!("keedavault-sftp-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).expect("temporary directory");
+        keygen(&dir.join("client_key"));
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("free port")
+            .port();
+        let sshd = Command::new("sh")
+            .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("../scripts/sftp-standin.sh"))
+            .arg(dir.join("server"))
+            .arg(port.to_string())
+            .env("SFTP_AUTHORIZED_KEYS", dir.join("client_key.pub"))
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("stand-in");
+        let stand_in = StandIn { dir, port, sshd };
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return Some(stand_in);
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        panic!("The SFTP stand-in did not start");
+    }
+
+    /// Fingerprint of a public key file, as OpenSSH prints it
+    fn ssh_fingerprint(public_key: &Path) -> String {
+        let output = Command::new("ssh-keygen")
+            .arg("-lf")
+            .arg(public_key)
+            .output()
+            .expect("ssh-keygen");
+        let output = String::from_utf8(output.stdout).expect("fingerprint");
+        output.split(' ').nth(1).expect("fingerprint").to_string()
+    }
+
+    #[test]
+    fn sftp_stand_in_host_keys_and_uploads() {
+        let Some(stand_in) = start() else { return };
+        let vault = stand_in.dir.join("server/data/Vault.kdbx");
+        let known_hosts = stand_in.dir.join("known_hosts");
+        let auth = RemoteAuth {
+            username: String::new(),
+            secret: String::new(),
+            key_file: Some(
+                stand_in
+                    .dir
+                    .join("client_key")
+                    .to_string_lossy()
+                    .into_owned(),
+            ),
+        };
+        let url = format!("sftp://127.0.0.1:{}{}", stand_in.port, vault.display());
+        let sftp = Sftp::new(&url, Some(auth), known_hosts.clone()).expect("client");
+        let expected = ssh_fingerprint(&stand_in.dir.join("server/etc/host_key.pub"));
+
+        // Another key on record for the host: refused, and never replaced
+        let other = stand_in.dir.join("other_key");
+        keygen(&other);
+        let other_key = fs::read_to_string(other.with_extension("pub")).expect("key");
+        let changed_hosts = stand_in.dir.join("changed_hosts");
+        let line = format!("[127.0.0.1]:{} {}", stand_in.port, other_key);
+        fs::write(&changed_hosts, line).expect("known_hosts");
+        let changed = Sftp {
+            known_hosts: changed_hosts.clone(),
+            ..sftp.clone()
+        };
+
+        tauri::async_runtime::block_on(async {
+            let Err(error) = sftp.fetch(None).await else {
+                panic!("an unknown host key was trusted");
+            };
+            assert_eq!(error.code, ErrorCode::UnknownHostKey);
+            assert!(error.message.contains(&expected), "{}", error.message);
+            assert!(!known_hosts.exists());
+
+            let host_key = sftp.host_key().await.expect("host key");
+            assert_eq!(host_key.host, format!("[127.0.0.1]:{}", stand_in.port));
+            assert_eq!(host_key.fingerprint, expected);
+            assert!(!host_key.known);
+            let error = sftp.trust_host_key("SHA256:not-the-key").await.err();
+            assert_eq!(error.map(|e| e.code), Some(ErrorCode::Conflict));
+            assert!(!known_hosts.exists());
+            sftp.trust_host_key(&expected).await.expect("trust");
+            assert!(sftp.host_key().await.expect("host key").known);
+
+            for result in [
+                changed.fetch(None).await.map(|_| ()),
+                changed.trust_host_key(&expected).await,
+            ] {
+                assert_eq!(result.err().map(|e| e.code), Some(ErrorCode::AuthFailed));
+            }
+            let line = fs::read_to_string(&changed_hosts).expect("known_hosts");
+            assert!(line.contains(other_key.trim()));
+
+            let first = sftp.store(b"first".to_vec(), None).await.expect("create");
+            assert_eq!(fs::read(&vault).expect("vault"), b"first");
+            let unchanged = sftp.fetch(first.as_deref()).await.expect("fetch");
+            assert!(matches!(unchanged, Fetched::Unchanged));
+            let second = sftp
+                .store(b"second!".to_vec(), first.as_deref())
+                .await
+                .expect("store");
+            let stale = sftp.store(b"stale".to_vec(), first.as_deref()).await;
+            assert_eq!(stale.err().map(|e| e.code), Some(ErrorCode::Conflict));
+            match sftp.fetch(None).await.expect("fetch") {
+                Fetched::Data { data, version } => {
+                    assert_eq!((data.as_slice(), version), (&b"second!"[..], second));
+                }
+                Fetched::Unchanged => panic!("nothing was downloaded"),
+            }
+        });
+    }
+}
@@ -0,0 +1,387 @@
+// WebDAV client for remote vaults (Nextcloud, ownCloud, Synology, Apache mod_dav...)
+//
+// Only GET and PUT on the vault file itself are needed. The ETag the server returns is
+// the version: downloads send `If-None-Match` to skip an unchanged file, uploads send
+// `If-Match` so the server rejects the save (412) when someone else uploaded in between.
+// Servers without strong ETags fall back to `Last-Modified` / `If-Unmodified-Since`.
+// An upload without a known version only creates the file (`If-None-Match: *`).
+//
+// `webdav://` and `webdavs://` both mean HTTPS. Plain HTTP sends the login and the vault
+// in the clear, so it is only used when the URL says `http://`.
+use super::{Fetched, RemoteAuth};
+use crate::error::{AppError, AppResult, ErrorCode};
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    IF_UNMODIFIED_SINCE, LAST_MODIFIED,
+};
+use reqwest::{Client, RequestBuilder, StatusCode, Url};
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Marks a version that is a `Last-Modified` date rather than an ETag
+const DATE_PREFIX: &str = "date:";
+
+pub struct WebDav {
+    client: Client,
+    url: Url,
+    auth: Option<RemoteAuth>,
+}
+
+/// Version token of a response: a strong ETag, else the `Last-Modified` date
+fn version_of(headers: &HeaderMap) -> Option<String> {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|v: &HeaderValue| v.to_str().ok())
+    };
+    // Weak ETags never match `If-Match`, which would turn every save into a merge
+    if let Some(etag) = header(ETAG).filter(|etag| !etag.starts_with("W/")) {
+        return Some(etag.to_string());
+    }
+    header(LAST_MODIFIED).map(|date| format!("{}{}", DATE_PREFIX, date))
+}
+
+fn check_status(status: StatusCode) -> AppResult<()> {
+    if status.is_success() {
+        return Ok(());
+    }
+    let (code, message) = match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => (
+            ErrorCode::AuthFailed,
+            "The WebDAV server rejected the login".to_string(),
+        ),
+        StatusCode::NOT_FOUND => (
+            ErrorCode::NotFound,
+            "The vault does not exist on the WebDAV server".to_string(),
+        ),
+        StatusCode::PRECONDITION_FAILED => (
+            ErrorCode::Conflict,
+            "The vault changed on the WebDAV server".to_string(),
+        ),
+        status if status.is_server_error() => (
+            ErrorCode::BackendUnavailable,
+            format!("The WebDAV server returned {}", status),
+        ),
+        status => (
+            ErrorCode::Io,
+            format!("The WebDAV server returned {}", status),
+        ),
+    };
+    Err(AppError::new(code, message).with_os_status(i64::from(status.as_u16())))
+}
+
+impl WebDav {
+    /// `url` is the vault file; `webdav://` and `webdavs://` stand for HTTPS, `http://`
+    /// is the explicit opt-in to an unencrypted connection
+    pub fn new(url: &str, auth: Option<RemoteAuth>) -> AppResult<Self> {
+        let url = match url.split_once("://") {
+            Some((scheme, rest))
+                if scheme.eq_ignore_ascii_case("webdav")
+                    || scheme.eq_ignore_ascii_case("webdavs") =>
+            {
+                format!("https://{}", rest)
+            }
+            _ => url.to_string(),
+        };
+        let url = Url::parse(&url)
+            .map_err(|e| AppError::invalid_input(format!("Invalid WebDAV URL: {}", e)))?;
+        if url.scheme() == "http" {
+            log::warn!(
+                "{} uses plain HTTP; the login and the vault are sent unencrypted",
+                url.host_str().unwrap_or_default()
+            );
+        }
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(format!("KeedaVault/{}", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self { client, url, auth })
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.auth {
+            Some(auth) => request.basic_auth(&auth.username, Some(&auth.secret)),
+            None => request,
+        }
+    }
+
+    /// Download the vault; `known` skips the download when the server still has that version
+    pub async fn fetch(&self, known: Option<&str>) -> AppResult<Fetched> {
+        let mut request = self.authorized(self.client.get(self.url.clone()));
+        match known.map(|v| v.strip_prefix(DATE_PREFIX).ok_or(v)) {
+            Some(Ok(date)) => request = request.header(IF_MODIFIED_SINCE, date),
+            Some(Err(etag)) => request = request.header(IF_NONE_MATCH, etag),
+            None => {}
+        }
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::Unchanged);
+        }
+        check_status(response.status())?;
+        let version = version_of(response.headers());
+        let data = response.bytes().await?.to_vec();
+        Ok(Fetched::Data { data, version })
+    }
+
+    /// Upload the vault if the server still has version `expected`, failing with `Conflict`
+    /// otherwise; `None` only creates the file and fails with `Conflict` when it exists.
+    /// Returns the new version, `None` when the response does not say.
+    pub async fn store(&self, data: Vec<u8>, expected: Option<&str>) -> AppResult<Option<String>> {
+        let request = self
+            .authorized(self.client.put(self.url.clone()))
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(data);
+        let request = match expected.map(|v| v.strip_prefix(DATE_PREFIX).ok_or(v)) {
+            Some(Ok(date)) => request.header(IF_UNMODIFIED_SINCE, date),
+            Some(Err(etag)) => request.header(IF_MATCH, etag),
+            None => request.header(IF_NONE_MATCH, "*"),
+        };
+
+        let response = request.send().await?;
+        check_status(response.status())?;
+        // Many servers do not return the new ETag from PUT. Asking with HEAD could return
+        // someone else's upload made since, and a version that is not ours would let the
+        // next save overwrite it; the next push merges instead.
+        Ok(version_of(response.headers()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::remote::test_server::{self, Request, Response};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    const LOGIN: &str = "Basic YWxpY2U6c2VjcmV0";
+
+    /// One-file WebDAV server with strong ETags and conditional requests, like
+    /// scripts/webdav-standin.js
+    #[derive(Default)]
+    struct StandIn {
+        file: Option<Vec<u8>>,
+        revision: usize,
+        /// Leave the ETag out of PUT responses, as many servers do
+        quiet_put: bool,
+        /// Method and headers (lowercase names) of every request
+        requests: Vec<(String, HashMap<String, String>)>,
+    }
+
+    impl StandIn {
+        fn etag(&self) -> String {
+            format!("\"v{}\"", self.revision)
+        }
+
+        fn handle(&mut self, request: &Request) -> Response {
+            self.requests
+                .push((request.method.clone(), request.headers.clone()));
+            if request.header("authorization") != Some(LOGIN) {
+                return Response::new(401);
+            }
+            let etag = self.etag();
+            match (request.method.as_str(), &self.file) {
+                ("GET" | "HEAD", None) => Response::new(404),
+                ("GET" | "HEAD", Some(_)) if request.header("if-none-match") == Some(&etag) => {
+                    Response::new(304).header("ETag", etag)
+                }
+                ("GET", Some(file)) => Response::new(200).header("ETag", etag).body(file.clone()),
+                ("HEAD", Some(_)) => Response::new(200).header("ETag", etag),
+                ("PUT", current) => {
+                    let failed = match (request.header("if-match"), request.header("if-none-match"))
+                    {
+                        (Some(expected), _) => current.is_none() || expected != etag,
+                        (None, Some("*")) => current.is_some(),
+                        _ => false,
+                    };
+                    if failed {
+                        return Response::new(412);
+                    }
+                    let response = Response::new(if current.is_some() { 204 } else { 201 });
+                    self.file = Some(request.body.clone());
+                    self.revision += 1;
+                    if self.quiet_put {
+                        response
+                    } else {
+                        response.header("ETag", self.etag())
+                    }
+                }
+                _ => Response::new(405),
+            }
+        }
+    }
+
+    /// Start a stand-in holding `file`; returns it and a client for its vault
+    fn serve(file: Option<&[u8]>, quiet_put: bool) -> (Arc<Mutex<StandIn>>, WebDav) {
+        let server = Arc::new(Mutex::new(StandIn {
+            file: file.map(<[u8]>::to_vec),
+            revision: 1,
+            quiet_put,
+            ..StandIn::default()
+        }));
+        let shared = server.clone();
+        let url =
+            test_server::serve(move |request| shared.lock().expect("stand-in").handle(request));
+        let auth = RemoteAuth {
+            username: "alice".to_string(),
+            secret: "secret".to_string(),
+            key_file: None,
+        };
+        let url = format!("{}/dav/Vault.kdbx", url);
+        (server, WebDav::new(&url, Some(auth)).expect("client"))
+    }
+
+    fn fetched(result: AppResult<Fetched>) -> Option<(Vec<u8>, Option<String>)> {
+        match result.expect("fetch") {
+            Fetched::Unchanged => None,
+            Fetched::Data { data, version } => Some((data, version)),
+        }
+    }
+
+    fn methods(server: &Mutex<StandIn>) -> Vec<String> {
+        let server = server.lock().expect("stand-in");
+        server
+            .requests
+            .iter()
+            .map(|(method, _)| method.clone())
+            .collect()
+    }
+
+    #[test]
+    fn webdav_urls_default_to_https() {
+        for (url, expected) in [
+            (
+                "webdav://nas.local/dav/Vault.kdbx",
+                "https://nas.local/dav/Vault.kdbx",
+            ),
+            (
+                "WebDAVs://nas.local:8443/Vault.kdbx",
+                "https://nas.local:8443/Vault.kdbx",
+            ),
+            (
+                "https://cloud.example.com/remote.php/dav/files/a/Vault.kdbx",
+                "https://cloud.example.com/remote.php/dav/files/a/Vault.kdbx",
+            ),
+            // Plain HTTP only when asked for by name
+            (
+                "http://127.0.0.1:8080/Vault.kdbx",
+                "http://127.0.0.1:8080/Vault.kdbx",
+            ),
+        ] {
+            let webdav = WebDav::new(url, None).expect("client");
+            assert_eq!(webdav.url.as_str(), expected);
+        }
+        let error = WebDav::new("webdav://", None).err();
+        assert_eq!(error.map(|e| e.code), Some(ErrorCode::InvalidInput));
+    }
+
+    #[test]
+    fn webdav_version_prefers_strong_etags() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Sat, 04 May 2024 10:00:00 GMT"),
+        );
+        assert_eq!(
+            version_of(&headers).as_deref(),
+            Some("date:Sat, 04 May 2024 10:00:00 GMT")
+        );
+        headers.insert(ETAG, HeaderValue::from_static("W/\"weak\""));
+        assert_eq!(
+            version_of(&headers).as_deref(),
+            Some("date:Sat, 04 May 2024 10:00:00 GMT")
+        );
+        headers.insert(ETAG, HeaderValue::from_static("\"strong\""));
+        assert_eq!(version_of(&headers).as_deref(), Some("\"strong\""));
+        assert_eq!(version_of(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn webdav_fetch_and_conditional_store() {
+        let (server, webdav) = serve(Some(b"first"), false);
+        tauri::async_runtime::block_on(async {
+            let (data, version) = fetched(webdav.fetch(None).await).expect("data");
+            assert_eq!(
+                (data.as_slice(), version.as_deref()),
+                (&b"first"[..], Some("\"v1\""))
+            );
+            assert!(fetched(webdav.fetch(Some("\"v1\"")).await).is_none());
+
+            let stored = webdav.store(b"second".to_vec(), Some("\"v1\"")).await;
+            assert_eq!(stored.expect("store").as_deref(), Some("\"v2\""));
+
+            // Someone else uploaded "v2" since "v1" was downloaded
+            let error = webdav
+                .store(b"stale".to_vec(), Some("\"v1\""))
+                .await
+                .expect_err("conflict");
+            assert_eq!(
+                (error.code, error.os_status),
+                (ErrorCode::Conflict, Some(412))
+            );
+            let (data, _) = fetched(webdav.fetch(Some("\"v1\"")).await).expect("data");
+            assert_eq!(data, b"second");
+        });
+        let server = server.lock().expect("stand-in");
+        assert!(server
+            .requests
+            .iter()
+            .all(|(_, headers)| headers.get("authorization").map(String::as_str) == Some(LOGIN)));
+        let (method, headers) = &server.requests[3];
+        assert_eq!(
+            (method.as_str(), headers.get("if-match").map(String::as_str)),
+            ("PUT", Some("\"v1\""))
+        );
+    }
+
+    #[test]
+    fn webdav_store_without_version_only_creates() {
+        let (server, webdav) = serve(None, false);
+        tauri::async_runtime::block_on(async {
+            let error = webdav.fetch(None).await.err();
+            assert_eq!(error.map(|e| e.code), Some(ErrorCode::NotFound));
+            let stored = webdav.store(b"new".to_vec(), None).await;
+            assert_eq!(stored.expect("create").as_deref(), Some("\"v2\""));
+            // Never overwrite a file the app has no version of
+            let error = webdav.store(b"other".to_vec(), None).await.err();
+            assert_eq!(error.map(|e| e.code), Some(ErrorCode::Conflict));
+        });
+        let server = server.lock().expect("stand-in");
+        assert_eq!(server.file.as_deref(), Some(&b"new"[..]));
+        assert_eq!(
+            server.requests[1]
+                .1
+                .get("if-none-match")
+                .map(String::as_str),
+            Some("*")
+        );
+    }
+
+    #[test]
+    fn webdav_missing_etag_leaves_the_version_unknown() {
+        let (server, webdav) = serve(Some(b"first"), true);
+        tauri::async_runtime::block_on(async {
+            let stored = webdav.store(b"second".to_vec(), Some("\"v1\"")).await;
+            assert_eq!(stored.expect("store"), None);
+        });
+        assert_eq!(
+            methods(&server),
+            ["PUT"],
+            "no HEAD that could see another upload"
+        );
+    }
+
+    #[test]
+    fn webdav_rejected_login() {
+        let (_server, webdav) = serve(Some(b"first"), false);
+        let webdav = WebDav {
+            auth: None,
+            ..webdav
+        };
+        let error = tauri::async_runtime::block_on(webdav.fetch(None)).err();
+        assert_eq!(
+            error.map(|e| (e.code, e.os_status)),
+            Some((ErrorCode::AuthFailed, Some(401)))
+        );
+    }
+}
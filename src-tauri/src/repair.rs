@@ -0,0 +1,148 @@
+// Deep verification, compatibility fixes and repair of vault files
+//
+// Verification checks every layer of a vault file and returns all findings instead of
+// stopping at the first one (see `kdbx::verify`). The compatibility lint looks for
+// values KeePassXC or KeePass 2 reject (see `kdbx::lint`); its fixes are saved through
+// the regular save transaction like a merge, so the frontend saves first, a backup of
+// the previous version is taken and the window reloads the result. Repair is the last resort when a vault
+// no longer opens and no good backup exists (see docs/EMERGENCY_RECOVERY.md). With the
+// vault's credentials, `kdbx::salvage` keeps every payload block that still verifies and
+// as much of the XML as can be read; the result is saved as a new vault next to the
+// damaged one, which is never modified. The report lists what was lost so it can be
+// looked up in a backup or another device.
+use crate::backup::{self, canonical_path, write_atomic};
+use crate::error::{AppError, AppResult};
+use crate::kdbx::lint::{self, LintReport};
+use crate::kdbx::salvage::{self, SalvageReport};
+use crate::kdbx::verify::{self, VerifyReport};
+use crate::kdbx::{Credentials, Database};
+use crate::logging::redact_path;
+use crate::read_only;
+use crate::unlock_attempts;
+use crate::watcher::WriteGuard;
+use std::path::Path;
+use tauri::command;
+
+/// Check every layer of the vault file at `path` and report all findings
+#[command]
+pub async fn verify_vault_deep(
+    app_handle: tauri::AppHandle,
+    path: String,
+    credentials: Credentials,
+) -> AppResult<VerifyReport> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let data = std::fs::read(&path)?;
+        let report =
+            unlock_attempts::attempt(&app_handle, &path, || verify::verify(&data, &credentials))?;
+        log::info!(
+            "Verified {}: {} finding(s), {}",
+            redact_path(&path),
+            report.findings.len(),
+            if report.is_valid() {
+                "valid"
+            } else {
+                "damaged"
+            }
+        );
+        Ok(report)
+    })
+    .await?
+}
+
+/// Look for values other KeePass clients reject in the vault at `path`. With `fix`,
+/// repair them and save the vault unless it changed since the window loaded it.
+#[command]
+pub async fn lint_vault(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    vault_id: Option<String>,
+    credentials: Credentials,
+    fix: bool,
+) -> AppResult<LintReport> {
+    if fix {
+        read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    }
+    let label = window.label().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let data = std::fs::read(&path)?;
+        let mut database = unlock_attempts::open(&app_handle, &path, &data, &credentials)?;
+        if let Some(vault_id) = &vault_id {
+            if database.identity() != vault_id {
+                return Err(AppError::invalid_input(
+                    "The vault file changed since it was opened",
+                ));
+            }
+        }
+
+        let report = lint::lint(&mut database, fix);
+        log::info!(
+            "Linted {}: {} issue(s), {} fixed",
+            redact_path(&path),
+            report.issues.len(),
+            report.fixed
+        );
+        if report.fixed == 0 {
+            return Ok(report);
+        }
+
+        let fixed = database.save(&credentials)?;
+        let entry_count = u32::try_from(database.entry_count()).ok();
+        backup::save_transaction(
+            &app_handle,
+            &label,
+            &path,
+            fixed,
+            vault_id,
+            entry_count,
+            true,
+            WriteGuard::Sha256(backup::sha256_hex(&data)),
+        )?;
+        Ok(report)
+    })
+    .await?
+}
+
+/// Recover what can be read from the vault at `path` and save it as `target_path`
+#[command]
+pub async fn repair_vault(
+    app_handle: tauri::AppHandle,
+    path: String,
+    credentials: Credentials,
+    target_path: String,
+) -> AppResult<SalvageReport> {
+    if canonical_path(&target_path) == canonical_path(&path) {
+        return Err(AppError::invalid_input(
+            "Save the repaired vault as a new file, not over the damaged one",
+        ));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let data = std::fs::read(&path)?;
+        let (mut database, report) =
+            unlock_attempts::attempt(&app_handle, &path, || salvage::salvage(&data, &credentials))?;
+        let repaired = database.save(&credentials)?;
+        // The new file must open with the strict reader before it is written
+        Database::open(&repaired, &credentials)?;
+        write_atomic(Path::new(&target_path), &repaired)?;
+
+        // The lost items name entries, so only their count is logged
+        if report.is_clean() {
+            log::info!(
+                "Repaired {} into {}: no damage found",
+                redact_path(&path),
+                redact_path(&target_path)
+            );
+        } else {
+            log::warn!(
+                "Repaired {} into {}: {} entries and {} groups recovered, {} losses",
+                redact_path(&path),
+                redact_path(&target_path),
+                report.entries,
+                report.groups,
+                report.lost.len()
+            );
+        }
+        Ok(report)
+    })
+    .await?
+}
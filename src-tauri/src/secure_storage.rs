@@ -1,91 +1,97 @@
+use crate::error::{AppError, AppResult};
+use crate::logging::redact_path;
 use keyring::Entry;
 use tauri::command;
 
 const SERVICE_NAME: &str = "keedavault-biometric";
 
+// Entry access for `quick_unlock`, with the account already encoded. On macOS the
+// keyring items are the ones `native_keychain` manages
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn entry_exists(account: &str) -> bool {
+    Entry::new(SERVICE_NAME, account)
+        .and_then(|entry| entry.get_password())
+        .is_ok()
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn store_entry(account: &str, password: &str) -> AppResult<()> {
+    Ok(Entry::new(SERVICE_NAME, account)?.set_password(password)?)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn delete_entry(account: &str) -> AppResult<()> {
+    match Entry::new(SERVICE_NAME, account)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
 #[command]
-pub async fn secure_store_password(vault_path: String, password: String) -> Result<(), String> {
-    println!("[Secure Storage] Storing password for path: {}", vault_path);
-    println!("[Secure Storage] Using service name: {}", SERVICE_NAME);
+pub async fn secure_store_password(vault_path: String, password: String) -> AppResult<()> {
+    log::debug!(
+        "Storing password for {} (service: {})",
+        redact_path(&vault_path),
+        SERVICE_NAME
+    );
     let entry = Entry::new(SERVICE_NAME, &vault_path).map_err(|e| {
-        let err_msg = format!("Failed to create keychain entry: {}", e);
-        println!("[Secure Storage] ERROR: {}", err_msg);
-        err_msg
+        log::error!("Failed to create keychain entry: {}", e);
+        AppError::from(e)
     })?;
     entry.set_password(&password).map_err(|e| {
-        let err_msg = format!("Failed to set password: {}", e);
-        println!("[Secure Storage] ERROR: {}", err_msg);
-        err_msg
+        log::error!("Failed to set password: {}", e);
+        AppError::from(e)
     })?;
-    println!(
-        "[Secure Storage] Password stored successfully for: {}",
-        vault_path
-    );
+    log::info!("Password stored for {}", redact_path(&vault_path));
     Ok(())
 }
 
 #[command]
-pub async fn secure_get_password(vault_path: String) -> Result<String, String> {
-    println!("[Secure Storage] Getting password for path: {}", vault_path);
-    let entry = Entry::new(SERVICE_NAME, &vault_path).map_err(|e| e.to_string())?;
+pub async fn secure_get_password(vault_path: String) -> AppResult<String> {
+    log::debug!("Getting password for {}", redact_path(&vault_path));
+    let entry = Entry::new(SERVICE_NAME, &vault_path)?;
     let password = entry.get_password().map_err(|e| {
-        println!("[Secure Storage] Password not found for: {}", vault_path);
-        e.to_string()
+        log::warn!("Password not found for {}", redact_path(&vault_path));
+        AppError::from(e)
     })?;
-    println!(
-        "[Secure Storage] Password retrieved successfully for: {}",
-        vault_path
-    );
+    log::info!("Password retrieved for {}", redact_path(&vault_path));
     Ok(password)
 }
 
 #[command]
-pub async fn secure_delete_password(vault_path: String) -> Result<(), String> {
-    println!(
-        "[Secure Storage] Deleting password for path: {}",
-        vault_path
-    );
-    let entry = Entry::new(SERVICE_NAME, &vault_path).map_err(|e| e.to_string())?;
-    entry.delete_credential().map_err(|e| e.to_string())?;
-    println!("[Secure Storage] Password deleted for: {}", vault_path);
+pub async fn secure_delete_password(vault_path: String) -> AppResult<()> {
+    log::debug!("Deleting password for {}", redact_path(&vault_path));
+    let entry = Entry::new(SERVICE_NAME, &vault_path)?;
+    entry.delete_credential()?;
+    log::info!("Password deleted for {}", redact_path(&vault_path));
     Ok(())
 }
 
 #[command]
-pub async fn secure_has_password(vault_path: String) -> Result<bool, String> {
-    println!(
-        "[Secure Storage] Checking if password exists for path: {}",
-        vault_path
+pub async fn secure_has_password(vault_path: String) -> AppResult<bool> {
+    log::debug!(
+        "Checking if password exists for {} (service: {})",
+        redact_path(&vault_path),
+        SERVICE_NAME
     );
-    println!("[Secure Storage] Using service name: {}", SERVICE_NAME);
-    println!("[Secure Storage] Path length: {}", vault_path.len());
-    println!("[Secure Storage] Path bytes: {:?}", vault_path.as_bytes());
 
     let entry = Entry::new(SERVICE_NAME, &vault_path).map_err(|e| {
-        println!("[Secure Storage] Failed to create entry: {}", e);
-        e.to_string()
+        log::error!("Failed to create keychain entry: {}", e);
+        AppError::from(e)
     })?;
 
-    println!("[Secure Storage] Entry created, attempting to get password...");
-
     match entry.get_password() {
-        Ok(password) => {
-            println!("[Secure Storage] Password EXISTS for: {}", vault_path);
-            println!("[Secure Storage] Password length: {}", password.len());
+        Ok(_) => {
+            log::debug!("Password exists for {}", redact_path(&vault_path));
             Ok(true)
         }
         Err(e) => {
-            println!(
-                "[Secure Storage] Password DOES NOT EXIST for: {}",
-                vault_path
+            // The keyring crate is known to miss items on macOS; native_keychain is the fallback
+            log::debug!(
+                "Password does not exist for {}: {}",
+                redact_path(&vault_path),
+                e
             );
-            println!("[Secure Storage] Error: {}", e);
-            println!("[Secure Storage] Error type: {:?}", e);
-
-            // Try to list what's actually in the keychain for debugging
-            println!("[Secure Storage] This might be a keyring crate issue on macOS");
-            println!("[Secure Storage] Consider using native_keychain instead");
-
             Ok(false)
         }
     }
@@ -0,0 +1,28 @@
+// Read access to the frontend's persisted UI settings (services/uiSettingsService.ts)
+use tauri_plugin_store::StoreExt;
+
+const SETTINGS_STORE: &str = "settings.json";
+const UI_SETTINGS_KEY: &str = "keedavault_ui_settings";
+
+/// Look up a value inside the UI settings object by JSON pointer, e.g. `/security/verboseLogging`
+pub fn ui_setting(app: &tauri::AppHandle, pointer: &str) -> Option<serde_json::Value> {
+    app.store(SETTINGS_STORE)
+        .ok()
+        .and_then(|store| store.get(UI_SETTINGS_KEY))
+        .and_then(|settings| settings.pointer(pointer).cloned())
+}
+
+/// Read a backend-owned value stored next to the UI settings
+pub fn get(app: &tauri::AppHandle, key: &str) -> Option<serde_json::Value> {
+    app.store(SETTINGS_STORE).ok().and_then(|store| store.get(key))
+}
+
+/// Persist a backend-owned value next to the UI settings
+pub fn set(app: &tauri::AppHandle, key: &str, value: serde_json::Value) {
+    if let Ok(store) = app.store(SETTINGS_STORE) {
+        store.set(key, value);
+        if let Err(e) = store.save() {
+            log::warn!("Failed to save settings store: {}", e);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// Merging another copy of a vault into the open one, and comparing two copies
+//
+// The frontend saves its in-memory state first, then asks the backend to merge a
+// second file into the vault on disk and reloads the result. When the file changed on
+// disk under an open window, the window's unsaved state is merged into it instead. The
+// merged file is written through the regular save transaction, so a backup of the
+// pre-merge state is always taken.
+//
+// Comparing opens both files read-only and reports the changes from the first to the
+// second (see `kdbx::diff`), e.g. to review a backup before restoring it or to check
+// what a merge did against the backup taken before it.
+use crate::backup;
+use crate::error::{AppError, AppResult};
+use crate::kdbx::diff::{self, VaultDiff};
+use crate::kdbx::merge::{self, MergeSummary};
+use crate::kdbx::{Credentials, Database};
+use crate::logging::redact_path;
+use crate::read_only;
+use crate::unlock_attempts;
+use crate::watcher::WriteGuard;
+use tauri::command;
+
+/// Merge `source` into the vault file at `path` and save the result, unless the file
+/// changed again in the meantime
+fn merge_into_file(
+    app: &tauri::AppHandle,
+    label: &str,
+    path: &str,
+    vault_id: Option<String>,
+    credentials: &Credentials,
+    source: Database,
+    source_name: &str,
+) -> AppResult<MergeSummary> {
+    let target_data = std::fs::read(path)?;
+    let mut target = unlock_attempts::open(app, path, &target_data, credentials)?;
+    if let Some(vault_id) = &vault_id {
+        if target.identity() != vault_id {
+            return Err(AppError::invalid_input(
+                "The vault file changed since it was opened",
+            ));
+        }
+    }
+
+    let summary = merge::merge(&mut target, source);
+    log::info!(
+        "Merged {} into {}: {} change(s)",
+        source_name,
+        redact_path(path),
+        summary.changes.len()
+    );
+    if summary.is_empty() {
+        return Ok(summary);
+    }
+
+    let data = target.save(credentials)?;
+    let entry_count = u32::try_from(target.entry_count()).ok();
+    backup::save_transaction(
+        app,
+        label,
+        path,
+        data,
+        vault_id,
+        entry_count,
+        true,
+        WriteGuard::Sha256(backup::sha256_hex(&target_data)),
+    )?;
+    Ok(summary)
+}
+
+/// Merge `source_path` into the vault at `path` (KeePassXC "Synchronize" semantics).
+/// `source_credentials` defaults to the vault's own credentials.
+#[command]
+pub async fn merge_vault_file(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    vault_id: Option<String>,
+    credentials: Credentials,
+    source_path: String,
+    source_credentials: Option<Credentials>,
+) -> AppResult<MergeSummary> {
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    if backup::canonical_path(&path) == backup::canonical_path(&source_path) {
+        return Err(AppError::invalid_input("Cannot merge a vault with itself"));
+    }
+
+    let label = window.label().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        let source_data = std::fs::read(&source_path)?;
+        let source = unlock_attempts::open(
+            &app_handle,
+            &source_path,
+            &source_data,
+            source_credentials.as_ref().unwrap_or(&credentials),
+        )?;
+        merge_into_file(
+            &app_handle,
+            &label,
+            &path,
+            vault_id,
+            &credentials,
+            source,
+            &redact_path(&source_path).to_string(),
+        )
+    })
+    .await?
+}
+
+/// Merge a window's unsaved state (`data`, the serialized vault) into the vault file,
+/// which changed on disk since the window loaded it. The window reloads the result.
+#[command]
+pub async fn merge_vault_data(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    vault_id: Option<String>,
+    credentials: Credentials,
+    data: Vec<u8>,
+) -> AppResult<MergeSummary> {
+    read_only::ensure_writable(&app_handle, window.label(), &path)?;
+    let label = window.label().to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        // The window's own state, but the credentials still come from the frontend
+        let source = unlock_attempts::open(&app_handle, &path, &data, &credentials)?;
+        merge_into_file(
+            &app_handle,
+            &label,
+            &path,
+            vault_id,
+            &credentials,
+            source,
+            "unsaved changes",
+        )
+    })
+    .await?
+}
+
+/// Changes from vault file `a` to vault file `b`, with protected values masked.
+/// `b_credentials` defaults to `credentials`, for copies saved with another master key.
+#[command]
+pub async fn diff_vaults(
+    app_handle: tauri::AppHandle,
+    a: String,
+    b: String,
+    credentials: Credentials,
+    b_credentials: Option<Credentials>,
+) -> AppResult<VaultDiff> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let a_db = unlock_attempts::open(&app_handle, &a, &std::fs::read(&a)?, &credentials)?;
+        let b_db = unlock_attempts::open(
+            &app_handle,
+            &b,
+            &std::fs::read(&b)?,
+            b_credentials.as_ref().unwrap_or(&credentials),
+        )?;
+        let result = diff::diff(&a_db, &b_db);
+        log::info!(
+            "Compared {} with {}: {} entry and {} group change(s)",
+            redact_path(&a),
+            redact_path(&b),
+            result.entries.len(),
+            result.groups.len()
+        );
+        Ok(result)
+    })
+    .await?
+}
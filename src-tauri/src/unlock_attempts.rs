@@ -0,0 +1,271 @@
+// Throttling of failed unlock attempts
+//
+// The frontend decrypts vaults itself, so it asks here before every unlock and reports
+// the outcome after. Failures are counted per vault, keyed like the backup store by
+// its location (file path, or server URL for a remote vault) since the vault's own
+// identity is encrypted. After `FREE_ATTEMPTS` failures every further attempt waits
+// for a delay that doubles with each failure, up to five minutes. The counters live
+// in the settings store, so quitting the app does not reset them. A successful unlock
+// clears the counter and reports how many attempts failed since the previous one.
+//
+// Backend commands that decrypt a vault with credentials from the frontend (merging,
+// comparing, verifying, re-keying...) go through `attempt`, so they honour the same
+// delay and count wrong credentials as failures: none of them can be used to try
+// passwords faster than the unlock form allows.
+use crate::backup::{canonical_path, vault_key};
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::kdbx::{Credentials, Database};
+use crate::logging::redact_path;
+use crate::settings;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::command;
+
+const ATTEMPTS_KEY: &str = "keedavault_unlock_attempts";
+const FREE_ATTEMPTS: u32 = 3;
+const FIRST_DELAY_SECS: i64 = 5;
+const MAX_DELAY_SECS: i64 = 5 * 60;
+// Failure times kept per vault; the count goes on beyond it
+const MAX_RECORDED: usize = 100;
+
+// Serializes read-modify-write of the stored counters
+static ATTEMPTS: Mutex<()> = Mutex::new(());
+
+/// Failed attempts on one vault since it was last unlocked
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Failures {
+    count: u32,
+    /// Most recent last
+    times: Vec<DateTime<Utc>>,
+}
+
+impl Failures {
+    /// Earliest time of the next attempt
+    fn next_attempt(&self) -> Option<DateTime<Utc>> {
+        let excess = self.count.checked_sub(FREE_ATTEMPTS)?;
+        let delay = FIRST_DELAY_SECS
+            .saturating_mul(1 << excess.min(16))
+            .min(MAX_DELAY_SECS);
+        Some(*self.times.last()? + Duration::seconds(delay))
+    }
+}
+
+/// State after a failed attempt
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockDelay {
+    pub failed_attempts: u32,
+    /// Time until the next attempt is accepted
+    pub retry_after_ms: u64,
+}
+
+/// Failed attempts reported on a successful unlock
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockSummary {
+    pub failed_attempts: u32,
+    pub last_failure: Option<DateTime<Utc>>,
+}
+
+fn location_key(location: &str) -> String {
+    vault_key(&canonical_path(location), None)
+}
+
+fn load(app: &tauri::AppHandle) -> HashMap<String, Failures> {
+    settings::get(app, ATTEMPTS_KEY)
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// Apply `f` to the failures of `location` and store the result
+fn update<T>(app: &tauri::AppHandle, location: &str, f: impl FnOnce(&mut Failures) -> T) -> T {
+    let _guard = ATTEMPTS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut all = load(app);
+    let key = location_key(location);
+    let mut failures = all.remove(&key).unwrap_or_default();
+    let result = f(&mut failures);
+    if failures.count > 0 {
+        all.insert(key, failures);
+    }
+    match serde_json::to_value(&all) {
+        Ok(value) => settings::set(app, ATTEMPTS_KEY, value),
+        Err(e) => log::warn!("Failed to store unlock attempts: {}", e),
+    }
+    result
+}
+
+fn remaining(next: Option<DateTime<Utc>>) -> u64 {
+    next.and_then(|next| (next - Utc::now()).to_std().ok())
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+fn check(app: &tauri::AppHandle, location: &str) -> AppResult<()> {
+    let wait = load(app)
+        .get(&location_key(location))
+        .map_or(0, |failures| remaining(failures.next_attempt()));
+    if wait > 0 {
+        return Err(AppError::new(
+            ErrorCode::TooManyAttempts,
+            format!(
+                "Too many failed attempts. Try again in {} seconds.",
+                wait.div_ceil(1000)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn record_failure(app: &tauri::AppHandle, location: &str) -> Failures {
+    let now = Utc::now();
+    let failures = update(app, location, |failures| {
+        failures.count = failures.count.saturating_add(1);
+        failures.times.push(now);
+        let excess = failures.times.len().saturating_sub(MAX_RECORDED);
+        failures.times.drain(..excess);
+        failures.clone()
+    });
+    log::warn!(
+        "Failed unlock of {} at {} ({} since the last unlock)",
+        redact_path(location),
+        now.to_rfc3339(),
+        failures.count
+    );
+    failures
+}
+
+/// Run `decrypt`, which uses credentials from the frontend for the vault at
+/// `location`, unless its unlock delay runs; a `WrongCredentials` failure counts as a
+/// failed unlock
+pub(crate) fn attempt<T>(
+    app: &tauri::AppHandle,
+    location: &str,
+    decrypt: impl FnOnce() -> AppResult<T>,
+) -> AppResult<T> {
+    check(app, location)?;
+    let result = decrypt();
+    if let Err(e) = &result {
+        if e.code == ErrorCode::WrongCredentials {
+            record_failure(app, location);
+        }
+    }
+    result
+}
+
+/// `Database::open` through `attempt`
+pub(crate) fn open(
+    app: &tauri::AppHandle,
+    location: &str,
+    data: &[u8],
+    credentials: &Credentials,
+) -> AppResult<Database> {
+    attempt(app, location, || Database::open(data, credentials))
+}
+
+/// Fail with `TooManyAttempts` while the delay after the last failed unlock of
+/// `location` runs
+#[command]
+pub async fn check_unlock_allowed(app_handle: tauri::AppHandle, location: String) -> AppResult<()> {
+    check(&app_handle, &location)
+}
+
+/// Count a failed unlock of `location`
+#[command]
+pub async fn record_failed_unlock(
+    app_handle: tauri::AppHandle,
+    location: String,
+) -> AppResult<UnlockDelay> {
+    let failures = record_failure(&app_handle, &location);
+    Ok(UnlockDelay {
+        failed_attempts: failures.count,
+        retry_after_ms: remaining(failures.next_attempt()),
+    })
+}
+
+/// Clear the failed attempts of `location` after it was unlocked
+#[command]
+pub async fn record_successful_unlock(
+    app_handle: tauri::AppHandle,
+    location: String,
+) -> AppResult<UnlockSummary> {
+    let failures = update(&app_handle, &location, std::mem::take);
+    if failures.count > 0 {
+        log::info!(
+            "Unlocked {} after {} failed attempts",
+            redact_path(&location),
+            failures.count
+        );
+    }
+    Ok(UnlockSummary {
+        failed_attempts: failures.count,
+        last_failure: failures.times.last().copied(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delay before the next attempt after `count` failures, in seconds
+    fn delay(count: u32) -> Option<i64> {
+        let last = Utc::now();
+        let failures = Failures {
+            count,
+            times: vec![last],
+        };
+        failures
+            .next_attempt()
+            .map(|next| (next - last).num_seconds())
+    }
+
+    #[test]
+    fn free_attempts_have_no_delay() {
+        for count in 0..FREE_ATTEMPTS {
+            assert_eq!(delay(count), None, "{} failures", count);
+        }
+        assert_eq!(delay(FREE_ATTEMPTS), Some(FIRST_DELAY_SECS));
+    }
+
+    #[test]
+    fn delay_doubles_with_each_failure() {
+        let delays: Vec<_> = (FREE_ATTEMPTS..FREE_ATTEMPTS + 6).map(delay).collect();
+        assert_eq!(
+            delays,
+            [Some(5), Some(10), Some(20), Some(40), Some(80), Some(160)]
+        );
+    }
+
+    #[test]
+    fn delay_is_capped_at_five_minutes() {
+        assert_eq!(delay(FREE_ATTEMPTS + 6), Some(MAX_DELAY_SECS));
+        // The shift stops at 16 so large counts cannot overflow it
+        for excess in [16, 17, 31, 32, 1000] {
+            assert_eq!(
+                delay(FREE_ATTEMPTS + excess),
+                Some(MAX_DELAY_SECS),
+                "{} failures beyond the free ones",
+                excess
+            );
+        }
+        assert_eq!(delay(u32::MAX), Some(MAX_DELAY_SECS));
+    }
+
+    #[test]
+    fn delay_runs_from_the_last_failure() {
+        let first = Utc::now() - Duration::hours(1);
+        let last = first + Duration::minutes(30);
+        let failures = Failures {
+            count: FREE_ATTEMPTS + 1,
+            times: vec![first, last],
+        };
+        assert_eq!(failures.next_attempt(), Some(last + Duration::seconds(10)));
+        // Counters from before failure times were recorded never block
+        let untimed = Failures {
+            count: FREE_ATTEMPTS + 1,
+            times: Vec::new(),
+        };
+        assert_eq!(untimed.next_attempt(), None);
+    }
+}
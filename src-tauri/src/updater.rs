@@ -0,0 +1,434 @@
+// Update checker backed by a signed release manifest
+//
+// The manifest (JSON, see docs/CHECK_UPDATES_FEATURE.md) is fetched together with its
+// detached minisign signature (`<manifest url>.minisig`). Nothing from the manifest is
+// trusted until the signature verifies against the public key compiled into the binary,
+// so a mirror or proxy serving the files cannot announce a fake release.
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::settings;
+use minisign_verify::{PublicKey, Signature};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{command, Manager};
+
+// Rewritten from every published release by .github/workflows/update-manifest.yml
+const DEFAULT_MANIFEST_URL: &str =
+    "https://github.com/daocatt/keedavault/releases/download/updates/latest.json";
+
+/// Base64 minisign public key, provided by the release build environment
+const UPDATE_PUBLIC_KEY: Option<&str> = option_env!("KEEDAVAULT_UPDATE_PUBKEY");
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const MAX_MANIFEST_SIZE: usize = 256 * 1024;
+
+// Random per-install identifier used to place this install in staged rollouts
+const INSTALL_ID_KEY: &str = "keedavault_install_id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    Stable,
+    Beta,
+}
+
+impl Channel {
+    /// Beta installs also receive stable releases; stable installs never see betas
+    fn accepts(self, release: Channel) -> bool {
+        match self {
+            Channel::Stable => release == Channel::Stable,
+            Channel::Beta => true,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    releases: Vec<ManifestRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestRelease {
+    version: String,
+    channel: Channel,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    notes: String,
+    url: String,
+    pub_date: String,
+    /// Fraction of installs (0.0-1.0) that should be offered this release
+    #[serde(default)]
+    rollout: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReleaseInfo {
+    pub version: String,
+    pub channel: Channel,
+    pub name: String,
+    pub notes: String,
+    pub url: String,
+    pub published_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateCheck {
+    pub current_version: String,
+    pub channel: Channel,
+    pub update: Option<ReleaseInfo>,
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> AppResult<Vec<u8>> {
+    let mut response = client.get(url).send().await?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(AppError::not_found(format!("{} returned 404", url)));
+    }
+    if !status.is_success() {
+        return Err(AppError::new(
+            ErrorCode::BackendUnavailable,
+            format!("{} returned {}", url, status),
+        )
+        .with_os_status(status.as_u16() as i64));
+    }
+
+    // Refused before it is read: the length is announced, or counted as the body arrives
+    let too_large = || AppError::new(ErrorCode::Corrupt, "Release manifest is too large");
+    if response
+        .content_length()
+        .is_some_and(|len| len > MAX_MANIFEST_SIZE as u64)
+    {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_MANIFEST_SIZE {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+fn corrupt(message: impl Into<String>) -> AppError {
+    AppError::new(ErrorCode::Corrupt, message).retryable(false)
+}
+
+fn verify_manifest(public_key: &str, manifest: &[u8], signature: &[u8]) -> AppResult<()> {
+    let public_key = PublicKey::from_base64(public_key)
+        .map_err(|e| AppError::internal(format!("Invalid update public key: {}", e)))?;
+    let signature = std::str::from_utf8(signature)
+        .map_err(|_| corrupt("Manifest signature is not valid UTF-8"))
+        .and_then(|text| {
+            Signature::decode(text)
+                .map_err(|e| corrupt(format!("Malformed manifest signature: {}", e)))
+        })?;
+    public_key
+        .verify(manifest, &signature, false)
+        .map_err(|e| corrupt(format!("Manifest signature verification failed: {}", e)))
+}
+
+fn parse_version(version: &str) -> Option<Version> {
+    Version::parse(version.trim().trim_start_matches('v')).ok()
+}
+
+/// Stable position of this install in [0, 1) for staged rollouts
+fn rollout_bucket(app: &tauri::AppHandle) -> f64 {
+    let id = settings::get(app, INSTALL_ID_KEY)
+        .and_then(|v| v.as_str().and_then(|s| uuid::Uuid::parse_str(s).ok()))
+        .unwrap_or_else(|| {
+            let id = uuid::Uuid::new_v4();
+            settings::set(app, INSTALL_ID_KEY, serde_json::Value::from(id.to_string()));
+            id
+        });
+    (id.as_u128() % 10_000) as f64 / 10_000.0
+}
+
+/// Pick the newest release this install is eligible for, if it is newer than `current`
+fn select_update(
+    manifest: Manifest,
+    current: &Version,
+    channel: Channel,
+    bucket: f64,
+) -> Option<(Version, ManifestRelease)> {
+    manifest
+        .releases
+        .into_iter()
+        .filter(|release| channel.accepts(release.channel))
+        .filter(|release| release.rollout.is_none_or(|fraction| bucket < fraction))
+        .filter_map(|release| parse_version(&release.version).map(|v| (v, release)))
+        .filter(|(version, _)| version > current)
+        // Stable installs must never be offered a pre-release, even if mislabelled
+        .filter(|(version, _)| channel == Channel::Beta || version.pre.is_empty())
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+}
+
+#[command]
+pub async fn check_updates(app_handle: tauri::AppHandle) -> AppResult<UpdateCheck> {
+    let public_key = UPDATE_PUBLIC_KEY.ok_or_else(|| {
+        AppError::new(
+            ErrorCode::BackendUnavailable,
+            "Update signing key is not configured in this build",
+        )
+        .retryable(false)
+    })?;
+
+    let current_version = app_handle.package_info().version.to_string();
+    let current = parse_version(&current_version)
+        .ok_or_else(|| AppError::internal("Invalid application version"))?;
+
+    let channel = settings::ui_setting(&app_handle, "/updates/channel")
+        .and_then(|v| serde_json::from_value::<Channel>(v).ok())
+        .unwrap_or(Channel::Stable);
+    let manifest_url = settings::ui_setting(&app_handle, "/updates/manifestUrl")
+        .and_then(|v| v.as_str().map(str::to_string))
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_MANIFEST_URL.to_string());
+
+    log::info!(
+        "Checking for updates (current {}, channel {:?})",
+        current_version,
+        channel
+    );
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(format!("KeedaVault/{}", current_version))
+        .build()?;
+    let manifest_bytes = fetch(&client, &manifest_url).await?;
+    let signature_bytes = fetch(&client, &format!("{}.minisig", manifest_url)).await?;
+
+    verify_manifest(public_key, &manifest_bytes, &signature_bytes)?;
+
+    let manifest: Manifest = serde_json::from_slice(&manifest_bytes)
+        .map_err(|e| corrupt(format!("Invalid release manifest: {}", e)))?;
+
+    let bucket = rollout_bucket(&app_handle);
+    let update =
+        select_update(manifest, &current, channel, bucket).map(|(version, release)| ReleaseInfo {
+            name: release
+                .name
+                .unwrap_or_else(|| format!("KeedaVault {}", version)),
+            version: version.to_string(),
+            channel: release.channel,
+            notes: release.notes,
+            url: release.url,
+            published_at: release.pub_date,
+        });
+
+    match &update {
+        Some(release) => log::info!("Update available: {}", release.version),
+        None => log::info!("No update available"),
+    }
+
+    Ok(UpdateCheck {
+        current_version,
+        channel,
+        update,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    // Test key pair; the secret key signed `SIGNED_MANIFEST` in minisign's default
+    // (pre-hashed) mode
+    const PUBLIC_KEY: &str = "RWQBAgMEBQYHCAOhB7/zzhC+HXDdGOdLwJln5NYwm6UNXx3chmQSVTG4";
+    const SIGNED_MANIFEST: &str = r#"{"releases":[{"version":"1.2.0","channel":"stable","notes":"Fixes","url":"https://example.com/v1.2.0","pub_date":"2026-01-01T00:00:00Z"}]}"#;
+    const SIGNATURE: &str = "untrusted comment: signature from minisign secret key
+RUQBAgMEBQYHCJiXUI8qLluw6Frccu2ZTKBlDj/4kp//GhP5jodg/D9oEciKI9uJU3yWFTU3NvgOohwQLmrhrsvGOKFuEOPPrAo=
+trusted comment: timestamp:1700000000 file:latest.json
+phjURBVKQUo5ckSBeRlaBG4LKZX0/d4rsKoeA/WQi92coZOQJopJsQ8twuP85f+wDpOoyVXpu10R8CX8qD4XDQ==
+";
+
+    fn manifest(releases: serde_json::Value) -> Manifest {
+        serde_json::from_value(serde_json::json!({ "releases": releases })).expect("manifest")
+    }
+
+    fn release(version: &str, channel: &str) -> serde_json::Value {
+        serde_json::json!({
+            "version": version,
+            "channel": channel,
+            "url": format!("https://example.com/{}", version),
+            "pub_date": "2026-01-01T00:00:00Z",
+        })
+    }
+
+    fn selected(
+        manifest: Manifest,
+        current: &str,
+        channel: Channel,
+        bucket: f64,
+    ) -> Option<String> {
+        let current = parse_version(current).expect("version");
+        select_update(manifest, &current, channel, bucket).map(|(version, _)| version.to_string())
+    }
+
+    #[test]
+    fn select_newest_release_of_the_channel() {
+        let releases = || {
+            manifest(serde_json::json!([
+                release("1.1.0", "stable"),
+                release("v1.3.0", "stable"),
+                release("1.2.0", "stable"),
+                release("1.4.0-beta.1", "beta"),
+                release("not a version", "stable"),
+            ]))
+        };
+        assert_eq!(
+            selected(releases(), "1.0.0", Channel::Stable, 0.5).as_deref(),
+            Some("1.3.0")
+        );
+        assert_eq!(
+            selected(releases(), "1.0.0", Channel::Beta, 0.5).as_deref(),
+            Some("1.4.0-beta.1")
+        );
+        assert_eq!(selected(releases(), "1.3.0", Channel::Stable, 0.5), None);
+        assert_eq!(selected(releases(), "2.0.0", Channel::Beta, 0.5), None);
+    }
+
+    #[test]
+    fn stable_installs_never_get_pre_releases() {
+        // Labelled stable by mistake
+        let releases = manifest(serde_json::json!([release("2.0.0-rc.1", "stable")]));
+        assert_eq!(selected(releases, "1.0.0", Channel::Stable, 0.5), None);
+        let releases = manifest(serde_json::json!([release("2.0.0-rc.1", "stable")]));
+        assert_eq!(
+            selected(releases, "1.0.0", Channel::Beta, 0.5).as_deref(),
+            Some("2.0.0-rc.1")
+        );
+    }
+
+    #[test]
+    fn staged_rollout_uses_the_install_bucket() {
+        let staged = || {
+            let mut staged = release("1.2.0", "stable");
+            staged["rollout"] = serde_json::json!(0.25);
+            manifest(serde_json::json!([release("1.1.0", "stable"), staged]))
+        };
+        assert_eq!(
+            selected(staged(), "1.0.0", Channel::Stable, 0.1).as_deref(),
+            Some("1.2.0")
+        );
+        // Outside the rollout the previous release is still offered
+        assert_eq!(
+            selected(staged(), "1.0.0", Channel::Stable, 0.25).as_deref(),
+            Some("1.1.0")
+        );
+        assert_eq!(selected(staged(), "1.1.0", Channel::Stable, 0.9), None);
+    }
+
+    #[test]
+    fn verify_manifest_signature() {
+        let manifest = SIGNED_MANIFEST.as_bytes();
+        let signature = SIGNATURE.as_bytes();
+        assert!(verify_manifest(PUBLIC_KEY, manifest, signature).is_ok());
+
+        let tampered = SIGNED_MANIFEST.replace("1.2.0", "9.9.9");
+        let error = verify_manifest(PUBLIC_KEY, tampered.as_bytes(), signature).err();
+        assert_eq!(error.map(|e| e.code), Some(ErrorCode::Corrupt));
+
+        let garbage = verify_manifest(PUBLIC_KEY, manifest, b"not a signature").err();
+        assert_eq!(
+            garbage.map(|e| (e.code, e.retryable)),
+            Some((ErrorCode::Corrupt, false))
+        );
+        let binary = verify_manifest(PUBLIC_KEY, manifest, &[0xff, 0xfe]).err();
+        assert_eq!(binary.map(|e| e.code), Some(ErrorCode::Corrupt));
+
+        // Signed, but by another key
+        let other_key = "RWQBAgMEBQYHCAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+        let error = verify_manifest(other_key, manifest, signature).err();
+        assert_eq!(
+            error.map(|e| (e.code, e.retryable)),
+            Some((ErrorCode::Corrupt, false))
+        );
+        let error = verify_manifest("not a key", manifest, signature).err();
+        assert_eq!(error.map(|e| e.code), Some(ErrorCode::Internal));
+    }
+
+    /// Answer one request on a local port with `response`; returns the URL to fetch
+    fn serve(response: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url = format!(
+            "http://{}/latest.json",
+            listener.local_addr().expect("address")
+        );
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => request.extend_from_slice(&buffer[..n]),
+                }
+            }
+            // The client hangs up on oversized bodies
+            let _ = stream.write_all(&response);
+        });
+        url
+    }
+
+    fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nConnection: close\r\n{}\r\n",
+            status, headers
+        )
+        .into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn fetch_from(response: Vec<u8>) -> AppResult<Vec<u8>> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("client");
+        let url = serve(response);
+        tauri::async_runtime::block_on(async move { fetch(&client, &url).await })
+    }
+
+    #[test]
+    fn fetch_from_local_server() {
+        let body = SIGNED_MANIFEST.as_bytes();
+        let headers = format!("Content-Length: {}\r\n", body.len());
+        let fetched = fetch_from(response("200 OK", &headers, body)).expect("fetch");
+        assert_eq!(fetched, body);
+
+        let missing = fetch_from(response("404 Not Found", "Content-Length: 0\r\n", b""));
+        assert_eq!(missing.err().map(|e| e.code), Some(ErrorCode::NotFound));
+        let failed = fetch_from(response(
+            "503 Service Unavailable",
+            "Content-Length: 0\r\n",
+            b"",
+        ));
+        let failed = failed.expect_err("503 fails");
+        assert_eq!(failed.code, ErrorCode::BackendUnavailable);
+        assert_eq!(failed.os_status, Some(503));
+    }
+
+    #[test]
+    fn fetch_refuses_oversized_manifests() {
+        // Announced: refused without reading the body
+        let headers = format!("Content-Length: {}\r\n", MAX_MANIFEST_SIZE + 1);
+        let announced = fetch_from(response("200 OK", &headers, b"{}"));
+        assert_eq!(announced.err().map(|e| e.code), Some(ErrorCode::Corrupt));
+
+        // Not announced: counted while it streams in
+        let body = vec![b' '; MAX_MANIFEST_SIZE + 1];
+        let streamed = fetch_from(response("200 OK", "", &body));
+        assert_eq!(streamed.err().map(|e| e.code), Some(ErrorCode::Corrupt));
+
+        let body = vec![b' '; MAX_MANIFEST_SIZE];
+        let headers = format!("Content-Length: {}\r\n", body.len());
+        let fetched = fetch_from(response("200 OK", &headers, &body)).expect("at the limit");
+        assert_eq!(fetched.len(), MAX_MANIFEST_SIZE);
+    }
+}
@@ -0,0 +1,368 @@
+// Advisory lock files for open vaults
+//
+// Like KeePass, every open vault gets a `<file name>.lock` next to it recording who
+// opened it: host, user, PID and open time, in the KeePass lock file layout with the PID
+// appended. Another window, instance or machine sharing the folder finds the lock and
+// lets the user open read-only, take the lock over or cancel. Locks are advisory: they
+// do not stop other programs, and a folder where no lock can be created is opened
+// without one. A lock is only deleted by the holder whose ID it carries.
+use crate::backup::canonical_path;
+use crate::error::{AppError, AppResult};
+use crate::logging::redact_path;
+use crate::read_only;
+use base64::Engine;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{command, Emitter, Manager};
+
+const LOCK_SUFFIX: &str = ".lock";
+const LOCK_HEADER: &str = "KeePass Lock File";
+
+struct HeldLock {
+    /// Random ID written into the lock file
+    id: String,
+    /// Window that has the vault open
+    window: String,
+    /// Path as that window opened it, echoed back in events
+    path: String,
+    /// `None` when the folder did not allow creating a lock file
+    lock_path: Option<PathBuf>,
+}
+
+/// Locks held by this process, keyed by canonical vault path (managed state)
+#[derive(Default)]
+pub struct VaultLocks(Mutex<HashMap<PathBuf, HeldLock>>);
+
+impl VaultLocks {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, HeldLock>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Who holds the lock on a vault
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockHolder {
+    pub host: String,
+    pub user: String,
+    pub pid: Option<u32>,
+    pub opened_at: Option<DateTime<Utc>>,
+    /// Held by another window of this app instance
+    pub this_instance: bool,
+    /// Left behind by a process on this host that is no longer running
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockStatus {
+    pub acquired: bool,
+    /// Present when the lock is held by someone else
+    pub holder: Option<LockHolder>,
+}
+
+/// Payload of `vault-lock-taken-over`, sent to the window that lost the lock
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockTakenOver {
+    pub path: String,
+}
+
+fn lock_path(canonical: &Path) -> PathBuf {
+    let mut name = canonical.file_name().unwrap_or_default().to_os_string();
+    name.push(LOCK_SUFFIX);
+    canonical.with_file_name(name)
+}
+
+fn user_name() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    // SAFETY: the buffer is valid for its length; the result is NUL-terminated on success
+    let result = unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) };
+    if result != 0 {
+        return String::new();
+    }
+    let end = buffer.iter().position(|&b| b == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn host_name() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+/// Whether a process with this PID is running; `None` where that cannot be checked
+#[cfg(unix)]
+fn process_alive(pid: u32) -> Option<bool> {
+    let pid = libc::pid_t::try_from(pid).ok()?;
+    // SAFETY: signal 0 only checks whether the process exists
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return Some(true);
+    }
+    Some(std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> Option<bool> {
+    None
+}
+
+fn new_lock_id() -> AppResult<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| AppError::internal(e.to_string()))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// KeePass layout: header, ID, time, user, machine, domain; then our PID
+fn lock_contents(id: &str) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}\n\n{}\n",
+        LOCK_HEADER,
+        id,
+        Utc::now().to_rfc3339(),
+        user_name(),
+        host_name(),
+        std::process::id()
+    )
+}
+
+struct LockFile {
+    id: String,
+    holder: LockHolder,
+}
+
+/// Parse a lock file written by us or by KeePass; unreadable files count as stale
+fn read_lock_file(path: &Path) -> Option<LockFile> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(_) => String::new(),
+    };
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let line = |index: usize| lines.get(index).copied().unwrap_or_default();
+    let valid = line(0) == LOCK_HEADER;
+
+    let opened_at = DateTime::parse_from_rfc3339(line(2))
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            // KeePass writes local time without an offset
+            NaiveDateTime::parse_from_str(line(2), "%Y-%m-%dT%H:%M:%S")
+                .ok()
+                .and_then(|t| Local.from_local_datetime(&t).earliest())
+                .map(|t| t.with_timezone(&Utc))
+        });
+    let host = line(4).to_string();
+    let pid = line(6).parse::<u32>().ok();
+    let same_host = !host.is_empty() && host.eq_ignore_ascii_case(&host_name());
+    let stale = !valid || (same_host && pid.is_some_and(|pid| process_alive(pid) == Some(false)));
+
+    Some(LockFile {
+        id: line(1).to_string(),
+        holder: LockHolder {
+            host,
+            user: line(3).to_string(),
+            pid,
+            opened_at,
+            this_instance: false,
+            stale,
+        },
+    })
+}
+
+/// Write our lock file; `create_new` fails if someone else created one meanwhile
+fn write_lock_file(path: &Path, id: &str, create_new: bool) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .create_new(create_new)
+        .truncate(true)
+        .open(path)?;
+    file.write_all(lock_contents(id).as_bytes())?;
+    file.sync_all()
+}
+
+/// Delete the lock file of `held` if it still carries our ID (nobody took it over)
+fn remove_lock_file(held: &HeldLock) {
+    let Some(path) = &held.lock_path else {
+        return;
+    };
+    if read_lock_file(path).is_some_and(|lock| lock.id == held.id) {
+        if let Err(e) = fs::remove_file(path) {
+            log::warn!("Failed to remove vault lock file: {}", e);
+        }
+    }
+}
+
+/// Release every lock `filter` selects
+fn release(app: &tauri::AppHandle, filter: impl Fn(&Path, &HeldLock) -> bool) {
+    let Some(locks) = app.try_state::<VaultLocks>() else {
+        return;
+    };
+    let mut locks = locks.lock();
+    locks.retain(|path, held| {
+        if !filter(path, held) {
+            return true;
+        }
+        remove_lock_file(held);
+        log::debug!("Released lock on {}", redact_path(&path.to_string_lossy()));
+        false
+    });
+}
+
+/// Release the locks of a window that was closed
+pub fn release_window(app: &tauri::AppHandle, label: &str) {
+    release(app, |_, held| held.window == label);
+}
+
+/// Release every lock this process holds (app exit)
+pub fn release_all(app: &tauri::AppHandle) {
+    release(app, |_, _| true);
+}
+
+/// Whether `window` holds the lock on the vault at `canonical` (see read_only::ensure_writable)
+pub(crate) fn holds_lock(app: &tauri::AppHandle, window: &str, canonical: &Path) -> bool {
+    app.try_state::<VaultLocks>().is_some_and(|locks| {
+        locks
+            .lock()
+            .get(canonical)
+            .is_some_and(|held| held.window == window)
+    })
+}
+
+/// Lock the vault at `path` for this window, ending its read-only session on the vault.
+/// Without `take_over`, a lock held by another window, instance or machine is reported
+/// instead of acquired. A window of this instance that loses the lock becomes read-only
+/// and is sent `vault-lock-taken-over`.
+#[command]
+pub async fn acquire_vault_lock(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    take_over: Option<bool>,
+) -> AppResult<LockStatus> {
+    let take_over = take_over.unwrap_or(false);
+    let canonical = canonical_path(&path);
+    let label = window.label().to_string();
+    let locks = app_handle.state::<VaultLocks>();
+    let mut locks = locks.lock();
+
+    let mut previous = None;
+    if let Some(held) = locks.get(&canonical) {
+        if held.window == label {
+            read_only::end_session(&app_handle, &label, &canonical);
+            return Ok(LockStatus {
+                acquired: true,
+                holder: None,
+            });
+        }
+        if !take_over {
+            let mut holder = held
+                .lock_path
+                .as_deref()
+                .and_then(read_lock_file)
+                .map(|lock| lock.holder)
+                .unwrap_or_else(|| LockHolder {
+                    host: host_name(),
+                    user: user_name(),
+                    pid: Some(std::process::id()),
+                    opened_at: None,
+                    this_instance: true,
+                    stale: false,
+                });
+            holder.this_instance = true;
+            return Ok(LockStatus {
+                acquired: false,
+                holder: Some(holder),
+            });
+        }
+        previous = Some((held.id.clone(), held.window.clone(), held.path.clone()));
+    }
+
+    let lock_file = lock_path(&canonical);
+    if !take_over {
+        if let Some(existing) = read_lock_file(&lock_file) {
+            return Ok(LockStatus {
+                acquired: false,
+                holder: Some(existing.holder),
+            });
+        }
+    }
+
+    // Taking over from another window of this instance keeps the file's ID valid
+    let id = match &previous {
+        Some((id, _, _)) => id.clone(),
+        None => new_lock_id()?,
+    };
+    let lock_path = match write_lock_file(&lock_file, &id, !take_over) {
+        Ok(()) => Some(lock_file),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            // Another instance created it between our check and now
+            let holder = read_lock_file(&lock_file).map(|lock| lock.holder);
+            return Ok(LockStatus {
+                acquired: false,
+                holder,
+            });
+        }
+        Err(e) => {
+            log::warn!("Opening vault without a lock file: {}", e);
+            None
+        }
+    };
+    if take_over {
+        log::info!("Took over the lock on {}", redact_path(&path));
+    }
+
+    // Holding the lock is what makes a window's session writable
+    read_only::end_session(&app_handle, &label, &canonical);
+    if let Some((_, previous_window, previous_path)) = previous {
+        read_only::begin_session(&app_handle, &previous_window, &canonical);
+        let _ = app_handle.emit_to(
+            previous_window.as_str(),
+            "vault-lock-taken-over",
+            LockTakenOver {
+                path: previous_path,
+            },
+        );
+    }
+    locks.insert(
+        canonical,
+        HeldLock {
+            id,
+            window: label,
+            path,
+            lock_path,
+        },
+    );
+    Ok(LockStatus {
+        acquired: true,
+        holder: None,
+    })
+}
+
+/// Release this window's lock on the vault at `path` (vault locked or closed)
+#[command]
+pub async fn release_vault_lock(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+) -> AppResult<()> {
+    let canonical = canonical_path(&path);
+    let label = window.label();
+    release(&app_handle, |path, held| {
+        path == canonical && held.window == label
+    });
+    Ok(())
+}
@@ -0,0 +1,404 @@
+// Detects changes other devices or programs make to open vault files
+//
+// Every window that opens a vault registers the fingerprint (SHA-256, size, mtime) of
+// the bytes it loaded. A watcher on the parent directory (sync clients replace files by
+// rename, which a watch on the file itself would lose) re-checks the file on each event
+// and emits `vault-changed-on-disk` to each window whose version differs. Saves compare
+// the file against the saving window's own fingerprint, so changes by another device,
+// program or window are never overwritten unless the user merged them or explicitly
+// chose to overwrite. Sync-conflict copies that appear next to a vault are announced
+// with `sync-conflict-found`.
+use crate::backup::{self, canonical_path, sha256_hex};
+use crate::conflicts;
+use crate::error::{AppError, AppResult, ErrorCode};
+use crate::logging::redact_path;
+use chrono::{DateTime, Utc};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tauri::{command, Emitter, Manager};
+
+// Sync clients often write in several steps; wait for the file to settle before
+// comparing it
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Fingerprint {
+    sha256: String,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Fingerprint {
+    /// `None` when the file does not exist
+    fn read(path: &Path) -> AppResult<Option<Self>> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Ok(Some(Self {
+            sha256: sha256_hex(&data),
+            size: data.len() as u64,
+            modified,
+        }))
+    }
+
+    /// Cheap check that skips hashing when size and mtime are unchanged
+    fn metadata_matches(&self, path: &Path) -> bool {
+        std::fs::metadata(path).is_ok_and(|m| {
+            m.len() == self.size && m.modified().ok().is_some_and(|t| Some(t) == self.modified)
+        })
+    }
+}
+
+/// One window's view of a watched vault
+struct WindowState {
+    /// What the window holds in memory: the loaded file or its last save
+    loaded: Option<Fingerprint>,
+    /// Content hash of the last change announced, so one change is reported once
+    notified: Option<Option<String>>,
+}
+
+struct WatchedVault {
+    /// Path as the frontend registered it, echoed back in events
+    path: String,
+    /// Windows that have this vault open, by label
+    windows: HashMap<String, WindowState>,
+    /// Conflict copies already announced
+    conflicts_found: HashSet<PathBuf>,
+    _watcher: RecommendedWatcher,
+}
+
+/// Watched vaults keyed by canonical path (managed state)
+#[derive(Default)]
+pub struct VaultWatchers(Mutex<HashMap<PathBuf, WatchedVault>>);
+
+impl VaultWatchers {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, WatchedVault>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// What a save may overwrite
+pub(crate) enum WriteGuard {
+    /// Only the version the window loaded or last saved
+    Loaded,
+    /// Only a file with this SHA-256 (a merge result based on that exact file)
+    Sha256(String),
+    /// Anything; the user chose to overwrite
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskChange {
+    pub path: String,
+    /// `false` when the file was deleted or moved away
+    pub exists: bool,
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictFound {
+    pub path: String,
+    pub conflict_path: String,
+}
+
+fn conflict(path: &Path) -> AppError {
+    log::warn!(
+        "Refusing to overwrite {}: changed on disk since it was loaded",
+        redact_path(&path.to_string_lossy())
+    );
+    AppError::new(
+        ErrorCode::Conflict,
+        "The vault file was changed on disk by another device or program",
+    )
+}
+
+/// Fail with `Conflict` unless window `label` may replace the file at `path` under
+/// `guard`. Must be called inside the backup transaction.
+pub(crate) fn check_write(
+    app: &tauri::AppHandle,
+    path: &str,
+    label: &str,
+    guard: &WriteGuard,
+) -> AppResult<()> {
+    let canonical = canonical_path(path);
+    let expected = match guard {
+        WriteGuard::Overwrite => return Ok(()),
+        WriteGuard::Sha256(sha256) => Some(sha256.clone()),
+        WriteGuard::Loaded => {
+            let watchers = app.state::<VaultWatchers>();
+            let watchers = watchers.lock();
+            // Vaults the window never registered (new files, other tools) have nothing to
+            // compare to
+            let Some(window) = watchers
+                .get(&canonical)
+                .and_then(|vault| vault.windows.get(label))
+            else {
+                return Ok(());
+            };
+            match &window.loaded {
+                Some(loaded) if loaded.metadata_matches(&canonical) => return Ok(()),
+                Some(loaded) => Some(loaded.sha256.clone()),
+                None => None,
+            }
+        }
+    };
+
+    let current = Fingerprint::read(&canonical)?.map(|f| f.sha256);
+    if current != expected {
+        return Err(conflict(&canonical));
+    }
+    Ok(())
+}
+
+/// Remember the file at `path` as the version window `label` now holds (after it saved,
+/// or reloads a merge). The vault's other windows still hold what they loaded and are
+/// told about the change. Must be called inside the backup transaction.
+pub(crate) fn record_written(app: &tauri::AppHandle, path: &str, label: &str) {
+    let canonical = canonical_path(path);
+    let watchers = app.state::<VaultWatchers>();
+    let mut watchers = watchers.lock();
+    if let Some(window) = watchers
+        .get_mut(&canonical)
+        .and_then(|vault| vault.windows.get_mut(label))
+    {
+        match Fingerprint::read(&canonical) {
+            Ok(fingerprint) => {
+                window.loaded = fingerprint;
+                window.notified = None;
+            }
+            Err(e) => log::warn!("Failed to fingerprint saved vault: {}", e),
+        }
+    }
+}
+
+/// Re-check the vault at `path` in the background, as after a file system event: a
+/// write this app meant to make found the file changed. Must be called outside the
+/// backup transaction.
+pub(crate) fn flag_changed(app: &tauri::AppHandle, path: &str) {
+    let (app, canonical) = (app.clone(), canonical_path(path));
+    std::thread::spawn(move || on_disk_event(&app, &canonical));
+}
+
+/// Re-check a watched vault after a file system event and notify its windows
+fn on_disk_event(app: &tauri::AppHandle, canonical: &Path) {
+    std::thread::sleep(SETTLE_DELAY);
+    // Wait for any save in progress so our own writes are recorded before comparing
+    let _guard = backup::lock_transaction();
+
+    let current = match Fingerprint::read(canonical) {
+        Ok(current) => current,
+        Err(e) => {
+            log::debug!("Failed to read changed vault: {}", e);
+            return;
+        }
+    };
+    let watchers = app.state::<VaultWatchers>();
+    let mut watchers = watchers.lock();
+    let Some(vault) = watchers.get_mut(canonical) else {
+        return;
+    };
+
+    let current_sha = current.as_ref().map(|f| f.sha256.clone());
+    let change = DiskChange {
+        path: vault.path.clone(),
+        exists: current.is_some(),
+        modified_at: current.and_then(|f| f.modified).map(DateTime::<Utc>::from),
+    };
+    for (label, window) in &mut vault.windows {
+        if window.loaded.as_ref().map(|f| &f.sha256) == current_sha.as_ref() {
+            // Touched or rewritten with identical content, or this window's own save
+            window.notified = None;
+            continue;
+        }
+        if window.notified.as_ref() == Some(&current_sha) {
+            continue;
+        }
+        window.notified = Some(current_sha.clone());
+        log::info!(
+            "Vault changed on disk under window {}: {}",
+            label,
+            redact_path(&vault.path)
+        );
+        let _ = app.emit_to(label.as_str(), "vault-changed-on-disk", change.clone());
+    }
+}
+
+/// Tell the windows of a vault about a sync-conflict copy that appeared next to it
+fn on_conflict_copy(app: &tauri::AppHandle, canonical: &Path, copy: PathBuf) {
+    if !copy.exists() {
+        return;
+    }
+    let watchers = app.state::<VaultWatchers>();
+    let mut watchers = watchers.lock();
+    let Some(vault) = watchers.get_mut(canonical) else {
+        return;
+    };
+    let found = ConflictFound {
+        path: vault.path.clone(),
+        conflict_path: copy.to_string_lossy().into_owned(),
+    };
+    if !vault.conflicts_found.insert(copy) {
+        return;
+    }
+
+    log::info!(
+        "Sync conflict copy appeared next to {}",
+        redact_path(&vault.path)
+    );
+    for label in vault.windows.keys() {
+        let _ = app.emit_to(label.as_str(), "sync-conflict-found", found.clone());
+    }
+}
+
+fn start_watcher(app: &tauri::AppHandle, canonical: &Path) -> AppResult<RecommendedWatcher> {
+    let parent = canonical
+        .parent()
+        .ok_or_else(|| AppError::invalid_input("Vault path has no parent directory"))?;
+    let file_name = canonical.file_name().map(|n| n.to_os_string());
+
+    let handler_app = app.clone();
+    let handler_path = canonical.to_path_buf();
+    let provider = conflicts::detect_provider(canonical);
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if event.kind.is_access() {
+            return;
+        }
+        if event
+            .paths
+            .iter()
+            .any(|p| p.file_name() == file_name.as_deref())
+        {
+            on_disk_event(&handler_app, &handler_path);
+            return;
+        }
+        if event.kind.is_remove() {
+            return;
+        }
+        for path in &event.paths {
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            let copy = handler_path.with_file_name(name);
+            if conflicts::is_conflict_copy(&handler_path, &copy, provider) {
+                on_conflict_copy(&handler_app, &handler_path, copy);
+            }
+        }
+    })
+    .map_err(|e| AppError::new(ErrorCode::BackendUnavailable, e.to_string()))?;
+    watcher
+        .watch(parent, RecursiveMode::NonRecursive)
+        .map_err(|e| AppError::new(ErrorCode::BackendUnavailable, e.to_string()))?;
+    Ok(watcher)
+}
+
+/// Remove `label` from the vaults matching `filter` and return the vaults nobody
+/// watches anymore. They must be dropped after releasing the lock: stopping a watcher
+/// can wait for its event thread, which may itself be waiting for the lock.
+fn release(
+    app: &tauri::AppHandle,
+    label: &str,
+    filter: impl Fn(&Path) -> bool,
+) -> Vec<WatchedVault> {
+    let watchers = app.state::<VaultWatchers>();
+    let mut watchers = watchers.lock();
+    let unused: Vec<PathBuf> = watchers
+        .iter_mut()
+        .filter(|(path, _)| filter(path))
+        .filter_map(|(path, vault)| {
+            vault.windows.remove(label);
+            vault.windows.is_empty().then(|| path.clone())
+        })
+        .collect();
+    unused
+        .iter()
+        .filter_map(|path| watchers.remove(path))
+        .collect()
+}
+
+/// Stop notifying `label` about any vault (the window was closed)
+pub fn forget_window(app: &tauri::AppHandle, label: &str) {
+    drop(release(app, label, |_| true));
+}
+
+/// Start watching a vault this window opened, or reopened. `sha256` is the hash of the
+/// bytes it loaded; a file that already differs is reported right away. Other windows
+/// of the vault keep their own fingerprints.
+#[command]
+pub async fn watch_vault(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+    sha256: Option<String>,
+) -> AppResult<()> {
+    let canonical = canonical_path(&path);
+    let current = Fingerprint::read(&canonical)?;
+    let (loaded, changed) = match (sha256, current) {
+        // Changed between the window reading it and registering; size and mtime of the
+        // loaded version are unknown, which forces a hash comparison on save
+        (Some(sha256), Some(current)) if sha256 != current.sha256 => (
+            Some(Fingerprint {
+                sha256,
+                size: 0,
+                modified: None,
+            }),
+            true,
+        ),
+        (_, current) => (current, false),
+    };
+
+    {
+        let state = WindowState {
+            loaded,
+            notified: None,
+        };
+        let watchers = app_handle.state::<VaultWatchers>();
+        let mut watchers = watchers.lock();
+        match watchers.get_mut(&canonical) {
+            Some(vault) => {
+                vault.windows.insert(window.label().to_string(), state);
+            }
+            None => {
+                let watcher = start_watcher(&app_handle, &canonical)?;
+                watchers.insert(
+                    canonical.clone(),
+                    WatchedVault {
+                        path: path.clone(),
+                        windows: HashMap::from([(window.label().to_string(), state)]),
+                        conflicts_found: HashSet::new(),
+                        _watcher: watcher,
+                    },
+                );
+            }
+        }
+    }
+    log::debug!("Watching {}", redact_path(&path));
+
+    if changed {
+        let app = app_handle.clone();
+        std::thread::spawn(move || on_disk_event(&app, &canonical));
+    }
+    Ok(())
+}
+
+#[command]
+pub async fn unwatch_vault(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    path: String,
+) -> AppResult<()> {
+    let canonical = canonical_path(&path);
+    drop(release(&app_handle, window.label(), |p| p == canonical));
+    Ok(())
+}
@@ -28,6 +28,10 @@ export interface Vault {
   hasKeyFile?: boolean;
   keyFileData?: Uint8Array;
   password?: kdbxweb.ProtectedValue;
+  /** Opened without the vault lock; changes are not saved */
+  readOnly?: boolean;
+  /** Server URL of a remote vault; `path` is then its local cache file */
+  remoteUrl?: string;
 }
 
 export interface VaultGroup {